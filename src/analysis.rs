@@ -0,0 +1,269 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use cursive::{views::TextView, CbSink, Cursive};
+use shakmaty::{
+    fen::Fen, san::SanPlus, uci::Uci, variant::VariantPosition, Color, EnPassantMode, Move,
+    Outcome, Position,
+};
+
+use crate::engine::{self, MATE};
+use crate::evalbar::{score_label, viewed, EvalBar};
+use crate::logic::PieceValues;
+use crate::settings::Settings;
+use crate::uci::{self, UciEngine};
+
+const MAX_DEPTH: u32 = 32;
+const NODE_LIMIT: u64 = 5_000_000;
+const UCI_DEPTH: u32 = 30;
+// Of each line when the engine shows more than one.
+const LINE_MOVES: usize = 4;
+
+enum Slot {
+    Unstarted,
+    Running(UciEngine),
+    Failed,
+}
+
+// The external engine, started on first use and shared by every search.
+// An engine that fails once is left for the built-in search.
+#[derive(Clone)]
+struct External {
+    path: String,
+    settings: Settings,
+    engine: Arc<Mutex<Slot>>,
+}
+
+// Runs a search on a background thread, one position at a time: the
+// external engine's when one is set up, else the built-in one. Starting a
+// new position or dropping the handle retires the old search.
+pub struct Analysis {
+    generation: Arc<AtomicUsize>,
+    cb_sink: CbSink,
+    external: Option<External>,
+    values: PieceValues,
+}
+
+impl Analysis {
+    pub fn new(cb_sink: CbSink, settings: &Settings) -> Self {
+        Analysis {
+            generation: Arc::new(AtomicUsize::new(0)),
+            cb_sink,
+            external: uci::engine_path(&settings.engine_path).map(|path| External {
+                path,
+                settings: settings.clone(),
+                engine: Arc::new(Mutex::new(Slot::Unstarted)),
+            }),
+            values: settings.piece_values,
+        }
+    }
+
+    pub fn start(&self, pos: VariantPosition) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let current = self.generation.clone();
+        let cb_sink = self.cb_sink.clone();
+        let values = self.values;
+        let turn = pos.turn();
+        let fen = Fen::from_position(pos.clone(), EnPassantMode::Legal).to_string();
+
+        if let Some(outcome) = pos.outcome() {
+            let (score, text) = match outcome {
+                Outcome::Decisive { winner } => (
+                    winner.fold_wb(MATE, -MATE),
+                    format!("{} won", winner.fold_wb("White", "Black")),
+                ),
+                Outcome::Draw => (0, "Drawn".to_owned()),
+            };
+            let _ = cb_sink.send(Box::new(move |s| show(s, fen, turn, score, text)));
+            return;
+        }
+
+        let external = self.external.clone().filter(|_| {
+            pos.variant() == shakmaty::variant::Variant::Chess
+                && !pos.castles().mode().is_chess960()
+        });
+        thread::spawn(move || {
+            let live = || current.load(Ordering::SeqCst) == generation;
+            let send = |score: i32, text: String| {
+                let (current, fen) = (current.clone(), fen.clone());
+                cb_sink
+                    .send(Box::new(move |s| {
+                        if current.load(Ordering::SeqCst) == generation {
+                            show(s, fen, turn, score, text);
+                        }
+                    }))
+                    .is_ok()
+            };
+            if let Some(external) = external {
+                if external.analyse(&pos, &fen, |score, text| live() && send(score, text)) {
+                    return;
+                }
+            }
+            engine::search_with(&pos, MAX_DEPTH, NODE_LIMIT, &values, |search| {
+                if !live() {
+                    return false;
+                }
+                let score = pos.turn().fold_wb(search.score, -search.score);
+                let text = format!(
+                    "Depth {}, {} nodes\n{}",
+                    search.depth,
+                    search.nodes,
+                    line(&pos, &search.pv)
+                );
+                send(score, text)
+            });
+        });
+    }
+
+    pub fn stop(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl External {
+    // False when the engine could not be used, so that the built-in search
+    // takes over.
+    fn analyse(
+        &self,
+        pos: &VariantPosition,
+        fen: &str,
+        mut send: impl FnMut(i32, String) -> bool,
+    ) -> bool {
+        let mut slot = self.engine.lock().unwrap_or_else(|e| e.into_inner());
+        if let Slot::Unstarted = *slot {
+            *slot = match UciEngine::spawn(
+                &self.path,
+                self.settings.engine_skill,
+                &self.settings.tablebase_path,
+                &self.settings.engine_options,
+            ) {
+                Ok(engine) => Slot::Running(engine),
+                Err(_) => Slot::Failed,
+            };
+        }
+        let Slot::Running(engine) = &mut *slot else {
+            return false;
+        };
+
+        let shown = self.settings.multipv();
+        let mut lines: Vec<Option<(i32, Vec<Move>)>> = vec![None; shown];
+        let analysed = engine.analyse(fen, UCI_DEPTH, |info| {
+            let Some((depth, index, score, pv)) = parse_info(info, pos) else {
+                return true;
+            };
+            if index >= shown {
+                return true;
+            }
+            lines[index] = Some((score, pv));
+            let best = lines[0].as_ref().map_or(0, |(score, _)| *score);
+            let text = if shown == 1 {
+                format!(
+                    "Engine depth {}\n{}",
+                    depth,
+                    line(pos, &lines[0].as_ref().unwrap().1)
+                )
+            } else {
+                let mut text = format!("Engine depth {}", depth);
+                for (score, pv) in lines.iter().flatten() {
+                    let pv = &pv[..pv.len().min(LINE_MOVES)];
+                    let score = viewed(*score, self.settings.eval_perspective, pos.turn());
+                    text += &format!("\n{:>5} {}", score_label(score), line(pos, pv));
+                }
+                text
+            };
+            send(best, text)
+        });
+        if analysed.is_err() {
+            *slot = Slot::Failed;
+        }
+        analysed.is_ok()
+    }
+}
+
+// The depth, the line's index from 0, its score from White's point of view
+// and its moves, from an info line that carries a whole line.
+fn parse_info(info: &str, pos: &VariantPosition) -> Option<(u32, usize, i32, Vec<Move>)> {
+    let words: Vec<&str> = info.split_whitespace().collect();
+    if words.contains(&"lowerbound") || words.contains(&"upperbound") {
+        return None;
+    }
+    let after = |key: &str| {
+        words
+            .iter()
+            .position(|w| *w == key)
+            .and_then(|i| words.get(i + 1))
+    };
+    let depth = after("depth")?.parse().ok()?;
+    let index = after("multipv").map_or(Some(1), |n| n.parse::<usize>().ok())?;
+    let score_at = words.iter().position(|w| *w == "score")?;
+    let value: i32 = words.get(score_at + 2)?.parse().ok()?;
+    let score = match *words.get(score_at + 1)? {
+        "cp" => value,
+        "mate" if value > 0 => MATE - (2 * value - 1),
+        "mate" => -MATE + 2 * -value,
+        _ => return None,
+    };
+
+    let start = words.iter().position(|w| *w == "pv")? + 1;
+    let mut line = pos.clone();
+    let mut pv = Vec::new();
+    for word in &words[start..] {
+        let Some(mv) = Uci::from_ascii(word.as_bytes())
+            .ok()
+            .and_then(|uci| uci.to_move(&line).ok())
+        else {
+            break;
+        };
+        line.play_unchecked(&mv);
+        pv.push(mv);
+    }
+    if pv.is_empty() {
+        return None;
+    }
+    Some((
+        depth,
+        index.checked_sub(1)?,
+        pos.turn().fold_wb(score, -score),
+        pv,
+    ))
+}
+
+impl Drop for Analysis {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn line(start: &VariantPosition, pv: &[Move]) -> String {
+    let mut pos = start.clone();
+    let mut number = pos.fullmoves().get();
+    let mut text = String::new();
+
+    for (i, mv) in pv.iter().enumerate() {
+        if pos.turn() == Color::White {
+            text += &format!("{}. ", number);
+        } else if i == 0 {
+            text += &format!("{}... ", number);
+        }
+        text += &SanPlus::from_move(pos.clone(), mv).to_string();
+        text.push(' ');
+        if pos.turn() == Color::Black {
+            number += 1;
+        }
+        pos.play_unchecked(mv);
+    }
+
+    text
+}
+
+fn show(siv: &mut Cursive, fen: String, turn: Color, score: i32, text: String) {
+    siv.call_on_name("analysis_eval", |bar: &mut EvalBar| {
+        bar.set(fen, score, Color::White, turn)
+    });
+    siv.call_on_name("analysis_pv", |view: &mut TextView| view.set_content(text));
+}
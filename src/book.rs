@@ -0,0 +1,98 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
+use rand::Rng;
+use shakmaty::{
+    zobrist::{Zobrist64, ZobristHash},
+    EnPassantMode, Move, Position, Role, Square,
+};
+
+const ENTRY_SIZE: u64 = 16;
+
+struct Entry {
+    key: u64,
+    raw_move: u16,
+    weight: u16,
+}
+
+fn read_entry(file: &mut File, index: u64) -> Result<Entry, String> {
+    let mut buf = [0; ENTRY_SIZE as usize];
+    file.seek(SeekFrom::Start(index * ENTRY_SIZE))
+        .and_then(|_| file.read_exact(&mut buf))
+        .map_err(|e| format!("could not read the book: {}", e))?;
+    Ok(Entry {
+        key: u64::from_be_bytes(buf[..8].try_into().unwrap()),
+        raw_move: u16::from_be_bytes([buf[8], buf[9]]),
+        weight: u16::from_be_bytes([buf[10], buf[11]]),
+    })
+}
+
+// Polyglot packs to, from and promotion into one word, and writes castling
+// as the king taking its own rook, which is also how shakmaty reports it.
+fn decode(pos: &impl Position, raw: u16) -> Option<Move> {
+    let to = Square::new(u32::from(raw & 63));
+    let from = Square::new(u32::from((raw >> 6) & 63));
+    let promotion = match (raw >> 12) & 7 {
+        0 => None,
+        1 => Some(Role::Knight),
+        2 => Some(Role::Bishop),
+        3 => Some(Role::Rook),
+        4 => Some(Role::Queen),
+        _ => return None,
+    };
+    pos.legal_moves()
+        .into_iter()
+        .find(|m| m.from() == Some(from) && m.to() == to && m.promotion() == promotion)
+}
+
+// Entries are sorted by key, so the position's moves are one contiguous run
+// found by binary search without loading the whole file.
+pub fn probe(path: &str, pos: &impl Position) -> Result<Vec<(Move, u16)>, String> {
+    let mut file = File::open(path).map_err(|e| format!("could not open {}: {}", path, e))?;
+    let len = file
+        .metadata()
+        .map_err(|e| format!("could not read {}: {}", path, e))?
+        .len();
+    let key = pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0;
+
+    let (mut lo, mut hi) = (0, len / ENTRY_SIZE);
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if read_entry(&mut file, mid)?.key < key {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+
+    let mut moves = Vec::new();
+    for index in lo..len / ENTRY_SIZE {
+        let entry = read_entry(&mut file, index)?;
+        if entry.key != key {
+            break;
+        }
+        if let Some(mv) = decode(pos, entry.raw_move) {
+            moves.push((mv, entry.weight));
+        }
+    }
+    Ok(moves)
+}
+
+pub fn pick(path: &str, pos: &impl Position, rng: &mut impl Rng) -> Result<Option<Move>, String> {
+    let moves = probe(path, pos)?;
+    let total: u32 = moves.iter().map(|&(_, weight)| u32::from(weight)).sum();
+    if total == 0 {
+        return Ok(moves.into_iter().next().map(|(mv, _)| mv));
+    }
+
+    let mut roll = rng.gen_range(0..total);
+    for (mv, weight) in moves {
+        match roll.checked_sub(u32::from(weight)) {
+            Some(rest) => roll = rest,
+            None => return Ok(Some(mv)),
+        }
+    }
+    Ok(None)
+}
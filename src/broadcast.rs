@@ -0,0 +1,365 @@
+use std::{
+    fs,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use cursive::{
+    direction::Direction,
+    event::{Event, EventResult, Key},
+    theme::{BaseColor, Color},
+    view::{CannotFocus, Nameable, Resizable, Scrollable},
+    views::{Dialog, EditView, LinearLayout, Panel, TextView},
+    CbSink, Cursive, Printer, Vec2,
+};
+use shakmaty::{Bitboard, Position};
+
+use crate::lang::tr;
+use crate::lichess;
+use crate::pgn::{self, Game};
+use crate::replay::draw_board;
+use crate::settings::{self, BoardTheme, PieceStyle};
+
+const FILE_POLL: Duration = Duration::from_secs(1);
+const URL_POLL: Duration = Duration::from_secs(10);
+// New moves go onto the board one at a time, this far apart.
+const STEP: Duration = Duration::from_millis(600);
+
+// Each broadcast window gets its own id, so a download that finishes after
+// the window was closed is dropped.
+static BROADCASTS: AtomicUsize = AtomicUsize::new(0);
+
+enum Source {
+    File(String),
+    Url(String),
+}
+
+impl Source {
+    fn parse(text: &str) -> Source {
+        let text = text.trim().to_owned();
+        if text.starts_with("http://") || text.starts_with("https://") {
+            Source::Url(text)
+        } else {
+            Source::File(text)
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Source::File(name) | Source::Url(name) => name,
+        }
+    }
+
+    fn interval(&self) -> Duration {
+        match self {
+            Source::File(_) => FILE_POLL,
+            Source::Url(_) => URL_POLL,
+        }
+    }
+}
+
+pub struct BroadcastView {
+    id: usize,
+    source: Source,
+    // Which game of the file or round is followed.
+    board: usize,
+    boards: usize,
+    game: Option<Game>,
+    // Plies on the board so far, which trail the game while moves animate.
+    shown: usize,
+    text: String,
+    fetching: bool,
+    polled: Option<Instant>,
+    stepped: Instant,
+    note: String,
+    style: PieceStyle,
+    theme: BoardTheme,
+}
+
+impl BroadcastView {
+    fn new(source: Source, style: PieceStyle, theme: BoardTheme) -> Self {
+        BroadcastView {
+            id: BROADCASTS.fetch_add(1, Ordering::SeqCst),
+            source,
+            board: 0,
+            boards: 0,
+            game: None,
+            shown: 0,
+            text: String::new(),
+            fetching: false,
+            polled: None,
+            stepped: Instant::now(),
+            note: "Waiting for the first moves".to_owned(),
+            style,
+            theme,
+        }
+    }
+
+    fn movetext(&self) -> String {
+        let Some(game) = &self.game else {
+            return String::new();
+        };
+        pgn::numbered(&game.positions[0], &game.sans[..self.shown])
+    }
+
+    fn status(&self) -> String {
+        let Some(game) = &self.game else {
+            return String::new();
+        };
+        let result = game.tag("Result").filter(|&result| result != "*");
+        match result {
+            _ if self.shown < game.moves.len() => "Catching up".to_owned(),
+            Some(result) => format!("Finished, {}", result),
+            None => "Live, waiting for a move".to_owned(),
+        }
+    }
+
+    // A game whose moves were corrected goes back to where the two part; one
+    // seen for the first time opens on its latest position. Returns whether
+    // the game changed.
+    fn update(&mut self, text: String) -> bool {
+        if text == self.text {
+            return false;
+        }
+        let chunks = pgn::split_games(&text);
+        self.boards = chunks.len();
+        let Some(chunk) = chunks.get(self.board) else {
+            self.note = format!("No board {} yet", self.board + 1);
+            return false;
+        };
+        match pgn::parse(chunk) {
+            Ok(game) => {
+                self.shown = match &self.game {
+                    Some(old) => {
+                        let common = old
+                            .sans
+                            .iter()
+                            .zip(&game.sans)
+                            .take_while(|(a, b)| a == b)
+                            .count();
+                        self.shown.min(common)
+                    }
+                    None => game.moves.len(),
+                };
+                self.game = Some(game);
+                self.text = text;
+                self.note.clear();
+                true
+            }
+            // Most likely a move that was only half written; the next poll
+            // tries again.
+            Err(e) => {
+                self.note = format!("Skipped an update: {}", e);
+                false
+            }
+        }
+    }
+
+    fn receive(&mut self, text: Result<String, String>) -> bool {
+        self.fetching = false;
+        match text {
+            Ok(text) => self.update(text),
+            Err(e) => {
+                self.note = e;
+                false
+            }
+        }
+    }
+
+    // Returns whether the moves on the board changed.
+    fn tick(&mut self, cb_sink: &CbSink) -> bool {
+        let mut changed = false;
+        let due = self
+            .polled
+            .is_none_or(|polled| polled.elapsed() >= self.source.interval());
+        if due && !self.fetching {
+            self.polled = Some(Instant::now());
+            match &self.source {
+                Source::File(path) => {
+                    let text = fs::read_to_string(path)
+                        .map_err(|e| format!("Could not read {}: {}", path, e));
+                    changed = self.receive(text);
+                }
+                Source::Url(url) => {
+                    self.fetching = true;
+                    let (id, url, cb_sink) = (self.id, url.clone(), cb_sink.clone());
+                    thread::spawn(move || {
+                        let text = lichess::fetch_pgn(&url);
+                        let _ = cb_sink.send(Box::new(move |s| receive(s, id, text)));
+                    });
+                }
+            }
+        }
+
+        let behind = self
+            .game
+            .as_ref()
+            .is_some_and(|game| self.shown < game.moves.len());
+        if behind && self.stepped.elapsed() >= STEP {
+            self.shown += 1;
+            self.stepped = Instant::now();
+            changed = true;
+        }
+        changed
+    }
+
+    fn switch(&mut self, board: usize) {
+        self.board = board;
+        self.game = None;
+        self.text.clear();
+        self.polled = None;
+    }
+
+    fn next_board(&mut self) {
+        if self.board + 1 < self.boards {
+            self.switch(self.board + 1);
+        }
+    }
+
+    fn previous_board(&mut self) {
+        if self.board > 0 {
+            self.switch(self.board - 1);
+        }
+    }
+}
+
+impl cursive::view::View for BroadcastView {
+    fn draw(&self, printer: &Printer) {
+        let (board, last_move) = match &self.game {
+            Some(game) => {
+                let last_move: Bitboard = match self.shown.checked_sub(1) {
+                    Some(i) => {
+                        let mv = &game.moves[i];
+                        mv.from().into_iter().chain([mv.to()]).collect()
+                    }
+                    None => Bitboard::EMPTY,
+                };
+                (game.positions[self.shown].board().clone(), last_move)
+            }
+            None => (shakmaty::Board::default(), Bitboard::EMPTY),
+        };
+        draw_board(printer, &board, self.style, self.theme, &[], |sq| {
+            last_move
+                .contains(sq)
+                .then_some(Color::Dark(BaseColor::Green))
+        });
+
+        if let Some(game) = &self.game {
+            let name = |tag| game.tag(tag).unwrap_or("?");
+            printer.print((0, 10), &format!("White: {}", name("White")));
+            printer.print((0, 11), &format!("Black: {}", name("Black")));
+        }
+        printer.print(
+            (0, 12),
+            &format!("Board {} of {}", self.board + 1, self.boards),
+        );
+        printer.print((0, 13), &self.status());
+        printer.print((0, 14), &self.note);
+    }
+
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::Consumed(None))
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        match event {
+            Event::Char(']') | Event::Key(Key::PageDown) => self.next_board(),
+            Event::Char('[') | Event::Key(Key::PageUp) => self.previous_board(),
+            _ => return EventResult::Ignored,
+        }
+        EventResult::Consumed(None)
+    }
+
+    fn required_size(&mut self, _: Vec2) -> Vec2 {
+        Vec2::new(30, 15)
+    }
+}
+
+fn show_moves(siv: &mut Cursive) {
+    let moves = siv.call_on_name("broadcast", |view: &mut BroadcastView| view.movetext());
+    if let Some(moves) = moves {
+        siv.call_on_name("broadcast_moves", |v: &mut TextView| v.set_content(moves));
+    }
+}
+
+fn receive(siv: &mut Cursive, id: usize, text: Result<String, String>) {
+    let changed = siv.call_on_name("broadcast", |view: &mut BroadcastView| {
+        view.id == id && view.receive(text)
+    });
+    if changed == Some(true) {
+        show_moves(siv);
+    }
+}
+
+pub fn tick(siv: &mut Cursive) {
+    let cb_sink = siv.cb_sink().clone();
+    let moved = siv.call_on_name("broadcast", |view: &mut BroadcastView| view.tick(&cb_sink));
+    if moved == Some(true) {
+        show_moves(siv);
+    }
+}
+
+fn control(siv: &mut Cursive, f: fn(&mut BroadcastView)) {
+    siv.call_on_name("broadcast", f);
+}
+
+fn open_broadcast(siv: &mut Cursive, source: Source) {
+    let settings = settings::current(siv);
+    let title = format!("Following {}", source.name());
+    let view = BroadcastView::new(source, settings.piece_style, settings.board_theme);
+    siv.set_fps(4);
+
+    siv.add_layer(
+        Dialog::new()
+            .title(title)
+            .content(
+                LinearLayout::horizontal()
+                    .child(Panel::new(view.with_name("broadcast")))
+                    .child(
+                        Panel::new(TextView::new("").with_name("broadcast_moves").scrollable())
+                            .title(tr("Moves"))
+                            .fixed_size((24, 17)),
+                    ),
+            )
+            .button(tr("Previous Board"), |s| {
+                control(s, BroadcastView::previous_board)
+            })
+            .button(tr("Next Board"), |s| control(s, BroadcastView::next_board))
+            .button(tr("Close"), |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn show_setup(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Follow Broadcast"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new(
+                        "A PGN file that is being written to, or a broadcast round's URL,\n\
+                         as in https://lichess.org/api/broadcast/round/<id>.pgn\n\
+                         [ and ] switch between the boards.",
+                    ))
+                    .child(
+                        EditView::new()
+                            .content("live.pgn")
+                            .with_name("broadcast_source")
+                            .fixed_width(60),
+                    ),
+            )
+            .button(tr("Follow"), |s| {
+                let text = s
+                    .call_on_name("broadcast_source", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                if text.trim().is_empty() {
+                    return s.add_layer(Dialog::info(tr("Enter a file or a URL.")));
+                }
+                s.pop_layer();
+                open_broadcast(s, Source::parse(&text));
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
@@ -0,0 +1,75 @@
+use rand::Rng;
+use shakmaty::{
+    Bitboard, Board, CastlingMode, Chess, Color, File, FromSetup, Rank, Role, Setup, Square,
+};
+
+pub const POSITIONS: u32 = 960;
+
+// Knight placements among the five files left after the bishops and queen.
+const KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+// Scharnagl numbering, so that 518 is the standard starting array.
+fn back_rank(number: u32) -> [Role; 8] {
+    let mut rank = [None; 8];
+    let n = number as usize;
+
+    rank[2 * (n % 4) + 1] = Some(Role::Bishop);
+    rank[2 * (n / 4 % 4)] = Some(Role::Bishop);
+
+    let mut place = |role, nth: usize| {
+        let file = (0..8).filter(|&f| rank[f].is_none()).nth(nth).unwrap();
+        rank[file] = Some(role);
+    };
+    let (first, second) = KNIGHTS[n / 96];
+    place(Role::Queen, n / 16 % 6);
+    place(Role::Knight, second);
+    place(Role::Knight, first);
+    place(Role::Rook, 0);
+    place(Role::King, 0);
+    place(Role::Rook, 0);
+
+    rank.map(Option::unwrap)
+}
+
+pub fn position(number: u32) -> Result<Chess, String> {
+    if number >= POSITIONS {
+        return Err(format!(
+            "Chess960 positions are numbered 0 to {}",
+            POSITIONS - 1
+        ));
+    }
+
+    let mut board = Board::empty();
+    for (file, role) in back_rank(number).into_iter().enumerate() {
+        let file = File::new(file as u32);
+        for color in [Color::White, Color::Black] {
+            let back = color.relative_rank(Rank::First);
+            let pawns = color.relative_rank(Rank::Second);
+            board.set_piece_at(Square::from_coords(file, back), role.of(color));
+            board.set_piece_at(Square::from_coords(file, pawns), Role::Pawn.of(color));
+        }
+    }
+
+    let setup = Setup {
+        castling_rights: board.rooks() & Bitboard::BACKRANKS,
+        board,
+        ..Setup::default()
+    };
+    Chess::from_setup(setup, CastlingMode::Chess960).map_err(|e| format!("Illegal position: {}", e))
+}
+
+pub fn random() -> (u32, Chess) {
+    let number = rand::thread_rng().gen_range(0..POSITIONS);
+    (number, position(number).unwrap())
+}
@@ -0,0 +1,228 @@
+use std::fs;
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use shakmaty::{san::SanPlus, Board, Chess, Color, Move, Position};
+
+use crate::duel;
+use crate::engine::{self, MATE};
+use crate::logic::{parse_fen, PieceValues};
+use crate::pgn;
+use crate::picture;
+use crate::puzzle;
+use crate::settings::Settings;
+
+// Random plies at the start, so that self-play games differ.
+const OPENING_PLIES: usize = 2;
+
+// From White's point of view: pawns, or M and the moves to a forced mate.
+fn score_text(score: i32) -> String {
+    if score.abs() >= MATE - 100 {
+        let sign = if score < 0 { "-" } else { "" };
+        format!("{}M{}", sign, (MATE - score.abs() + 1) / 2)
+    } else {
+        format!("{:+.2}", score as f32 / 100.0)
+    }
+}
+
+fn sans(start: &Chess, moves: &[Move]) -> Vec<String> {
+    let mut pos = start.clone();
+    moves
+        .iter()
+        .map(|mv| {
+            let san = SanPlus::from_move_and_play_unchecked(&mut pos, mv);
+            san.to_string()
+        })
+        .collect()
+}
+
+// One line, for scripts: "depth 4 nodes 18236 score +0.25 best e4 pv e4 e5 Nf3".
+pub fn analyze(fen: &str, depth: u32) -> Result<String, String> {
+    let pos: Chess = if fen.trim().is_empty() {
+        Chess::default()
+    } else {
+        parse_fen(fen)?
+    };
+    if let Some(outcome) = pos.outcome() {
+        return Ok(format!("game over {}", outcome));
+    }
+    let values = Settings::load().piece_values;
+    let search = engine::search(&pos, depth, &values);
+    let score = pos.turn().fold_wb(search.score, -search.score);
+    let pv = sans(&pos, &search.pv);
+    Ok(format!(
+        "depth {} nodes {} score {} best {} pv {}",
+        search.depth,
+        search.nodes,
+        score_text(score),
+        pv.first().map_or("-", String::as_str),
+        pv.join(" ")
+    ))
+}
+
+fn play_game(depth: u32, values: &PieceValues, rng: &mut StdRng) -> (Vec<String>, &'static str) {
+    duel::play_out(&Chess::default(), |pos, ply| {
+        if ply < OPENING_PLIES {
+            pos.legal_moves().choose(rng).cloned()
+        } else {
+            engine::search(pos, depth, values).best
+        }
+    })
+}
+
+// The games as PGN. The tally closes the output on a "%" line, which PGN
+// readers skip.
+pub fn selfplay(depth: u32, games: u32, seed: u64) -> Result<String, String> {
+    if games == 0 {
+        return Err("--games must be at least 1".to_owned());
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let values = Settings::load().piece_values;
+    let player = format!("Built-in AI, depth {}", depth);
+    let mut out = String::new();
+    let (mut white, mut black, mut drawn) = (0, 0, 0);
+
+    for round in 1..=games {
+        let (sans, result) = play_game(depth, &values, &mut rng);
+        match result {
+            "1-0" => white += 1,
+            "0-1" => black += 1,
+            _ => drawn += 1,
+        }
+        let mut tags = pgn::seven_tags("Self-play", player.clone(), player.clone(), result);
+        tags[3].1 = round.to_string();
+        out += &pgn::write(tags, &pgn::numbered(&Chess::default(), &sans), result);
+        out.push('\n');
+    }
+
+    out += &format!(
+        "% {} games at depth {}: White won {}, Black won {}, {} drawn (seed {})",
+        games, depth, white, black, drawn, seed
+    );
+    Ok(out)
+}
+
+// The engine is asked for the first move of each puzzle, one line each.
+pub fn puzzles(path: &str, depth: u32) -> Result<String, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let puzzles = puzzle::load(&text)?;
+    if puzzles.is_empty() {
+        return Err(format!("No puzzles in {}", path));
+    }
+
+    let values = Settings::load().piece_values;
+    let mut out = String::new();
+    let mut solved = 0;
+    for (i, puzzle) in puzzles.iter().enumerate() {
+        let name = puzzle.id.clone().unwrap_or_else(|| format!("#{}", i + 1));
+        let best = engine::search(&puzzle.start, depth, &values).best;
+        let played = best.as_ref().map_or("-".to_owned(), |mv| {
+            SanPlus::from_move(puzzle.start.clone(), mv).to_string()
+        });
+        let found = best
+            .as_ref()
+            .is_some_and(|mv| puzzle.accepts(0, &puzzle.start, mv));
+        if found {
+            solved += 1;
+        }
+        out += &format!(
+            "{} {} played {}, expected {}\n",
+            name,
+            if found { "solved" } else { "failed" },
+            played,
+            puzzle.solution_text().lines().next().unwrap_or("")
+        );
+    }
+    out += &format!("solved {} of {} at depth {}", solved, puzzles.len(), depth);
+    Ok(out)
+}
+
+// One position from a FEN, or every position of the first game in a PGN
+// file. SVG goes to one file a position, numbered by ply; ANSI art puts them
+// all in one text, each under the move that led to it.
+pub fn render(
+    format: &str,
+    fen: &str,
+    pgn_path: Option<&str>,
+    out: Option<&str>,
+) -> Result<String, String> {
+    let mut frames: Vec<(String, Board, Option<Move>)> = Vec::new();
+    match pgn_path {
+        Some(path) => {
+            let text =
+                fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+            let game = pgn::parse(&text)?;
+            for (ply, pos) in game.positions.iter().enumerate() {
+                let heading = match ply.checked_sub(1) {
+                    Some(i) => format!(
+                        "{}{} {}",
+                        i / 2 + 1,
+                        if i % 2 == 0 { "." } else { "..." },
+                        game.sans[i]
+                    ),
+                    None => "Start".to_owned(),
+                };
+                let last = ply.checked_sub(1).map(|i| game.moves[i].clone());
+                frames.push((heading, pos.board().clone(), last));
+            }
+        }
+        None => {
+            let pos: Chess = if fen.trim().is_empty() {
+                Chess::default()
+            } else {
+                parse_fen(fen)?
+            };
+            frames.push((String::new(), pos.board().clone(), None));
+        }
+    }
+
+    match format {
+        "svg" if frames.len() == 1 => {
+            let (_, board, last) = &frames[0];
+            let image = picture::svg(board, Color::White, last.as_ref());
+            match out {
+                Some(path) => write(path, &image),
+                None => Ok(image),
+            }
+        }
+        "svg" => {
+            let path = out.ok_or("--out is needed for a whole game, one file a position")?;
+            let stem = path.strip_suffix(".svg").unwrap_or(path);
+            for (ply, (_, board, last)) in frames.iter().enumerate() {
+                let image = picture::svg(board, Color::White, last.as_ref());
+                write(&format!("{}-{:03}.svg", stem, ply), &image)?;
+            }
+            Ok(format!(
+                "Wrote {} files, {}-000.svg to {}-{:03}.svg",
+                frames.len(),
+                stem,
+                stem,
+                frames.len() - 1
+            ))
+        }
+        "ansi" => {
+            let text: Vec<String> = frames
+                .iter()
+                .map(|(heading, board, last)| {
+                    let art = picture::ansi(board, Color::White, last.as_ref());
+                    if heading.is_empty() {
+                        art
+                    } else {
+                        format!("{}\n{}", heading, art)
+                    }
+                })
+                .collect();
+            let text = text.join("\n");
+            match out {
+                Some(path) => write(path, &text),
+                None => Ok(text.trim_end().to_owned()),
+            }
+        }
+        _ => Err(format!("unknown format {}: use svg or ansi", format)),
+    }
+}
+
+pub fn write(path: &str, contents: &str) -> Result<String, String> {
+    fs::write(path, contents)
+        .map(|()| format!("Wrote {}", path))
+        .map_err(|e| format!("Could not write {}: {}", path, e))
+}
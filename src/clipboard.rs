@@ -0,0 +1,129 @@
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+use cursive::{views::Dialog, Cursive};
+use shakmaty::fen::Fen;
+
+use crate::logic::BoardView;
+use crate::pgn;
+use crate::replay;
+
+// Tried in turn until one of them works.
+const COPY: [(&str, &[&str]); 5] = [
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("pbcopy", &[]),
+    ("clip.exe", &[]),
+];
+const PASTE: [(&str, &[&str]); 5] = [
+    ("wl-paste", &["--no-newline"]),
+    ("xclip", &["-selection", "clipboard", "-o"]),
+    ("xsel", &["--clipboard", "--output"]),
+    ("pbpaste", &[]),
+    (
+        "powershell.exe",
+        &["-NoProfile", "-Command", "Get-Clipboard"],
+    ),
+];
+
+fn run_copy(program: &str, args: &[&str], text: &str) -> io::Result<bool> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(text.as_bytes())?;
+    Ok(child.wait()?.success())
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(DIGITS[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// Without a clipboard tool, the terminal itself is asked to take the text
+// (OSC 52). Many terminals do, including over SSH, but none says whether it did.
+pub fn copy(text: &str) -> Result<(), String> {
+    if COPY
+        .iter()
+        .any(|(program, args)| run_copy(program, args, text).unwrap_or(false))
+    {
+        return Ok(());
+    }
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))
+        .and_then(|()| stdout.flush())
+        .map_err(|e| format!("Could not copy: {}", e))
+}
+
+pub fn paste() -> Result<String, String> {
+    PASTE
+        .iter()
+        .find_map(|(program, args)| {
+            let output = Command::new(program)
+                .args(*args)
+                .stderr(Stdio::null())
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+        })
+        .ok_or_else(|| "No clipboard tool found (wl-paste, xclip, xsel or pbpaste).".to_owned())
+}
+
+pub fn copy_text(siv: &mut Cursive, what: &str, text: &str) {
+    let msg = match copy(text) {
+        Ok(()) => format!("Copied the {} to the clipboard.", what),
+        Err(e) => e,
+    };
+    siv.add_layer(Dialog::info(msg));
+}
+
+pub fn copy_fen(siv: &mut Cursive) {
+    if let Some(fen) = siv.call_on_name("board", |board: &mut BoardView| board.fen()) {
+        copy_text(siv, "FEN", &fen);
+    }
+}
+
+pub fn copy_pgn(siv: &mut Cursive) {
+    if let Some(pgn) = siv.call_on_name("board", |board: &mut BoardView| pgn::render(board)) {
+        copy_text(siv, "PGN", &pgn);
+    }
+}
+
+// A FEN or a whole PGN, opened for analysis.
+pub fn paste_position(siv: &mut Cursive) {
+    let game = paste().and_then(|text| {
+        let text = text.trim();
+        if Fen::from_ascii(text.as_bytes()).is_ok() {
+            pgn::parse(&format!("[FEN \"{}\"]\n\n*", text))
+        } else {
+            pgn::parse(text)
+                .map_err(|e| format!("The clipboard holds neither a FEN nor a PGN: {}", e))
+        }
+    });
+    match game {
+        Ok(game) => replay::open_replay(siv, game, true),
+        Err(e) => siv.add_layer(Dialog::info(e)),
+    }
+}
@@ -0,0 +1,195 @@
+use std::time::{Duration, Instant};
+
+use shakmaty::{ByColor, Color};
+
+// What the per-move seconds of a time control do.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    // Added after every move.
+    #[default]
+    Fischer,
+    // Given back after a move, up to the time the move took.
+    Bronstein,
+    // Waited out at the start of each move before the clock runs down.
+    Simple,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 3] = [Mode::Fischer, Mode::Bronstein, Mode::Simple];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Mode::Fischer => "fischer",
+            Mode::Bronstein => "bronstein",
+            Mode::Simple => "simple",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Mode> {
+        Mode::ALL.into_iter().find(|m| m.key() == key)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Mode::Fischer => "Increment",
+            Mode::Bronstein => "Bronstein delay",
+            Mode::Simple => "Simple delay",
+        }
+    }
+}
+
+// How long a move took and, on a clock, what the mover had left after it.
+#[derive(Clone, Copy)]
+pub struct Stamp {
+    pub spent: Duration,
+    pub left: Option<Duration>,
+}
+
+pub struct Clock {
+    // For the side to move, what it had when its move began.
+    remaining: ByColor<Duration>,
+    increment: Duration,
+    mode: Mode,
+    running: Option<Color>,
+    // Time used on the current move before `since`.
+    spent: Duration,
+    since: Option<Instant>,
+}
+
+impl Clock {
+    // With no side given, neither clock runs until the first move.
+    pub fn new(
+        remaining: ByColor<Duration>,
+        increment: Duration,
+        mode: Mode,
+        first: Option<Color>,
+    ) -> Self {
+        Clock {
+            remaining,
+            increment,
+            mode,
+            running: first,
+            spent: Duration::ZERO,
+            since: first.map(|_| Instant::now()),
+        }
+    }
+
+    pub fn increment(&self) -> Duration {
+        self.increment
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn used(&self) -> Duration {
+        self.spent + self.since.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    pub fn remaining(&self, color: Color) -> Duration {
+        let left = *self.remaining.get(color);
+        if self.running != Some(color) {
+            return left;
+        }
+        let charged = match self.mode {
+            Mode::Simple => self.used().saturating_sub(self.increment),
+            Mode::Fischer | Mode::Bronstein => self.used(),
+        };
+        left.saturating_sub(charged)
+    }
+
+    fn settle(&mut self) {
+        self.spent = self.used();
+        if self.since.is_some() {
+            self.since = Some(Instant::now());
+        }
+    }
+
+    // Charges the side to move for its move so far and starts a fresh one.
+    fn close_move(&mut self) -> Duration {
+        let used = self.used();
+        if let Some(running) = self.running {
+            *self.remaining.get_mut(running) = self.remaining(running);
+        }
+        self.spent = Duration::ZERO;
+        if self.since.is_some() {
+            self.since = Some(Instant::now());
+        }
+        used
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some() && self.since.is_some()
+    }
+
+    // Called after every ply: the mover gets back what the mode allows and
+    // the other side's clock starts. A flag that has fallen stays down.
+    pub fn press(&mut self, mover: Color) -> Stamp {
+        if self.running.is_none() {
+            self.running = Some(!mover);
+            self.since = Some(Instant::now());
+            return Stamp {
+                spent: Duration::ZERO,
+                left: Some(*self.remaining.get(mover)),
+            };
+        }
+        let used = self.close_move();
+        let left = self.remaining.get_mut(mover);
+        if !left.is_zero() {
+            *left += match self.mode {
+                Mode::Fischer => self.increment,
+                Mode::Bronstein => used.min(self.increment),
+                Mode::Simple => Duration::ZERO,
+            };
+        }
+        self.running = Some(!mover);
+        Stamp {
+            spent: used,
+            left: Some(*self.remaining.get(mover)),
+        }
+    }
+
+    pub fn set_running(&mut self, color: Color) {
+        self.close_move();
+        self.running = Some(color);
+    }
+
+    pub fn pause(&mut self) {
+        self.settle();
+        self.since = None;
+    }
+
+    pub fn resume(&mut self) {
+        if self.running.is_some() && self.since.is_none() {
+            self.since = Some(Instant::now());
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.close_move();
+        self.since = None;
+        self.running = None;
+    }
+
+    // Takes the times from a server that keeps the real clock. The side to
+    // move counts down afresh from what it was given.
+    #[cfg(feature = "online")]
+    pub fn set(&mut self, remaining: ByColor<Duration>) {
+        self.remaining = remaining;
+        self.spent = Duration::ZERO;
+        if self.since.is_some() {
+            self.since = Some(Instant::now());
+        }
+    }
+
+    pub fn flagged(&self) -> Option<Color> {
+        Color::ALL
+            .into_iter()
+            .find(|&color| self.remaining(color).is_zero())
+    }
+}
+
+pub fn format(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
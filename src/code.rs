@@ -0,0 +1,132 @@
+use shakmaty::{
+    variant::{Variant, VariantPosition},
+    Color,
+};
+
+use crate::logic::parse_variant_fen;
+use crate::settings::Difficulty;
+
+const VERSION: &str = "2";
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+pub struct GameCode {
+    pub position: VariantPosition,
+    // Missing from codes made before version 2.
+    pub difficulty: Option<Difficulty>,
+    pub human: Option<Color>,
+    pub step_mode: bool,
+}
+
+impl GameCode {
+    pub fn encode(
+        fen: &str,
+        variant: Variant,
+        difficulty: Difficulty,
+        human: Color,
+        step_mode: bool,
+    ) -> String {
+        let flags = if step_mode { "s" } else { "" };
+        let mut bytes = format!(
+            "{};{};{};{};{};{}",
+            VERSION,
+            variant.uci(),
+            fen,
+            difficulty.key(),
+            human.char(),
+            flags
+        )
+        .into_bytes();
+        bytes.push(checksum(&bytes));
+        to_base64(&bytes)
+    }
+
+    pub fn decode(code: &str) -> Result<GameCode, String> {
+        let corrupt = |why: &str| format!("Corrupted game code: {}", why);
+
+        let mut bytes = from_base64(code.trim()).ok_or_else(|| corrupt("not a game code"))?;
+        let sum = bytes.pop().ok_or_else(|| corrupt("empty"))?;
+        if checksum(&bytes) != sum {
+            return Err(corrupt("checksum mismatch"));
+        }
+        let text = String::from_utf8(bytes).map_err(|_| corrupt("not a game code"))?;
+
+        let position = |variant: &str, fen: &str| {
+            let variant = Variant::from_uci(variant)
+                .map_err(|_| format!("Unsupported variant in game code: {}", variant))?;
+            parse_variant_fen(variant, fen)
+        };
+
+        let fields: Vec<&str> = text.split(';').collect();
+        match fields[..] {
+            [VERSION, variant, fen, difficulty, human, flags] => {
+                let difficulty = Difficulty::ALL
+                    .into_iter()
+                    .find(|d| d.key() == difficulty)
+                    .ok_or_else(|| corrupt("unknown difficulty"))?;
+                let human = human
+                    .chars()
+                    .next()
+                    .and_then(Color::from_char)
+                    .ok_or_else(|| corrupt("unknown color"))?;
+                Ok(GameCode {
+                    position: position(variant, fen)?,
+                    difficulty: Some(difficulty),
+                    human: Some(human),
+                    step_mode: flags.contains('s'),
+                })
+            }
+            ["1", variant, fen, flags] => Ok(GameCode {
+                position: position(variant, fen)?,
+                difficulty: None,
+                human: None,
+                step_mode: flags.contains('s'),
+            }),
+            [version, ..] if version != VERSION && version != "1" => {
+                Err(format!("Unsupported game code version: {}", version))
+            }
+            _ => Err(corrupt("missing fields")),
+        }
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0u8, |sum, &b| sum.rotate_left(1).wrapping_add(b))
+}
+
+fn to_base64(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    out
+}
+
+fn from_base64(text: &str) -> Option<Vec<u8>> {
+    let digits = text
+        .bytes()
+        .map(|c| ALPHABET.iter().position(|&a| a == c).map(|d| d as u32))
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut out = Vec::new();
+    for chunk in digits.chunks(4) {
+        if chunk.len() < 2 {
+            return None;
+        }
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &d)| n | d << (18 - 6 * i));
+        for i in 0..chunk.len() - 1 {
+            out.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(out)
+}
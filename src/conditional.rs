@@ -0,0 +1,251 @@
+use cursive::{
+    view::{Nameable, Resizable, Scrollable},
+    views::{Dialog, EditView, LinearLayout, SelectView, TextView},
+    Cursive,
+};
+use shakmaty::{san::SanPlus, variant::VariantPosition, Color, Position};
+
+use crate::lang::tr;
+use crate::logic::BoardView;
+
+// The reply to play if the opponent makes a move, and what to do after.
+#[derive(Clone)]
+pub struct Branch {
+    pub theirs: String,
+    pub reply: String,
+    pub then: Vec<Branch>,
+}
+
+// Replies set up in advance for when the opponent moves. The branches are
+// for the opponent's move with index `ply`.
+#[derive(Clone, Default)]
+pub struct Conditions {
+    pub ply: usize,
+    pub branches: Vec<Branch>,
+}
+
+impl Conditions {
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty()
+    }
+
+    // Each branch by its depth and the moves that lead to it, parents first.
+    pub fn nodes(&self) -> Vec<(usize, Vec<String>)> {
+        fn walk(branches: &[Branch], path: &[String], out: &mut Vec<(usize, Vec<String>)>) {
+            for branch in branches {
+                let mut path = path.to_vec();
+                path.extend([branch.theirs.clone(), branch.reply.clone()]);
+                out.push((path.len() / 2 - 1, path.clone()));
+                walk(&branch.then, &path, out);
+            }
+        }
+        let mut out = Vec::new();
+        walk(&self.branches, &[], &mut out);
+        out
+    }
+
+    // Every line from the root to a leaf.
+    pub fn lines(&self) -> Vec<Vec<String>> {
+        let nodes = self.nodes();
+        nodes
+            .iter()
+            .enumerate()
+            .filter(|(i, (depth, _))| nodes.get(i + 1).is_none_or(|(next, _)| next <= depth))
+            .map(|(_, (_, path))| path.clone())
+            .collect()
+    }
+
+    // Merges a line, the opponent's move then the reply in turn, into the
+    // tree. A line that answers a move differently from one already set is
+    // turned down.
+    pub fn add(&mut self, ply: usize, line: &[String]) -> Result<(), String> {
+        if self.ply != ply {
+            *self = Conditions {
+                ply,
+                branches: Vec::new(),
+            };
+        }
+        let mut branches = &mut self.branches;
+        for pair in line.chunks(2) {
+            let [theirs, reply] = pair else {
+                return Err("the line has to end with your reply".to_owned());
+            };
+            let i = match branches.iter().position(|b| b.theirs == *theirs) {
+                Some(i) if branches[i].reply != *reply => {
+                    return Err(format!(
+                        "{} is already answered with {}",
+                        theirs, branches[i].reply
+                    ))
+                }
+                Some(i) => i,
+                None => {
+                    branches.push(Branch {
+                        theirs: theirs.clone(),
+                        reply: reply.clone(),
+                        then: Vec::new(),
+                    });
+                    branches.len() - 1
+                }
+            };
+            branches = &mut branches[i].then;
+        }
+        Ok(())
+    }
+
+    // Takes out the branch the path leads to, with everything after it.
+    pub fn remove(&mut self, path: &[String]) {
+        let mut branches = &mut self.branches;
+        for (depth, pair) in path.chunks(2).enumerate() {
+            let Some(i) = branches.iter().position(|b| b.theirs == pair[0]) else {
+                return;
+            };
+            if depth + 1 == path.len() / 2 {
+                branches.remove(i);
+                return;
+            }
+            branches = &mut branches[i].then;
+        }
+    }
+
+    // The reply set for the opponent's move `san` at `ply`, if there is
+    // one. The tree moves on to follow it, or is dropped when the opponent
+    // went another way.
+    pub fn answer(&mut self, ply: usize, san: &str) -> Option<String> {
+        let branches = std::mem::take(&mut self.branches);
+        if ply != self.ply {
+            return None;
+        }
+        let branch = branches.into_iter().find(|b| b.theirs == san)?;
+        self.ply = ply + 2;
+        self.branches = branch.then;
+        Some(branch.reply)
+    }
+}
+
+// SAN moves from the opponent's turn, checked and written the way the move
+// list writes them.
+pub fn parse_line(pos: &VariantPosition, text: &str) -> Result<Vec<String>, String> {
+    let mut pos = pos.clone();
+    let mut line = Vec::new();
+    for token in text.split_whitespace() {
+        let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if token.is_empty() {
+            continue;
+        }
+        let mv = SanPlus::from_ascii(token.as_bytes())
+            .ok()
+            .and_then(|san| san.san.to_move(&pos).ok())
+            .ok_or_else(|| format!("illegal move: {}", token))?;
+        line.push(SanPlus::from_move(pos.clone(), &mv).to_string());
+        pos.play_unchecked(&mv);
+    }
+    if line.is_empty() {
+        return Err("type the opponent's move and your reply".to_owned());
+    }
+    if line.len() % 2 == 1 {
+        return Err("the line has to end with your reply".to_owned());
+    }
+    Ok(line)
+}
+
+// "If 12... Nf3, play d5", indented under the branch it follows.
+fn label(pos: &VariantPosition, depth: usize, path: &[String]) -> String {
+    let number = pos.fullmoves().get() as usize + depth;
+    let dots = if pos.turn() == Color::White {
+        "."
+    } else {
+        "..."
+    };
+    let theirs = &path[path.len() - 2];
+    let reply = &path[path.len() - 1];
+    format!(
+        "{:indent$}If {}{} {}, play {}",
+        "",
+        number,
+        dots,
+        theirs,
+        reply,
+        indent = 2 * depth
+    )
+}
+
+fn refresh(siv: &mut Cursive) {
+    let found = siv.call_on_name("board", |board: &mut BoardView| {
+        (board.position().clone(), board.conditions().nodes())
+    });
+    let Some((pos, nodes)) = found else {
+        return;
+    };
+    siv.call_on_name("conditions", |v: &mut SelectView<Vec<String>>| {
+        v.clear();
+        for (depth, path) in nodes {
+            v.add_item(label(&pos, depth, &path), path);
+        }
+    });
+}
+
+fn add_line(siv: &mut Cursive) {
+    let text = siv
+        .call_on_name("condition_line", |v: &mut EditView| v.get_content())
+        .unwrap();
+    let added = siv
+        .call_on_name("board", |board: &mut BoardView| board.plan(&text))
+        .unwrap_or(Ok(()));
+    match added {
+        Ok(()) => {
+            siv.call_on_name("condition_line", |v: &mut EditView| v.set_content(""));
+            refresh(siv);
+        }
+        Err(e) => siv.add_layer(Dialog::info(e)),
+    }
+}
+
+fn remove_selected(siv: &mut Cursive) {
+    let path = siv
+        .call_on_name("conditions", |v: &mut SelectView<Vec<String>>| {
+            v.selection()
+        })
+        .flatten();
+    if let Some(path) = path {
+        siv.call_on_name("board", |board: &mut BoardView| board.unplan(&path));
+        refresh(siv);
+    }
+}
+
+// Replies are only set while the opponent is to move, so the tree always
+// starts from the position on the board.
+pub fn show(siv: &mut Cursive) {
+    let waiting = siv.call_on_name("board", |board: &mut BoardView| board.can_plan());
+    match waiting {
+        Some(Ok(())) => {}
+        Some(Err(e)) => return siv.add_layer(Dialog::info(e)),
+        None => return,
+    }
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Conditional Moves"))
+            .content(
+                LinearLayout::vertical()
+                    .child(
+                        SelectView::<Vec<String>>::new()
+                            .with_name("conditions")
+                            .scrollable()
+                            .min_size((40, 3))
+                            .max_height(12),
+                    )
+                    .child(TextView::new(
+                        "\nTheir move and your reply, then more pairs\nto follow, as in: Nf3 d5 c4 e6",
+                    ))
+                    .child(
+                        EditView::new()
+                            .on_submit(|s, _| add_line(s))
+                            .with_name("condition_line")
+                            .fixed_width(40),
+                    ),
+            )
+            .button(tr("Add"), add_line)
+            .button(tr("Remove"), remove_selected)
+            .dismiss_button(tr("Close")),
+    );
+    refresh(siv);
+}
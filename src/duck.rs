@@ -0,0 +1,410 @@
+use cursive::{
+    direction::Direction,
+    event::{Event, EventResult, Key, MouseEvent},
+    theme::{BaseColor, Color, ColorStyle},
+    view::CannotFocus,
+    views::{Dialog, Panel},
+    Cursive, Printer, Vec2,
+};
+use rand::{rngs::StdRng, seq::IteratorRandom, seq::SliceRandom, SeedableRng};
+use shakmaty::{attacks, Bitboard, Board, Color as CColor, File, Rank, Role, Square};
+
+use crate::lang::tr;
+use crate::logic::{color_name, game_over, piece_color, piece_to_char, square_at};
+use crate::settings::{self, BoardTheme, PieceStyle};
+
+const DUCK: char = '\u{25C6}';
+const ASCII_DUCK: char = '*';
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct DuckMove {
+    from: Square,
+    to: Square,
+    promotion: Option<Role>,
+}
+
+struct DuckGame {
+    board: Board,
+    turn: CColor,
+    castling: Bitboard,
+    ep_square: Option<Square>,
+    duck: Option<Square>,
+}
+
+impl DuckGame {
+    fn new() -> Self {
+        DuckGame {
+            board: Board::default(),
+            turn: CColor::White,
+            castling: Bitboard::CORNERS,
+            ep_square: None,
+            duck: None,
+        }
+    }
+
+    fn duck(&self) -> Bitboard {
+        self.duck.map_or(Bitboard::EMPTY, Bitboard::from_square)
+    }
+
+    // There is no check in Duck Chess, so every move that respects the duck
+    // as a blocker is playable, including ones that leave the king en prise.
+    fn moves(&self) -> Vec<DuckMove> {
+        let us = self.board.by_color(self.turn);
+        let them = self.board.by_color(!self.turn);
+        let blockers = self.board.occupied() | self.duck();
+        let mut moves = Vec::new();
+
+        for from in us {
+            let piece = self.board.piece_at(from).unwrap();
+
+            let targets = if piece.role == Role::Pawn {
+                let ep = self
+                    .ep_square
+                    .map_or(Bitboard::EMPTY, Bitboard::from_square);
+                let mut targets = attacks::pawn_attacks(self.turn, from) & (them | ep);
+                let step = self.turn.fold_wb(8, -8);
+                if let Some(one) = from.offset(step).filter(|&sq| !blockers.contains(sq)) {
+                    targets.add(one);
+                    if from.rank() == self.turn.relative_rank(Rank::Second) {
+                        if let Some(two) = one.offset(step).filter(|&sq| !blockers.contains(sq)) {
+                            targets.add(two);
+                        }
+                    }
+                }
+                targets & !self.duck()
+            } else {
+                attacks::attacks(from, piece, blockers) & !us & !self.duck()
+            };
+
+            for to in targets {
+                if piece.role == Role::Pawn && to.rank() == (!self.turn).backrank() {
+                    for role in [Role::Queen, Role::Rook, Role::Bishop, Role::Knight] {
+                        moves.push(DuckMove {
+                            from,
+                            to,
+                            promotion: Some(role),
+                        });
+                    }
+                } else {
+                    moves.push(DuckMove {
+                        from,
+                        to,
+                        promotion: None,
+                    });
+                }
+            }
+
+            if piece.role == Role::King {
+                moves.extend(self.castling_moves(from, blockers));
+            }
+        }
+
+        moves
+    }
+
+    fn castling_moves(&self, king: Square, blockers: Bitboard) -> Vec<DuckMove> {
+        let rank = self.turn.backrank();
+        if king != Square::from_coords(File::E, rank) {
+            return Vec::new();
+        }
+
+        [(File::H, File::G), (File::A, File::C)]
+            .into_iter()
+            .filter(|&(rook_file, _)| {
+                let rook = Square::from_coords(rook_file, rank);
+                self.castling.contains(rook)
+                    && self.board.piece_at(rook) == Some(Role::Rook.of(self.turn))
+                    && (attacks::between(king, rook) & blockers).is_empty()
+            })
+            .map(|(_, king_file)| DuckMove {
+                from: king,
+                to: Square::from_coords(king_file, rank),
+                promotion: None,
+            })
+            .collect()
+    }
+
+    fn play(&mut self, mv: DuckMove) -> bool {
+        let piece = self.board.remove_piece_at(mv.from).unwrap();
+        let mut captured = self.board.remove_piece_at(mv.to);
+
+        if piece.role == Role::Pawn && Some(mv.to) == self.ep_square && captured.is_none() {
+            captured = self
+                .board
+                .remove_piece_at(Square::from_coords(mv.to.file(), mv.from.rank()));
+        }
+
+        if piece.role == Role::King && mv.from.distance(mv.to) == 2 {
+            let rank = mv.from.rank();
+            let (rook_from, rook_to) = if mv.to.file() == File::G {
+                (File::H, File::F)
+            } else {
+                (File::A, File::D)
+            };
+            let rook = self
+                .board
+                .remove_piece_at(Square::from_coords(rook_from, rank))
+                .unwrap();
+            self.board
+                .set_piece_at(Square::from_coords(rook_to, rank), rook);
+        }
+
+        self.board.set_piece_at(
+            mv.to,
+            mv.promotion.map_or(piece, |role| role.of(piece.color)),
+        );
+
+        self.ep_square = (piece.role == Role::Pawn && mv.from.distance(mv.to) == 2).then(|| {
+            Square::from_coords(
+                mv.from.file(),
+                mv.from.rank().offset(self.turn.fold_wb(1, -1)).unwrap(),
+            )
+        });
+
+        self.castling.discard(mv.from);
+        self.castling.discard(mv.to);
+        if piece.role == Role::King {
+            self.castling
+                .discard(Bitboard::from_rank(self.turn.backrank()));
+        }
+
+        captured.is_some_and(|p| p.role == Role::King)
+    }
+
+    fn duck_squares(&self) -> Bitboard {
+        !self.board.occupied() & !self.duck()
+    }
+
+    fn place_duck(&mut self, sq: Square) {
+        self.duck = Some(sq);
+        self.turn = !self.turn;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Moving,
+    PlacingDuck,
+}
+
+pub struct DuckView {
+    game: DuckGame,
+    stage: Stage,
+    focused: Option<Square>,
+    highlighted: Option<Square>,
+    rng: StdRng,
+    style: PieceStyle,
+    theme: BoardTheme,
+}
+
+impl DuckView {
+    pub fn new(style: PieceStyle, theme: BoardTheme) -> Self {
+        DuckView {
+            style,
+            theme,
+            game: DuckGame::new(),
+            stage: Stage::Moving,
+            focused: None,
+            highlighted: None,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    fn win(winner: CColor, reason: &str) -> EventResult {
+        let msg = format!("Game Over. {} wins {}.", color_name(winner), reason);
+        EventResult::with_cb(move |s| game_over(s, &msg, Some(winner)))
+    }
+
+    fn process_click(&mut self, sq: Square) -> EventResult {
+        match (self.stage, self.focused) {
+            (Stage::PlacingDuck, _) => {
+                if !self.game.duck_squares().contains(sq) {
+                    return EventResult::Consumed(None);
+                }
+                self.game.place_duck(sq);
+                self.stage = Stage::Moving;
+                self.cpu_reply()
+            }
+            (Stage::Moving, Some(from)) => {
+                let mv = self
+                    .game
+                    .moves()
+                    .into_iter()
+                    .find(|m| m.from == from && m.to == sq);
+                self.focused = None;
+
+                match mv {
+                    Some(mv) => {
+                        if self.game.play(mv) {
+                            return Self::win(self.game.turn, "by capturing the king");
+                        }
+                        self.stage = Stage::PlacingDuck;
+                        EventResult::Consumed(None)
+                    }
+                    None if self.game.board.by_color(self.game.turn).contains(sq) => {
+                        self.focused = Some(sq);
+                        EventResult::Consumed(None)
+                    }
+                    None => EventResult::Consumed(None),
+                }
+            }
+            (Stage::Moving, None) => {
+                if self.game.board.by_color(self.game.turn).contains(sq) {
+                    self.focused = Some(sq);
+                }
+                EventResult::Consumed(None)
+            }
+        }
+    }
+
+    fn cpu_reply(&mut self) -> EventResult {
+        let cpu = self.game.turn;
+
+        let mv = match self.game.moves().choose(&mut self.rng) {
+            Some(&mv) => mv,
+            None => return Self::win(cpu, "with no moves left"),
+        };
+        if self.game.play(mv) {
+            return Self::win(cpu, "by capturing the king");
+        }
+
+        let duck = self
+            .game
+            .duck_squares()
+            .into_iter()
+            .choose(&mut self.rng)
+            .unwrap();
+        self.game.place_duck(duck);
+
+        if self.game.moves().is_empty() {
+            return Self::win(self.game.turn, "with no moves left");
+        }
+
+        EventResult::Consumed(None)
+    }
+}
+
+impl cursive::view::View for DuckView {
+    fn draw(&self, printer: &Printer) {
+        let targets: Bitboard = match (self.stage, self.focused) {
+            (Stage::Moving, Some(from)) => self
+                .game
+                .moves()
+                .iter()
+                .filter(|m| m.from == from)
+                .map(|m| m.to)
+                .collect(),
+            _ => Bitboard::EMPTY,
+        };
+
+        printer.with_color(ColorStyle::secondary(), |printer| {
+            for i in 0..8 {
+                printer.print((0, i), &Rank::new(7 - i as u32).char().to_string());
+                printer.print((3 + 3 * i, 8), &File::new(i as u32).char().to_string());
+            }
+        });
+
+        for sq in Bitboard::FULL {
+            let x = 2 + 3 * usize::from(sq.file());
+            let y = 7 - usize::from(sq.rank());
+
+            let text = if self.game.duck == Some(sq) {
+                let duck = match self.style {
+                    PieceStyle::Unicode | PieceStyle::Solid => DUCK,
+                    PieceStyle::Ascii => ASCII_DUCK,
+                };
+                format!(" {} ", duck)
+            } else {
+                match self.game.board.piece_at(sq) {
+                    Some(p) => format!(" {} ", piece_to_char(p, self.style)),
+                    None => "   ".to_owned(),
+                }
+            };
+
+            let color = if self.focused == Some(sq) {
+                Color::Dark(BaseColor::Yellow)
+            } else if self.highlighted == Some(sq) {
+                Color::Light(BaseColor::Yellow)
+            } else if targets.contains(sq) {
+                Color::Dark(BaseColor::Cyan)
+            } else {
+                self.theme.square(sq.is_dark())
+            };
+
+            let piece = self.game.board.piece_at(sq);
+            printer.with_color(
+                ColorStyle::new(piece_color(piece, self.style), color),
+                |printer| printer.print((x, y), &text),
+            );
+        }
+
+        let status = match self.stage {
+            Stage::Moving => "Move a piece",
+            Stage::PlacingDuck => "Place the duck on an empty square",
+        };
+        printer.print((0, 10), status);
+    }
+
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::Consumed(None))
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        match event {
+            Event::Mouse {
+                offset,
+                position,
+                event: MouseEvent::Press(_),
+            } => match square_at(position, offset + (2, 0), CColor::White, (3, 1)) {
+                Some(sq) => {
+                    self.highlighted = Some(sq);
+                    self.process_click(sq)
+                }
+                None => EventResult::Ignored,
+            },
+            Event::Key(key @ (Key::Left | Key::Right | Key::Up | Key::Down)) => {
+                let sq = self.highlighted.unwrap_or(Square::E2);
+                let (df, dr) = match key {
+                    Key::Right => (1, 0),
+                    Key::Left => (-1, 0),
+                    Key::Up => (0, 1),
+                    _ => (0, -1),
+                };
+                self.highlighted = match (sq.file().offset(df), sq.rank().offset(dr)) {
+                    (Some(file), Some(rank)) if self.highlighted.is_some() => {
+                        Some(Square::from_coords(file, rank))
+                    }
+                    _ => Some(sq),
+                };
+                EventResult::Consumed(None)
+            }
+            Event::Char(' ') => match self.highlighted {
+                Some(sq) => self.process_click(sq),
+                None => {
+                    self.highlighted = Some(Square::E2);
+                    EventResult::Consumed(None)
+                }
+            },
+            _ => EventResult::Ignored,
+        }
+    }
+
+    fn required_size(&mut self, _: Vec2) -> Vec2 {
+        Vec2::new(35, 11)
+    }
+}
+
+pub fn new_game(siv: &mut Cursive) {
+    let settings = settings::current(siv);
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Duck Chess"))
+            .content(Panel::new(DuckView::new(
+                settings.piece_style,
+                settings.board_theme,
+            )))
+            .button(tr("Quit Game"), |s| {
+                s.pop_layer();
+            }),
+    );
+}
@@ -0,0 +1,311 @@
+use std::{fs, path::Path};
+
+use shakmaty::{
+    san::SanPlus,
+    zobrist::{Zobrist64, ZobristHash},
+    Chess, Color, EnPassantMode, Move, Outcome, Position,
+};
+
+use crate::epd::Epd;
+use crate::pgn;
+use crate::settings::Settings;
+use crate::spectate::Player;
+use crate::uci::{self, UciEngine};
+
+// Games are adjudicated drawn after this many plies.
+const MAX_PLIES: usize = 300;
+// The SPRT's error rates, for H1 accepted wrongly and for H0.
+const ALPHA: f64 = 0.05;
+const BETA: f64 = 0.05;
+
+// "depth=3" for the built-in search, or "engine=/path/to/engine" with
+// "skill=N", "name=..." and any UCI options, as in
+// "engine=stockfish,skill=10,Hash=64", comma-separated.
+fn player(spec: &str) -> Result<Player, String> {
+    let (mut path, mut depth, mut skill, mut name) = (None, None, uci::MAX_SKILL, None);
+    let mut options = Vec::new();
+    for field in spec.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+        let (key, value) = field
+            .split_once('=')
+            .ok_or_else(|| format!("no \"=\" in \"{}\"", field))?;
+        let number = || {
+            value
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| format!("{} expects a number", key))
+        };
+        match key.trim() {
+            "engine" => path = Some(value.trim().to_owned()),
+            "depth" => depth = Some(number()?),
+            "skill" => skill = number()?.min(u32::from(uci::MAX_SKILL)) as u8,
+            "name" => name = Some(value.trim().to_owned()),
+            option => options.push((option.to_owned(), value.trim().to_owned())),
+        }
+    }
+
+    let Some(path) = path else {
+        if !options.is_empty() {
+            return Err(format!("UCI options need an engine: {}", spec));
+        }
+        let values = Settings::load().piece_values;
+        return Ok(Player::BuiltIn(depth.unwrap_or(3), values));
+    };
+    let engine = UciEngine::spawn(&path, skill, "", &options)?;
+    if let Some((option, _)) = options
+        .iter()
+        .find(|(option, _)| !engine.options.iter().any(|o| &o.name == option))
+    {
+        return Err(format!("{} has no option {}", path, option));
+    }
+    let name = name.unwrap_or_else(|| {
+        Path::new(&path)
+            .file_name()
+            .map_or(path.clone(), |name| name.to_string_lossy().into_owned())
+    });
+    Ok(Player::Engine(name, engine))
+}
+
+// Plays a game out from the start, asking `choose` for each move along with
+// the plies played so far, and adjudicates repetitions, the fifty-move rule
+// and overlong games as draws.
+pub fn play_out(
+    start: &Chess,
+    mut choose: impl FnMut(&Chess, usize) -> Option<Move>,
+) -> (Vec<String>, &'static str) {
+    let mut pos = start.clone();
+    let mut sans = Vec::new();
+    let mut seen = vec![pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal)];
+
+    let result = loop {
+        match pos.outcome() {
+            Some(Outcome::Decisive {
+                winner: Color::White,
+            }) => break "1-0",
+            Some(Outcome::Decisive { .. }) => break "0-1",
+            Some(Outcome::Draw) => break "1/2-1/2",
+            None => {}
+        }
+        let hash = seen.last().unwrap();
+        if sans.len() >= MAX_PLIES
+            || pos.halfmoves() >= 100
+            || seen.iter().filter(|&h| h == hash).count() >= 3
+        {
+            break "1/2-1/2";
+        }
+
+        // A player that fails or plays an illegal move loses the game.
+        let mv = match choose(&pos, sans.len()) {
+            Some(mv) if pos.is_legal(&mv) => mv,
+            _ => break pos.turn().fold_wb("0-1", "1-0"),
+        };
+        sans.push(SanPlus::from_move_and_play_unchecked(&mut pos, &mv).to_string());
+        seen.push(pos.zobrist_hash(EnPassantMode::Legal));
+    };
+    (sans, result)
+}
+
+fn play_game(players: [&mut Player; 2], start: &Chess) -> (Vec<String>, &'static str) {
+    let [white, black] = players;
+    play_out(start, |pos, _| {
+        let mover = if pos.turn() == Color::White {
+            &mut *white
+        } else {
+            &mut *black
+        };
+        mover.choose(pos).ok()
+    })
+}
+
+// Wins, draws and losses, from the first player's side.
+#[derive(Default)]
+struct Tally {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+}
+
+impl Tally {
+    fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    fn score(&self) -> f64 {
+        (f64::from(self.wins) + f64::from(self.draws) / 2.0) / f64::from(self.games())
+    }
+
+    // The spread of one game's score around the mean. Until every kind of
+    // result has come up, each counts half a game more, so a clean sweep
+    // does not look certain.
+    fn variance(&self) -> f64 {
+        let extra = match self.wins.min(self.draws).min(self.losses) {
+            0 => 0.5,
+            _ => 0.0,
+        };
+        let [w, d, l] = [self.wins, self.draws, self.losses].map(|n| f64::from(n) + extra);
+        let n = w + d + l;
+        let s = (w + d / 2.0) / n;
+        (w * (1.0 - s).powi(2) + d * (0.5 - s).powi(2) + l * s.powi(2)) / n
+    }
+
+    // The Elo difference and its 95% margin, from the logistic model.
+    fn elo(&self) -> Option<(f64, f64)> {
+        let s = self.score();
+        if self.games() == 0 || s <= 0.0 || s >= 1.0 {
+            return None;
+        }
+        let margin = 1.96 * (self.variance() / f64::from(self.games())).sqrt();
+        let low = elo((s - margin).max(1e-6));
+        let high = elo((s + margin).min(1.0 - 1e-6));
+        Some((elo(s), (high - low) / 2.0))
+    }
+
+    // The log-likelihood ratio of elo1 over elo0, by the normal
+    // approximation of the game scores.
+    fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+        let variance = self.variance();
+        let (s0, s1) = (expected(elo0), expected(elo1));
+        f64::from(self.games()) * (s1 - s0) * (2.0 * self.score() - s0 - s1) / (2.0 * variance)
+    }
+}
+
+// Adding zero turns an even score's -0 into 0.
+fn elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10() + 0.0
+}
+
+fn expected(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn sprt_bounds() -> (f64, f64) {
+    ((BETA / (1.0 - ALPHA)).ln(), ((1.0 - BETA) / ALPHA).ln())
+}
+
+fn load_openings(path: &str) -> Result<Vec<(String, Chess)>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let openings: Vec<(String, Chess)> = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            let epd = Epd::parse(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            Ok((
+                epd.id.unwrap_or_else(|| format!("#{}", i + 1)),
+                epd.position,
+            ))
+        })
+        .collect::<Result<_, String>>()?;
+    if openings.is_empty() {
+        return Err(format!("No positions in {}", path));
+    }
+    Ok(openings)
+}
+
+// Each opening is played twice, once with each player as White, until
+// `games` are played or the SPRT between elo0 and elo1 is decided. The
+// report ends with the tally, an Elo estimate and the test's verdict.
+pub fn run(
+    first: &str,
+    second: &str,
+    openings: &str,
+    games: Option<u32>,
+    (elo0, elo1): (f64, f64),
+    pgn_out: Option<&str>,
+) -> Result<String, String> {
+    if elo1 <= elo0 {
+        return Err("--elo1 must be above --elo0".to_owned());
+    }
+    let openings = load_openings(openings)?;
+    let mut players = [
+        player(first).map_err(|e| format!("first: {}", e))?,
+        player(second).map_err(|e| format!("second: {}", e))?,
+    ];
+    let names = [players[0].name(), players[1].name()];
+    let games = games.unwrap_or(2 * openings.len() as u32);
+    let (lower, upper) = sprt_bounds();
+
+    let mut tally = Tally::default();
+    let mut report = String::new();
+    let mut pgns = String::new();
+    let mut verdict = "undecided";
+    for round in 0..games {
+        let (id, start) = &openings[round as usize / 2 % openings.len()];
+        let swapped = round % 2 == 1;
+        let [a, b] = &mut players;
+        let (sans, result) = if swapped {
+            play_game([b, a], start)
+        } else {
+            play_game([a, b], start)
+        };
+        let [white, black] = if swapped {
+            [&names[1], &names[0]]
+        } else {
+            [&names[0], &names[1]]
+        };
+        match (result, swapped) {
+            ("1-0", false) | ("0-1", true) => tally.wins += 1,
+            ("1-0", true) | ("0-1", false) => tally.losses += 1,
+            _ => tally.draws += 1,
+        }
+        let line = format!(
+            "Game {} ({}): {} - {} {}",
+            round + 1,
+            id,
+            white,
+            black,
+            result
+        );
+        eprintln!("{}", line);
+        report += &line;
+        report.push('\n');
+
+        let mut tags = pgn::seven_tags("Engine test", white.clone(), black.clone(), result);
+        tags[3].1 = (round + 1).to_string();
+        let fen = shakmaty::fen::Fen::from_position(start.clone(), EnPassantMode::Legal);
+        tags.push(("FEN", fen.to_string()));
+        tags.push(("SetUp", "1".to_owned()));
+        pgns += &pgn::write(tags, &pgn::numbered(start, &sans), result);
+        pgns.push('\n');
+
+        let llr = tally.llr(elo0, elo1);
+        if llr >= upper {
+            verdict = "H1 accepted";
+            break;
+        }
+        if llr <= lower {
+            verdict = "H0 accepted";
+            break;
+        }
+    }
+
+    if let Some(path) = pgn_out {
+        fs::write(path, &pgns).map_err(|e| format!("Could not write {}: {}", path, e))?;
+    }
+    report += &format!(
+        "\n{} vs {}: {} games, +{} ={} -{}, score {:.1}%\n",
+        names[0],
+        names[1],
+        tally.games(),
+        tally.wins,
+        tally.draws,
+        tally.losses,
+        100.0 * tally.score()
+    );
+    report += &match tally.elo() {
+        Some((elo, margin)) => format!("Elo {:+.1} +/- {:.1}\n", elo, margin),
+        None => "Elo not estimated: one side scored everything\n".to_owned(),
+    };
+    report += &format!(
+        "SPRT elo0 {} elo1 {}: LLR {:.2} ({:.2}, {:.2}), {}",
+        elo0,
+        elo1,
+        tally.llr(elo0, elo1),
+        lower,
+        upper,
+        verdict
+    );
+    Ok(report)
+}
@@ -0,0 +1,138 @@
+// A small table of well-known lines; the longest one the game starts with
+// names the opening.
+const OPENINGS: &[(&str, &str, &str)] = &[
+    ("A00", "Polish Opening", "b4"),
+    ("A00", "Grob Opening", "g4"),
+    ("A01", "Nimzo-Larsen Attack", "b3"),
+    ("A02", "Bird's Opening", "f4"),
+    ("A04", "Reti Opening", "Nf3"),
+    ("A05", "Reti Opening", "Nf3 Nf6"),
+    ("A06", "Reti Opening", "Nf3 d5"),
+    ("A10", "English Opening", "c4"),
+    ("A15", "English: Anglo-Indian", "c4 Nf6"),
+    ("A20", "English: King's English", "c4 e5"),
+    ("A30", "English: Symmetrical", "c4 c5"),
+    ("A40", "Queen's Pawn Game", "d4"),
+    ("A43", "Old Benoni Defense", "d4 c5"),
+    ("A45", "Indian Defense", "d4 Nf6"),
+    ("A50", "Indian Defense", "d4 Nf6 c4"),
+    ("A56", "Benoni Defense", "d4 Nf6 c4 c5"),
+    ("A57", "Benko Gambit", "d4 Nf6 c4 c5 d5 b5"),
+    ("A80", "Dutch Defense", "d4 f5"),
+    ("B00", "King's Pawn Opening", "e4"),
+    ("B01", "Scandinavian Defense", "e4 d5"),
+    ("B02", "Alekhine's Defense", "e4 Nf6"),
+    ("B06", "Modern Defense", "e4 g6"),
+    ("B07", "Pirc Defense", "e4 d6 d4 Nf6"),
+    ("B10", "Caro-Kann Defense", "e4 c6"),
+    ("B12", "Caro-Kann: Advance", "e4 c6 d4 d5 e5"),
+    ("B13", "Caro-Kann: Exchange", "e4 c6 d4 d5 exd5 cxd5"),
+    ("B20", "Sicilian Defense", "e4 c5"),
+    ("B21", "Sicilian: Smith-Morra Gambit", "e4 c5 d4 cxd4 c3"),
+    ("B22", "Sicilian: Alapin", "e4 c5 c3"),
+    ("B23", "Sicilian: Closed", "e4 c5 Nc3"),
+    ("B30", "Sicilian Defense", "e4 c5 Nf3 Nc6"),
+    ("B33", "Sicilian: Open", "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6"),
+    (
+        "B33",
+        "Sicilian: Sveshnikov",
+        "e4 c5 Nf3 Nc6 d4 cxd4 Nxd4 Nf6 Nc3 e5",
+    ),
+    ("B40", "Sicilian Defense", "e4 c5 Nf3 e6"),
+    ("B50", "Sicilian Defense", "e4 c5 Nf3 d6"),
+    ("B54", "Sicilian: Open", "e4 c5 Nf3 d6 d4 cxd4 Nxd4"),
+    (
+        "B70",
+        "Sicilian: Dragon",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6",
+    ),
+    (
+        "B90",
+        "Sicilian: Najdorf",
+        "e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6",
+    ),
+    ("C00", "French Defense", "e4 e6"),
+    ("C01", "French: Exchange", "e4 e6 d4 d5 exd5"),
+    ("C02", "French: Advance", "e4 e6 d4 d5 e5"),
+    ("C03", "French: Tarrasch", "e4 e6 d4 d5 Nd2"),
+    ("C10", "French: Paulsen", "e4 e6 d4 d5 Nc3"),
+    ("C11", "French: Classical", "e4 e6 d4 d5 Nc3 Nf6"),
+    ("C15", "French: Winawer", "e4 e6 d4 d5 Nc3 Bb4"),
+    ("C20", "King's Pawn Game", "e4 e5"),
+    ("C21", "Center Game", "e4 e5 d4"),
+    ("C23", "Bishop's Opening", "e4 e5 Bc4"),
+    ("C25", "Vienna Game", "e4 e5 Nc3"),
+    ("C30", "King's Gambit", "e4 e5 f4"),
+    ("C33", "King's Gambit Accepted", "e4 e5 f4 exf4"),
+    ("C40", "King's Knight Opening", "e4 e5 Nf3"),
+    ("C41", "Philidor Defense", "e4 e5 Nf3 d6"),
+    ("C42", "Petrov's Defense", "e4 e5 Nf3 Nf6"),
+    ("C44", "King's Pawn Game", "e4 e5 Nf3 Nc6"),
+    ("C44", "Ponziani Opening", "e4 e5 Nf3 Nc6 c3"),
+    ("C44", "Scotch Game", "e4 e5 Nf3 Nc6 d4"),
+    ("C45", "Scotch Game", "e4 e5 Nf3 Nc6 d4 exd4 Nxd4"),
+    ("C46", "Three Knights Game", "e4 e5 Nf3 Nc6 Nc3"),
+    ("C47", "Four Knights Game", "e4 e5 Nf3 Nc6 Nc3 Nf6"),
+    ("C50", "Italian Game", "e4 e5 Nf3 Nc6 Bc4"),
+    ("C50", "Giuoco Piano", "e4 e5 Nf3 Nc6 Bc4 Bc5"),
+    ("C51", "Evans Gambit", "e4 e5 Nf3 Nc6 Bc4 Bc5 b4"),
+    ("C53", "Italian: Classical", "e4 e5 Nf3 Nc6 Bc4 Bc5 c3"),
+    ("C55", "Two Knights Defense", "e4 e5 Nf3 Nc6 Bc4 Nf6"),
+    (
+        "C57",
+        "Two Knights: Knight Attack",
+        "e4 e5 Nf3 Nc6 Bc4 Nf6 Ng5",
+    ),
+    ("C60", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5"),
+    ("C65", "Ruy Lopez: Berlin Defense", "e4 e5 Nf3 Nc6 Bb5 Nf6"),
+    ("C68", "Ruy Lopez: Exchange", "e4 e5 Nf3 Nc6 Bb5 a6 Bxc6"),
+    (
+        "C70",
+        "Ruy Lopez: Morphy Defense",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4",
+    ),
+    ("C78", "Ruy Lopez", "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O"),
+    (
+        "C80",
+        "Ruy Lopez: Open",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Nxe4",
+    ),
+    (
+        "C84",
+        "Ruy Lopez: Closed",
+        "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O Be7",
+    ),
+    ("D00", "Queen's Pawn Game", "d4 d5"),
+    ("D00", "London System", "d4 d5 Bf4"),
+    ("D06", "Queen's Gambit", "d4 d5 c4"),
+    ("D07", "Chigorin Defense", "d4 d5 c4 Nc6"),
+    ("D08", "Albin Countergambit", "d4 d5 c4 e5"),
+    ("D10", "Slav Defense", "d4 d5 c4 c6"),
+    ("D20", "Queen's Gambit Accepted", "d4 d5 c4 dxc4"),
+    ("D30", "Queen's Gambit Declined", "d4 d5 c4 e6"),
+    ("D43", "Semi-Slav Defense", "d4 d5 c4 c6 Nf3 Nf6 Nc3 e6"),
+    ("D80", "Grunfeld Defense", "d4 Nf6 c4 g6 Nc3 d5"),
+    ("E00", "Indian Defense", "d4 Nf6 c4 e6"),
+    ("E01", "Catalan Opening", "d4 Nf6 c4 e6 g3"),
+    ("E11", "Bogo-Indian Defense", "d4 Nf6 c4 e6 Nf3 Bb4"),
+    ("E12", "Queen's Indian Defense", "d4 Nf6 c4 e6 Nf3 b6"),
+    ("E20", "Nimzo-Indian Defense", "d4 Nf6 c4 e6 Nc3 Bb4"),
+    ("E60", "King's Indian Defense", "d4 Nf6 c4 g6"),
+    ("E61", "King's Indian Defense", "d4 Nf6 c4 g6 Nc3 Bg7"),
+];
+
+// Takes the game's SAN moves from the standard starting position.
+pub fn name(sans: &[String]) -> Option<(&'static str, &'static str)> {
+    let played: Vec<&str> = sans
+        .iter()
+        .map(|san| san.trim_end_matches(['+', '#']))
+        .collect();
+    OPENINGS
+        .iter()
+        .filter(|(_, _, line)| {
+            let line: Vec<&str> = line.split_whitespace().collect();
+            played.starts_with(&line)
+        })
+        .max_by_key(|(_, _, line)| line.split_whitespace().count())
+        .map(|&(eco, name, _)| (eco, name))
+}
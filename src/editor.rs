@@ -0,0 +1,252 @@
+use cursive::{
+    direction::Direction,
+    event::{Event, EventResult, Key, MouseEvent},
+    theme::{BaseColor, Color, ColorStyle},
+    view::{CannotFocus, Nameable},
+    views::{Checkbox, Dialog, LinearLayout, PaddedView, Panel, RadioGroup, TextView},
+    Cursive, Printer, Vec2,
+};
+use shakmaty::{
+    fen::Fen, variant::Variant, Bitboard, Board, Color as CColor, File, Piece, Rank, Role, Setup,
+    Square,
+};
+
+use crate::lang::tr;
+use crate::logic::{self, parse_variant_fen, piece_color, piece_to_char, square_at};
+use crate::settings::{self, BoardTheme, PieceStyle};
+
+const CASTLES: [(&str, Square); 4] = [
+    ("White O-O", Square::H1),
+    ("White O-O-O", Square::A1),
+    ("Black O-O", Square::H8),
+    ("Black O-O-O", Square::A8),
+];
+
+pub struct EditorView {
+    board: Board,
+    cursor: Square,
+    brush: Option<Piece>,
+    style: PieceStyle,
+    theme: BoardTheme,
+}
+
+impl EditorView {
+    fn new(board: Board, style: PieceStyle, theme: BoardTheme) -> Self {
+        EditorView {
+            board,
+            cursor: Square::E1,
+            brush: None,
+            style,
+            theme,
+        }
+    }
+
+    // Clicking a square holding the brush piece empties it again.
+    fn paint(&mut self, sq: Square) {
+        self.cursor = sq;
+        match self.brush {
+            Some(piece) if self.board.piece_at(sq) != Some(piece) => {
+                self.board.set_piece_at(sq, piece)
+            }
+            _ => {
+                self.board.discard_piece_at(sq);
+            }
+        }
+    }
+}
+
+impl cursive::view::View for EditorView {
+    fn draw(&self, printer: &Printer) {
+        printer.with_color(ColorStyle::secondary(), |printer| {
+            for i in 0..8 {
+                printer.print((0, i), &Rank::new(7 - i as u32).char().to_string());
+                printer.print((3 + 3 * i, 8), &File::new(i as u32).char().to_string());
+            }
+        });
+
+        for sq in Bitboard::FULL {
+            let x = 2 + 3 * usize::from(sq.file());
+            let y = 7 - usize::from(sq.rank());
+
+            let text = match self.board.piece_at(sq) {
+                Some(p) => format!(" {} ", piece_to_char(p, self.style)),
+                None => "   ".to_owned(),
+            };
+
+            let color = if self.cursor == sq {
+                Color::Light(BaseColor::Yellow)
+            } else {
+                self.theme.square(sq.is_dark())
+            };
+
+            let piece = self.board.piece_at(sq);
+            printer.with_color(
+                ColorStyle::new(piece_color(piece, self.style), color),
+                |printer| printer.print((x, y), &text),
+            );
+        }
+
+        let brush = match self.brush {
+            Some(p) => piece_to_char(p, self.style).to_string(),
+            None => "eraser".to_owned(),
+        };
+        printer.print((0, 10), &format!("Brush: {}", brush));
+    }
+
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::Consumed(None))
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        match event {
+            Event::Mouse {
+                offset,
+                position,
+                event: MouseEvent::Press(_),
+            } => match square_at(position, offset + (2, 0), CColor::White, (3, 1)) {
+                Some(sq) => {
+                    self.paint(sq);
+                    EventResult::Consumed(None)
+                }
+                None => EventResult::Ignored,
+            },
+            Event::Key(key @ (Key::Left | Key::Right | Key::Up | Key::Down)) => {
+                let (df, dr) = match key {
+                    Key::Right => (1, 0),
+                    Key::Left => (-1, 0),
+                    Key::Up => (0, 1),
+                    _ => (0, -1),
+                };
+                if let (Some(file), Some(rank)) =
+                    (self.cursor.file().offset(df), self.cursor.rank().offset(dr))
+                {
+                    self.cursor = Square::from_coords(file, rank);
+                }
+                EventResult::Consumed(None)
+            }
+            Event::Char(' ') => {
+                self.paint(self.cursor);
+                EventResult::Consumed(None)
+            }
+            Event::Key(Key::Del | Key::Backspace) => {
+                self.board.discard_piece_at(self.cursor);
+                EventResult::Consumed(None)
+            }
+            Event::Char('0') => {
+                self.brush = None;
+                EventResult::Consumed(None)
+            }
+            Event::Char(c) => match Piece::from_char(c) {
+                Some(piece) => {
+                    self.brush = Some(piece);
+                    self.board.set_piece_at(self.cursor, piece);
+                    EventResult::Consumed(None)
+                }
+                None => EventResult::Ignored,
+            },
+            _ => EventResult::Ignored,
+        }
+    }
+
+    fn required_size(&mut self, _: Vec2) -> Vec2 {
+        Vec2::new(26, 11)
+    }
+}
+
+fn setup(s: &mut Cursive, turn: CColor) -> Setup {
+    let board = s
+        .call_on_name("editor", |v: &mut EditorView| v.board.clone())
+        .unwrap();
+    let castling_rights = CASTLES
+        .into_iter()
+        .filter(|&(name, rook)| {
+            // Rights without the king and rook at home are dropped rather
+            // than rejected.
+            let color = CColor::from_white(rook.rank() == Rank::First);
+            let king = Square::from_coords(File::E, rook.rank());
+            board.piece_at(rook) == Some(Role::Rook.of(color))
+                && board.piece_at(king) == Some(Role::King.of(color))
+                && s.call_on_name(name, |c: &mut Checkbox| c.is_checked())
+                    .unwrap_or(false)
+        })
+        .map(|(_, rook)| rook)
+        .collect();
+    Setup {
+        board,
+        turn,
+        castling_rights,
+        ..Setup::default()
+    }
+}
+
+pub fn show_editor(siv: &mut Cursive, start: Setup) {
+    let settings = settings::current(siv);
+    let mut turn = RadioGroup::new();
+
+    let mut castling = LinearLayout::vertical().child(TextView::new("Castling:"));
+    for (name, rook) in CASTLES {
+        castling.add_child(
+            LinearLayout::horizontal()
+                .child(
+                    Checkbox::new()
+                        .with_checked(start.castling_rights.contains(rook))
+                        .with_name(name),
+                )
+                .child(TextView::new(format!(" {}", name))),
+        );
+    }
+
+    let mut side = LinearLayout::vertical().child(TextView::new("To move:"));
+    for color in CColor::ALL {
+        let button = turn.button(color, logic::color_name(color));
+        side.add_child(if color == start.turn {
+            button.selected()
+        } else {
+            button
+        });
+    }
+    side.add_child(TextView::new(""));
+    side.add_child(castling);
+
+    let hint = "Type a letter (PNBRQK, lowercase for Black) to place a piece,\n\
+                Space or a click to paint it again, 0 for the eraser, Del to clear.";
+
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Set Up Position"))
+            .content(
+                LinearLayout::vertical()
+                    .child(
+                        LinearLayout::horizontal()
+                            .child(Panel::new(
+                                EditorView::new(
+                                    start.board,
+                                    settings.piece_style,
+                                    settings.board_theme,
+                                )
+                                .with_name("editor"),
+                            ))
+                            .child(PaddedView::lrtb(2, 0, 0, 0, side)),
+                    )
+                    .child(TextView::new(hint)),
+            )
+            .button(tr("Clear"), |s| {
+                s.call_on_name("editor", |v: &mut EditorView| v.board = Board::empty());
+            })
+            .button(tr("Reset"), |s| {
+                s.call_on_name("editor", |v: &mut EditorView| v.board = Board::default());
+            })
+            .button(tr("Start"), move |s| {
+                let setup = setup(s, *turn.selection());
+                let fen = Fen::from_setup(setup).to_string();
+                match parse_variant_fen(Variant::Chess, &fen) {
+                    Ok(pos) => {
+                        s.pop_layer();
+                        logic::new_game(s, pos, "Play As");
+                    }
+                    Err(e) => s.add_layer(Dialog::info(format!("{}\n\n{}", e, fen))),
+                }
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
@@ -0,0 +1,344 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+    time::Duration,
+};
+
+use shakmaty::{
+    fen::Fen, variant::VariantPosition, Color, EnPassantMode, Move, Outcome, Position, Role, Square,
+};
+
+use crate::logic::PieceValues;
+
+pub const MATE: i32 = 100_000;
+const NODE_LIMIT: u64 = 300_000;
+
+#[rustfmt::skip]
+const PAWN: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+// Opened by --engine-log. Each move the built-in search picks for the CPU
+// adds a line.
+static LOG: Mutex<Option<File>> = Mutex::new(None);
+
+pub fn open_log(path: &Path) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    *LOG.lock().unwrap_or_else(|e| e.into_inner()) = Some(file);
+    Ok(())
+}
+
+// "fen=... depth=4 nodes=18236 time_ms=41 best=e2e4 eval=25", with the eval
+// in centipawns from White's point of view.
+pub fn log_record(pos: &VariantPosition, search: &Search, time: Duration) -> String {
+    let fen = Fen::from_position(pos.clone(), EnPassantMode::Legal);
+    let best = search.best.as_ref().map_or("-".to_owned(), |mv| {
+        mv.to_uci(pos.castles().mode()).to_string()
+    });
+    format!(
+        "fen={} depth={} nodes={} time_ms={} best={} eval={}",
+        fen.to_string().replace(' ', "_"),
+        search.depth,
+        search.nodes,
+        time.as_millis(),
+        best,
+        pos.turn().fold_wb(search.score, -search.score)
+    )
+}
+
+// A log that cannot be written to is closed, and the error says why.
+pub fn write_log(record: &str) -> Result<(), String> {
+    let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(file) = log.as_mut() else {
+        return Ok(());
+    };
+    if let Err(e) = writeln!(file, "{}", record) {
+        *log = None;
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+pub struct Search {
+    pub best: Option<Move>,
+    pub pv: Vec<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+fn square_bonus(role: Role, color: Color, sq: Square) -> i32 {
+    let table = match role {
+        Role::Pawn => &PAWN,
+        Role::Knight => &KNIGHT,
+        Role::Bishop => &BISHOP,
+        Role::Rook => &ROOK,
+        Role::Queen => &QUEEN,
+        Role::King => &KING,
+    };
+    // The tables are laid out from White's side with the eighth rank first.
+    let index = match color {
+        Color::White => usize::from(sq) ^ 56,
+        Color::Black => usize::from(sq),
+    };
+    table[index]
+}
+
+// Material counts at the values set in Settings.
+pub fn evaluate(pos: &impl Position, values: &PieceValues) -> i32 {
+    let score: i32 = pos
+        .board()
+        .clone()
+        .into_iter()
+        .map(|(sq, piece)| {
+            let score = values.centipawns(piece.role) + square_bonus(piece.role, piece.color, sq);
+            piece.color.fold_wb(score, -score)
+        })
+        .sum();
+    // Crazyhouse pockets count as material in hand.
+    let in_hand: i32 = pos.pockets().map_or(0, |pockets| {
+        Role::ALL
+            .into_iter()
+            .map(|role| {
+                let count =
+                    i32::from(*pockets.white.get(role)) - i32::from(*pockets.black.get(role));
+                count * values.centipawns(role)
+            })
+            .sum()
+    });
+    let score = score + in_hand;
+    pos.turn().fold_wb(score, -score)
+}
+
+fn ordered_moves(pos: &impl Position, values: &PieceValues) -> Vec<Move> {
+    let value = |role| values.centipawns(role);
+    let mut moves: Vec<Move> = pos.legal_moves().into_iter().collect();
+    moves.sort_by_key(|m| {
+        let victim = m.capture().map_or(0, value);
+        let promotion = m.promotion().map_or(0, value);
+        -(10 * victim - value(m.role()) / 10 + promotion)
+    });
+    moves
+}
+
+// What a search may spend and how it counts material.
+struct Budget<'a> {
+    nodes: u64,
+    limit: u64,
+    values: &'a PieceValues,
+}
+
+fn negamax<P: Position + Clone>(
+    pos: &P,
+    depth: u32,
+    ply: i32,
+    mut alpha: i32,
+    beta: i32,
+    budget: &mut Budget,
+    pv: &mut Vec<Move>,
+) -> i32 {
+    pv.clear();
+    budget.nodes += 1;
+
+    match pos.outcome() {
+        Some(Outcome::Decisive { winner }) if winner == pos.turn() => return MATE - ply,
+        Some(Outcome::Decisive { .. }) => return -MATE + ply,
+        Some(Outcome::Draw) => return 0,
+        None if pos.halfmoves() >= 100 => return 0,
+        None => {}
+    }
+    if depth == 0 || budget.nodes >= budget.limit {
+        return evaluate(pos, budget.values);
+    }
+
+    let mut line = Vec::new();
+    for mv in ordered_moves(pos, budget.values) {
+        let mut child = pos.clone();
+        child.play_unchecked(&mv);
+        let score = -negamax(&child, depth - 1, ply + 1, -beta, -alpha, budget, &mut line);
+        if score >= beta {
+            return beta;
+        }
+        if score > alpha {
+            alpha = score;
+            pv.clear();
+            pv.push(mv);
+            pv.append(&mut line);
+        }
+    }
+    alpha
+}
+
+pub fn search<P: Position + Clone>(pos: &P, max_depth: u32, values: &PieceValues) -> Search {
+    search_with(pos, max_depth, NODE_LIMIT, values, |_| true)
+}
+
+// Iterative deepening under a node budget; `on_depth` sees every completed
+// iteration and can stop the search by returning false.
+pub fn search_with<P: Position + Clone>(
+    pos: &P,
+    max_depth: u32,
+    node_limit: u64,
+    values: &PieceValues,
+    mut on_depth: impl FnMut(&Search) -> bool,
+) -> Search {
+    let mut result = Search {
+        best: None,
+        pv: Vec::new(),
+        score: 0,
+        depth: 0,
+        nodes: 0,
+    };
+    let mut budget = Budget {
+        nodes: 0,
+        limit: node_limit,
+        values,
+    };
+
+    for depth in 1..=max_depth {
+        let mut best = None;
+        let mut pv = Vec::new();
+        let mut line = Vec::new();
+        let mut alpha = -MATE - 1;
+
+        let mut moves = ordered_moves(pos, values);
+        // Search the previous iteration's best move first.
+        if let Some(i) = moves.iter().position(|m| Some(m) == result.best.as_ref()) {
+            moves[..=i].rotate_right(1);
+        }
+
+        for mv in moves {
+            let mut child = pos.clone();
+            child.play_unchecked(&mv);
+            let score = -negamax(
+                &child,
+                depth - 1,
+                1,
+                -MATE - 1,
+                -alpha,
+                &mut budget,
+                &mut line,
+            );
+            if score > alpha {
+                alpha = score;
+                pv = [mv.clone()].into_iter().chain(line.drain(..)).collect();
+                best = Some(mv);
+            }
+        }
+        result.nodes = budget.nodes;
+
+        // A search cut short by the node limit is not trusted over a complete one.
+        if budget.nodes >= node_limit && result.best.is_some() {
+            break;
+        }
+        result.best = best;
+        result.pv = pv;
+        result.score = alpha;
+        result.depth = depth;
+        if !on_depth(&result) || budget.nodes >= node_limit || alpha.abs() >= MATE - 100 {
+            break;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::{fen::Fen, CastlingMode, Chess};
+
+    fn position(fen: &str) -> Chess {
+        fen.parse::<Fen>()
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap()
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let pos = position("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1");
+        let search = search(&pos, 3, &PieceValues::default());
+        let best = search.best.unwrap();
+        assert_eq!((best.from(), best.to()), (Some(Square::A1), Square::A8));
+        assert_eq!(search.score, MATE - 1);
+    }
+
+    #[test]
+    fn counts_material_at_the_set_values() {
+        let pos = position("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+        let mut values = PieceValues::default();
+        let base = evaluate(&pos, &values);
+        values.set(Role::Rook, 6.0);
+        assert_eq!(evaluate(&pos, &values) - base, 100);
+    }
+}
@@ -0,0 +1,71 @@
+use shakmaty::{san::SanPlus, Chess, Move};
+
+use crate::logic::parse_fen;
+
+pub struct Epd {
+    pub position: Chess,
+    pub id: Option<String>,
+    pub best: Vec<Move>,
+    pub avoid: Vec<Move>,
+    // Moves to a forced mate, from "dm".
+    pub mate: Option<u32>,
+    pub comments: Vec<String>,
+}
+
+impl Epd {
+    pub fn parse(record: &str) -> Result<Epd, String> {
+        let mut rest = record.trim();
+        let mut position_fields = Vec::new();
+        while position_fields.len() < 4 && !rest.is_empty() {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            position_fields.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        if position_fields.len() < 4 {
+            return Err(
+                "An EPD record needs board, side, castling and en passant fields".to_owned(),
+            );
+        }
+        let position = parse_fen(&position_fields.join(" "))?;
+
+        let mut epd = Epd {
+            position,
+            id: None,
+            best: Vec::new(),
+            avoid: Vec::new(),
+            mate: None,
+            comments: Vec::new(),
+        };
+
+        for operation in rest.split(';') {
+            let operation = operation.trim();
+            let (opcode, operands) = operation
+                .split_once(char::is_whitespace)
+                .unwrap_or((operation, ""));
+            match opcode {
+                "bm" => epd.best = parse_moves(&epd.position, operands)?,
+                "am" => epd.avoid = parse_moves(&epd.position, operands)?,
+                "dm" => epd.mate = operands.trim().parse().ok(),
+                "id" => epd.id = Some(operands.trim().trim_matches('"').to_owned()),
+                "c0" | "c1" | "c2" | "c3" | "c4" | "c5" | "c6" | "c7" | "c8" | "c9" => epd
+                    .comments
+                    .push(operands.trim().trim_matches('"').to_owned()),
+                _ => {}
+            }
+        }
+
+        Ok(epd)
+    }
+}
+
+fn parse_moves(position: &Chess, operands: &str) -> Result<Vec<Move>, String> {
+    operands
+        .split_whitespace()
+        .map(|san| {
+            SanPlus::from_ascii(san.as_bytes())
+                .ok()
+                .and_then(|san| san.san.to_move(position).ok())
+                .ok_or_else(|| format!("Invalid move in EPD: {}", san))
+        })
+        .collect()
+}
@@ -0,0 +1,122 @@
+use cursive::{
+    theme::{BaseColor, Color, ColorStyle},
+    Printer, Vec2,
+};
+use shakmaty::Color as CColor;
+
+use crate::engine::MATE;
+use crate::settings::EvalPerspective;
+
+const WIDTH: usize = 5;
+const BAR_WIDTH: usize = 2;
+const CLAMP: i32 = 1000;
+
+// "+0.3", or "M4" for a mate in four moves.
+pub fn score_label(score: i32) -> String {
+    if score.abs() >= MATE - 100 {
+        let moves = (MATE - score.abs() + 1) / 2;
+        let sign = if score < 0 { "-" } else { "" };
+        if moves == 0 {
+            "#".to_owned()
+        } else {
+            format!("{}M{}", sign, moves)
+        }
+    } else {
+        format!("{:+.1}", score as f32 / 100.0)
+    }
+}
+
+// A score from White's point of view, turned to the side to move's when
+// the setting asks for it.
+pub fn viewed(score: i32, perspective: EvalPerspective, turn: CColor) -> i32 {
+    match perspective {
+        EvalPerspective::SideToMove if turn == CColor::Black => -score,
+        _ => score,
+    }
+}
+
+pub struct EvalBar {
+    fen: String,
+    score: i32,
+    orientation: CColor,
+    turn: CColor,
+    perspective: EvalPerspective,
+    height: usize,
+}
+
+impl EvalBar {
+    pub fn new(height: usize, perspective: EvalPerspective) -> Self {
+        EvalBar {
+            fen: String::new(),
+            score: 0,
+            orientation: CColor::White,
+            turn: CColor::White,
+            perspective,
+            height,
+        }
+    }
+
+    pub fn is_for(&self, fen: &str) -> bool {
+        self.fen == fen
+    }
+
+    // Follows a board that scales with the window.
+    pub fn set_height(&mut self, height: usize) {
+        self.height = height;
+    }
+
+    // The score is from White's point of view, and so is the bar. The label
+    // reads it the way the setting asks.
+    pub fn set(&mut self, fen: String, score: i32, orientation: CColor, turn: CColor) {
+        self.fen = fen;
+        self.score = score;
+        self.orientation = orientation;
+        self.turn = turn;
+    }
+
+    pub fn label(&self) -> String {
+        score_label(viewed(self.score, self.perspective, self.turn))
+    }
+
+    fn white_rows(&self) -> usize {
+        let score = if self.score.abs() >= MATE - 100 {
+            self.score.signum() * CLAMP
+        } else {
+            self.score.clamp(-CLAMP, CLAMP)
+        };
+        let share = (score + CLAMP) as f32 / (2 * CLAMP) as f32;
+        (share * self.height as f32).round() as usize
+    }
+}
+
+impl cursive::view::View for EvalBar {
+    fn draw(&self, printer: &Printer) {
+        let white = self.white_rows();
+        let blank = " ".repeat(BAR_WIDTH);
+        let x = (WIDTH - BAR_WIDTH) / 2;
+
+        for row in 0..self.height {
+            // White's share grows from the side of the board White plays from.
+            let from_white = match self.orientation {
+                CColor::White => self.height - 1 - row,
+                CColor::Black => row,
+            };
+            let color = if from_white < white {
+                Color::Light(BaseColor::White)
+            } else {
+                Color::Dark(BaseColor::Black)
+            };
+            printer.with_color(ColorStyle::new(color, color), |printer| {
+                printer.print((x, row), &blank)
+            });
+        }
+
+        let label = self.label();
+        let x = WIDTH.saturating_sub(label.len()) / 2;
+        printer.print((x, self.height), &label);
+    }
+
+    fn required_size(&mut self, _: Vec2) -> Vec2 {
+        Vec2::new(WIDTH, self.height + 1)
+    }
+}
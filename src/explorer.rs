@@ -0,0 +1,185 @@
+use std::{cmp::Reverse, fs};
+
+use shakmaty::{
+    fen::Fen,
+    san::SanPlus,
+    uci::Uci,
+    variant::{Variant, VariantPosition},
+    zobrist::{Zobrist64, ZobristHash},
+    CastlingMode, EnPassantMode, Position,
+};
+
+use crate::library::data_dir;
+use crate::lichess;
+use crate::pgn;
+
+const URL: &str = "https://explorer.lichess.ovh/lichess";
+const SHOWN: usize = 12;
+
+pub struct Continuation {
+    pub uci: String,
+    pub san: String,
+    // Wins for White, draws and wins for Black.
+    results: [u64; 3],
+}
+
+impl Continuation {
+    fn games(&self) -> u64 {
+        self.results.iter().sum()
+    }
+
+    // "Nf3     35k 40/32/28": the move, how often it was played and how those
+    // games ended, in percent.
+    pub fn row(&self) -> String {
+        let games = self.games().max(1);
+        let [white, draws, black] = self.results.map(|n| (n * 100 + games / 2) / games);
+        format!(
+            "{:<7}{:>5} {:>2}/{:>2}/{:>2}",
+            self.san,
+            count(self.games()),
+            white,
+            draws,
+            black
+        )
+    }
+}
+
+pub const HEADER: &str = "Move   Games  W/ D/ B";
+
+fn count(n: u64) -> String {
+    if n >= 1_000_000 {
+        format!("{:.1}M", n as f64 / 1e6)
+    } else if n >= 10_000 {
+        format!("{}k", n / 1000)
+    } else {
+        n.to_string()
+    }
+}
+
+fn continuation(pos: &VariantPosition, uci: &str, results: [u64; 3]) -> Option<Continuation> {
+    let mv = Uci::from_ascii(uci.as_bytes()).ok()?.to_move(pos).ok()?;
+    let mode = CastlingMode::from_chess960(pos.castles().mode().is_chess960());
+    Some(Continuation {
+        uci: mv.to_uci(mode).to_string(),
+        san: SanPlus::from_move(pos.clone(), &mv).to_string(),
+        results,
+    })
+}
+
+fn number_field(json: &str, key: &str) -> Option<u64> {
+    let start = json.find(&format!("\"{}\":", key))? + key.len() + 3;
+    let digits: String = json[start..]
+        .chars()
+        .skip_while(|c| c.is_whitespace())
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+// Each entry of "moves" is a flat object but for an optional "game" object,
+// whose own "white" and "black" would shadow the counts. Only the outer
+// level of each entry is kept.
+fn parse(json: &str, pos: &VariantPosition) -> Result<Vec<Continuation>, String> {
+    let start = json
+        .find("\"moves\":[")
+        .ok_or_else(|| "unexpected reply from the explorer".to_owned())?
+        + 9;
+    let mut found = Vec::new();
+    let mut entry = String::new();
+    let (mut depth, mut in_string, mut escaped) = (0, false, false);
+    for c in json[start..].chars() {
+        if in_string {
+            if depth == 1 {
+                entry.push(c);
+            }
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                if depth == 1 {
+                    entry.push(c);
+                }
+            }
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    let results = ["white", "draws", "black"]
+                        .map(|key| number_field(&entry, key).unwrap_or(0));
+                    let uci = lichess::string_field(&entry, "uci").unwrap_or_default();
+                    found.extend(continuation(pos, &uci, results));
+                    entry.clear();
+                }
+            }
+            ']' if depth == 0 => break,
+            c if depth == 1 => entry.push(c),
+            _ => {}
+        }
+    }
+    Ok(found)
+}
+
+fn from_lichess(pos: &VariantPosition) -> Result<Vec<Continuation>, String> {
+    if pos.variant() != Variant::Chess || pos.castles().mode().is_chess960() {
+        return Err("Lichess only has statistics for standard chess".to_owned());
+    }
+    let fen = Fen::from_position(pos.clone(), EnPassantMode::Legal).to_string();
+    let url = format!("{}?variant=standard&fen={}", URL, fen.replace(' ', "%20"));
+    parse(&lichess::fetch(&url)?, pos)
+}
+
+// Every saved game that passed through the position counts once, with the
+// move played there and the game's result.
+fn from_games(pos: &VariantPosition) -> Result<Vec<Continuation>, String> {
+    let path = data_dir()
+        .map(|dir| dir.join("games.pgn"))
+        .ok_or_else(|| "no home directory".to_owned())?;
+    let text = fs::read_to_string(&path).unwrap_or_default();
+    let key = pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal);
+
+    let mut found: Vec<Continuation> = Vec::new();
+    for game in pgn::parse_all(&text)? {
+        let result = match game.tag("Result") {
+            Some("1-0") => 0,
+            Some("1/2-1/2") => 1,
+            Some("0-1") => 2,
+            _ => continue,
+        };
+        let Some(ply) = game.positions[..game.moves.len()].iter().position(|p| {
+            p.variant() == pos.variant() && p.zobrist_hash::<Zobrist64>(EnPassantMode::Legal) == key
+        }) else {
+            continue;
+        };
+        let mode = CastlingMode::from_chess960(pos.castles().mode().is_chess960());
+        let uci = game.moves[ply].to_uci(mode).to_string();
+        match found.iter_mut().find(|c| c.uci == uci) {
+            Some(known) => known.results[result] += 1,
+            None => {
+                let mut results = [0; 3];
+                results[result] = 1;
+                found.extend(continuation(pos, &uci, results));
+            }
+        }
+    }
+    Ok(found)
+}
+
+// Lichess when it can be reached, and the games saved here otherwise. The
+// name of the source comes first.
+pub fn lookup(pos: &VariantPosition) -> Result<(&'static str, Vec<Continuation>), String> {
+    let (source, mut found) = match from_lichess(pos) {
+        Ok(found) => ("Lichess games", found),
+        Err(_) => ("Your saved games", from_games(pos)?),
+    };
+    found.sort_by_key(|c| Reverse(c.games()));
+    found.truncate(SHOWN);
+    Ok((source, found))
+}
@@ -0,0 +1,127 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+use cursive::{CbSink, Cursive};
+
+use crate::logic::{refresh_side_views, BoardView};
+
+type Response = (u16, String);
+
+// A client that stops sending mid-request is dropped after this long.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn serve(port: u16, cb_sink: CbSink) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+
+    thread::spawn(move || {
+        // Each connection gets its own thread, so a slow one holds up no other.
+        for stream in listener.incoming().flatten() {
+            let cb_sink = cb_sink.clone();
+            thread::spawn(move || handle(stream, &cb_sink));
+        }
+    });
+
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream, cb_sink: &CbSink) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length.min(1024)];
+    reader.read_exact(&mut body)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (code, text) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/" | "/fen")) => on_ui(cb_sink, get_fen),
+        (Some("POST"), Some("/move")) => {
+            let mv = String::from_utf8_lossy(&body).trim().to_owned();
+            on_ui(cb_sink, move |s| post_move(s, &mv))
+        }
+        _ => (404, "not found\n".to_owned()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason(code),
+        text.len(),
+        text
+    )
+}
+
+fn on_ui<F>(cb_sink: &CbSink, f: F) -> Response
+where
+    F: FnOnce(&mut Cursive) -> Response + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let sent = cb_sink.send(Box::new(move |s| {
+        let _ = tx.send(f(s));
+    }));
+
+    match sent.ok().and_then(|_| rx.recv().ok()) {
+        Some(response) => response,
+        None => (503, "the app is shutting down\n".to_owned()),
+    }
+}
+
+fn report(board: &BoardView) -> String {
+    format!("{}\n{}\n", board.fen(), board.status())
+}
+
+fn no_game() -> Response {
+    (409, "no game in progress\n".to_owned())
+}
+
+fn get_fen(s: &mut Cursive) -> Response {
+    s.call_on_name("board", |board: &mut BoardView| (200, report(board)))
+        .unwrap_or_else(no_game)
+}
+
+fn post_move(s: &mut Cursive, mv: &str) -> Response {
+    let played = s.call_on_name("board", |board: &mut BoardView| {
+        board.play_uci(mv).map(|result| (report(board), result))
+    });
+
+    match played {
+        None => no_game(),
+        Some(Err(e)) => (400, e + "\n"),
+        Some(Ok((text, result))) => {
+            result.process(s);
+            refresh_side_views(s);
+            (200, text)
+        }
+    }
+}
+
+fn reason(code: u16) -> &'static str {
+    match code {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Service Unavailable",
+    }
+}
@@ -0,0 +1,290 @@
+use std::fs;
+
+use cursive::{
+    event::{Event, Key},
+    view::Scrollable,
+    views::{Dialog, TextView},
+    Cursive,
+};
+
+use crate::lang::tr;
+use crate::settings;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Left,
+    Right,
+    Up,
+    Down,
+    Select,
+    Undo,
+    Hint,
+    Flip,
+    MoveEntry,
+    Back,
+    Forward,
+    Jump,
+    Annotate,
+    Bindings,
+}
+
+impl Action {
+    const ALL: [Action; 14] = [
+        Action::Left,
+        Action::Right,
+        Action::Up,
+        Action::Down,
+        Action::Select,
+        Action::Undo,
+        Action::Hint,
+        Action::Flip,
+        Action::MoveEntry,
+        Action::Back,
+        Action::Forward,
+        Action::Jump,
+        Action::Annotate,
+        Action::Bindings,
+    ];
+
+    fn key(self) -> &'static str {
+        match self {
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Select => "select",
+            Action::Undo => "undo",
+            Action::Hint => "hint",
+            Action::Flip => "flip",
+            Action::MoveEntry => "move_entry",
+            Action::Back => "back",
+            Action::Forward => "forward",
+            Action::Jump => "jump",
+            Action::Annotate => "annotate",
+            Action::Bindings => "bindings",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Action::Left => "Cursor left",
+            Action::Right => "Cursor right",
+            Action::Up => "Cursor up",
+            Action::Down => "Cursor down",
+            Action::Select => "Pick up or put down",
+            Action::Undo => "Take back",
+            Action::Hint => "Hint",
+            Action::Flip => "Flip the board",
+            Action::MoveEntry => "Type a move",
+            Action::Back => "Look back",
+            Action::Forward => "Step forward",
+            Action::Jump => "Jump to a square",
+            Action::Annotate => "Annotate a move",
+            Action::Bindings => "List the bindings",
+        }
+    }
+
+    fn defaults(self) -> Vec<Event> {
+        match self {
+            Action::Left => vec![Event::Key(Key::Left)],
+            Action::Right => vec![Event::Key(Key::Right)],
+            Action::Up => vec![Event::Key(Key::Up)],
+            Action::Down => vec![Event::Key(Key::Down)],
+            Action::Select => vec![Event::Char(' ')],
+            Action::Undo => vec![Event::Char('u')],
+            Action::Hint => vec![Event::Char('h')],
+            Action::Flip => vec![Event::Char('f')],
+            Action::MoveEntry => vec![Event::Char('m'), Event::Char(':')],
+            Action::Back => vec![Event::Char('<')],
+            Action::Forward => vec![Event::Char('>')],
+            Action::Jump => vec![Event::Char('/')],
+            Action::Annotate => vec![Event::Char('n')],
+            Action::Bindings => vec![Event::Char('?')],
+        }
+    }
+}
+
+// With vim keys on, hjkl move the cursor and the hint moves to H.
+const VIM: [(char, Action); 5] = [
+    ('h', Action::Left),
+    ('j', Action::Down),
+    ('k', Action::Up),
+    ('l', Action::Right),
+    ('H', Action::Hint),
+];
+
+const NAMED: [(&str, Key); 12] = [
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("Enter", Key::Enter),
+    ("Tab", Key::Tab),
+    ("Backspace", Key::Backspace),
+    ("Del", Key::Del),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+];
+
+// "h", "Space", "Left", "Ctrl+p" or "Alt+x". Esc is left out: it always
+// drops the selection.
+fn parse_key(name: &str) -> Option<Event> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(Event::Char(c));
+    }
+    if name == "Space" {
+        return Some(Event::Char(' '));
+    }
+    if let Some(&(_, key)) = NAMED.iter().find(|(n, _)| *n == name) {
+        return Some(Event::Key(key));
+    }
+    let single = |rest: &str| {
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c.to_ascii_lowercase()),
+            _ => None,
+        }
+    };
+    if let Some(rest) = name.strip_prefix("Ctrl+") {
+        return single(rest).map(Event::CtrlChar);
+    }
+    if let Some(rest) = name.strip_prefix("Alt+") {
+        return single(rest).map(Event::AltChar);
+    }
+    None
+}
+
+fn key_name(event: &Event) -> String {
+    match event {
+        Event::Char(' ') => "Space".to_owned(),
+        Event::Char(c) => c.to_string(),
+        Event::CtrlChar(c) => format!("Ctrl+{}", c),
+        Event::AltChar(c) => format!("Alt+{}", c),
+        Event::Key(key) => NAMED
+            .iter()
+            .find(|(_, k)| k == key)
+            .map_or("?".to_owned(), |(name, _)| (*name).to_owned()),
+        _ => "?".to_owned(),
+    }
+}
+
+#[derive(Clone)]
+pub struct Keymap {
+    bindings: Vec<(Event, Action)>,
+    // Lines of the keys file that could not be read.
+    pub problems: Vec<String>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            bindings: Action::ALL
+                .iter()
+                .flat_map(|&action| action.defaults().into_iter().map(move |e| (e, action)))
+                .collect(),
+            problems: Vec::new(),
+        }
+    }
+}
+
+impl Keymap {
+    // Each line gives an action and its keys, as in "left = h, Left". The
+    // keys replace the action's defaults and are taken from any other action
+    // that had them.
+    pub fn load(vim: bool) -> Self {
+        let mut keymap = Keymap::default();
+        if vim {
+            for (c, action) in VIM {
+                keymap.bindings.retain(|(e, _)| *e != Event::Char(c));
+                keymap.bindings.push((Event::Char(c), action));
+            }
+        }
+        let contents = match settings::config_file("keys").map(fs::read_to_string) {
+            Some(Ok(contents)) => contents,
+            _ => return keymap,
+        };
+
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((name, keys)) = line.split_once('=') else {
+                keymap.problems.push(format!("no \"=\" in \"{}\"", line));
+                continue;
+            };
+            let Some(&action) = Action::ALL.iter().find(|a| a.key() == name.trim()) else {
+                keymap
+                    .problems
+                    .push(format!("no action called \"{}\"", name.trim()));
+                continue;
+            };
+            let mut events = Vec::new();
+            for key in keys.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+                match parse_key(key) {
+                    Some(event) => events.push(event),
+                    None => keymap.problems.push(format!("unknown key \"{}\"", key)),
+                }
+            }
+            keymap
+                .bindings
+                .retain(|(event, a)| *a != action && !events.contains(event));
+            keymap
+                .bindings
+                .extend(events.into_iter().map(|event| (event, action)));
+        }
+        keymap
+    }
+
+    pub fn action(&self, event: &Event) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(e, _)| e == event)
+            .map(|&(_, action)| action)
+    }
+
+    pub fn keys(&self, action: Action) -> String {
+        let keys: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|&&(_, a)| a == action)
+            .map(|(event, _)| key_name(event))
+            .collect();
+        if keys.is_empty() {
+            "(none)".to_owned()
+        } else {
+            keys.join(", ")
+        }
+    }
+}
+
+pub fn show_bindings(siv: &mut Cursive) {
+    let keymap = settings::current(siv).keys;
+    let mut text: String = Action::ALL
+        .iter()
+        .map(|&action| {
+            format!(
+                "{:<20}{:<12}{}\n",
+                action.label(),
+                action.key(),
+                keymap.keys(action)
+            )
+        })
+        .collect();
+    let path = settings::config_file("keys")
+        .map_or("the keys file".to_owned(), |p| p.display().to_string());
+    text += &format!(
+        "\nTo change them, add lines such as\n  left = h, Left\nto {}\nand restart.",
+        path
+    );
+    for problem in &keymap.problems {
+        text += &format!("\nIgnored: {}", problem);
+    }
+    siv.add_layer(
+        Dialog::around(TextView::new(text).scrollable())
+            .title(tr("Key Bindings"))
+            .dismiss_button(tr("Ok")),
+    );
+}
@@ -0,0 +1,427 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    English,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Spanish];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Language> {
+        Language::ALL.into_iter().find(|l| l.code() == code)
+    }
+
+    // Each language by its own name.
+    pub fn label(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Spanish => "Español",
+        }
+    }
+
+    fn table(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::English => &[],
+            Language::Spanish => &SPANISH,
+        }
+    }
+}
+
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+pub fn set(language: Language) {
+    CURRENT.store(language as u8, Ordering::SeqCst);
+}
+
+fn current() -> Language {
+    Language::ALL
+        .get(usize::from(CURRENT.load(Ordering::SeqCst)))
+        .copied()
+        .unwrap_or_default()
+}
+
+// Strings are looked up by their English text, which is also what shows
+// when a language has no entry for one.
+pub fn tr(text: &'static str) -> &'static str {
+    current()
+        .table()
+        .iter()
+        .find(|(english, _)| *english == text)
+        .map_or(text, |&(_, translated)| translated)
+}
+
+// Fills each "{}" of the translated template with the next argument.
+pub fn fill(template: &'static str, args: &[&str]) -> String {
+    let mut parts = tr(template).split("{}");
+    let mut text = parts.next().unwrap_or_default().to_owned();
+    for (part, arg) in parts.zip(args.iter().chain(std::iter::repeat(&""))) {
+        text += arg;
+        text += part;
+    }
+    text
+}
+
+const SPANISH: [(&str, &str); 236] = [
+    // Main menu
+    ("Chess", "Ajedrez"),
+    ("New game", "Nueva partida"),
+    ("Resume game", "Continuar partida"),
+    ("Load game", "Cargar partida"),
+    ("Correspondence", "Por correspondencia"),
+    ("Open PGN", "Abrir PGN"),
+    ("Puzzle of the day", "Problema del día"),
+    ("Puzzle trainer", "Entrenador de problemas"),
+    ("Mate problems", "Problemas de mate"),
+    ("Repertoire", "Repertorio"),
+    ("Network game", "Partida en red"),
+    ("Watch engines", "Ver motores"),
+    ("Follow broadcast", "Seguir retransmisión"),
+    ("Stats", "Estadísticas"),
+    ("Settings", "Ajustes"),
+    ("Rules", "Reglas"),
+    ("Exit", "Salir"),
+    // Game window
+    ("New Tab", "Pestaña"),
+    ("Close Tab", "Cerrar"),
+    ("Undo", "Deshacer"),
+    ("Resign", "Rendirse"),
+    ("Offer Draw", "Tablas"),
+    ("Save Game", "Guardar"),
+    ("Export PGN", "PGN"),
+    ("Share", "Compartir"),
+    ("Quit Game", "Salir"),
+    ("Ok", "Aceptar"),
+    ("Don't show again", "No volver a mostrar"),
+    // Turn and status
+    ("White", "Blancas"),
+    ("Black", "Negras"),
+    ("{} to move, move {}{}", "Juegan {}, jugada {}{}"),
+    (" (check)", " (jaque)"),
+    ("{} to move", "Juegan {}"),
+    ("{} to move, in check", "Juegan {}, en jaque"),
+    ("{} wins by {}", "Ganan {} por {}"),
+    ("Drawn by {}", "Tablas por {}"),
+    ("Move {}", "Jugada {}"),
+    ("Halfmove clock {}/100", "Reloj de medias jugadas {}/100"),
+    ("Eval {}", "Evaluación {}"),
+    ("Holding {}", "En la mano {}"),
+    // Game over
+    ("Game Over. {}{}", "Fin de la partida. {}{}"),
+    ("{} wins on time.", "Ganan {} por tiempo."),
+    ("{} wins by {}.", "Ganan {} por {}."),
+    ("{} wins.", "Ganan {}."),
+    ("Stalemate \u{2014} draw.", "Ahogado \u{2014} tablas."),
+    (
+        "Both kings reached the eighth rank \u{2014} draw.",
+        "Ambos reyes llegaron a la octava fila \u{2014} tablas.",
+    ),
+    ("Draw by {}.", "Tablas por {}."),
+    ("Draw.", "Tablas."),
+    (" Well played.", " Bien jugado."),
+    (" Better luck next time.", " Más suerte la próxima vez."),
+    (
+        " You win. I demand a rematch!",
+        " Has ganado. ¡Exijo la revancha!",
+    ),
+    (" I win. Hahaha.", " He ganado. Jajaja."),
+    (" Let's call it even.", " Quedamos en paz."),
+    ("checkmate", "jaque mate"),
+    ("the variant rules", "las reglas de la variante"),
+    ("blowing up the king", "hacer explotar al rey"),
+    ("bringing the king to the centre", "llevar el rey al centro"),
+    (
+        "racing the king to the eighth rank",
+        "llevar el rey a la octava fila",
+    ),
+    ("giving three checks", "dar tres jaques"),
+    ("capturing the whole horde", "capturar toda la horda"),
+    (
+        "losing every piece or running out of moves",
+        "perder todas las piezas o quedarse sin jugadas",
+    ),
+    ("timeout", "tiempo"),
+    ("stalemate", "ahogado"),
+    ("insufficient material", "material insuficiente"),
+    ("75-move rule", "la regla de las 75 jugadas"),
+    ("fivefold repetition", "quíntuple repetición"),
+    ("threefold repetition", "triple repetición"),
+    ("fifty-move rule", "la regla de las cincuenta jugadas"),
+    // Settings
+    ("Language", "Idioma"),
+    ("Evaluation", "Evaluación"),
+    ("CPU messages", "Mensajes de la CPU"),
+    ("Cursor edges", "Bordes del cursor"),
+    ("Clicking another own piece", "Clic en otra pieza propia"),
+    ("Pieces", "Piezas"),
+    ("Board size", "Tamaño del tablero"),
+    ("Board colours", "Colores del tablero"),
+    ("Highlights", "Resaltados"),
+    ("Board faces", "Orientación del tablero"),
+    ("Sounds", "Sonidos"),
+    ("Step mode", "Modo paso a paso"),
+    ("Evaluation bar", "Barra de evaluación"),
+    ("Announce moves", "Anunciar jugadas"),
+    ("Back", "Volver"),
+    ("Pawn", "Peón"),
+    ("Knight", "Caballo"),
+    ("Bishop", "Alfil"),
+    ("Rook", "Torre"),
+    ("Queen", "Dama"),
+    ("Cursor {}", "Cursor {}"),
+    // Dialogs shared across windows
+    ("Close", "Cerrar"),
+    ("Cancel", "Cancelar"),
+    ("Add", "Añadir"),
+    ("Remove", "Quitar"),
+    ("Clear", "Vaciar"),
+    ("Reset", "Restablecer"),
+    ("Start", "Empezar"),
+    ("Save", "Guardar"),
+    ("Load", "Cargar"),
+    ("Open", "Abrir"),
+    ("New", "Nueva"),
+    ("Edit", "Editar"),
+    ("Rename", "Renombrar"),
+    ("Delete", "Borrar"),
+    ("Refresh", "Actualizar"),
+    ("Play", "Jugar"),
+    ("Random", "Al azar"),
+    ("Defaults", "Valores por defecto"),
+    ("Moves", "Jugadas"),
+    // Game window dialogs
+    ("Resign this game?", "¿Abandonar esta partida?"),
+    ("{} offers a draw.", "{} ofrecen tablas."),
+    ("Accept", "Aceptar"),
+    ("Decline", "Rechazar"),
+    ("Offer a draw?", "¿Ofrecer tablas?"),
+    ("Offer", "Ofrecer"),
+    ("Draw offer declined.", "Oferta de tablas rechazada."),
+    (
+        "The other player offers a draw.",
+        "El otro jugador ofrece tablas.",
+    ),
+    (
+        "The other player declined the draw.",
+        "El otro jugador rechazó las tablas.",
+    ),
+    (
+        "The other player has left the game.",
+        "El otro jugador ha abandonado la partida.",
+    ),
+    ("Take Back", "Deshacer jugada"),
+    ("Promote to", "Coronar a"),
+    ("Games", "Partidas"),
+    ("Analyze", "Analizar"),
+    ("Review", "Revisar"),
+    ("Learn", "Aprender"),
+    ("Lessons", "Lecciones"),
+    ("Next step", "Siguiente paso"),
+    ("Next lesson", "Siguiente lección"),
+    ("Enter Move", "Introducir jugada"),
+    (
+        "No move has been played yet.",
+        "Aún no se ha jugado ninguna jugada.",
+    ),
+    ("Annotate {}", "Anotar {}"),
+    ("Moves read out", "Jugadas leídas"),
+    ("Write File", "Escribir archivo"),
+    ("Write SVG", "Escribir SVG"),
+    ("Write ANSI", "Escribir ANSI"),
+    ("Conditional Moves", "Jugadas condicionales"),
+    ("Duck Chess", "Ajedrez del pato"),
+    ("Key Bindings", "Atajos de teclado"),
+    // New game
+    ("Select Variant", "Elegir variante"),
+    ("Experimental", "Experimental"),
+    ("FEN Position", "Posición FEN"),
+    ("EPD Position", "Posición EPD"),
+    ("Game Code", "Código de partida"),
+    ("Odds Game", "Partida con ventaja"),
+    ("Chess960", "Ajedrez960"),
+    ("Set Up Position", "Preparar posición"),
+    // Saved games
+    ("Restore Game", "Recuperar partida"),
+    ("Restore", "Recuperar"),
+    ("Discard All", "Descartar todas"),
+    (
+        "Could not restore the game: {}",
+        "No se pudo recuperar la partida: {}",
+    ),
+    (
+        "No game to resume.",
+        "No hay ninguna partida para continuar.",
+    ),
+    ("Resume Game", "Continuar partida"),
+    (
+        "Could not resume the game: {}",
+        "No se pudo continuar la partida: {}",
+    ),
+    (
+        "Could not save the game to resume: {}",
+        "No se pudo guardar la partida para continuarla: {}",
+    ),
+    ("Saved \"{}\".", "Guardada \"{}\"."),
+    ("Could not load \"{}\": {}", "No se pudo cargar \"{}\": {}"),
+    (
+        "Could not open the game library: {}",
+        "No se pudo abrir la biblioteca de partidas: {}",
+    ),
+    ("No saved games yet.", "Aún no hay partidas guardadas."),
+    ("Load Game", "Cargar partida"),
+    ("Rename \"{}\"", "Renombrar \"{}\""),
+    (
+        "New Correspondence Game",
+        "Nueva partida por correspondencia",
+    ),
+    (
+        "Could not start the game: {}",
+        "No se pudo empezar la partida: {}",
+    ),
+    (
+        "Could not open the game: {}",
+        "No se pudo abrir la partida: {}",
+    ),
+    // Puzzles and training
+    (
+        "Fetching the puzzle of the day...",
+        "Descargando el problema del día...",
+    ),
+    ("Puzzle of the day: {}", "Problema del día: {}"),
+    ("Puzzle", "Problema"),
+    ("Solution", "Solución"),
+    ("Next", "Siguiente"),
+    ("Retry", "Reintentar"),
+    ("Show Solution", "Ver solución"),
+    ("Show Refutation", "Ver refutación"),
+    (
+        "That was the last puzzle. {}",
+        "Ese era el último problema. {}",
+    ),
+    (
+        "No puzzles match that theme.",
+        "Ningún problema coincide con ese tema.",
+    ),
+    (
+        "Solved {}, failed {}, {} left",
+        "Resueltos {}, fallados {}, quedan {}",
+    ),
+    ("Puzzle Trainer", "Entrenador de problemas"),
+    ("That was the last problem.", "Ese era el último problema."),
+    ("Composing a mate in {}...", "Componiendo un mate en {}..."),
+    ("Problems", "Problemas"),
+    (
+        "No problem came up this time. Try again.",
+        "Esta vez no salió ningún problema. Inténtalo de nuevo.",
+    ),
+    ("Refutation", "Refutación"),
+    ("Mate Problems", "Problemas de mate"),
+    ("No problems in {}", "No hay problemas en {}"),
+    ("Compose", "Componer"),
+    (
+        "{} has no lines with a move for {} to play.",
+        "{} no tiene líneas con una jugada para {}.",
+    ),
+    ("Repertoire Trainer", "Entrenador de repertorio"),
+    (
+        "Every line is learned for now. The next one is due in {} day.",
+        "Todas las líneas están aprendidas. La siguiente toca en {} día.",
+    ),
+    (
+        "Every line is learned for now. The next one is due in {} days.",
+        "Todas las líneas están aprendidas. La siguiente toca en {} días.",
+    ),
+    // Replay and analysis
+    ("Try Move", "Probar jugada"),
+    ("Time per Move", "Tiempo por jugada"),
+    ("Analysis", "Análisis"),
+    ("Explorer", "Explorador"),
+    ("Times", "Tiempos"),
+    ("Best Move", "Mejor jugada"),
+    ("Learn From This Loss", "Aprender de esta derrota"),
+    // Network and broadcasts
+    ("Network Game", "Partida en red"),
+    ("Host", "Crear"),
+    ("Join", "Unirse"),
+    ("No game: {}", "No hay partida: {}"),
+    (
+        "Could not listen on port {}: {}",
+        "No se pudo escuchar en el puerto {}: {}",
+    ),
+    ("\"{}\" is not a port", "\"{}\" no es un puerto"),
+    (
+        "Enter the host's address, as in 192.168.1.20:7531",
+        "Escribe la dirección del anfitrión, como 192.168.1.20:7531",
+    ),
+    ("Follow Broadcast", "Seguir retransmisión"),
+    ("Follow", "Seguir"),
+    ("Enter a file or a URL.", "Escribe un archivo o una URL."),
+    ("Previous Board", "Tablero anterior"),
+    ("Next Board", "Tablero siguiente"),
+    ("Watch Engines", "Ver motores"),
+    ("Pause", "Pausa"),
+    ("Step", "Paso"),
+    ("Slower", "Más lento"),
+    ("Faster", "Más rápido"),
+    ("Statistics", "Estadísticas"),
+    // Engine settings
+    ("Piece Values", "Valor de las piezas"),
+    ("Invalid value for {}", "Valor no válido para {}"),
+    ("Engine Options", "Opciones del motor"),
+    (
+        "No engine path set (Settings or CHESS_ENGINE).",
+        "No hay ruta de motor (Ajustes o CHESS_ENGINE).",
+    ),
+    ("Engine options: {}", "Opciones del motor: {}"),
+    (
+        "The engine has no options to set.",
+        "El motor no tiene opciones que ajustar.",
+    ),
+    // Lichess
+    ("Lichess", "Lichess"),
+    ("Play AI", "Jugar con la IA"),
+    ("Seek", "Buscar rival"),
+    (
+        "Play the Lichess AI at level:",
+        "Jugar con la IA de Lichess en el nivel:",
+    ),
+    ("as:", "con:"),
+    (
+        "\nor seek a casual {}+{} game against a person.",
+        "\no buscar una partida amistosa {}+{} contra una persona.",
+    ),
+    (
+        "Set LICHESS_TOKEN to a Lichess token with the board:play scope.",
+        "Pon en LICHESS_TOKEN un token de Lichess con el permiso board:play.",
+    ),
+    (
+        "Challenging the Lichess AI...",
+        "Desafiando a la IA de Lichess...",
+    ),
+    (
+        "Looking for an opponent on Lichess...",
+        "Buscando un rival en Lichess...",
+    ),
+    ("No game on Lichess: {}", "No hay partida en Lichess: {}"),
+    ("Lichess turned that down: {}", "Lichess lo rechazó: {}"),
+    (
+        "The game ended on Lichess: {}.",
+        "La partida terminó en Lichess: {}.",
+    ),
+    ("a draw was agreed", "se acordaron tablas"),
+    ("a player resigned", "un jugador abandonó"),
+    ("a flag fell", "se cayó una bandera"),
+    ("a player left", "un jugador se fue"),
+    ("the game was aborted", "la partida se anuló"),
+    ("the game never started", "la partida no llegó a empezar"),
+    ("the game was stopped", "la partida se detuvo"),
+];
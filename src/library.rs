@@ -0,0 +1,715 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use cursive::{
+    view::{Nameable, Resizable},
+    views::{Dialog, DummyView, EditView, LinearLayout, RadioGroup, SelectView, TextView},
+    Cursive,
+};
+
+use shakmaty::{
+    fen::Fen,
+    variant::{Variant, VariantPosition},
+    ByColor, Color, EnPassantMode, Move, Position,
+};
+
+use crate::clock;
+use crate::lang::{fill, tr};
+use crate::logic::{self, parse_variant_fen, BoardView};
+use crate::pgn::Note;
+use crate::settings::{self, Settings};
+
+pub fn data_dir() -> Option<PathBuf> {
+    let data = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+    Some(data.join("terminal_chess_app"))
+}
+
+fn data_file(name: &str) -> io::Result<PathBuf> {
+    let dir =
+        data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(name))
+}
+
+fn resume_path() -> io::Result<PathBuf> {
+    data_file("resume.fen")
+}
+
+// One file per open game, so tabs don't overwrite each other.
+fn recovery_dir() -> io::Result<PathBuf> {
+    let dir = data_file("recovery")?;
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn recovery_path(key: &str) -> io::Result<PathBuf> {
+    Ok(recovery_dir()?.join(format!("{}.fen", key)))
+}
+
+fn games_dir() -> io::Result<PathBuf> {
+    let dir = data_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?
+        .join("games");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn game_path(name: &str) -> io::Result<PathBuf> {
+    Ok(games_dir()?.join(format!("{}.fen", name)))
+}
+
+fn check_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        Err(format!("\"{}\" is not a valid game name", name))
+    } else {
+        Ok(name)
+    }
+}
+
+fn list() -> io::Result<Vec<String>> {
+    let mut names = fs::read_dir(games_dir()?)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            (path.extension()? == "fen").then(|| path.file_stem()?.to_str().map(str::to_owned))?
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    Ok(names)
+}
+
+struct Record {
+    fen: String,
+    variant: Variant,
+    start: String,
+    moves: Vec<String>,
+    notes: Vec<(usize, Note)>,
+    human: Option<Color>,
+    clock: Option<(ByColor<Duration>, Duration, clock::Mode)>,
+    // The opponent's ply each conditional line starts at, and the line.
+    conditions: Vec<(usize, Vec<String>)>,
+}
+
+impl Record {
+    fn of(board: &BoardView) -> Self {
+        Record {
+            fen: board.fen(),
+            variant: board.variant(),
+            start: board.start_fen(),
+            moves: board.sans().to_vec(),
+            notes: board
+                .notes()
+                .iter()
+                .map(|(&i, note)| (i, note.clone()))
+                .collect(),
+            human: board.cpu().map(|cpu| !cpu),
+            clock: board.clock_state(),
+            conditions: board
+                .conditions()
+                .lines()
+                .into_iter()
+                .map(|line| (board.conditions().ply, line))
+                .collect(),
+        }
+    }
+}
+
+fn side_key(human: Option<Color>) -> &'static str {
+    match human {
+        Some(Color::White) => "white",
+        Some(Color::Black) => "black",
+        None => "both",
+    }
+}
+
+// Files from before delay clocks leave out the mode, which was always an
+// increment.
+fn parse_clock_line(line: &str) -> Option<(ByColor<Duration>, Duration, clock::Mode)> {
+    let mut fields = line.split_whitespace();
+    let mut millis = || fields.next()?.parse().ok().map(Duration::from_millis);
+    let white = millis()?;
+    let black = millis()?;
+    let increment = millis()?;
+    let mode = match fields.next() {
+        Some(key) => clock::Mode::from_key(key)?,
+        None => clock::Mode::Fischer,
+    };
+    Some((ByColor { white, black }, increment, mode))
+}
+
+// "3 1 Best by test" is a ! and a comment on the fourth move; "-" stands
+// for no symbol.
+fn parse_note_line(line: &str) -> Option<(usize, Note)> {
+    let mut fields = line.splitn(3, ' ');
+    let index = fields.next()?.parse().ok()?;
+    let nag = match fields.next()? {
+        "-" => None,
+        nag => Some(nag.parse().ok()?),
+    };
+    let comment = fields.next().unwrap_or_default().to_owned();
+    Some((index, Note { nag, comment }))
+}
+
+// The first line is always the current position, so files written before
+// move histories were kept still load.
+fn save(name: &str, record: &Record) -> Result<(), String> {
+    let path = game_path(check_name(name)?).map_err(|e| e.to_string())?;
+    write(&path, record)
+}
+
+fn write(path: &Path, record: &Record) -> Result<(), String> {
+    let mut contents = format!("{}\n", record.fen);
+    if record.variant != Variant::Chess {
+        contents += &format!("{}\n", record.variant.uci());
+    }
+    if !record.moves.is_empty() {
+        contents += &format!("start {}\n", record.start);
+        contents += &format!("moves {}\n", record.moves.join(" "));
+    }
+    for (i, note) in &record.notes {
+        let nag = note.nag.map_or("-".to_owned(), |nag| nag.to_string());
+        contents += &format!("note {} {} {}\n", i, nag, note.comment);
+    }
+    contents += &format!("side {}\n", side_key(record.human));
+    if let Some((remaining, increment, mode)) = record.clock {
+        contents += &format!(
+            "clock {} {} {} {}\n",
+            remaining.white.as_millis(),
+            remaining.black.as_millis(),
+            increment.as_millis(),
+            mode.key()
+        );
+    }
+    for (ply, line) in &record.conditions {
+        contents += &format!("if {} {}\n", ply, line.join(" "));
+    }
+    // Written aside and renamed over the file, so a crash mid-write leaves
+    // the old copy whole.
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, contents)
+        .and_then(|()| fs::rename(&temp, path))
+        .map_err(|e| e.to_string())
+}
+
+fn load(name: &str, settings: Settings) -> Result<BoardView, String> {
+    read(
+        &game_path(name).map_err(|e| e.to_string())?,
+        settings,
+        false,
+    )
+}
+
+// Files from before sides and clocks were saved leave the player on the
+// side to move, unclocked. A kept game goes on saving itself to the file.
+fn read(path: &Path, settings: Settings, kept: bool) -> Result<BoardView, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut lines = contents.lines();
+    let fen = lines.next().unwrap_or_default();
+
+    let mut variant = Variant::Chess;
+    let (mut start, mut moves, mut notes) = (None, Vec::new(), Vec::new());
+    let (mut side, mut clock) = (None, None);
+    let mut conditions = Vec::new();
+    for line in lines.map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(fen) = line.strip_prefix("start ") {
+            start = Some(fen);
+        } else if let Some(sans) = line.strip_prefix("moves ") {
+            moves = sans.split_whitespace().collect();
+        } else if let Some(note) = line.strip_prefix("note ") {
+            notes.push(parse_note_line(note).ok_or("malformed note line")?);
+        } else if let Some(key) = line.strip_prefix("side ") {
+            side = match key {
+                "white" => Some(Some(Color::White)),
+                "black" => Some(Some(Color::Black)),
+                "both" => Some(None),
+                _ => return Err(format!("unknown side {}", key)),
+            };
+        } else if let Some(times) = line.strip_prefix("clock ") {
+            clock = Some(parse_clock_line(times).ok_or("malformed clock line")?);
+        } else if let Some(line) = line.strip_prefix("if ") {
+            let (ply, line) = line.split_once(' ').ok_or("malformed conditional line")?;
+            let ply = ply.parse().map_err(|_| "malformed conditional line")?;
+            conditions.push((
+                ply,
+                line.split_whitespace()
+                    .map(str::to_owned)
+                    .collect::<Vec<_>>(),
+            ));
+        } else {
+            variant = Variant::from_uci(line).map_err(|_| format!("unknown variant {}", line))?;
+        }
+    }
+
+    let mut view = match start {
+        Some(start) => BoardView::from_moves(settings, parse_variant_fen(variant, start)?, &moves)?,
+        None => BoardView::from_position(settings, parse_variant_fen(variant, fen)?),
+    };
+    for (i, note) in notes {
+        view.set_note(i, note);
+    }
+    for (ply, line) in conditions {
+        view.set_condition(ply, &line)?;
+    }
+    if kept {
+        view.keep_at(path.to_owned());
+    }
+    if let Some(human) = side {
+        view.restore(human, clock);
+    }
+    Ok(view)
+}
+
+// Quitting a game in progress parks it here for Resume game; a finished one
+// clears the slot.
+pub fn save_resume(siv: &mut Cursive) {
+    let record = siv.call_on_name("board", |board: &mut BoardView| {
+        for key in board.recovery_keys() {
+            clear_recovery(&key);
+        }
+        (board.result() == "*" && !board.is_correspondence()).then(|| Record::of(board))
+    });
+    let path = match resume_path() {
+        Ok(path) => path,
+        Err(_) => return,
+    };
+    let saved = match record.flatten() {
+        Some(record) => write(&path, &record),
+        None => {
+            let _ = fs::remove_file(&path);
+            Ok(())
+        }
+    };
+    if let Err(e) = saved {
+        siv.add_layer(Dialog::info(fill(
+            "Could not save the game to resume: {}",
+            &[&e],
+        )));
+    }
+}
+
+// Rewritten after every ply, so a crash or a dropped connection loses at
+// most the move being made. Leaving the game any ordinary way clears it.
+pub fn autosave(board: &BoardView) {
+    let Ok(path) = recovery_path(board.recovery_key()) else {
+        return;
+    };
+    if board.result() == "*" {
+        let _ = write(&path, &Record::of(board));
+    } else {
+        let _ = fs::remove_file(path);
+    }
+}
+
+pub fn clear_recovery(key: &str) {
+    if let Ok(path) = recovery_path(key) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+fn recovered() -> Vec<PathBuf> {
+    let Ok(entries) = recovery_dir().and_then(fs::read_dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "fen"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+// The restored game keeps saving to the file it came from.
+fn restore(siv: &mut Cursive, path: &Path) {
+    let key = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    match read(path, settings::current(siv), false) {
+        Ok(mut view) => {
+            view.recover_as(key);
+            logic::open_game(siv, view)
+        }
+        Err(e) => {
+            clear_recovery(&key);
+            siv.add_layer(Dialog::info(fill("Could not restore the game: {}", &[&e])));
+        }
+    }
+}
+
+// At startup, recovery files mean the last session ended mid-game. Games
+// left unpicked are offered again next time.
+pub fn offer_recovery(siv: &mut Cursive) {
+    let paths = recovered();
+    if paths.is_empty() {
+        return;
+    }
+    let mut list = SelectView::new();
+    for path in &paths {
+        let label = match read(path, settings::current(siv), false) {
+            Ok(view) => view.summary(),
+            Err(e) => format!("unreadable: {}", e),
+        };
+        list.add_item(label, path.clone());
+    }
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Restore Game"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new(
+                        "The last session was interrupted. Pick a game up where it stopped?",
+                    ))
+                    .child(DummyView)
+                    .child(
+                        list.on_submit(|s, path: &PathBuf| {
+                            s.pop_layer();
+                            restore(s, path);
+                        })
+                        .with_name("recovered"),
+                    ),
+            )
+            .button(tr("Restore"), |s| {
+                let path = s
+                    .call_on_name("recovered", |v: &mut SelectView<PathBuf>| v.selection())
+                    .flatten();
+                s.pop_layer();
+                if let Some(path) = path {
+                    restore(s, &path);
+                }
+            })
+            .button(tr("Discard All"), move |s| {
+                for path in &paths {
+                    let _ = fs::remove_file(path);
+                }
+                s.pop_layer();
+            }),
+    );
+}
+
+pub fn resume(siv: &mut Cursive) {
+    let path = match resume_path() {
+        Ok(path) if path.exists() => path,
+        _ => {
+            siv.add_layer(Dialog::info(tr("No game to resume.")).title(tr("Resume Game")));
+            return;
+        }
+    };
+    match read(&path, settings::current(siv), false) {
+        Ok(view) => {
+            let _ = fs::remove_file(&path);
+            logic::open_game(siv, view);
+        }
+        Err(e) => siv.add_layer(Dialog::info(fill("Could not resume the game: {}", &[&e]))),
+    }
+}
+
+fn rename(from: &str, to: &str) -> Result<(), String> {
+    let to = game_path(check_name(to)?).map_err(|e| e.to_string())?;
+    if to.exists() {
+        return Err("A game with that name already exists".to_owned());
+    }
+    fs::rename(game_path(from).map_err(|e| e.to_string())?, to).map_err(|e| e.to_string())
+}
+
+fn delete(name: &str) -> Result<(), String> {
+    fs::remove_file(game_path(name).map_err(|e| e.to_string())?).map_err(|e| e.to_string())
+}
+
+pub fn show_save(siv: &mut Cursive) {
+    let record = match siv.call_on_name("board", |board: &mut BoardView| Record::of(board)) {
+        Some(record) => record,
+        None => return,
+    };
+
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Save Game"))
+            .content(EditView::new().with_name("save_name").fixed_width(30))
+            .button(tr("Save"), move |s| {
+                let name = s
+                    .call_on_name("save_name", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                match save(&name, &record) {
+                    Ok(()) => {
+                        s.pop_layer();
+                        s.add_layer(Dialog::info(fill("Saved \"{}\".", &[name.trim()])));
+                    }
+                    Err(e) => s.add_layer(Dialog::info(e)),
+                }
+            })
+            .dismiss_button(tr("Cancel")),
+    );
+}
+
+fn selected(siv: &mut Cursive) -> Option<String> {
+    siv.call_on_name("library", |v: &mut SelectView<String>| {
+        v.selection().map(|name| (*name).clone())
+    })
+    .flatten()
+}
+
+fn refresh(siv: &mut Cursive) {
+    let names = list().unwrap_or_default();
+    siv.call_on_name("library", |v: &mut SelectView<String>| {
+        v.clear();
+        v.add_all(names.into_iter().map(|name| (name.clone(), name)));
+    });
+}
+
+fn load_selected(siv: &mut Cursive, name: &str) {
+    match load(name, settings::current(siv)) {
+        Ok(view) => {
+            siv.pop_layer();
+            logic::open_game(siv, view);
+        }
+        Err(e) => siv.add_layer(Dialog::info(fill("Could not load \"{}\": {}", &[name, &e]))),
+    }
+}
+
+pub fn show_library(siv: &mut Cursive) {
+    let names = match list() {
+        Ok(names) => names,
+        Err(e) => {
+            siv.add_layer(Dialog::info(fill(
+                "Could not open the game library: {}",
+                &[&e.to_string()],
+            )));
+            return;
+        }
+    };
+
+    if names.is_empty() {
+        siv.add_layer(Dialog::info(tr("No saved games yet.")).title(tr("Load Game")));
+        return;
+    }
+
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Load Game"))
+            .content(
+                SelectView::<String>::new()
+                    .with_all(names.into_iter().map(|name| (name.clone(), name)))
+                    .on_submit(|s, name: &String| load_selected(s, name))
+                    .with_name("library")
+                    .min_width(20),
+            )
+            .button(tr("Load"), |s| {
+                if let Some(name) = selected(s) {
+                    load_selected(s, &name);
+                }
+            })
+            .button(tr("Rename"), |s| {
+                if let Some(name) = selected(s) {
+                    show_rename(s, name);
+                }
+            })
+            .button(tr("Delete"), |s| {
+                if let Some(name) = selected(s) {
+                    if let Err(e) = delete(&name) {
+                        s.add_layer(Dialog::info(e));
+                    }
+                    refresh(s);
+                }
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
+
+fn show_rename(siv: &mut Cursive, name: String) {
+    siv.add_layer(
+        Dialog::new()
+            .title(fill("Rename \"{}\"", &[&name]))
+            .content(
+                EditView::new()
+                    .content(name.clone())
+                    .with_name("rename")
+                    .fixed_width(30),
+            )
+            .button(tr("Rename"), move |s| {
+                let to = s
+                    .call_on_name("rename", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                match rename(&name, &to) {
+                    Ok(()) => {
+                        s.pop_layer();
+                        refresh(s);
+                    }
+                    Err(e) => s.add_layer(Dialog::info(e)),
+                }
+            })
+            .dismiss_button(tr("Cancel")),
+    );
+}
+
+fn correspondence_dir() -> io::Result<PathBuf> {
+    let dir = data_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?
+        .join("correspondence");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+pub fn keep(path: &Path, board: &BoardView) -> Result<(), String> {
+    write(path, &Record::of(board))
+}
+
+// Called from the thinking thread. The reply is only written if the file
+// still holds the position it was worked out for.
+pub fn keep_reply(path: &Path, before: &VariantPosition, mv: &Move) -> Result<(), String> {
+    let mut view = read(path, Settings::default(), true)?;
+    if view.fen() != Fen::from_position(before.clone(), EnPassantMode::Legal).to_string() {
+        return Ok(());
+    }
+    view.record_reply(mv);
+    keep(path, &view)
+}
+
+pub fn retire_correspondence(siv: &mut Cursive) {
+    let path = siv
+        .call_on_name("board", |board: &mut BoardView| board.take_correspondence())
+        .flatten();
+    if let Some(path) = path {
+        let _ = fs::remove_file(path);
+    }
+}
+
+fn pending() -> io::Result<Vec<(String, PathBuf)>> {
+    let mut games = fs::read_dir(correspondence_dir()?)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "fen"))
+        .filter_map(|path| Some((path.file_stem()?.to_str()?.to_owned(), path)))
+        .collect::<Vec<_>>();
+    games.sort();
+    Ok(games)
+}
+
+fn pending_label(name: &str, path: &Path) -> String {
+    let state = match read(path, Settings::default(), true) {
+        Ok(view) if view.cpu() == Some(view.position().turn()) => {
+            format!("CPU to move, move {}", view.position().fullmoves())
+        }
+        Ok(view) => format!("your move, move {}", view.position().fullmoves()),
+        Err(e) => format!("unreadable: {}", e),
+    };
+    format!("{:<16} {}", name, state)
+}
+
+fn open_correspondence(siv: &mut Cursive, view: BoardView) {
+    logic::open_game(siv, view);
+    let thinking = siv
+        .call_on_name("board", |board: &mut BoardView| board.resume_thinking())
+        .flatten();
+    if let Some(result) = thinking {
+        result.process(siv);
+    }
+}
+
+fn refresh_pending(siv: &mut Cursive) {
+    let games = pending().unwrap_or_default();
+    siv.call_on_name("pending", |v: &mut SelectView<PathBuf>| {
+        v.clear();
+        v.add_all(
+            games
+                .into_iter()
+                .map(|(name, path)| (pending_label(&name, &path), path)),
+        );
+    });
+}
+
+fn show_new_correspondence(siv: &mut Cursive) {
+    let mut side = RadioGroup::new();
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("New Correspondence Game"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new("Name:"))
+                    .child(EditView::new().with_name("pending_name").fixed_width(20))
+                    .child(side.button(Color::White, "Play White").selected())
+                    .child(side.button(Color::Black, "Play Black")),
+            )
+            .button(tr("Start"), move |s| {
+                let name = s
+                    .call_on_name("pending_name", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                let path = check_name(&name)
+                    .map_err(|e| e.to_string())
+                    .and_then(|name| {
+                        let path = correspondence_dir()
+                            .map_err(|e| e.to_string())?
+                            .join(format!("{}.fen", name));
+                        if path.exists() {
+                            return Err("A game with that name already exists".to_owned());
+                        }
+                        Ok(path)
+                    });
+                let path = match path {
+                    Ok(path) => path,
+                    Err(e) => return s.add_layer(Dialog::info(e)),
+                };
+                let view = BoardView::correspondence(
+                    settings::current(s),
+                    path.clone(),
+                    *side.selection(),
+                );
+                if let Err(e) = keep(&path, &view) {
+                    return s.add_layer(Dialog::info(fill("Could not start the game: {}", &[&e])));
+                }
+                s.pop_layer();
+                s.pop_layer();
+                open_correspondence(s, view);
+            })
+            .dismiss_button(tr("Cancel")),
+    );
+}
+
+// Games against a CPU that thinks for as long as it likes. Each is kept on
+// disk after every move, so it can be closed at any point and picked up
+// later. A reply the CPU finds after its tab was closed is still saved.
+pub fn show_correspondence(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Correspondence"))
+            .content(
+                SelectView::<PathBuf>::new()
+                    .on_submit(
+                        |s, path: &PathBuf| match read(path, settings::current(s), true) {
+                            Ok(view) => {
+                                s.pop_layer();
+                                open_correspondence(s, view);
+                            }
+                            Err(e) => s.add_layer(Dialog::info(fill(
+                                "Could not open the game: {}",
+                                &[&e],
+                            ))),
+                        },
+                    )
+                    .with_name("pending")
+                    .min_width(40)
+                    .min_height(3),
+            )
+            .button(tr("New"), show_new_correspondence)
+            .button(tr("Refresh"), refresh_pending)
+            .button(tr("Delete"), |s| {
+                let path = s
+                    .call_on_name("pending", |v: &mut SelectView<PathBuf>| v.selection())
+                    .flatten();
+                if let Some(path) = path {
+                    if let Err(e) = fs::remove_file(&*path) {
+                        s.add_layer(Dialog::info(e.to_string()));
+                    }
+                    refresh_pending(s);
+                }
+            })
+            .dismiss_button(tr("Back")),
+    );
+    refresh_pending(siv);
+}
@@ -0,0 +1,163 @@
+use std::{
+    env,
+    io::Write,
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use cursive::{views::Dialog, Cursive};
+use shakmaty::{san::SanPlus, uci::Uci, Chess, Position};
+
+use crate::lang::{fill, tr};
+use crate::logic::open_puzzle;
+use crate::puzzle::Puzzle;
+
+const DAILY_URL: &str = "https://lichess.org/api/puzzle/daily";
+
+pub fn fetch(url: &str) -> Result<String, String> {
+    request(url, "application/json")
+}
+
+pub fn fetch_pgn(url: &str) -> Result<String, String> {
+    request(url, "application/x-chess-pgn")
+}
+
+// The app has no HTTPS client of its own, so requests go through curl.
+// Some endpoints, such as the opening explorer, want a personal API token,
+// which is read from LICHESS_TOKEN. The token goes to curl on its stdin,
+// not its arguments, where any user on the machine could read it.
+pub fn curl(url: &str, args: &[&str]) -> Result<Child, String> {
+    let token = env::var("LICHESS_TOKEN").ok();
+    let mut curl = Command::new("curl");
+    curl.args(args);
+    if token.is_some() {
+        curl.args(["-H", "@-"]);
+    }
+    let mut child = curl
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run curl: {}", e))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    if let Some(token) = token {
+        let _ = writeln!(stdin, "Authorization: Bearer {}", token.trim());
+    }
+    Ok(child)
+}
+
+pub fn finish(child: Child, url: &str) -> Result<String, String> {
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("could not run curl: {}", e))?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("could not fetch {}: {}", url, err.trim()));
+    }
+    String::from_utf8(output.stdout).map_err(|_| "the reply is not UTF-8".to_owned())
+}
+
+fn request(url: &str, accept: &str) -> Result<String, String> {
+    let accept = format!("Accept: {}", accept);
+    let child = curl(url, &["-sSf", "--max-time", "15", "-H", &accept])?;
+    finish(child, url)
+}
+
+// Just enough JSON for the puzzle reply: a string or a flat array of strings
+// under the first occurrence of a key.
+pub fn string_field(json: &str, key: &str) -> Option<String> {
+    let start = json.find(&format!("\"{}\":\"", key))? + key.len() + 4;
+    let mut value = String::new();
+    let mut chars = json[start..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => value.extend(chars.next()),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+fn array_field(json: &str, key: &str) -> Option<Vec<String>> {
+    let start = json.find(&format!("\"{}\":[", key))? + key.len() + 4;
+    let end = start + json[start..].find(']')?;
+    Some(
+        json[start..end]
+            .split(',')
+            .map(|item| item.trim().trim_matches('"').to_owned())
+            .filter(|item| !item.is_empty())
+            .collect(),
+    )
+}
+
+fn parse_daily(json: &str) -> Result<Puzzle, String> {
+    let malformed = || "unexpected reply from Lichess".to_owned();
+    let puzzle = &json[json.find("\"puzzle\":").ok_or_else(malformed)?..];
+    let pgn = string_field(json, "pgn").ok_or_else(malformed)?;
+    let solution = array_field(puzzle, "solution").ok_or_else(malformed)?;
+
+    let mut start = Chess::default();
+    let mut lead = None;
+    for san in pgn.split_whitespace() {
+        let mv = SanPlus::from_ascii(san.as_bytes())
+            .ok()
+            .and_then(|san| san.san.to_move(&start).ok())
+            .ok_or_else(|| format!("illegal move in the puzzle game: {}", san))?;
+        start.play_unchecked(&mv);
+        lead = Some(mv);
+    }
+
+    let mut pos = start.clone();
+    let mut moves = Vec::new();
+    for uci in &solution {
+        let mv = Uci::from_ascii(uci.as_bytes())
+            .ok()
+            .and_then(|uci| uci.to_move(&pos).ok())
+            .ok_or_else(|| format!("illegal move in the solution: {}", uci))?;
+        pos.play_unchecked(&mv);
+        moves.push(mv);
+    }
+
+    Ok(Puzzle {
+        id: string_field(puzzle, "id"),
+        start,
+        solution: moves,
+        alternatives: Vec::new(),
+        avoid: Vec::new(),
+        themes: array_field(puzzle, "themes").unwrap_or_default(),
+        lead,
+    })
+}
+
+// Fetched on a thread, so the screen goes on drawing while curl waits. A
+// puzzle that arrives after Cancel is dropped.
+pub fn show_daily(siv: &mut Cursive) {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let flag = cancelled.clone();
+    siv.add_layer(
+        Dialog::text(tr("Fetching the puzzle of the day...")).button(tr("Cancel"), move |s| {
+            flag.store(true, Ordering::SeqCst);
+            s.pop_layer();
+        }),
+    );
+    let cb_sink = siv.cb_sink().clone();
+    thread::spawn(move || {
+        let fetched = fetch(DAILY_URL).and_then(|json| parse_daily(&json));
+        let _ = cb_sink.send(Box::new(move |s| {
+            if cancelled.load(Ordering::SeqCst) {
+                return;
+            }
+            s.pop_layer();
+            match fetched {
+                Ok(puzzle) => open_puzzle(s, puzzle),
+                Err(e) => s.add_layer(Dialog::info(fill("Puzzle of the day: {}", &[&e]))),
+            }
+        }));
+    });
+}
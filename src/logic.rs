@@ -1,284 +1,4631 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    path::PathBuf,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 use cursive::{
     direction::Direction,
-    event::{Event, EventResult, Key, MouseEvent},
+    event::{Event, EventResult, Key, MouseButton, MouseEvent},
     theme::{BaseColor, Color, ColorStyle},
-    view::CannotFocus,
-    views::{Dialog, Panel, SelectView},
-    Cursive, Printer, Vec2,
+    view::{CannotFocus, Finder, Nameable, Resizable, ScrollStrategy, Scrollable, SizeConstraint},
+    views::{
+        Checkbox, Dialog, EditView, LinearLayout, NamedView, OnLayoutView, PaddedView, Panel,
+        RadioGroup, SelectView, TextView,
+    },
+    Cursive, Printer, Vec2, View,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use shakmaty::{
+    attacks,
+    fen::Fen,
+    san::{San, SanError, SanPlus},
+    uci::Uci,
+    variant::{Variant, VariantPosition},
+    Bitboard, Board, ByColor, ByRole, CastlingMode, Chess, Color as CColor, EnPassantMode, File,
+    FromSetup, Piece, Position, Rank, Role, Setup, Square,
 };
-use rand::seq::SliceRandom;
-use shakmaty::{Chess, Color as CColor, Position, Rank, Role, Square};
 
-struct BoardView {
-    board: Chess,
-    focused: Option<Square>,
-    highlighted: Option<Square>,
-    rng: rand::rngs::ThreadRng,
-    promotion: Rc<RefCell<Option<Role>>>,
+use crate::book;
+use crate::chess960;
+use crate::clipboard;
+use crate::clock::{self, Clock, Stamp};
+use crate::code::GameCode;
+use crate::conditional::{self, Conditions};
+use crate::duck;
+use crate::eco;
+use crate::editor;
+use crate::engine;
+use crate::epd::Epd;
+use crate::evalbar::EvalBar;
+use crate::keymap::{self, Action};
+use crate::lang::{fill, tr};
+use crate::library;
+use crate::marks::{Marks, ARROW};
+use crate::net::{Message, Peer};
+use crate::odds;
+use crate::pgn::{self, Note, NAGS};
+use crate::problem::{self, Problem, Problems};
+use crate::puzzle::{Puzzle, Trainer};
+use crate::replay;
+use crate::settings::{
+    self, BoardFacing, BoardSize, CpuTone, CursorEdges, Difficulty, EvalPerspective, FriendlyClick,
+    HighlightIntensity, PieceStyle, Settings, TimeControl,
+};
+use crate::sound::{self, Cue};
+use crate::speech;
+use crate::stats;
+use crate::tutorial::{self, Lesson};
+use crate::uci::{self, UciEngine};
+
+#[derive(Clone, Copy)]
+pub struct PieceValues(ByRole<f32>);
+
+impl Default for PieceValues {
+    fn default() -> Self {
+        PieceValues(ByRole {
+            pawn: 1.0,
+            knight: 3.0,
+            bishop: 3.0,
+            rook: 5.0,
+            queen: 9.0,
+            king: 0.0,
+        })
+    }
 }
 
-impl BoardView {
-    pub fn new() -> Self {
-        let board = Chess::default();
+impl PieceValues {
+    pub fn get(&self, role: Role) -> f32 {
+        *self.0.get(role)
+    }
 
-        BoardView {
-            board,
-            focused: None,
-            highlighted: None,
-            rng: rand::thread_rng(),
-            promotion: Rc::new(RefCell::new(None)),
+    pub fn set(&mut self, role: Role, value: f32) {
+        *self.0.get_mut(role) = value;
+    }
+
+    pub fn material(&self, board: &Board, color: CColor) -> f32 {
+        board
+            .material_side(color)
+            .zip_role()
+            .iter()
+            .map(|&(role, count)| f32::from(count) * self.get(role))
+            .sum()
+    }
+
+    // The engine counts in hundredths of a pawn.
+    pub fn centipawns(&self, role: Role) -> i32 {
+        (self.get(role) * 100.0).round() as i32
+    }
+
+    pub fn balance(&self, board: &Board) -> f32 {
+        self.material(board, CColor::White) - self.material(board, CColor::Black)
+    }
+}
+
+const PHASE_WEIGHTS: ByRole<u32> = ByRole {
+    pawn: 0,
+    knight: 1,
+    bishop: 1,
+    rook: 2,
+    queen: 4,
+    king: 0,
+};
+
+pub const MAX_PHASE: u32 = 24;
+
+pub fn phase_weight(board: &Board) -> u32 {
+    let material = board.material();
+    let weight: u32 = Role::ALL
+        .iter()
+        .map(|&role| {
+            let count = u32::from(*material.white.get(role)) + u32::from(*material.black.get(role));
+            count * PHASE_WEIGHTS.get(role)
+        })
+        .sum();
+    weight.min(MAX_PHASE)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GamePhase {
+    Opening,
+    Middlegame,
+    Endgame,
+}
+
+impl GamePhase {
+    pub fn of(board: &Board) -> Self {
+        let weight = phase_weight(board);
+
+        let undeveloped = [
+            (Square::B1, CColor::White),
+            (Square::C1, CColor::White),
+            (Square::F1, CColor::White),
+            (Square::G1, CColor::White),
+            (Square::B8, CColor::Black),
+            (Square::C8, CColor::Black),
+            (Square::F8, CColor::Black),
+            (Square::G8, CColor::Black),
+        ]
+        .iter()
+        .filter(|&&(sq, color)| {
+            board.color_at(sq) == Some(color)
+                && matches!(board.role_at(sq), Some(Role::Knight | Role::Bishop))
+        })
+        .count();
+
+        if weight <= 6 {
+            GamePhase::Endgame
+        } else if weight >= 20 && undeveloped >= 4 {
+            GamePhase::Opening
+        } else {
+            GamePhase::Middlegame
         }
     }
 
-    fn get_sq(&self, mouse_pos: Vec2, offset: Vec2) -> Option<Square> {
-        mouse_pos
-            .checked_sub(offset)
-            .map(|pos| pos.map_x(|x| x / 3))
-            .and_then(|pos| {
-                if pos.fits_in(Vec2::new(8, 8)) {
-                    Some(Square::new((pos.x + 8 * (7 - pos.y)).try_into().unwrap()))
-                } else {
-                    None
-                }
-            })
+    pub fn name(self) -> &'static str {
+        match self {
+            GamePhase::Opening => "Opening",
+            GamePhase::Middlegame => "Middlegame",
+            GamePhase::Endgame => "Endgame",
+        }
+    }
+}
+
+// How much of the position is drawn, for blindfold training. The move list
+// stays visible in every mode.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum RenderMode {
+    #[default]
+    Normal,
+    HideOpponent,
+    Blindfold,
+}
+
+impl RenderMode {
+    fn next(self) -> Self {
+        match self {
+            RenderMode::Normal => RenderMode::HideOpponent,
+            RenderMode::HideOpponent => RenderMode::Blindfold,
+            RenderMode::Blindfold => RenderMode::Normal,
+        }
     }
+}
 
-    fn move_and_reply(&mut self, mv: shakmaty::Move) -> Option<EventResult> {
-        self.board.play_unchecked(&mv);
+const STATUS_ROWS: usize = 7;
+const ANNOUNCED_ROWS: usize = 4;
+const LOW_TIME: Duration = Duration::from_secs(10);
+const LABEL_COLS: usize = 2;
+pub const UCI_DEPTH: u32 = 12;
+// Correspondence replies may take minutes.
+const DEEP_DEPTH: u32 = 12;
+const DEEP_NODES: u64 = 50_000_000;
+const DEEP_UCI_DEPTH: u32 = 30;
+const DEEP_TIMEOUT: Duration = Duration::from_secs(3600);
+const DRAW_MARGIN: i32 = 50;
+const HINT_DEPTH: u32 = 3;
+const TABLEBASE_PIECES: usize = 7;
+const EVAL_DEPTH: u32 = 3;
 
-        fn game_over(siv: &mut Cursive, msg: &str) {
-            siv.pop_layer();
-            siv.add_layer(Dialog::info(msg))
+type Shelf = Rc<RefCell<Vec<BoardView>>>;
+
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+// Each reply the CPU works on gets an id, so one that arrives after its game
+// was taken back or closed is dropped.
+static THOUGHTS: AtomicUsize = AtomicUsize::new(0);
+static GAMES: AtomicUsize = AtomicUsize::new(0);
+
+// Everything the CPU needs to choose a move. It goes to a worker thread while
+// the CPU thinks and comes back with the move. Copies share the engine, so
+// a hint can be worked out beside it.
+#[derive(Clone)]
+struct Opponent {
+    settings: Settings,
+    difficulty: Difficulty,
+    rng: StdRng,
+    uci: Arc<Mutex<Option<UciEngine>>>,
+    warning: Option<String>,
+    // Thinks at correspondence depth.
+    deep: bool,
+    // The last built-in search, for the engine log.
+    record: Option<String>,
+}
+
+impl Opponent {
+    fn choose(&mut self, board: &VariantPosition) -> Option<shakmaty::Move> {
+        if let Some(mv) = self
+            .book_choice(board)
+            .or_else(|| self.tablebase_choice(board))
+        {
+            return Some(mv);
+        }
+        if self.difficulty == Difficulty::Engine {
+            match self.uci_choice(board) {
+                Ok(mv) => return Some(mv),
+                Err(e) => {
+                    self.warning = Some(format!("External engine disabled: {}", e));
+                    self.difficulty = Difficulty::Random;
+                }
+            }
         }
 
-        if self.board.is_checkmate() {
-            return Some(EventResult::with_cb(|s| {
-                game_over(s, "Game Over. You win.")
-            }));
-        } else if self.board.is_game_over() {
-            return Some(EventResult::with_cb(|s| game_over(s, "Game Over.")));
+        let values = &self.settings.piece_values;
+        let began = Instant::now();
+        let search = match self.difficulty.depth(self.settings.adaptive_level) {
+            Some(_) if self.deep => {
+                engine::search_with(board, DEEP_DEPTH, DEEP_NODES, values, |_| true)
+            }
+            Some(depth) => engine::search(board, depth, values),
+            None => return board.legal_moves().choose(&mut self.rng).cloned(),
         };
+        self.record = Some(engine::log_record(board, &search, began.elapsed()));
+        search.best
+    }
+
+    fn book_choice(&mut self, board: &VariantPosition) -> Option<shakmaty::Move> {
+        let path = self.settings.book_path.trim().to_owned();
+        if path.is_empty()
+            || board.fullmoves().get() > self.settings.book_moves
+            || !is_standard_chess(board)
+        {
+            return None;
+        }
+        match book::pick(&path, board, &mut self.rng) {
+            Ok(mv) => mv,
+            Err(e) => {
+                self.warning = Some(format!("Opening book disabled: {}", e));
+                self.settings.book_path.clear();
+                None
+            }
+        }
+    }
 
-        let legals = self.board.legal_moves();
-        let cpu_move = legals.choose(&mut self.rng).unwrap();
+    // There is no built-in Syzygy prober: endgames covered by the tables are
+    // handed to the external engine, which reads them through SyzygyPath.
+    fn tablebase_choice(&mut self, board: &VariantPosition) -> Option<shakmaty::Move> {
+        if self.settings.tablebase_path.trim().is_empty()
+            || self.difficulty == Difficulty::Engine
+            || board.board().occupied().count() > TABLEBASE_PIECES
+            || !is_standard_chess(board)
+        {
+            return None;
+        }
+        match self.uci_choice(board) {
+            Ok(mv) => Some(mv),
+            Err(e) => {
+                self.warning = Some(format!("Tablebase play disabled: {}", e));
+                self.settings.tablebase_path.clear();
+                None
+            }
+        }
+    }
 
-        self.board.play_unchecked(cpu_move);
+    fn uci_choice(&mut self, board: &VariantPosition) -> Result<shakmaty::Move, String> {
+        let mut slot = self.uci.lock().unwrap_or_else(|e| e.into_inner());
+        if slot.is_none() {
+            let path = uci::engine_path(&self.settings.engine_path)
+                .ok_or("no engine path set (Settings or CHESS_ENGINE)")?;
+            *slot = Some(UciEngine::spawn(
+                &path,
+                self.settings.engine_skill,
+                &self.settings.tablebase_path,
+                &self.settings.engine_options,
+            )?);
+        }
 
-        if self.board.is_checkmate() {
-            return Some(EventResult::with_cb(|s| {
-                game_over(s, "Game Over. I win. Hahaha.")
-            }));
-        } else if self.board.is_game_over() {
-            return Some(EventResult::with_cb(|s| game_over(s, "Game Over.")));
+        let fen = Fen::from_position(board.clone(), EnPassantMode::Legal).to_string();
+        let engine = slot.as_mut().unwrap();
+        let reply = if self.deep {
+            engine.best_move_within(&fen, DEEP_UCI_DEPTH, DEEP_TIMEOUT)
+        } else {
+            engine.best_move(&fen, UCI_DEPTH)
         };
+        if reply.is_err() {
+            *slot = None;
+        }
+        let reply = reply?;
+        Uci::from_ascii(reply.as_bytes())
+            .ok()
+            .and_then(|uci| uci.to_move(board).ok())
+            .ok_or_else(|| format!("engine played an illegal move: {}", reply))
+    }
+}
+
+fn is_standard_chess(board: &VariantPosition) -> bool {
+    board.variant() == Variant::Chess && !board.castles().mode().is_chess960()
+}
+
+pub struct BoardView {
+    board: VariantPosition,
+    history: Vec<VariantPosition>,
+    human: CColor,
+    two_player: bool,
+    moves: Vec<String>,
+    captured: Vec<(usize, Piece)>,
+    focused: Option<Square>,
+    drop: Option<Role>,
+    highlighted: Option<Square>,
+    // Where the selected piece is being dragged, relative to the board.
+    dragging: Option<Vec2>,
+    marks: Marks,
+    // Characters per square, as last laid out.
+    cell: (usize, usize),
+    render_mode: RenderMode,
+    // Turned around from the way the settings face it.
+    flipped: bool,
+    // A square being typed after the jump key: its file, once given.
+    jump: Option<Option<File>>,
+    // An earlier position on show, by ply, read-only.
+    viewing: Option<usize>,
+    // Annotations, by the index of the move they follow.
+    notes: BTreeMap<usize, Note>,
+    // How long each move took, by its index.
+    times: BTreeMap<usize, Stamp>,
+    // When the move now being thought about began, for games with no clock,
+    // and when the game was parked, if it is.
+    move_began: Instant,
+    parked: Option<Instant>,
+    overview: Bitboard,
+    snapshot: Option<Board>,
+    compare: bool,
+    promotion: Rc<RefCell<Option<Role>>>,
+    settings: Settings,
+    step_mode: bool,
+    pending: Option<shakmaty::Move>,
+    // Squares picked while waiting for the CPU, tried once it has moved.
+    premove: Option<(Square, Square)>,
+    last_move: Option<shakmaty::Move>,
+    hint: Option<shakmaty::Move>,
+    hints: u32,
+    took_back: bool,
+    rejected: Option<(Square, String)>,
+    lesson: Option<Lesson>,
+    problem: Option<Problems>,
+    clock: Option<Clock>,
+    // Whether each side has been warned that its time is running out.
+    low_time: ByColor<bool>,
+    difficulty: Difficulty,
+    // Away while it thinks.
+    opponent: Option<Opponent>,
+    thinking: Option<(usize, Instant)>,
+    // A reply that came in while the game was parked, played on return.
+    arrived: Option<(Opponent, Option<shakmaty::Move>)>,
+    // The search on the human's time, and the reply it found for the
+    // position it guessed, by FEN.
+    pondering: Option<usize>,
+    pondered: Option<(String, Opponent, Option<shakmaty::Move>)>,
+    // The other player, across the network.
+    peer: Option<Peer>,
+    // Replies to play as soon as the opponent's move comes in.
+    conditions: Conditions,
+    // A correspondence game's file, rewritten whenever the move count
+    // changes from the one last saved.
+    correspondence: Option<PathBuf>,
+    saved_plies: usize,
+    warning: Option<String>,
+    puzzle: Option<Puzzle>,
+    trainer: Option<Rc<RefCell<Trainer>>>,
+    tab: usize,
+    recovery: String,
+    shelf: Shelf,
+}
 
-        None
+impl BoardView {
+    pub fn new(settings: Settings) -> Self {
+        Self::from_position(settings, Chess::default())
     }
 
-    fn process_focus_change(&mut self, sq: Square) -> EventResult {
-        match self.focused {
-            None if self.board.us().contains(sq) => {
-                self.focused = Some(sq);
+    pub fn from_position(settings: Settings, board: impl Into<VariantPosition>) -> Self {
+        Self::with_rng(settings, board, StdRng::from_entropy())
+    }
 
+    pub fn from_fen(settings: Settings, fen: &str) -> Result<Self, String> {
+        if fen.trim().is_empty() {
+            return Ok(Self::new(settings));
+        }
+        parse_fen::<Chess>(fen).map(|board| Self::from_position(settings, board))
+    }
 
-                if sq.rank() == Rank::Seventh && self.board.board().role_at(sq) == Some(Role::Pawn)
-                {
-                    let p = self.promotion.clone();
-                    EventResult::with_cb(move |s| {
-                        let p = p.clone();
-                        s.add_layer(
-                            Dialog::new().content(
-                                SelectView::new()
-                                    .item("Queen", Role::Queen)
-                                    .item("Rook", Role::Rook)
-                                    .item("Bishop", Role::Bishop)
-                                    .item("Knight", Role::Knight)
-                                    .on_submit(move |s, &piece| {
-                                        s.pop_layer();
-                                        *p.borrow_mut() = Some(piece);
-                                    }),
-                            ),
-                        );
-                    })
-                } else {
-                    *self.promotion.borrow_mut() = None;
-                    EventResult::Consumed(None)
-                }
-            }
+    pub fn from_moves(
+        settings: Settings,
+        start: VariantPosition,
+        moves: &[&str],
+    ) -> Result<Self, String> {
+        let mut view = Self::from_position(settings, start);
+        for &san in moves {
+            let mv = SanPlus::from_ascii(san.as_bytes())
+                .ok()
+                .and_then(|san| san.san.to_move(&view.board).ok())
+                .ok_or_else(|| format!("illegal move in game record: {}", san))?;
+            view.play(&mv);
+        }
+        view.times.clear();
+        view.human = view.board.turn();
+        Ok(view)
+    }
 
-            Some(from) => {
-                let input_move = self.board.legal_moves().into_iter().find(|m| {
-                    m.from() == Some(from)
-                        && m.to() == sq
-                        && if self.promotion.borrow().is_some() {
-                            m.promotion() == *self.promotion.borrow()
-                        } else {
-                            true
-                        }
-                });
+    pub fn against_peer(settings: Settings, peer: Peer, human: CColor) -> Self {
+        let mut view = Self::new(settings);
+        view.human = human;
+        view.opponent = None;
+        view.peer = Some(peer);
+        view
+    }
 
+    pub fn correspondence(settings: Settings, path: PathBuf, human: CColor) -> Self {
+        let mut view = Self::new(settings);
+        view.human = human;
+        view.keep_at(path);
+        view
+    }
 
-                match input_move.and_then(|mv| self.move_and_reply(mv)) {
-                    Some(event_result) => event_result,
-                    None => {
-                        self.focused = None;
-                        EventResult::Consumed(None)
-                    }
+    pub fn keep_at(&mut self, path: PathBuf) {
+        self.correspondence = Some(path);
+        self.saved_plies = self.moves.len();
+        if let Some(opponent) = &mut self.opponent {
+            opponent.deep = true;
+        }
+    }
+
+    pub fn is_correspondence(&self) -> bool {
+        self.correspondence.is_some()
+    }
+
+    // A finished correspondence game stops saving itself.
+    pub fn take_correspondence(&mut self) -> Option<PathBuf> {
+        self.correspondence.take()
+    }
+
+    // Games with no file of their own go to the recovery file instead.
+    fn keep(&mut self) {
+        if self.moves.len() == self.saved_plies {
+            return;
+        }
+        self.saved_plies = self.moves.len();
+        match self.correspondence.clone() {
+            Some(path) => {
+                if let Err(e) = library::keep(&path, self) {
+                    self.warning = Some(format!("Could not save the correspondence game: {}", e));
                 }
             }
-            _ => EventResult::Ignored,
+            None if self.recoverable() => library::autosave(self),
+            None => {}
         }
     }
-}
 
-impl cursive::view::View for BoardView {
-    fn draw(&self, printer: &Printer) {
-        for file in 0..8 {
-            for rank in 0..8 {
-                let x = file * 3;
-                let y = 7 - rank;
+    // Puzzles, lessons and network games cannot be picked up again.
+    pub fn recovery_key(&self) -> &str {
+        &self.recovery
+    }
 
-                let sq = Square::new(file + 8 * rank);
+    pub fn recover_as(&mut self, key: String) {
+        self.recovery = key;
+    }
 
-                let text = match self.board.board().piece_at(sq) {
-                    Some(p) => {
-                        let symbol = piece_to_char(p);
-                        format!(" {} ", symbol)
-                    }
-                    None => "   ".to_owned(),
-                };
+    // This game and the ones parked in other tabs.
+    pub fn recovery_keys(&self) -> Vec<String> {
+        let shelf = self.shelf.borrow();
+        std::iter::once(self)
+            .chain(shelf.iter())
+            .map(|game| game.recovery.clone())
+            .collect()
+    }
 
-                let color = if self.focused == Some(sq) {
-                    Color::Dark(BaseColor::Yellow)
-                } else if self.highlighted == Some(sq) {
-                    Color::Light(BaseColor::Yellow)
-                } else if sq.is_dark() {
-                    Color::RgbLowRes(1, 1, 1)
-                } else {
-                    Color::RgbLowRes(4, 4, 4)
-                };
+    fn recoverable(&self) -> bool {
+        !self.is_puzzle() && self.lesson.is_none() && self.peer.is_none()
+    }
 
-                printer.with_color(
-                    ColorStyle::new(Color::Dark(BaseColor::Black), color),
-                    |printer| printer.print((x, y), &text),
-                );
-            }
+    // Correspondence games open without waiting for the CPU, which then
+    // thinks in the background.
+    pub fn resume_thinking(&mut self) -> Option<EventResult> {
+        let waiting = self.cpu() == Some(self.board.turn()) && self.thinking.is_none();
+        (waiting && self.ending().is_none()).then(|| self.think())
+    }
+
+    // A reply worked out while the game was closed, and the move set up to
+    // answer it.
+    pub fn record_reply(&mut self, mv: &shakmaty::Move) {
+        self.play(mv);
+        if let Some(mv) = self.planned_reply() {
+            self.play(&mv);
         }
     }
 
-    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
-        Ok(EventResult::Consumed(None))
+    pub fn conditions(&self) -> &Conditions {
+        &self.conditions
     }
 
-    fn on_event(&mut self, event: Event) -> EventResult {
-        match event {
-            // Mouse Input
-            Event::Mouse {
-                offset,
-                position,
-                event: MouseEvent::Press(_),
-            } => {
-                if let Some(sq) = self.get_sq(position, offset) {
-                    self.process_focus_change(sq)
-                } else {
-                    EventResult::Ignored
-                }
+    pub fn can_plan(&self) -> Result<(), String> {
+        if self.two_player || (self.correspondence.is_none() && self.peer.is_none()) {
+            return Err("Conditional moves are for correspondence and network games.".to_owned());
+        }
+        if self.board.turn() == self.human || self.ending().is_some() {
+            return Err("Conditional moves are set while the opponent is to move.".to_owned());
+        }
+        Ok(())
+    }
+
+    pub fn plan(&mut self, text: &str) -> Result<(), String> {
+        self.can_plan()?;
+        let line = conditional::parse_line(&self.board, text)?;
+        self.conditions.add(self.moves.len(), &line)?;
+        self.keep_conditions();
+        Ok(())
+    }
+
+    pub fn unplan(&mut self, path: &[String]) {
+        self.conditions.remove(path);
+        self.keep_conditions();
+    }
+
+    // Read back from a saved game.
+    pub fn set_condition(&mut self, ply: usize, line: &[String]) -> Result<(), String> {
+        self.conditions.add(ply, line)
+    }
+
+    fn keep_conditions(&mut self) {
+        if let Some(path) = self.correspondence.clone() {
+            if let Err(e) = library::keep(&path, self) {
+                self.warning = Some(format!("Could not save the correspondence game: {}", e));
             }
+        }
+    }
 
-            // Keyboard Input
-            Event::Key(Key::Left | Key::Right | Key::Up | Key::Down) | Event::Char(' ')
-                if self.highlighted.is_none() =>
-            {
-                self.highlighted = Some(Square::A1);
-                EventResult::Consumed(None)
+    fn planned_reply(&mut self) -> Option<shakmaty::Move> {
+        let ply = self.moves.len().checked_sub(1)?;
+        let reply = self.conditions.answer(ply, &self.moves[ply])?;
+        if self.ending().is_some() {
+            return None;
+        }
+        let san = SanPlus::from_ascii(reply.as_bytes()).ok()?;
+        san.san.to_move(&self.board).ok()
+    }
+
+    fn play_planned(&mut self) -> Option<EventResult> {
+        let mv = self.planned_reply()?;
+        self.move_and_reply(mv)
+    }
+
+    pub fn playing_as(settings: Settings, start: VariantPosition, human: CColor) -> Self {
+        let mut view = Self::from_position(settings, start);
+        view.human = human;
+        if view.board.turn() != human {
+            if let Some(mv) = view.cpu_choice() {
+                view.play_cpu_move(mv);
             }
-            Event::Char(' ') => self.process_focus_change(self.highlighted.unwrap()),
-            Event::Key(key) => {
-                let sq = self.highlighted.unwrap();
-                match key {
-                    Key::Right => {
-                        self.highlighted = sq.offset(1);
-                        EventResult::Consumed(None)
+        }
+        view
+    }
 
-                    }
-                    Key::Left => {
-                        self.highlighted = sq.offset(-1);
-                        EventResult::Consumed(None)
-                    }
+    pub fn two_player(settings: Settings, start: VariantPosition) -> Self {
+        let mut view = Self::from_position(settings, start);
+        view.two_player = true;
+        view
+    }
 
-                    Key::Up => {
-                        self.highlighted = sq.offset(8);
-                        EventResult::Consumed(None)
-                    }
-                    Key::Down => {
-                        self.highlighted = sq.offset(-8);
-                        EventResult::Consumed(None)
-                    }
-                    _ => EventResult::Ignored,
-                }
+    fn with_rng(settings: Settings, board: impl Into<VariantPosition>, rng: StdRng) -> Self {
+        let board = board.into();
+        BoardView {
+            human: board.turn(),
+            two_player: false,
+            board,
+            history: Vec::new(),
+            moves: Vec::new(),
+            captured: Vec::new(),
+            focused: None,
+            drop: None,
+            highlighted: None,
+            dragging: None,
+            marks: Marks::default(),
+            cell: settings.board_size.cell(|_| false),
+            render_mode: RenderMode::Normal,
+            flipped: false,
+            jump: None,
+            viewing: None,
+            notes: BTreeMap::new(),
+            times: BTreeMap::new(),
+            move_began: Instant::now(),
+            parked: None,
+            overview: Bitboard::EMPTY,
+            snapshot: None,
+            compare: false,
+            promotion: Rc::new(RefCell::new(None)),
+            step_mode: settings.step_mode,
+            pending: None,
+            premove: None,
+            last_move: None,
+            hint: None,
+            hints: 0,
+            took_back: false,
+            rejected: None,
+            lesson: None,
+            problem: None,
+            clock: None,
+            difficulty: settings.difficulty,
+            opponent: Some(Opponent {
+                settings: settings.clone(),
+                difficulty: settings.difficulty,
+                rng,
+                uci: Arc::default(),
+                warning: None,
+                deep: false,
+                record: None,
+            }),
+            thinking: None,
+            arrived: None,
+            pondering: None,
+            pondered: None,
+            peer: None,
+            conditions: Conditions::default(),
+            correspondence: None,
+            saved_plies: 0,
+            low_time: ByColor::default(),
+            warning: None,
+            puzzle: None,
+            trainer: None,
+            settings,
+            tab: 1,
+            recovery: format!(
+                "{}-{}",
+                std::process::id(),
+                GAMES.fetch_add(1, Ordering::SeqCst)
+            ),
+            shelf: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    pub fn start_clock(&mut self, time: Option<(ByColor<u64>, u64)>) {
+        self.clock = time.map(|(minutes, increment)| {
+            Clock::new(
+                ByColor::new_with(|color| Duration::from_secs(60 * minutes.get(color))),
+                Duration::from_secs(increment),
+                self.settings.clock_mode,
+                self.clock_starts(),
+            )
+        });
+    }
+
+    // The side whose clock runs now, or none while the first move is awaited.
+    fn clock_starts(&self) -> Option<CColor> {
+        let waits = self.settings.clock_on_first_move && self.history.is_empty();
+        (!waits).then_some(self.board.turn())
+    }
+
+    pub fn has_clock(&self) -> bool {
+        self.clock.is_some()
+    }
+
+    pub fn clock_state(&self) -> Option<(ByColor<Duration>, Duration, clock::Mode)> {
+        let clock = self.clock.as_ref()?;
+        let remaining = ByColor::new_with(|color| clock.remaining(color));
+        Some((remaining, clock.increment(), clock.mode()))
+    }
+
+    // For a game read back from disk: None seats two players at the board.
+    pub fn restore(
+        &mut self,
+        human: Option<CColor>,
+        clock: Option<(ByColor<Duration>, Duration, clock::Mode)>,
+    ) {
+        self.two_player = human.is_none();
+        self.human = human.unwrap_or(self.board.turn());
+        if self.board.turn() != self.human && self.correspondence.is_none() {
+            if let Some(mv) = self.cpu_choice() {
+                self.play_cpu_move(mv);
             }
-            _ => EventResult::Ignored,
         }
+        self.clock = clock.map(|(remaining, increment, mode)| {
+            Clock::new(remaining, increment, mode, self.clock_starts())
+        });
     }
 
-    fn required_size(&mut self, _: Vec2) -> Vec2 {
-        Vec2::new(8, 8).map_x(|x| 3 * x)
+    fn pause_clock(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            clock.pause();
+        }
+        self.parked.get_or_insert_with(Instant::now);
     }
-}
 
-fn piece_to_char(p: shakmaty::Piece) -> char {
-    match (p.color, p.role) {
-        (CColor::Black, Role::Pawn) => '\u{265F}',
-        (CColor::Black, Role::Knight) => '\u{265E}',
-        (CColor::Black, Role::Bishop) => '\u{265D}',
-        (CColor::Black, Role::Rook) => '\u{265C}',
-        (CColor::Black, Role::Queen) => '\u{265B}',
-        (CColor::Black, Role::King) => '\u{265A}',
-        (CColor::White, Role::Pawn) => '\u{2659}',
-        (CColor::White, Role::Knight) => '\u{2658}',
-        (CColor::White, Role::Bishop) => '\u{2657}',
-        (CColor::White, Role::Rook) => '\u{2656}',
-        (CColor::White, Role::Queen) => '\u{2655}',
-        (CColor::White, Role::King) => '\u{2654}',
+    fn resume_clock(&mut self) {
+        if let Some(clock) = &mut self.clock {
+            clock.resume();
+        }
+        if let Some(parked) = self.parked.take() {
+            self.move_began += parked.elapsed();
+        }
     }
-}
 
-pub fn show_options(siv: &mut Cursive) {
-    siv.add_layer(
-        Dialog::new()
-            .title("Select Variant")
-            .content(
-                SelectView::new()
-                    .item_str("Chess")
-                    .item_str("Atomic")
-                    .on_submit(|s, option: &str| {
-                        s.pop_layer();
-                        match option {
-                            "Chess" => new_game(s),
-                            _ => s.add_layer(Dialog::info("Coming soon")),
-                        };
-                    }),
+    fn low_time_cue(&mut self) -> bool {
+        let Some(clock) = self.clock.as_ref().filter(|clock| clock.is_running()) else {
+            return false;
+        };
+        let turn = self.board.turn();
+        let warned = self.low_time.get_mut(turn);
+        if *warned || clock.remaining(turn) > LOW_TIME || !(self.two_player || turn == self.human) {
+            return false;
+        }
+        *warned = true;
+        true
+    }
+
+    fn flag_fall(&mut self) -> Option<(String, CColor)> {
+        let clock = self.clock.as_mut().filter(|clock| clock.is_running())?;
+        let loser = clock.flagged()?;
+        clock.stop();
+        let msg = game_over_message(self.tone(), Some(!loser), self.human, Some(Ending::Timeout));
+        Some((msg, !loser))
+    }
+
+    fn clock_text(&self) -> Option<String> {
+        let clock = self.clock.as_ref()?;
+        let side = |color: CColor| {
+            let mark = if clock.is_running() && self.board.turn() == color {
+                "*"
+            } else {
+                " "
+            };
+            format!(
+                "{}{} {}",
+                mark,
+                color_name(color),
+                clock::format(clock.remaining(color))
             )
-            .dismiss_button("Back"),
-    );
-}
+        };
+        Some(format!("{}  {}", side(CColor::White), side(CColor::Black)))
+    }
 
-fn new_game(siv: &mut Cursive) {
-    siv.add_layer(
-        Dialog::new()
-            .title("Chess")
-            .content(Panel::new(BoardView::new()))
-            .button("Quit Game", |s| {
-                s.pop_layer();
-            }),
-    );
+    fn tab_count(&self) -> usize {
+        1 + self.shelf.borrow().len()
+    }
 
-    siv.add_layer(Dialog::info(
-        "Controls:
-Click with the mouse on the piece you want to move,
-then click on the square you want to move it to.
-Or use Arrows and Space.",
-    ));
+    // Messages from the network look for their game on screen.
+    fn new_tab(&mut self, settings: Settings) {
+        if self.peer.is_some() {
+            return;
+        }
+        let mut fresh = BoardView::new(settings);
+        fresh.tab = self.tab_count() + 1;
+        fresh.shelf = self.shelf.clone();
+
+        self.pause_clock();
+        let parked = std::mem::replace(self, fresh);
+        self.shelf.borrow_mut().push(parked);
+    }
+
+    fn switch_tab(&mut self, tab: usize) -> bool {
+        let shelf = self.shelf.clone();
+        let mut shelf = shelf.borrow_mut();
+        match shelf.iter().position(|game| game.tab == tab) {
+            Some(i) => {
+                self.pause_clock();
+                std::mem::swap(self, &mut shelf[i]);
+                self.resume_clock();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn switch_to(&mut self, tab: usize) -> Option<EventResult> {
+        if tab == self.tab || !self.switch_tab(tab) {
+            return None;
+        }
+        let (opponent, choice) = self.arrived.take()?;
+        self.receive(opponent, choice)
+    }
+
+    // A CPU keeps thinking for a game that is switched away from.
+    fn park_reply(&self, id: usize, opponent: Opponent, choice: Option<shakmaty::Move>) {
+        let mut shelf = self.shelf.borrow_mut();
+        if let Some(game) = shelf
+            .iter_mut()
+            .find(|game| game.thinking.map(|(job, _)| job) == Some(id))
+        {
+            game.arrived = Some((opponent, choice));
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let opponent = if self.two_player {
+            "two players".to_owned()
+        } else {
+            format!("you play {}", color_name(self.human).to_lowercase())
+        };
+        let waiting = if self.arrived.is_some() {
+            ", CPU has moved"
+        } else if self.thinking.is_some() {
+            ", CPU is thinking"
+        } else {
+            ""
+        };
+        format!(
+            "{} ({}): {}{}",
+            pgn::variant_name(self).unwrap_or("Chess"),
+            opponent,
+            self.turn_text(),
+            waiting
+        )
+    }
+
+    // Every open game by tab, this one included.
+    fn games(&self) -> Vec<(usize, String)> {
+        let mut games: Vec<(usize, String)> = self
+            .shelf
+            .borrow()
+            .iter()
+            .map(|game| (game.tab, game.summary()))
+            .chain([(self.tab, self.summary())])
+            .collect();
+        games.sort_by_key(|&(tab, _)| tab);
+        games
+    }
+
+    pub fn tab_bar(&self) -> String {
+        if self.tab_count() == 1 {
+            return String::new();
+        }
+        let shelf = self.shelf.borrow();
+        let mut tabs: Vec<&BoardView> = shelf.iter().chain([self]).collect();
+        tabs.sort_by_key(|game| game.tab);
+        tabs.iter()
+            .map(|game| {
+                let name = pgn::variant_name(game).unwrap_or("Chess");
+                let mark = if game.arrived.is_some() { "*" } else { "" };
+                if game.tab == self.tab {
+                    format!("[{} {}{}]", game.tab, name, mark)
+                } else {
+                    format!(" {} {}{} ", game.tab, name, mark)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn close_tab(&mut self) -> bool {
+        let shelf = self.shelf.clone();
+        let mut shelf = shelf.borrow_mut();
+        if shelf.is_empty() {
+            return false;
+        }
+
+        let closed = self.tab;
+        let next = shelf
+            .iter()
+            .position(|game| game.tab > closed)
+            .unwrap_or(shelf.len() - 1);
+        *self = shelf.remove(next);
+        self.resume_clock();
+
+        for game in shelf.iter_mut().chain(std::iter::once(&mut *self)) {
+            if game.tab > closed {
+                game.tab -= 1;
+            }
+        }
+        true
+    }
+
+    fn close_all_tabs(&mut self) {
+        self.shelf.borrow_mut().clear();
+    }
+
+    fn material_text(&self) -> String {
+        let balance = self.settings.piece_values.balance(self.board.board());
+        let (label, balance) = match self.settings.eval_perspective {
+            EvalPerspective::White => ("White", balance),
+            EvalPerspective::SideToMove if self.board.turn() == CColor::Black => {
+                ("to move", -balance)
+            }
+            EvalPerspective::SideToMove => ("to move", balance),
+        };
+        format!("Material ({}): {:+}", label, balance)
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.board.variant()
+    }
+
+    pub fn fen(&self) -> String {
+        Fen::from_position(self.board.clone(), EnPassantMode::Legal).to_string()
+    }
+
+    pub fn start_fen(&self) -> String {
+        let start = self.history.first().unwrap_or(&self.board);
+        Fen::from_position(start.clone(), EnPassantMode::Legal).to_string()
+    }
+
+    pub fn sans(&self) -> &[String] {
+        &self.moves
+    }
+
+    pub fn position(&self) -> &VariantPosition {
+        &self.board
+    }
+
+    pub fn last_move(&self) -> Option<&shakmaty::Move> {
+        self.last_move.as_ref()
+    }
+
+    pub fn is_puzzle(&self) -> bool {
+        self.puzzle.is_some() || self.problem.is_some()
+    }
+
+    // Only games played without help count towards the player's rating.
+    pub fn cpu_rating(&self) -> Option<i32> {
+        if self.cpu().is_none() || self.is_puzzle() || self.hints > 0 || self.took_back {
+            return None;
+        }
+        Some(
+            self.difficulty
+                .rating(self.settings.engine_skill, self.settings.adaptive_level),
+        )
+    }
+
+    // Only rated games move the adaptive level.
+    pub fn adapts(&self) -> bool {
+        self.difficulty == Difficulty::Adaptive && self.cpu_rating().is_some()
+    }
+
+    pub fn cpu(&self) -> Option<CColor> {
+        (!self.two_player && self.peer.is_none()).then_some(!self.human)
+    }
+
+    pub fn result(&self) -> &'static str {
+        match self.ending() {
+            Some((Some(CColor::White), _)) => "1-0",
+            Some((Some(CColor::Black), _)) => "0-1",
+            Some((None, _)) => "1/2-1/2",
+            None => "*",
+        }
+    }
+
+    pub fn status(&self) -> &'static str {
+        match self.ending() {
+            None => "ongoing",
+            Some((_, Ending::Checkmate)) => "checkmate",
+            Some((
+                _,
+                Ending::VariantWin
+                | Ending::KingExploded
+                | Ending::Hill
+                | Ending::Race
+                | Ending::ThreeChecks
+                | Ending::HordeCaptured
+                | Ending::PiecesLost,
+            )) => "variant end",
+            Some((_, Ending::Stalemate)) => "stalemate",
+            Some((_, Ending::Timeout)) => "timeout",
+            Some(_) => "draw",
+        }
+    }
+
+    pub fn play_uci(&mut self, uci: &str) -> Result<EventResult, String> {
+        if !self.human_to_move() {
+            return Err("waiting for the CPU move to be played".to_owned());
+        }
+
+        let uci =
+            Uci::from_ascii(uci.as_bytes()).map_err(|_| format!("cannot parse move: {}", uci))?;
+        let mv = uci
+            .to_move(&self.board)
+            .map_err(|_| format!("illegal move: {}: {}", uci, self.explain_illegal(&uci)))?;
+
+        self.focused = None;
+        Ok(self
+            .move_and_reply(mv)
+            .unwrap_or(EventResult::Consumed(None)))
+    }
+
+    // Typed moves: UCI first, since squares like b1c3 also read as
+    // (illegal) SAN, then SAN with optional check marks.
+    pub fn play_text(&mut self, text: &str) -> Result<EventResult, String> {
+        let text = text.trim();
+        if Uci::from_ascii(text.as_bytes()).is_ok() {
+            return self.play_uci(text);
+        }
+        if !self.human_to_move() {
+            return Err("waiting for the CPU move to be played".to_owned());
+        }
+
+        let san = SanPlus::from_ascii(text.as_bytes())
+            .map_err(|_| format!("cannot parse move: {}", text))?;
+        let mv = san.san.to_move(&self.board).map_err(|e| match e {
+            SanError::AmbiguousSan => format!("ambiguous move: {}: name the file or rank", text),
+            SanError::IllegalSan => format!("illegal move: {}", text),
+        })?;
+
+        self.focused = None;
+        Ok(self
+            .move_and_reply(mv)
+            .unwrap_or(EventResult::Consumed(None)))
+    }
+
+    fn explain_illegal(&self, uci: &Uci) -> String {
+        let (from, to, promotion) = match *uci {
+            Uci::Normal {
+                from,
+                to,
+                promotion,
+            } => (from, to, promotion),
+            _ => return "only normal moves can be played".to_owned(),
+        };
+
+        let board = self.board.board();
+        let turn = self.board.turn();
+        let piece = match board.piece_at(from) {
+            Some(piece) => piece,
+            None => return format!("no piece on {}", from),
+        };
+        let name = role_name(piece.role).to_lowercase();
+        if piece.color != turn {
+            return format!("the {} on {} is {}'s", name, from, color_name(!turn));
+        }
+        if board.by_color(turn).contains(to) && piece.role != Role::King {
+            return format!("{} is occupied by your own piece", to);
+        }
+
+        let reach = match piece.role {
+            Role::Pawn => {
+                let step = turn.fold_wb(8, -8);
+                let pushes: Bitboard = [from.offset(step), from.offset(2 * step)]
+                    .into_iter()
+                    .flatten()
+                    .take(if from.rank() == turn.relative_rank(Rank::Second) {
+                        2
+                    } else {
+                        1
+                    })
+                    .collect();
+                let captures = attacks::pawn_attacks(turn, from);
+
+                if captures.contains(to) {
+                    if board.by_color(!turn).contains(to)
+                        || self.board.ep_square(EnPassantMode::Legal) == Some(to)
+                    {
+                        None
+                    } else {
+                        return "a pawn only moves diagonally to capture".to_owned();
+                    }
+                } else if pushes.contains(to) {
+                    if (attacks::between(from, to).with(to) & board.occupied()).any() {
+                        return format!("the pawn on {} is blocked", from);
+                    }
+                    None
+                } else {
+                    Some(false)
+                }
+            }
+            Role::King if from.distance(to) == 2 && from.rank() == to.rank() => {
+                return "castling is not possible right now".to_owned();
+            }
+            _ => Some(attacks::attacks(from, piece, Bitboard::EMPTY).contains(to)),
+        };
+
+        match reach {
+            Some(false) => return format!("{}-{} is not how a {} moves", from, to, name),
+            Some(true) if !attacks::attacks(from, piece, board.occupied()).contains(to) => {
+                return format!("the path from {} to {} is blocked", from, to);
+            }
+            _ => {}
+        }
+
+        let last_rank = to.rank() == (!turn).backrank();
+        match promotion {
+            None if piece.role == Role::Pawn && last_rank => {
+                format!("choose a piece to promote to, e.g. {}{}q", from, to)
+            }
+            Some(_) if piece.role != Role::Pawn || !last_rank => {
+                "only a pawn reaching the last rank can promote".to_owned()
+            }
+            Some(Role::Pawn | Role::King) => "a pawn cannot promote to that piece".to_owned(),
+            _ if self.board.is_check() => {
+                "that move does not get your king out of check".to_owned()
+            }
+            _ => "that move leaves your king in check".to_owned(),
+        }
+    }
+
+    fn changed_since_snapshot(&self) -> Bitboard {
+        match &self.snapshot {
+            Some(snapshot) if self.compare => Bitboard::FULL
+                .into_iter()
+                .filter(|&sq| snapshot.piece_at(sq) != self.board.board().piece_at(sq))
+                .collect(),
+            _ => Bitboard::EMPTY,
+        }
+    }
+
+    fn promotion_preview(&self) -> Option<(Bitboard, Piece)> {
+        let from = self.focused?;
+        let role = (*self.promotion.borrow())?;
+
+        let targets = self
+            .board
+            .legal_moves()
+            .iter()
+            .filter(|m| m.from() == Some(from) && m.promotion() == Some(role))
+            .map(|m| m.to())
+            .collect();
+
+        Some((targets, role.of(self.board.turn())))
+    }
+
+    fn inspected(&self) -> Option<(Square, Piece, Bitboard)> {
+        if self.focused.is_some() {
+            return None;
+        }
+        let sq = self.highlighted?;
+        let piece = self.board.board().piece_at(sq)?;
+
+        let position = if piece.color == self.board.turn() {
+            Some(self.board.clone())
+        } else {
+            self.board.clone().swap_turn().ok()
+        };
+
+        let targets = match position {
+            Some(position) => position
+                .legal_moves()
+                .iter()
+                .filter(|m| m.from() == Some(sq))
+                .map(|m| m.to())
+                .collect(),
+            None => self.board.board().attacks_from(sq) & !self.board.board().by_color(piece.color),
+        };
+
+        Some((sq, piece, targets))
+    }
+
+    fn move_preview(&self) -> Option<String> {
+        if !self.settings.move_preview {
+            return None;
+        }
+
+        let (from, to) = (self.focused?, self.highlighted?);
+        let mv = self.board.legal_moves().into_iter().find(|m| {
+            m.from() == Some(from)
+                && m.to() == to
+                && matches!(m.promotion(), None | Some(Role::Queen))
+        })?;
+
+        let mut effects = Vec::new();
+        if let Some(role) = mv.capture() {
+            effects.push(format!("captures {}", role_name(role).to_lowercase()));
+        }
+        if let Some(role) = mv.promotion() {
+            effects.push(format!("promotes to {}", role_name(role).to_lowercase()));
+        }
+        if mv.is_castle() {
+            effects.push("castles".to_owned());
+        }
+
+        let mut after = self.board.clone();
+        after.play_unchecked(&mv);
+        if after.is_checkmate() {
+            effects.push("checkmates".to_owned());
+        } else if after.is_check() {
+            effects.push("gives check".to_owned());
+        } else if after.is_stalemate() {
+            effects.push("stalemates".to_owned());
+        }
+
+        if effects.is_empty() {
+            effects.push("quiet move".to_owned());
+        }
+        Some(format!(
+            "{}: {}",
+            San::from_move(&self.board, &mv),
+            effects.join(", ")
+        ))
+    }
+
+    fn status_lines(&self, inspected: Option<(Square, Piece, Bitboard)>) -> Vec<String> {
+        let mut lines: Vec<String> = self.clock_text().into_iter().collect();
+        if let Some(ply) = self.viewing {
+            lines.push(format!("Viewing move {} of {}", ply, self.moves.len()));
+            lines.push("< > step, any key returns".to_owned());
+            return lines;
+        }
+        if let Some(file) = self.jump {
+            lines.push(format!(
+                "Jump to: {}_",
+                file.map_or(String::new(), |f| f.char().to_string())
+            ));
+        }
+        lines.extend([
+            self.material_text(),
+            format!("Phase: {}", GamePhase::of(self.board.board()).name()),
+        ]);
+        if let Some(checks) = self.board.remaining_checks() {
+            lines.push(format!(
+                "Checks left: White {}, Black {}",
+                u32::from(checks.white),
+                u32::from(checks.black)
+            ));
+        }
+        if self.board.pockets().is_some() {
+            lines.push(self.pocket_line(CColor::White));
+            lines.push(self.pocket_line(CColor::Black));
+        } else {
+            lines.extend(self.captured_line(CColor::White));
+            lines.extend(self.captured_line(CColor::Black));
+        }
+        if let Some(role) = self.drop {
+            lines.push(format!(
+                "Drop: {}, pick a square",
+                role_name(role).to_lowercase()
+            ));
+        }
+
+        if let Some(puzzle) = &self.puzzle {
+            let task = if puzzle.solution.is_empty() {
+                "avoid the wrong move".to_owned()
+            } else if puzzle.solution.len() > 1 {
+                let found = self.moves.len() / 2 + 1;
+                format!("move {} of {}", found, puzzle.moves_to_find())
+            } else {
+                "find the best move".to_owned()
+            };
+            match &puzzle.id {
+                Some(id) => lines.push(format!("Puzzle {}: {}", id, task)),
+                None => lines.push(format!("Puzzle: {}", task)),
+            }
+        }
+
+        if let Some(problem) = self.problem.as_ref().and_then(Problems::current) {
+            lines.push(problem.title());
+            let left = self.mate_left(problem);
+            if left < problem.moves {
+                lines.push(format!("Mate in {} from here", left));
+            }
+        }
+        if let Some(lesson) = self.lesson {
+            lines.push(format!("Lesson: {}", lesson.current().task()));
+        }
+
+        if let Some(ending) = self.claimable() {
+            lines.push(format!("Draw by {} available: press =", ending.reason()));
+        }
+        if self.peer.as_ref().is_some_and(|peer| peer.offered) {
+            lines.push("Draw offered, waiting".to_owned());
+        }
+        if let Some((from, to)) = self.premove {
+            lines.push(format!("Premove {}{} (Esc cancels)", from, to));
+        }
+        if !self.conditions.is_empty() {
+            let count = self.conditions.nodes().len();
+            lines.push(format!("Conditional moves: {} (C)", count));
+        }
+        if let Some((_, since)) = self.thinking {
+            let frame = since.elapsed().as_millis() / 250;
+            lines.push(format!(
+                "CPU is thinking {}",
+                SPINNER[frame as usize % SPINNER.len()]
+            ));
+        } else if self.pending.is_some() {
+            lines.push("CPU move ready: press Enter to play it".to_owned());
+        } else if self.step_mode {
+            lines.push("Step mode (P to turn off)".to_owned());
+        }
+        match self.render_mode {
+            RenderMode::Normal => {}
+            RenderMode::HideOpponent => lines.push("Opponent's pieces hidden".to_owned()),
+            RenderMode::Blindfold => lines.push("Blindfold (B shows all)".to_owned()),
+        }
+        if let Some((_, reason)) = &self.rejected {
+            lines.push(format!("Can't move there: {}", reason));
+        }
+        if let Some(hint) = &self.hint {
+            let san = San::from_move(&self.board, hint);
+            lines.push(format!("Hint: {}", san));
+        }
+        if let Some(preview) = self.move_preview() {
+            lines.push(preview);
+        }
+        if let Some((sq, piece, targets)) = inspected {
+            lines.push(format!(
+                "{} {} on {}: {} moves",
+                color_name(piece.color),
+                role_name(piece.role),
+                sq,
+                targets.count()
+            ));
+        }
+        if self.tab_count() > 1 {
+            lines.push(format!(
+                "Game {} of {} (G or Alt+n)",
+                self.tab,
+                self.tab_count()
+            ));
+        }
+
+        lines
+    }
+
+    fn step_cursor(&self, sq: Square, df: i32, dr: i32) -> Square {
+        let wrap = self.settings.cursor_edges == CursorEdges::Wrap;
+
+        let file = sq
+            .file()
+            .offset(df)
+            .or_else(|| wrap.then_some(if df > 0 { File::A } else { File::H }));
+        let rank = sq
+            .rank()
+            .offset(dr)
+            .or_else(|| wrap.then_some(if dr > 0 { Rank::First } else { Rank::Eighth }));
+
+        match (file, rank) {
+            (Some(file), Some(rank)) => Square::from_coords(file, rank),
+            _ => sq,
+        }
+    }
+
+    fn move_and_reply(&mut self, mv: shakmaty::Move) -> Option<EventResult> {
+        if let Some(lesson) = self.lesson {
+            return Some(self.lesson_move(&mv, lesson));
+        }
+        if let Some(puzzle) = &self.puzzle {
+            return Some(self.puzzle_move(&mv, puzzle.clone()));
+        }
+        if let Some(problems) = &self.problem {
+            return Some(self.problem_move(&mv, problems.clone()));
+        }
+
+        debug_assert!(self.board.is_legal(&mv));
+        self.play(&mv);
+        self.focused = None;
+
+        if let Some(peer) = &mut self.peer {
+            let uci = mv.to_uci(CastlingMode::Standard).to_string();
+            if let Err(e) = peer.send(&Message::Move(uci)) {
+                self.warning = Some(e);
+            }
+            return self.check_game_over();
+        }
+
+        if let Some(result) = self.check_game_over() {
+            return Some(result);
+        }
+
+        if self.two_player {
+            self.human = self.board.turn();
+            return None;
+        }
+
+        Some(self.think())
+    }
+
+    // The reply is worked out on another thread and delivered through the
+    // callback sink, so the interface keeps running while the CPU thinks.
+    fn think(&mut self) -> EventResult {
+        self.pondering = None;
+        let fen = self.fen();
+        let pondered = self.pondered.take().filter(|(guess, _, _)| *guess == fen);
+        let Some(mut opponent) = self.opponent.take() else {
+            return EventResult::Consumed(None);
+        };
+        // The copy that pondered shares the engine and carries the search.
+        if let Some((_, opponent, choice)) = pondered {
+            return self
+                .receive(opponent, choice)
+                .unwrap_or(EventResult::Consumed(None));
+        }
+        let id = THOUGHTS.fetch_add(1, Ordering::SeqCst);
+        self.thinking = Some((id, Instant::now()));
+        let board = self.board.clone();
+        let kept = self.correspondence.clone();
+        EventResult::with_cb_once(move |s| {
+            s.set_fps(4);
+            let cb_sink = s.cb_sink().clone();
+            thread::spawn(move || {
+                let choice = opponent.choose(&board);
+                // Saved first, in case the game is no longer open.
+                if let (Some(path), Some(mv)) = (&kept, &choice) {
+                    let _ = library::keep_reply(path, &board, mv);
+                }
+                let _ = cb_sink.send(Box::new(move |s| receive_cpu_move(s, id, opponent, choice)));
+            });
+        })
+    }
+
+    fn receive(
+        &mut self,
+        mut opponent: Opponent,
+        choice: Option<shakmaty::Move>,
+    ) -> Option<EventResult> {
+        self.thinking = None;
+        self.difficulty = opponent.difficulty;
+        self.warning = opponent.warning.take();
+        self.log_search(opponent.record.take());
+        self.opponent = Some(opponent);
+
+        // Every position without legal moves is an ending, so this only
+        // triggers if the two ever disagree.
+        let cpu_move = match choice {
+            Some(mv) => mv,
+            None => {
+                let msg = game_over_message(self.tone(), None, self.human, None);
+                return Some(EventResult::with_cb(move |s| game_over(s, &msg, None)));
+            }
+        };
+
+        if self.step_mode {
+            self.pending = Some(cpu_move);
+            return None;
+        }
+
+        let result = self.play_cpu_move(cpu_move);
+        match self.ponder() {
+            Some(ponder) => Some(result.unwrap_or(EventResult::Consumed(None)).and(ponder)),
+            None => result,
+        }
+    }
+
+    // The guess is the built-in search's pick for the human. A human who
+    // plays something else leaves the answer unused.
+    fn ponder(&mut self) -> Option<EventResult> {
+        self.pondered = None;
+        let ready = self.settings.ponder
+            && self.correspondence.is_none()
+            && self.human_to_move()
+            && self.ending().is_none();
+        let opponent = self.opponent.clone().filter(|_| ready)?;
+        let id = THOUGHTS.fetch_add(1, Ordering::SeqCst);
+        self.pondering = Some(id);
+        let board = self.board.clone();
+        let values = self.settings.piece_values;
+        Some(EventResult::with_cb_once(move |s| {
+            let cb_sink = s.cb_sink().clone();
+            thread::spawn(move || {
+                let Some(guess) = engine::search(&board, HINT_DEPTH, &values).best else {
+                    return;
+                };
+                let mut after = board;
+                after.play_unchecked(&guess);
+                let mut opponent = opponent;
+                let choice = opponent.choose(&after);
+                let fen = Fen::from_position(after, EnPassantMode::Legal).to_string();
+                let _ = cb_sink.send(Box::new(move |s| {
+                    receive_ponder(s, id, fen, opponent, choice)
+                }));
+            });
+        }))
+    }
+
+    // Puzzles start from an empty history, so the plies played so far index
+    // the solution. A correct move is answered with the solution's reply.
+    fn puzzle_move(&mut self, mv: &shakmaty::Move, puzzle: Puzzle) -> EventResult {
+        let ply = self.moves.len();
+        let san = San::from_move(&self.board, mv).to_string();
+        if !puzzle.accepts(ply, &self.board, mv) {
+            let msg = if ply == 0 && puzzle.avoid.contains(mv) {
+                format!("{} is the move to avoid.", san)
+            } else {
+                format!("{} is not it.", san)
+            };
+            self.puzzle = None;
+            let trainer = self.trainer.clone();
+            return EventResult::with_cb(move |s| puzzle_over(s, &msg, &puzzle, true, &trainer));
+        }
+
+        self.play(mv);
+        match puzzle.solution.get(ply + 1) {
+            Some(reply) => {
+                self.play(reply);
+                EventResult::Consumed(None)
+            }
+            None => {
+                let msg = format!("Correct! {} solves it.", san);
+                self.puzzle = None;
+                let trainer = self.trainer.clone();
+                EventResult::with_cb(move |s| puzzle_over(s, &msg, &puzzle, false, &trainer))
+            }
+        }
+    }
+
+    // Moves left for the solver to mate in.
+    fn mate_left(&self, problem: &Problem) -> u32 {
+        problem.moves.saturating_sub((self.moves.len() / 2) as u32)
+    }
+
+    // Any move that still forces mate in time is accepted, and the defence
+    // holds out as long as it can.
+    fn problem_move(&mut self, mv: &shakmaty::Move, problems: Problems) -> EventResult {
+        let (VariantPosition::Chess(pos), Some(problem)) = (&self.board, problems.current()) else {
+            return EventResult::Consumed(None);
+        };
+        let left = self.mate_left(problem);
+        let san = San::from_move(pos, mv).to_string();
+        let mut after = pos.clone();
+        after.play_unchecked(mv);
+
+        let (msg, refutation) = if after.is_checkmate() {
+            (format!("Correct! {} is mate.", san), None)
+        } else if problem::forced(&after, left - 1) {
+            self.play(mv);
+            if let Some(reply) = problem::defence(&after, left - 1) {
+                self.play(&reply);
+            }
+            return EventResult::Consumed(None);
+        } else if after.legal_moves().is_empty() {
+            (format!("{} is stalemate.", san), None)
+        } else {
+            let refutation = problem::refutation(&after, left - 1).map(|reply| {
+                let reply = San::from_move(&after, &reply);
+                match left {
+                    1 => format!("{} is met by {}.", san, reply),
+                    _ => format!(
+                        "{} is met by {}, and there is no mate in {} after it.",
+                        san,
+                        reply,
+                        left - 1
+                    ),
+                }
+            });
+            (
+                format!("{} does not force mate in {}.", san, left),
+                refutation,
+            )
+        };
+        self.problem = None;
+        EventResult::with_cb(move |s| problem::over(s, &msg, refutation.clone(), problems.clone()))
+    }
+
+    fn lesson_move(&mut self, mv: &shakmaty::Move, lesson: Lesson) -> EventResult {
+        self.focused = None;
+        if let Err(reason) = lesson.current().check(mv) {
+            self.rejected = Some((mv.to(), reason));
+            return EventResult::Consumed(None);
+        }
+        self.play(mv);
+        self.lesson = None;
+        EventResult::with_cb(move |s| tutorial::step_done(s, lesson))
+    }
+
+    // Chosen on this thread: the CPU's first move, before the view is on
+    // screen, and the replies in `fuzz`.
+    fn cpu_choice(&mut self) -> Option<shakmaty::Move> {
+        let opponent = self.opponent.as_mut()?;
+        let choice = opponent.choose(&self.board);
+        self.difficulty = opponent.difficulty;
+        self.warning = opponent.warning.take();
+        let record = opponent.record.take();
+        self.log_search(record);
+        choice
+    }
+
+    fn log_search(&mut self, record: Option<String>) {
+        if let Some(Err(e)) = record.map(|record| engine::write_log(&record)) {
+            self.warning = Some(format!("Engine log disabled: {}", e));
+        }
+    }
+
+    fn is_standard_chess(&self) -> bool {
+        is_standard_chess(&self.board)
+    }
+
+    fn play_cpu_move(&mut self, mv: shakmaty::Move) -> Option<EventResult> {
+        debug_assert!(self.board.is_legal(&mv));
+        self.play(&mv);
+
+        self.check_game_over().or_else(|| {
+            sound::play(self.settings.sound, self.move_cue(&mv));
+            self.play_premove().or_else(|| self.play_planned())
+        })
+    }
+
+    fn move_cue(&self, mv: &shakmaty::Move) -> Cue {
+        if self.board.is_check() {
+            Cue::Check
+        } else if mv.is_capture() {
+            Cue::Capture
+        } else {
+            Cue::Move
+        }
+    }
+
+    fn play(&mut self, mv: &shakmaty::Move) {
+        let ply = self.history.len();
+        let before = self.board.board().material();
+        let mover = self.board.turn();
+
+        self.history.push(self.board.clone());
+        self.moves
+            .push(SanPlus::from_move(self.board.clone(), mv).to_string());
+        self.board.play_unchecked(mv);
+        self.last_move = Some(mv.clone());
+        self.marks.clear();
+        self.drop = None;
+        let stamp = match &mut self.clock {
+            Some(clock) => clock.press(mover),
+            None => Stamp {
+                spent: self.move_began.elapsed(),
+                left: None,
+            },
+        };
+        self.times.insert(ply, stamp);
+        self.move_began = Instant::now();
+
+        // Diffing material rather than reading the move also catches every
+        // piece caught in an atomic explosion.
+        let after = self.board.board().material();
+        for color in [CColor::White, CColor::Black] {
+            for role in Role::ALL {
+                let mut lost = before
+                    .get(color)
+                    .get(role)
+                    .saturating_sub(*after.get(color).get(role));
+                if color == mover && role == Role::Pawn && mv.promotion().is_some() {
+                    lost -= 1;
+                }
+                for _ in 0..lost {
+                    self.captured.push((ply, role.of(color)));
+                }
+            }
+        }
+    }
+
+    fn captured_line(&self, color: CColor) -> Option<String> {
+        let pieces: String = self
+            .captured
+            .iter()
+            .filter(|(_, piece)| piece.color == !color)
+            .map(|&(_, piece)| piece_to_char(piece, self.piece_style()))
+            .collect();
+        (!pieces.is_empty()).then(|| format!("{} took: {}", color_name(color), pieces))
+    }
+
+    // Only Crazyhouse has pockets; captured pieces switch sides and wait there.
+    fn pocket(&self, color: CColor) -> Vec<(Role, u8)> {
+        self.board.pockets().map_or(Vec::new(), |pockets| {
+            Role::ALL
+                .into_iter()
+                .map(|role| (role, *pockets.get(color).get(role)))
+                .filter(|&(_, count)| count > 0)
+                .collect()
+        })
+    }
+
+    fn pocket_prefix(color: CColor) -> String {
+        format!("{} pocket: ", color_name(color))
+    }
+
+    fn pocket_line(&self, color: CColor) -> String {
+        let pieces: Vec<String> = self
+            .pocket(color)
+            .into_iter()
+            .map(|(role, count)| {
+                let symbol = piece_to_char(role.of(color), self.piece_style());
+                format!("{}{}", symbol, count)
+            })
+            .collect();
+        let pieces = if pieces.is_empty() {
+            "-".to_owned()
+        } else {
+            pieces.join(" ")
+        };
+        Self::pocket_prefix(color) + &pieces
+    }
+
+    fn pick_drop(&mut self, role: Option<Role>) -> EventResult {
+        if !self.human_to_move() {
+            return EventResult::Consumed(None);
+        }
+        self.focused = None;
+        *self.promotion.borrow_mut() = None;
+        self.drop = role;
+        EventResult::Consumed(None)
+    }
+
+    // D steps through the pieces in my pocket and then back to none.
+    fn cycle_drop(&mut self) -> EventResult {
+        let pocket: Vec<Role> = self
+            .pocket(self.board.turn())
+            .into_iter()
+            .map(|(role, _)| role)
+            .collect();
+        let next = match self.drop.and_then(|r| pocket.iter().position(|&p| p == r)) {
+            Some(i) => pocket.get(i + 1).copied(),
+            None => pocket.first().copied(),
+        };
+        self.pick_drop(next)
+    }
+
+    // A click on my pocket line in the status area picks the piece under it.
+    fn pocket_click(&mut self, local: Vec2) -> EventResult {
+        let (_, h) = self.cell;
+        let turn = self.board.turn();
+        let line = self.pocket_line(turn);
+        let row = match local.y.checked_sub(8 * h + 1 + self.board_origin().y) {
+            Some(row) => row,
+            None => return EventResult::Ignored,
+        };
+        if self.status_lines(None).get(row) != Some(&line) {
+            return EventResult::Ignored;
+        }
+
+        let mut x = Self::pocket_prefix(turn).chars().count();
+        for (role, count) in self.pocket(turn) {
+            let width = 1 + count.to_string().len();
+            if (x..x + width).contains(&local.x) {
+                return self.pick_drop(Some(role));
+            }
+            x += width + 1;
+        }
+        EventResult::Consumed(None)
+    }
+
+    fn drop_targets(&self) -> Bitboard {
+        match self.drop {
+            Some(role) => self
+                .board
+                .legal_moves()
+                .iter()
+                .filter(|m| m.from().is_none() && m.role() == role)
+                .map(|m| m.to())
+                .collect(),
+            None => Bitboard::EMPTY,
+        }
+    }
+
+    fn drop_on(&mut self, role: Role, sq: Square) -> EventResult {
+        let mv = shakmaty::Move::Put { role, to: sq };
+        if self.board.is_legal(&mv) {
+            return self
+                .move_and_reply(mv)
+                .unwrap_or(EventResult::Consumed(None));
+        }
+        if self.board.us().contains(sq) {
+            self.drop = None;
+            return self.select(sq);
+        }
+
+        let reason = if self.board.board().occupied().contains(sq) {
+            format!("{} is occupied", sq)
+        } else if role == Role::Pawn && Bitboard::BACKRANKS.contains(sq) {
+            "pawns cannot be dropped on the first or last rank".to_owned()
+        } else if self.board.is_check() {
+            "the drop does not stop the check".to_owned()
+        } else {
+            "that drop is not legal".to_owned()
+        };
+        self.rejected = Some((sq, reason));
+        EventResult::Consumed(None)
+    }
+
+    pub fn turn_text(&self) -> String {
+        let check = if self.board.is_check() {
+            tr(" (check)")
+        } else {
+            ""
+        };
+        fill(
+            "{} to move, move {}{}",
+            &[
+                tr(color_name(self.board.turn())),
+                &self.board.fullmoves().to_string(),
+                check,
+            ],
+        )
+    }
+
+    // The line under the board. The evaluation is the eval bar's, when it
+    // is on.
+    pub fn status_bar(&self, eval: Option<String>) -> String {
+        let turn = tr(color_name(self.board.turn()));
+        let state = match self.ending() {
+            Some((Some(winner), ending)) => fill(
+                "{} wins by {}",
+                &[tr(color_name(winner)), tr(ending.reason())],
+            ),
+            Some((None, ending)) => fill("Drawn by {}", &[tr(ending.reason())]),
+            None if self.board.is_check() => fill("{} to move, in check", &[turn]),
+            None => fill("{} to move", &[turn]),
+        };
+        let mut parts = vec![
+            state,
+            fill("Move {}", &[&self.board.fullmoves().to_string()]),
+            fill(
+                "Halfmove clock {}/100",
+                &[&self.board.halfmoves().to_string()],
+            ),
+        ];
+        parts.extend(eval.map(|eval| fill("Eval {}", &[&eval])));
+        if self.settings.announce_moves {
+            if let Some(sq) = self.focused {
+                parts.push(fill("Holding {}", &[&speech::piece_on(self.shown(), sq)]));
+            }
+            if let Some(sq) = self.highlighted {
+                parts.push(fill("Cursor {}", &[&speech::piece_on(self.shown(), sq)]));
+            }
+        }
+        parts.join("  |  ")
+    }
+
+    // Every move so far, one sentence to a line.
+    pub fn announcements(&self) -> String {
+        let mut text = String::new();
+        for (before, san) in self.history.iter().zip(&self.moves) {
+            let mv = SanPlus::from_ascii(san.as_bytes())
+                .ok()
+                .and_then(|san| san.san.to_move(before).ok());
+            if let Some(mv) = mv {
+                text += &format!(
+                    "{}. {}\n",
+                    before.fullmoves(),
+                    speech::sentence(before, &mv)
+                );
+            }
+        }
+        text
+    }
+
+    // Solid figurines tell the sides apart by colour alone, so reading out
+    // moves falls back to letters for them.
+    fn piece_style(&self) -> PieceStyle {
+        match self.settings.piece_style {
+            PieceStyle::Solid if self.settings.announce_moves => PieceStyle::Ascii,
+            style => style,
+        }
+    }
+
+    // The move list under the name of the opening, when the game began from
+    // the standard position.
+    pub fn move_panel(&self) -> String {
+        let standard = Fen::from_position(Chess::default(), EnPassantMode::Legal).to_string();
+        let opening = if self.is_standard_chess() && self.start_fen() == standard {
+            eco::name(&self.moves)
+        } else {
+            None
+        };
+        match opening {
+            Some((code, name)) => format!("{} {}\n\n{}", code, name, self.move_list()),
+            None => self.move_list(),
+        }
+    }
+
+    pub fn move_list(&self) -> String {
+        self.listed(false)
+    }
+
+    // The moves for PGN, with NAGs in place of the annotation symbols.
+    pub fn movetext(&self) -> String {
+        self.listed(true)
+    }
+
+    fn listed(&self, pgn: bool) -> String {
+        if !pgn && !self.times.is_empty() {
+            return self.timed_list();
+        }
+        let start = self.history.first().unwrap_or(&self.board);
+        let mut number = start.fullmoves().get();
+        let mut text = String::new();
+
+        let annotated = |i: usize| {
+            let note = self.notes.get(&i).map(|note| note.text(pgn));
+            let time = self.times.get(&i).filter(|_| pgn).map(pgn::time_comment);
+            let san = if pgn {
+                self.moves[i].clone()
+            } else {
+                self.list_san(i)
+            };
+            format!(
+                "{}{}{}",
+                san,
+                note.unwrap_or_default(),
+                time.unwrap_or_default()
+            )
+        };
+        let mut moves = 0..self.moves.len();
+        if start.turn() == CColor::Black {
+            if let Some(i) = moves.next() {
+                text += &format!("{}... {}\n", number, annotated(i));
+                number += 1;
+            }
+        }
+        while let Some(white) = moves.next() {
+            text += &format!("{}. {}", number, annotated(white));
+            if let Some(black) = moves.next() {
+                text += &format!(" {}", annotated(black));
+            }
+            text.push('\n');
+            number += 1;
+        }
+
+        text
+    }
+
+    // One move a line, each with the time it took.
+    fn timed_list(&self) -> String {
+        let start = self.history.first().unwrap_or(&self.board);
+        let first = usize::from(start.turn() == CColor::Black);
+        let mut number = start.fullmoves().get();
+        let mut text = String::new();
+        for i in 0..self.moves.len() {
+            let san = self.list_san(i);
+            let note = self.notes.get(&i).map(|note| note.text(false));
+            let dots = if (i + first) % 2 == 0 { "." } else { "..." };
+            let played = format!("{}{} {}{}", number, dots, san, note.unwrap_or_default());
+            let time = self
+                .times
+                .get(&i)
+                .map_or(String::new(), |stamp| clock::format(stamp.spent));
+            text += &format!("{:<11}{:>5}\n", played, time);
+            if dots != "." {
+                number += 1;
+            }
+        }
+        text
+    }
+
+    // The move as the list shows it, with the promotion glyph if asked for.
+    fn list_san(&self, i: usize) -> String {
+        let san = &self.moves[i];
+        let promoted = san
+            .split_once('=')
+            .filter(|_| self.settings.promotion_glyphs)
+            .and_then(|(head, tail)| {
+                let role = Role::from_char(tail.chars().next()?.to_ascii_lowercase())?;
+                let color = self.history.get(i)?.turn();
+                let glyph = piece_to_char(role.of(color), self.piece_style());
+                Some(format!("{}={}{}", head, glyph, &tail[1..]))
+            });
+        promoted.unwrap_or_else(|| san.clone())
+    }
+
+    pub fn notes(&self) -> &BTreeMap<usize, Note> {
+        &self.notes
+    }
+
+    pub fn set_note(&mut self, index: usize, note: Note) {
+        if note.is_empty() {
+            self.notes.remove(&index);
+        } else if index < self.moves.len() {
+            self.notes.insert(index, note);
+        }
+        if let Some(path) = self.correspondence.clone() {
+            if let Err(e) = library::keep(&path, self) {
+                self.warning = Some(format!("Could not save the correspondence game: {}", e));
+            }
+        }
+    }
+
+    // The move on show when looking back, or else the last one played.
+    fn annotated_move(&self) -> Option<(usize, String, Note)> {
+        let index = self.viewing.unwrap_or(self.moves.len()).checked_sub(1)?;
+        let note = self.notes.get(&index).cloned().unwrap_or_default();
+        Some((index, self.moves[index].clone(), note))
+    }
+
+    fn take_back(&mut self) -> bool {
+        if self.thinking.is_some() || self.peer.is_some() {
+            return false;
+        }
+        let target = if self.two_player {
+            self.history.len().checked_sub(1)
+        } else {
+            self.history
+                .iter()
+                .rposition(|pos| pos.turn() == self.human)
+        };
+        let target = match target {
+            Some(target) => target,
+            None => return false,
+        };
+
+        self.history.truncate(target + 1);
+        self.board = self.history.pop().unwrap();
+        self.moves.truncate(target);
+        self.notes.retain(|&i, _| i < target);
+        self.times.retain(|&i, _| i < target);
+        self.conditions = Conditions::default();
+        self.move_began = Instant::now();
+        self.captured.retain(|&(ply, _)| ply < target);
+        if self.two_player {
+            self.human = self.board.turn();
+        }
+        if let Some(clock) = &mut self.clock {
+            clock.set_running(self.board.turn());
+            clock.resume();
+        }
+
+        self.pending = None;
+        self.premove = None;
+        self.last_move = None;
+        self.focused = None;
+        self.drop = None;
+        self.highlighted = None;
+        self.took_back = true;
+        true
+    }
+
+    fn tone(&self) -> CpuTone {
+        if self.cpu().is_none() {
+            CpuTone::Silent
+        } else {
+            self.settings.cpu_tone
+        }
+    }
+
+    // Input moves are only accepted here; a CPU reply held back in step mode
+    // keeps the board on the CPU's turn until it is played.
+    fn human_to_move(&self) -> bool {
+        self.pending.is_none() && self.board.turn() == self.human
+    }
+
+    // From White's point of view, with a short search so that nearby mates
+    // show up.
+    pub fn evaluation(&self) -> i32 {
+        match self.ending() {
+            Some((Some(winner), _)) => winner.fold_wb(engine::MATE, -engine::MATE),
+            Some((None, _)) => 0,
+            None => {
+                let values = &self.settings.piece_values;
+                let score = engine::search(&self.board, EVAL_DEPTH, values).score;
+                self.board.turn().fold_wb(score, -score)
+            }
+        }
+    }
+
+    pub fn orientation(&self) -> CColor {
+        let facing = match self.settings.board_facing {
+            BoardFacing::White => CColor::White,
+            BoardFacing::Black => CColor::Black,
+            BoardFacing::MyColor => self.human,
+            BoardFacing::SideToMove => self.board.turn(),
+        };
+        if self.flipped {
+            !facing
+        } else {
+            facing
+        }
+    }
+
+    fn resign(&self) -> EventResult {
+        let human = self.human;
+        let msg = game_over_message(self.tone(), Some(!human), human, None);
+
+        EventResult::with_cb(move |s| {
+            let msg = msg.clone();
+            s.add_layer(
+                Dialog::text(tr("Resign this game?"))
+                    .button(tr("Resign"), move |s| {
+                        s.pop_layer();
+                        tell_peer(s, Message::Resign);
+                        game_over(s, &msg, Some(!human));
+                    })
+                    .dismiss_button(tr("Cancel")),
+            );
+        })
+    }
+
+    // A claimable draw needs no consent, so offering one simply claims it.
+    fn offer_draw(&self) -> EventResult {
+        if self.human_to_move() && self.claimable().is_some() {
+            return self.claim_draw();
+        }
+
+        if self.peer.is_some() {
+            return EventResult::with_cb(|s| tell_peer(s, Message::Draw));
+        }
+
+        let human = self.human;
+        let cpu_accepts =
+            engine::evaluate(&self.board, &self.settings.piece_values).abs() <= DRAW_MARGIN;
+        let msg = game_over_message(self.tone(), None, human, None);
+
+        if self.two_player {
+            return EventResult::with_cb(move |s| {
+                let msg = msg.clone();
+                s.add_layer(
+                    Dialog::text(fill("{} offers a draw.", &[tr(color_name(human))]))
+                        .button(tr("Accept"), move |s| {
+                            s.pop_layer();
+                            game_over(s, &msg, None);
+                        })
+                        .dismiss_button(tr("Decline")),
+                );
+            });
+        }
+
+        EventResult::with_cb(move |s| {
+            let msg = msg.clone();
+            s.add_layer(
+                Dialog::text(tr("Offer a draw?"))
+                    .button(tr("Offer"), move |s| {
+                        s.pop_layer();
+                        if cpu_accepts {
+                            game_over(s, &msg, None);
+                        } else {
+                            s.add_layer(Dialog::info(tr("Draw offer declined.")));
+                        }
+                    })
+                    .dismiss_button(tr("Cancel")),
+            );
+        })
+    }
+
+    fn repetitions(&self) -> usize {
+        let same = |pos: &VariantPosition| {
+            pos.board() == self.board.board()
+                && pos.turn() == self.board.turn()
+                && pos.castles().castling_rights() == self.board.castles().castling_rights()
+                && pos.ep_square(EnPassantMode::Legal) == self.board.ep_square(EnPassantMode::Legal)
+                && pos.pockets() == self.board.pockets()
+        };
+        1 + self.history.iter().filter(|pos| same(pos)).count()
+    }
+
+    fn ending(&self) -> Option<(Option<CColor>, Ending)> {
+        if let Some(loser) = self.clock.as_ref().and_then(Clock::flagged) {
+            return Some((Some(!loser), Ending::Timeout));
+        }
+        if let Some(outcome) = self.board.variant_outcome() {
+            let ending = match self.variant() {
+                Variant::Atomic => Ending::KingExploded,
+                Variant::KingOfTheHill => Ending::Hill,
+                Variant::RacingKings => Ending::Race,
+                Variant::ThreeCheck => Ending::ThreeChecks,
+                Variant::Horde => Ending::HordeCaptured,
+                Variant::Antichess => Ending::PiecesLost,
+                _ => Ending::VariantWin,
+            };
+            return Some((outcome.winner(), ending));
+        }
+        if self.board.legal_moves().is_empty() {
+            if self.board.is_check() {
+                Some((Some(!self.board.turn()), Ending::Checkmate))
+            } else {
+                Some((None, Ending::Stalemate))
+            }
+        } else if self.board.is_insufficient_material() {
+            Some((None, Ending::InsufficientMaterial))
+        } else if self.board.halfmoves() >= 150 {
+            Some((None, Ending::SeventyFiveMoves))
+        } else if self.repetitions() >= 5 {
+            Some((None, Ending::FivefoldRepetition))
+        } else {
+            None
+        }
+    }
+
+    fn claimable(&self) -> Option<Ending> {
+        if self.repetitions() >= 3 {
+            Some(Ending::ThreefoldRepetition)
+        } else if self.board.halfmoves() >= 100 {
+            Some(Ending::FiftyMoves)
+        } else {
+            None
+        }
+    }
+
+    fn claim_draw(&self) -> EventResult {
+        match self.claimable() {
+            Some(ending) => {
+                let msg = game_over_message(self.tone(), None, self.human, Some(ending));
+                EventResult::with_cb(move |s| {
+                    tell_peer(s, Message::Draw);
+                    game_over(s, &msg, None)
+                })
+            }
+            None => EventResult::Consumed(None),
+        }
+    }
+
+    // The game stays a network game, so it is still not rated.
+    fn hang_up(&mut self) {
+        if let Some(peer) = &mut self.peer {
+            peer.hang_up();
+        }
+    }
+
+    fn hear(&mut self, message: Message) -> Option<EventResult> {
+        let human = self.human;
+        let offered = self.peer.as_ref().is_some_and(|peer| peer.offered);
+        match message {
+            Message::Move(uci) => {
+                let mv = Uci::from_ascii(uci.as_bytes())
+                    .ok()
+                    .and_then(|uci| uci.to_move(&self.board).ok())
+                    .filter(|_| self.board.turn() != human);
+                match mv {
+                    Some(mv) => {
+                        if let Some(peer) = &mut self.peer {
+                            peer.offered = false;
+                        }
+                        self.play_cpu_move(mv)
+                    }
+                    None => {
+                        self.hang_up();
+                        let msg = format!("The other player sent an illegal move, {}.", uci);
+                        Some(EventResult::with_cb(move |s| {
+                            s.add_layer(Dialog::info(msg.clone()))
+                        }))
+                    }
+                }
+            }
+            Message::Resign => {
+                let msg = game_over_message(CpuTone::Silent, Some(human), human, None);
+                Some(EventResult::with_cb(move |s| {
+                    game_over(
+                        s,
+                        &format!("The other player resigned. {}", msg),
+                        Some(human),
+                    )
+                }))
+            }
+            Message::Draw if offered || self.claimable().is_some() => {
+                let msg = game_over_message(CpuTone::Silent, None, human, self.claimable());
+                Some(EventResult::with_cb(move |s| game_over(s, &msg, None)))
+            }
+            Message::Draw => {
+                let msg = game_over_message(CpuTone::Silent, None, human, None);
+                Some(EventResult::with_cb(move |s| {
+                    let msg = msg.clone();
+                    s.add_layer(
+                        Dialog::text(tr("The other player offers a draw."))
+                            .button(tr("Accept"), move |s| {
+                                s.pop_layer();
+                                tell_peer(s, Message::Draw);
+                                game_over(s, &msg, None);
+                            })
+                            .button(tr("Decline"), |s| {
+                                s.pop_layer();
+                                tell_peer(s, Message::Decline);
+                            }),
+                    );
+                }))
+            }
+            Message::Decline => {
+                if let Some(peer) = &mut self.peer {
+                    peer.offered = false;
+                }
+                Some(EventResult::with_cb(|s| {
+                    s.add_layer(Dialog::info(tr("The other player declined the draw.")))
+                }))
+            }
+            #[cfg(feature = "online")]
+            Message::Over(winner, reason) => {
+                let msg = fill("The game ended on Lichess: {}.", &[tr(reason)]);
+                Some(EventResult::with_cb(move |s| game_over(s, &msg, winner)))
+            }
+            #[cfg(feature = "online")]
+            Message::Clock(remaining) => {
+                if let Some(clock) = &mut self.clock {
+                    clock.set(remaining);
+                }
+                None
+            }
+            Message::Gone if self.ending().is_some() => None,
+            Message::Gone => {
+                self.hang_up();
+                Some(EventResult::with_cb(|s| {
+                    s.add_layer(Dialog::info(tr("The other player has left the game.")))
+                }))
+            }
+        }
+    }
+
+    fn check_game_over(&mut self) -> Option<EventResult> {
+        let (winner, ending) = self.ending()?;
+        if let Some(clock) = &mut self.clock {
+            clock.stop();
+        }
+
+        let msg = game_over_message(self.tone(), winner, self.human, Some(ending));
+        sound::play(self.settings.sound, Cue::GameEnd);
+        Some(EventResult::with_cb(move |s| {
+            s.add_layer(
+                Dialog::text(msg.clone())
+                    .button(tr("Take Back"), |s| {
+                        s.pop_layer();
+                        s.call_on_name("board", |board: &mut BoardView| board.take_back());
+                        refresh_side_views(s);
+                    })
+                    .button(tr("Export PGN"), pgn::show_export)
+                    .button(tr("Ok"), move |s| {
+                        s.pop_layer();
+                        stats::record(s, winner);
+                        end_game(s);
+                    }),
+            )
+        }))
+    }
+
+    fn deselect(&mut self) -> EventResult {
+        self.focused = None;
+        self.premove = None;
+        self.dragging = None;
+        self.drop = None;
+        *self.promotion.borrow_mut() = None;
+        EventResult::Consumed(None)
+    }
+
+    // Asking the move generator rather than testing the rank covers both
+    // colours and skips the prompt for a pawn that cannot actually advance.
+    fn promotes(&self, sq: Square) -> bool {
+        self.board
+            .legal_moves()
+            .iter()
+            .any(|m| m.from() == Some(sq) && m.is_promotion())
+    }
+
+    fn select(&mut self, sq: Square) -> EventResult {
+        self.focused = Some(sq);
+
+        *self.promotion.borrow_mut() =
+            (self.settings.auto_queen && self.promotes(sq)).then_some(Role::Queen);
+        EventResult::Consumed(None)
+    }
+
+    // Asked once the promoting move is made, with Queen first so Enter takes
+    // it, or ahead of time with X. Then the move to `to` goes ahead.
+    fn choose_promotion(&self, to: Option<Square>) -> EventResult {
+        let p = self.promotion.clone();
+        EventResult::with_cb(move |s| {
+            let p = p.clone();
+            // The clock waits while the piece is being picked.
+            s.call_on_name("board", |board: &mut BoardView| board.pause_clock());
+            s.add_layer(
+                Dialog::new()
+                    .title(tr("Promote to"))
+                    .content(
+                        SelectView::new()
+                            .item("Queen", Role::Queen)
+                            .item("Rook", Role::Rook)
+                            .item("Bishop", Role::Bishop)
+                            .item("Knight", Role::Knight)
+                            .autojump()
+                            .on_submit(move |s, &piece| {
+                                s.pop_layer();
+                                *p.borrow_mut() = Some(piece);
+                                let result = s
+                                    .call_on_name("board", |board: &mut BoardView| {
+                                        board.resume_clock();
+                                        to.map(|to| board.process_focus_change(to))
+                                    })
+                                    .flatten();
+                                if let Some(result) = result {
+                                    result.process(s);
+                                    refresh_side_views(s);
+                                }
+                            }),
+                    )
+                    .button(tr("Cancel"), |s| {
+                        s.pop_layer();
+                        s.call_on_name("board", |board: &mut BoardView| board.resume_clock());
+                    }),
+            );
+        })
+    }
+
+    fn pick_premove(&mut self, sq: Square) -> EventResult {
+        if self.two_player || self.puzzle.is_some() {
+            return EventResult::Consumed(None);
+        }
+        match self.focused {
+            None if self.board.board().by_color(self.human).contains(sq) => {
+                self.focused = Some(sq);
+                self.premove = None;
+            }
+            Some(from) if from != sq => {
+                self.focused = None;
+                self.premove = Some((from, sq));
+            }
+            _ => self.focused = None,
+        }
+        EventResult::Consumed(None)
+    }
+
+    // A premove that is no longer legal is simply dropped; promotions queen.
+    fn play_premove(&mut self) -> Option<EventResult> {
+        let (from, to) = self.premove.take()?;
+        let mv = self.board.legal_moves().into_iter().find(|m| {
+            m.from() == Some(from)
+                && m.to() == to
+                && matches!(m.promotion(), None | Some(Role::Queen))
+        })?;
+        self.move_and_reply(mv)
+    }
+
+    fn process_focus_change(&mut self, sq: Square) -> EventResult {
+        if !self.human_to_move() {
+            return self.pick_premove(sq);
+        }
+        if let Some(role) = self.drop {
+            return self.drop_on(role, sq);
+        }
+
+        match self.focused {
+            None if self.board.us().contains(sq) => self.select(sq),
+
+            Some(from) => {
+                let input_move = self.board.legal_moves().into_iter().find(|m| {
+                    m.from() == Some(from)
+                        && m.to() == sq
+                        && if self.promotion.borrow().is_some() {
+                            m.promotion() == *self.promotion.borrow()
+                        } else {
+                            true
+                        }
+                });
+
+                if input_move.is_none()
+                    && sq != from
+                    && self.board.us().contains(sq)
+                    && self.settings.friendly_click == FriendlyClick::Reselect
+                {
+                    return self.select(sq);
+                }
+
+                if input_move.as_ref().is_some_and(|mv| mv.is_promotion())
+                    && self.promotion.borrow().is_none()
+                {
+                    return self.choose_promotion(Some(sq));
+                }
+
+                // Keep the piece in hand and say why rather than dropping it.
+                if input_move.is_none() && !self.board.us().contains(sq) {
+                    let uci = Uci::Normal {
+                        from,
+                        to: sq,
+                        promotion: *self.promotion.borrow(),
+                    };
+                    self.rejected = Some((sq, self.explain_illegal(&uci)));
+                    return EventResult::Consumed(None);
+                }
+
+                match input_move.and_then(|mv| self.move_and_reply(mv)) {
+                    Some(event_result) => event_result,
+                    None => {
+                        self.focused = None;
+                        EventResult::Consumed(None)
+                    }
+                }
+            }
+            _ => EventResult::Consumed(None),
+        }
+    }
+
+    // Where the keyboard cursor first appears: on my king, which sits on my
+    // side of the board whichever way it faces.
+    fn home_square(&self) -> Square {
+        self.board
+            .board()
+            .king_of(self.human)
+            .unwrap_or_else(|| Square::from_coords(File::E, self.human.backrank()))
+    }
+
+    // Worked out on another thread, like the CPU's replies, and dropped if
+    // the position has moved on by the time it arrives.
+    fn show_hint(&mut self) -> EventResult {
+        if !self.human_to_move() || self.ending().is_some() {
+            return EventResult::Consumed(None);
+        }
+        let helper = self
+            .opponent
+            .clone()
+            .filter(|opponent| opponent.difficulty == Difficulty::Engine);
+        let board = self.board.clone();
+        let values = self.settings.piece_values;
+        let fen = self.fen();
+        EventResult::with_cb_once(move |s| {
+            let cb_sink = s.cb_sink().clone();
+            thread::spawn(move || {
+                let (hint, warning) = hint_choice(helper, &board, &values);
+                let _ = cb_sink.send(Box::new(move |s| receive_hint(s, &fen, hint, warning)));
+            });
+        })
+    }
+
+    fn take_hint(&mut self, hint: Option<shakmaty::Move>, warning: Option<String>) {
+        if warning.is_some() {
+            self.warning = warning;
+        }
+        self.hint = hint;
+        if self.hint.is_some() {
+            self.hints += 1;
+        }
+    }
+
+    fn refreshed(&mut self, result: EventResult) -> EventResult {
+        self.keep();
+        let warning = self.warning.take();
+        EventResult::with_cb(move |s| {
+            refresh_side_views(s);
+            if let Some(warning) = &warning {
+                s.add_layer(Dialog::info(warning.clone()));
+            }
+        })
+        .and(result)
+    }
+
+    fn shown(&self) -> &VariantPosition {
+        self.viewing
+            .and_then(|ply| self.history.get(ply))
+            .unwrap_or(&self.board)
+    }
+
+    // The king of the side to move in the position on show, if it is in
+    // check and not hidden.
+    fn checked_king(&self) -> Option<Square> {
+        let position = self.shown();
+        if position.is_check() && self.shows(position.turn()) {
+            position.board().king_of(position.turn())
+        } else {
+            None
+        }
+    }
+
+    // The move that led to the position on show.
+    fn shown_last_move(&self) -> Option<shakmaty::Move> {
+        let Some(ply) = self.viewing else {
+            return self.last_move.clone();
+        };
+        let before = self.history.get(ply.checked_sub(1)?)?;
+        let san = SanPlus::from_ascii(self.moves[ply - 1].as_bytes()).ok()?;
+        san.san.to_move(before).ok()
+    }
+
+    // The move number of the move shown as the last one.
+    fn shown_last_number(&self) -> Option<String> {
+        let ply = self.viewing.unwrap_or(self.history.len());
+        let before = self.history.get(ply.checked_sub(1)?)?;
+        Some(before.fullmoves().to_string())
+    }
+
+    fn step_history(&mut self, forward: bool) -> EventResult {
+        let live = self.history.len();
+        let ply = self.viewing.unwrap_or(live);
+        let ply = if forward {
+            (ply + 1).min(live)
+        } else {
+            ply.saturating_sub(1)
+        };
+        self.viewing = (ply < live).then_some(ply);
+        self.focused = None;
+        self.dragging = None;
+        self.drop = None;
+        self.hint = None;
+        self.rejected = None;
+        self.overview = Bitboard::EMPTY;
+        EventResult::Consumed(None)
+    }
+
+    // None when the action does not apply now, leaving the key to the
+    // handlers below.
+    fn perform(&mut self, action: Action) -> Option<EventResult> {
+        let (df, dr) = match action {
+            Action::Left => (-1, 0),
+            Action::Right => (1, 0),
+            Action::Up => (0, 1),
+            Action::Down => (0, -1),
+            Action::Select => match self.highlighted {
+                Some(sq) => return Some(self.process_focus_change(sq)),
+                None => (0, 0),
+            },
+            Action::Undo => {
+                self.take_back();
+                return Some(EventResult::Consumed(None));
+            }
+            Action::Hint => return Some(self.show_hint()),
+            Action::Flip => {
+                self.flipped = !self.flipped;
+                return Some(EventResult::Consumed(None));
+            }
+            Action::MoveEntry => {
+                return self
+                    .human_to_move()
+                    .then(|| EventResult::with_cb(show_move_entry))
+            }
+            Action::Back => return Some(self.step_history(false)),
+            Action::Forward => return None,
+            Action::Jump => {
+                self.jump = Some(None);
+                return Some(EventResult::Consumed(None));
+            }
+            Action::Bindings => return Some(EventResult::with_cb(keymap::show_bindings)),
+            Action::Annotate => return Some(EventResult::with_cb(show_annotate)),
+        };
+
+        self.highlighted = Some(match self.highlighted {
+            None => self.home_square(),
+            Some(sq) => {
+                let flip = self.orientation().fold_wb(1, -1);
+                self.step_cursor(sq, df * flip, dr * flip)
+            }
+        });
+        Some(EventResult::Consumed(None))
+    }
+
+    fn shows(&self, color: CColor) -> bool {
+        match self.render_mode {
+            RenderMode::Normal => true,
+            RenderMode::HideOpponent => color == self.human,
+            RenderMode::Blindfold => false,
+        }
+    }
+
+    fn fit_cell(&self, available: Vec2) -> (usize, usize) {
+        self.settings
+            .board_size
+            .cell(|cell| view_size(cell, self.settings.labels_all_sides).fits_in(available))
+    }
+
+    // Where the squares start inside the view, past the labels.
+    fn board_origin(&self) -> Vec2 {
+        let top = if self.settings.labels_all_sides { 1 } else { 0 };
+        Vec2::new(LABEL_COLS, top)
+    }
+
+    pub fn board_rows(&self) -> usize {
+        8 * self.cell.1
+    }
+
+    pub fn panel_title(&self) -> String {
+        match self.hints {
+            0 => String::new(),
+            1 => "1 hint used".to_owned(),
+            n => format!("{} hints used", n),
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) -> EventResult {
+        let overview = std::mem::take(&mut self.overview);
+        self.hint = None;
+        self.rejected = None;
+
+        if let Some(result) = self
+            .settings
+            .keys
+            .action(&event)
+            .and_then(|action| self.perform(action))
+        {
+            return result;
+        }
+
+        match event {
+            // Mouse Input
+            Event::Mouse {
+                offset,
+                position,
+                event: MouseEvent::Press(MouseButton::Right),
+            } => {
+                let sq = square_at(
+                    position,
+                    offset + self.board_origin(),
+                    self.orientation(),
+                    self.cell,
+                );
+                self.marks.press(sq);
+                self.deselect()
+            }
+            Event::Mouse {
+                offset,
+                position,
+                event: MouseEvent::Release(MouseButton::Right),
+            } => {
+                let sq = square_at(
+                    position,
+                    offset + self.board_origin(),
+                    self.orientation(),
+                    self.cell,
+                );
+                self.marks.release(sq);
+                EventResult::Consumed(None)
+            }
+            Event::Mouse {
+                offset,
+                position,
+                event: MouseEvent::Hold(MouseButton::Left),
+            } if self.focused.is_some() => {
+                self.dragging = position.checked_sub(offset + self.board_origin());
+                EventResult::Consumed(None)
+            }
+            // Letting go on another square finishes a drag. Letting go where
+            // the piece was picked up leaves it selected, as after a click.
+            Event::Mouse {
+                offset,
+                position,
+                event: MouseEvent::Release(MouseButton::Left),
+            } if self.dragging.is_some() => {
+                self.dragging = None;
+                match square_at(
+                    position,
+                    offset + self.board_origin(),
+                    self.orientation(),
+                    self.cell,
+                ) {
+                    Some(sq) if Some(sq) != self.focused => {
+                        self.highlighted = Some(sq);
+                        self.process_focus_change(sq)
+                    }
+                    _ => EventResult::Consumed(None),
+                }
+            }
+            Event::Mouse {
+                offset,
+                position,
+                event: MouseEvent::Press(_),
+            } => {
+                if let Some(sq) = square_at(
+                    position,
+                    offset + self.board_origin(),
+                    self.orientation(),
+                    self.cell,
+                ) {
+                    self.highlighted = Some(sq);
+                    self.process_focus_change(sq)
+                } else if let Some(local) = position.checked_sub(offset) {
+                    self.pocket_click(local)
+                } else {
+                    EventResult::Ignored
+                }
+            }
+
+            // Keyboard Input
+            Event::Key(Key::Esc)
+                if self.focused.is_some() || self.drop.is_some() || self.premove.is_some() =>
+            {
+                self.deselect()
+            }
+            Event::Char('a') => {
+                if overview.is_empty() {
+                    self.overview = self.board.legal_moves().iter().map(|m| m.to()).collect();
+                }
+                EventResult::Consumed(None)
+            }
+            Event::AltChar(c @ '1'..='9') => self
+                .switch_to(c.to_digit(10).unwrap() as usize)
+                .unwrap_or(EventResult::Consumed(None)),
+            Event::Char('g') if self.tab_count() > 1 => EventResult::with_cb(show_games),
+            Event::Key(Key::Enter) if self.pending.is_some() => {
+                let mv = self.pending.take().unwrap();
+                self.play_cpu_move(mv)
+                    .unwrap_or(EventResult::Consumed(None))
+            }
+            Event::Char('p') => {
+                self.step_mode = !self.step_mode;
+                if !self.step_mode {
+                    if let Some(mv) = self.pending.take() {
+                        return self
+                            .play_cpu_move(mv)
+                            .unwrap_or(EventResult::Consumed(None));
+                    }
+                }
+                EventResult::Consumed(None)
+            }
+            Event::Char('x') if self.focused.is_some_and(|sq| self.promotes(sq)) => {
+                self.choose_promotion(None)
+            }
+            Event::Char('=') if self.human_to_move() => self.claim_draw(),
+            Event::Char('d') if self.board.pockets().is_some() => self.cycle_drop(),
+            Event::Char('R') if self.ending().is_none() => self.resign(),
+            Event::Char('D') if self.ending().is_none() => self.offer_draw(),
+            Event::Char('C') => EventResult::with_cb(conditional::show),
+            Event::Char('s') => {
+                self.snapshot = Some(self.board.board().clone());
+                EventResult::Consumed(None)
+            }
+            Event::Char('y') => EventResult::with_cb(clipboard::copy_fen),
+            Event::Char('Y') => EventResult::with_cb(clipboard::copy_pgn),
+            Event::Char('v') => EventResult::with_cb(clipboard::paste_position),
+            Event::Char('b') => {
+                self.render_mode = self.render_mode.next();
+                EventResult::Consumed(None)
+            }
+            Event::Char('c') if self.snapshot.is_some() => {
+                self.compare = !self.compare;
+                EventResult::Consumed(None)
+            }
+            _ => EventResult::Ignored,
+        }
+    }
+}
+
+impl cursive::view::View for BoardView {
+    fn draw(&self, printer: &Printer) {
+        let live = self.viewing.is_none();
+        let position = self.shown();
+        let changed = if live {
+            self.changed_since_snapshot()
+        } else {
+            Bitboard::EMPTY
+        };
+        let preview = self.promotion_preview();
+        let inspected = self
+            .inspected()
+            .filter(|&(_, piece, _)| live && self.shows(piece.color));
+        let inspected_targets =
+            inspected.map_or(Bitboard::EMPTY, |(_, _, targets)| targets) | self.drop_targets();
+        let last_move = self.shown_last_move().map_or(Bitboard::EMPTY, |mv| {
+            mv.from().into_iter().chain([mv.to()]).collect()
+        });
+        // Squares of one row can't fit a number beside the piece.
+        let last_number = self
+            .settings
+            .last_move_numbers
+            .then(|| self.shown_last_number())
+            .flatten()
+            .filter(|number| self.cell.1 > 1 && number.len() < self.cell.0);
+        let hint = self.hint.as_ref().map_or(Bitboard::EMPTY, |mv| {
+            mv.from().into_iter().chain([mv.to()]).collect()
+        });
+        let premove: Bitboard = self
+            .premove
+            .filter(|_| live)
+            .map_or(Bitboard::EMPTY, |(from, to)| {
+                Bitboard::from(from) | Bitboard::from(to)
+            });
+        let orientation = self.orientation();
+        let checked_king = self.checked_king();
+
+        let (w, h) = self.cell;
+        let origin = self.board_origin();
+        let all_sides = self.settings.labels_all_sides;
+
+        // The labels in line with the cursor stand out, as a crosshair.
+        let label_style = |on: bool| {
+            if on {
+                ColorStyle::title_primary()
+            } else {
+                ColorStyle::secondary()
+            }
+        };
+        for i in 0..8 {
+            let (file, rank) = match orientation {
+                CColor::White => (File::new(i), Rank::new(7 - i)),
+                CColor::Black => (File::new(7 - i), Rank::new(i)),
+            };
+            let i = i as usize;
+            let cursor = self.highlighted;
+            let on_rank = cursor.is_some_and(|sq| sq.rank() == rank);
+            let on_file = cursor.is_some_and(|sq| sq.file() == file);
+            let (rank, file) = (rank.char().to_string(), file.char().to_string());
+            let y = origin.y + h * i + (h - 1) / 2;
+            let x = LABEL_COLS + w * i + w / 2;
+            printer.with_color(label_style(on_rank), |printer| {
+                printer.print((0, y), &rank);
+                if all_sides {
+                    printer.print((LABEL_COLS + 8 * w + 1, y), &rank);
+                }
+            });
+            printer.with_color(label_style(on_file), |printer| {
+                printer.print((x, origin.y + 8 * h), &file);
+                if all_sides {
+                    printer.print((x, 0), &file);
+                }
+            });
+        }
+
+        let board = printer.offset(origin);
+        let mut backgrounds = [Color::TerminalDefault; 64];
+        for file in 0..8 {
+            for rank in 0..8 {
+                let (col, row) = match orientation {
+                    CColor::White => (file, 7 - rank),
+                    CColor::Black => (7 - file, rank),
+                };
+                let (x, y) = (w * col as usize, h * row as usize);
+
+                let sq = Square::new(file + 8 * rank);
+
+                let piece = match preview {
+                    Some((targets, piece)) if targets.contains(sq) => Some(piece),
+                    _ if self.dragging.is_some() && self.focused == Some(sq) => None,
+                    _ => position.board().piece_at(sq),
+                }
+                .filter(|piece| self.shows(piece.color));
+
+                let symbol = piece.map_or(' ', |p| piece_to_char(p, self.piece_style()));
+                let blank = " ".repeat(w);
+                let text = format!("{:^w$}", symbol);
+
+                let highlight = |base, light| {
+                    highlight_color(self.settings.highlight_intensity, base, light, sq)
+                };
+
+                let color = if self.focused == Some(sq) {
+                    highlight(BaseColor::Yellow, false)
+                } else if premove.contains(sq) {
+                    highlight(BaseColor::Cyan, true)
+                } else if self
+                    .rejected
+                    .as_ref()
+                    .is_some_and(|(target, _)| *target == sq)
+                {
+                    highlight(BaseColor::Red, true)
+                } else if checked_king == Some(sq) {
+                    highlight(BaseColor::Red, false)
+                } else if self.highlighted == Some(sq) {
+                    highlight(BaseColor::Yellow, true)
+                } else if self.marks.squares.contains(sq) {
+                    highlight(BaseColor::Magenta, true)
+                } else if hint.contains(sq) {
+                    highlight(BaseColor::Green, true)
+                } else if self.overview.contains(sq) {
+                    highlight(BaseColor::Cyan, false)
+                } else if inspected_targets.contains(sq) {
+                    highlight(BaseColor::Blue, true)
+                } else if changed.contains(sq) {
+                    highlight(BaseColor::Magenta, false)
+                } else if last_move.contains(sq) {
+                    // Always a faint tint, so it never competes with the cursor.
+                    highlight_color(HighlightIntensity::Subtle, BaseColor::Green, false, sq)
+                } else {
+                    self.settings.board_theme.square(sq.is_dark())
+                };
+                backgrounds[usize::from(sq)] = color;
+
+                board.with_color(
+                    ColorStyle::new(piece_color(piece, self.piece_style()), color),
+                    |printer| {
+                        for dy in 0..h {
+                            let line = if dy == (h - 1) / 2 { &text } else { &blank };
+                            printer.print((x, y + dy), line);
+                        }
+                        if let Some(number) =
+                            last_number.as_ref().filter(|_| last_move.contains(sq))
+                        {
+                            printer.print((x, y + h - 1), number);
+                        }
+                    },
+                );
+            }
+        }
+
+        let shown = CColor::ALL
+            .into_iter()
+            .filter(|&color| self.shows(color))
+            .map(|color| position.board().by_color(color))
+            .fold(Bitboard::EMPTY, |all, pieces| all | pieces);
+        let arrows = self.marks.overlay(orientation, (w, h), shown);
+        for (pos, glyph) in arrows {
+            if let Some(sq) = square_at(pos, Vec2::zero(), orientation, (w, h)) {
+                board.with_color(
+                    ColorStyle::new(ARROW, backgrounds[usize::from(sq)]),
+                    |printer| printer.print(pos, &glyph.to_string()),
+                );
+            }
+        }
+
+        let held = self
+            .focused
+            .and_then(|sq| Some((sq, self.board.board().piece_at(sq)?)))
+            .filter(|(_, piece)| self.shows(piece.color));
+        if let (Some(pos), Some((sq, piece))) = (self.dragging, held) {
+            let style = self.piece_style();
+            let color = highlight_color(
+                self.settings.highlight_intensity,
+                BaseColor::Yellow,
+                false,
+                sq,
+            );
+            board.with_color(
+                ColorStyle::new(piece_color(Some(piece), style), color),
+                |printer| printer.print(pos, &piece_to_char(piece, style).to_string()),
+            );
+        }
+
+        for (row, line) in self.status_lines(inspected).iter().enumerate() {
+            printer.print((0, origin.y + 8 * h + 1 + row), line);
+        }
+    }
+
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::Consumed(None))
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        // Clock ticks are not input and must not clear transient highlights.
+        if event == Event::Refresh {
+            return EventResult::Ignored;
+        }
+        // Looking back is read-only: anything but stepping returns to the game.
+        if self.viewing.is_some() {
+            return match self.settings.keys.action(&event) {
+                Some(Action::Back) => self.step_history(false),
+                Some(Action::Forward) => self.step_history(true),
+                Some(Action::Annotate) => EventResult::with_cb(show_annotate),
+                _ => {
+                    self.viewing = None;
+                    EventResult::Consumed(None)
+                }
+            };
+        }
+        // Anything but a file and then a rank gives up on the jump.
+        if let Some(file) = self.jump.take() {
+            if let Event::Char(c) = event {
+                match (file, File::from_char(c), Rank::from_char(c)) {
+                    (None, Some(file), _) => self.jump = Some(Some(file)),
+                    (Some(file), _, Some(rank)) => {
+                        self.highlighted = Some(Square::from_coords(file, rank))
+                    }
+                    _ => {}
+                }
+            }
+            return self.refreshed(EventResult::Consumed(None));
+        }
+        match self.handle_event(event) {
+            EventResult::Ignored => EventResult::Ignored,
+            result => self.refreshed(result),
+        }
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        view_size(self.fit_cell(constraint), self.settings.labels_all_sides)
+    }
+
+    fn layout(&mut self, size: Vec2) {
+        self.cell = self.fit_cell(size);
+    }
+}
+
+fn view_size((w, h): (usize, usize), all_sides: bool) -> Vec2 {
+    let (right, top) = if all_sides { (LABEL_COLS, 1) } else { (0, 0) };
+    Vec2::new(LABEL_COLS + 8 * w + right, top + 8 * h + 1 + STATUS_ROWS)
+}
+
+fn puzzle_over(
+    siv: &mut Cursive,
+    msg: &str,
+    puzzle: &Puzzle,
+    failed: bool,
+    trainer: &Option<Rc<RefCell<Trainer>>>,
+) {
+    end_game(siv);
+    let mut msg = msg.to_owned();
+    if let Some(trainer) = trainer {
+        trainer.borrow_mut().record(!failed);
+        msg += &format!("\n\n{}", trainer.borrow().score());
+    }
+
+    let mut dialog = Dialog::text(msg).title(tr("Puzzle"));
+    if let Some(trainer) = trainer.clone() {
+        dialog.add_button(tr("Next"), move |s| {
+            s.pop_layer();
+            match trainer.borrow_mut().advance() {
+                Some(next) => start_puzzle(s, next, Some(trainer.clone())),
+                None => s.add_layer(Dialog::info(fill(
+                    "That was the last puzzle. {}",
+                    &[&trainer.borrow().score()],
+                ))),
+            }
+        });
+    }
+    if failed {
+        let (retry, trainer) = (puzzle.clone(), trainer.clone());
+        dialog.add_button(tr("Retry"), move |s| {
+            s.pop_layer();
+            start_puzzle(s, retry.clone(), trainer.clone());
+        });
+    }
+    let solution = puzzle.solution_text();
+    dialog.add_button(tr("Show Solution"), move |s| {
+        s.add_layer(Dialog::info(solution.clone()).title(tr("Solution")))
+    });
+    siv.add_layer(dialog.dismiss_button(tr("Close")));
+}
+
+fn start_puzzle(siv: &mut Cursive, puzzle: Puzzle, trainer: Option<Rc<RefCell<Trainer>>>) {
+    let settings = settings::current(siv);
+    let mut view = BoardView::from_position(settings, puzzle.start.clone());
+    view.last_move = puzzle.lead.clone();
+    view.puzzle = Some(puzzle);
+    view.trainer = trainer;
+    open_game(siv, view);
+}
+
+pub fn open_puzzle(siv: &mut Cursive, puzzle: Puzzle) {
+    start_puzzle(siv, puzzle, None);
+}
+
+pub fn open_problem(siv: &mut Cursive, problem: Problem, problems: Problems) {
+    let mut view = BoardView::from_position(settings::current(siv), problem.start);
+    view.problem = Some(problems);
+    open_game(siv, view);
+}
+
+pub fn open_lesson(siv: &mut Cursive, lesson: Lesson) {
+    let step = lesson.current();
+    let board = parse_fen::<Chess>(step.fen).expect("lesson positions are valid");
+    let mut view = BoardView::from_position(settings::current(siv), board);
+    view.last_move = step.lead;
+    view.lesson = Some(lesson);
+    open_game(siv, view);
+}
+
+pub fn open_training(siv: &mut Cursive, trainer: Trainer) {
+    match trainer.current().cloned() {
+        Some(puzzle) => start_puzzle(siv, puzzle, Some(Rc::new(RefCell::new(trainer)))),
+        None => siv.add_layer(Dialog::info(tr("No puzzles match that theme."))),
+    }
+}
+
+pub fn game_over(siv: &mut Cursive, msg: &str, winner: Option<CColor>) {
+    sound::play(settings::current(siv).sound, Cue::GameEnd);
+    library::retire_correspondence(siv);
+    let game = replay::finished_game(siv);
+    // Only a player who lost to the CPU is offered a look at their mistakes.
+    let loser = siv
+        .call_on_name("board", |board: &mut BoardView| board.cpu())
+        .flatten()
+        .filter(|&cpu| winner == Some(cpu))
+        .map(|cpu| !cpu);
+    stats::record(siv, winner);
+    end_game(siv);
+    let mut dialog = Dialog::text(msg);
+    if let Some(game) = game {
+        if let Some(human) = loser {
+            let lost = game.clone();
+            dialog.add_button(tr("Learn"), move |s| {
+                s.pop_layer();
+                replay::show_mistakes(s, lost.clone(), human);
+            });
+        }
+        let review = game.clone();
+        dialog.add_button(tr("Analyze"), move |s| {
+            s.pop_layer();
+            replay::open_replay(s, game.clone(), true);
+        });
+        dialog.add_button(tr("Review"), move |s| {
+            s.pop_layer();
+            replay::open_review(s, review.clone());
+        });
+    }
+    siv.add_layer(dialog.dismiss_button(tr("Ok")))
+}
+
+fn receive_ponder(
+    siv: &mut Cursive,
+    id: usize,
+    fen: String,
+    opponent: Opponent,
+    choice: Option<shakmaty::Move>,
+) {
+    siv.call_on_name("board", |board: &mut BoardView| {
+        if board.pondering == Some(id) {
+            board.pondering = None;
+            board.pondered = Some((fen, opponent, choice));
+        }
+    });
+}
+
+fn hint_choice(
+    helper: Option<Opponent>,
+    board: &VariantPosition,
+    values: &PieceValues,
+) -> (Option<shakmaty::Move>, Option<String>) {
+    let mut warning = None;
+    if let Some(mut opponent) = helper {
+        match opponent.uci_choice(board) {
+            Ok(mv) => return (Some(mv), None),
+            Err(e) => {
+                warning = Some(format!(
+                    "Engine hint failed, using the built-in search: {}",
+                    e
+                ))
+            }
+        }
+    }
+    (engine::search(board, HINT_DEPTH, values).best, warning)
+}
+
+fn receive_hint(
+    siv: &mut Cursive,
+    fen: &str,
+    hint: Option<shakmaty::Move>,
+    warning: Option<String>,
+) {
+    let result = siv.call_on_name("board", |board: &mut BoardView| {
+        if board.fen() != fen || !board.human_to_move() {
+            return None;
+        }
+        board.take_hint(hint, warning);
+        Some(board.refreshed(EventResult::Consumed(None)))
+    });
+    match result.flatten() {
+        Some(result) => result.process(siv),
+        None => refresh_side_views(siv),
+    }
+}
+
+fn receive_cpu_move(
+    siv: &mut Cursive,
+    id: usize,
+    opponent: Opponent,
+    choice: Option<shakmaty::Move>,
+) {
+    let result = siv
+        .call_on_name("board", |board: &mut BoardView| {
+            if board.thinking.map(|(job, _)| job) != Some(id) {
+                board.park_reply(id, opponent, choice);
+                return None;
+            }
+            let result = board
+                .receive(opponent, choice)
+                .unwrap_or(EventResult::Consumed(None));
+            Some(board.refreshed(result))
+        })
+        .flatten();
+    match result {
+        Some(result) => result.process(siv),
+        None => refresh_side_views(siv),
+    }
+}
+
+pub fn peer_message(siv: &mut Cursive, id: usize, message: Message) {
+    let result = siv
+        .call_on_name("board", |board: &mut BoardView| {
+            if board.peer.as_ref().map(|peer| peer.id) != Some(id) {
+                return None;
+            }
+            let result = board.hear(message).unwrap_or(EventResult::Consumed(None));
+            Some(board.refreshed(result))
+        })
+        .flatten();
+    if let Some(result) = result {
+        result.process(siv);
+    }
+}
+
+fn tell_peer(siv: &mut Cursive, message: Message) {
+    let sent = siv.call_on_name("board", |board: &mut BoardView| {
+        board
+            .peer
+            .as_mut()
+            .map_or(Ok(()), |peer| peer.send(&message))
+    });
+    if let Some(Err(e)) = sent {
+        siv.add_layer(Dialog::info(e));
+    }
+}
+
+fn switch_game(siv: &mut Cursive, tab: usize) {
+    let result = siv
+        .call_on_name("board", |board: &mut BoardView| {
+            let result = board.switch_to(tab).unwrap_or(EventResult::Consumed(None));
+            board.refreshed(result)
+        })
+        .unwrap_or(EventResult::Ignored);
+    result.process(siv);
+}
+
+fn show_games(siv: &mut Cursive) {
+    let Some(games) = siv.call_on_name("board", |board: &mut BoardView| board.games()) else {
+        return;
+    };
+    let current = siv
+        .call_on_name("board", |board: &mut BoardView| board.tab)
+        .unwrap_or(1);
+    let mut list = SelectView::new();
+    for (tab, summary) in games {
+        list.add_item(format!("{}. {}", tab, summary), tab);
+    }
+    list.set_selection(current - 1);
+    siv.add_layer(
+        Dialog::around(list.on_submit(|s, &tab| {
+            s.pop_layer();
+            switch_game(s, tab);
+        }))
+        .title(tr("Games"))
+        .dismiss_button(tr("Back")),
+    );
+}
+
+pub fn end_game(siv: &mut Cursive) {
+    let switched = siv
+        .call_on_name("board", |board: &mut BoardView| {
+            library::clear_recovery(board.recovery_key());
+            board.close_tab()
+        })
+        .unwrap_or(false);
+    if switched {
+        refresh_side_views(siv);
+    } else {
+        siv.pop_layer();
+    }
+}
+
+pub fn tick_clock(siv: &mut Cursive) {
+    let sound = settings::current(siv).sound;
+    let ticked = siv.call_on_name("board", |board: &mut BoardView| {
+        (board.low_time_cue(), board.flag_fall())
+    });
+    let Some((low_time, msg)) = ticked else {
+        return;
+    };
+    if low_time {
+        sound::play(sound, Cue::LowTime);
+    }
+    if let Some((msg, winner)) = msg {
+        sound::play(sound, Cue::GameEnd);
+        siv.add_layer(
+            Dialog::text(msg)
+                .button(tr("Export PGN"), pgn::show_export)
+                .button(tr("Analyze"), replay::analyze_game)
+                .button(tr("Review"), replay::review_game)
+                .button(tr("Ok"), move |s| {
+                    s.pop_layer();
+                    stats::record(s, Some(winner));
+                    end_game(s);
+                }),
+        );
+    }
+}
+
+pub fn refresh_side_views(siv: &mut Cursive) {
+    let texts = siv.call_on_name("board", |board: &mut BoardView| {
+        (
+            board.tab_bar(),
+            board.turn_text(),
+            board.move_panel(),
+            board.panel_title(),
+        )
+    });
+    if let Some((tabs, turn, moves, title)) = texts {
+        siv.call_on_name("tabs", |view: &mut TextView| view.set_content(tabs));
+        siv.call_on_name("turn", |view: &mut TextView| view.set_content(turn));
+        siv.call_on_name("moves", |view: &mut TextView| view.set_content(moves));
+        siv.call_on_name("board_panel", |view: &mut Panel<NamedView<BoardView>>| {
+            view.set_title(title)
+        });
+    }
+
+    let fen = match siv.call_on_name("board", |board: &mut BoardView| board.fen()) {
+        Some(fen) => fen,
+        None => return,
+    };
+    if siv.call_on_name("eval", |bar: &mut EvalBar| bar.is_for(&fen)) == Some(false) {
+        let (score, orientation, turn) = siv
+            .call_on_name("board", |board: &mut BoardView| {
+                (board.evaluation(), board.orientation(), board.board.turn())
+            })
+            .unwrap();
+        siv.call_on_name("eval", |bar: &mut EvalBar| {
+            bar.set(fen, score, orientation, turn)
+        });
+    }
+
+    let eval = siv.call_on_name("eval", |bar: &mut EvalBar| bar.label());
+    let texts = siv.call_on_name("board", |board: &mut BoardView| {
+        let spoken = board.settings.announce_moves.then(|| board.announcements());
+        (board.status_bar(eval), spoken)
+    });
+    if let Some((status, spoken)) = texts {
+        siv.call_on_name("status_bar", |view: &mut TextView| view.set_content(status));
+        if let Some(spoken) = spoken {
+            siv.call_on_name("announcements", |view: &mut TextView| {
+                view.set_content(spoken)
+            });
+        }
+    }
+}
+
+fn highlight_color(
+    intensity: HighlightIntensity,
+    base: BaseColor,
+    light: bool,
+    sq: Square,
+) -> Color {
+    let (r, g, b) = match base {
+        BaseColor::Yellow => (1, 1, 0),
+        BaseColor::Cyan => (0, 1, 1),
+        BaseColor::Blue => (0, 0, 1),
+        BaseColor::Magenta => (1, 0, 1),
+        BaseColor::Red => (1, 0, 0),
+        BaseColor::Green => (0, 1, 0),
+        BaseColor::Black => (0, 0, 0),
+        BaseColor::White => (1, 1, 1),
+    };
+
+    match intensity {
+        HighlightIntensity::Normal if light => Color::Light(base),
+        HighlightIntensity::Normal => Color::Dark(base),
+        HighlightIntensity::Subtle => {
+            // Tint the square's own shade instead of replacing it.
+            let shade = if sq.is_dark() { 1 } else { 4 };
+            let step = if light { 1 } else { 0 };
+            let tint = |c: u8| if c == 1 { shade + 1 } else { shade - step };
+            Color::RgbLowRes(tint(r), tint(g), tint(b))
+        }
+        HighlightIntensity::Bold => {
+            let level = if light { 5 } else { 3 };
+            Color::RgbLowRes(r * level, g * level, b * level)
+        }
+    }
+}
+
+pub fn color_name(color: CColor) -> &'static str {
+    match color {
+        CColor::White => "White",
+        CColor::Black => "Black",
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Ending {
+    Checkmate,
+    VariantWin,
+    KingExploded,
+    Hill,
+    Race,
+    ThreeChecks,
+    HordeCaptured,
+    PiecesLost,
+    Timeout,
+    Stalemate,
+    InsufficientMaterial,
+    SeventyFiveMoves,
+    FivefoldRepetition,
+    ThreefoldRepetition,
+    FiftyMoves,
+}
+
+impl Ending {
+    fn reason(self) -> &'static str {
+        match self {
+            Ending::Checkmate => "checkmate",
+            Ending::VariantWin => "the variant rules",
+            Ending::KingExploded => "blowing up the king",
+            Ending::Hill => "bringing the king to the centre",
+            Ending::Race => "racing the king to the eighth rank",
+            Ending::ThreeChecks => "giving three checks",
+            Ending::HordeCaptured => "capturing the whole horde",
+            Ending::PiecesLost => "losing every piece or running out of moves",
+            Ending::Timeout => "timeout",
+            Ending::Stalemate => "stalemate",
+            Ending::InsufficientMaterial => "insufficient material",
+            Ending::SeventyFiveMoves => "75-move rule",
+            Ending::FivefoldRepetition => "fivefold repetition",
+            Ending::ThreefoldRepetition => "threefold repetition",
+            Ending::FiftyMoves => "fifty-move rule",
+        }
+    }
+}
+
+fn game_over_message(
+    tone: CpuTone,
+    winner: Option<CColor>,
+    human: CColor,
+    ending: Option<Ending>,
+) -> String {
+    let result = match (winner, ending) {
+        (Some(color), Some(Ending::Timeout)) => fill("{} wins on time.", &[tr(color_name(color))]),
+        (Some(color), Some(ending)) => fill(
+            "{} wins by {}.",
+            &[tr(color_name(color)), tr(ending.reason())],
+        ),
+        (Some(color), None) => fill("{} wins.", &[tr(color_name(color))]),
+        (None, Some(Ending::Stalemate)) => tr("Stalemate \u{2014} draw.").to_owned(),
+        (None, Some(Ending::Race)) => {
+            tr("Both kings reached the eighth rank \u{2014} draw.").to_owned()
+        }
+        (None, Some(ending)) => fill("Draw by {}.", &[tr(ending.reason())]),
+        (None, None) => tr("Draw.").to_owned(),
+    };
+
+    let remark = match (tone, winner) {
+        (CpuTone::Silent, _) => "",
+        (CpuTone::Neutral, Some(color)) if color == human => " Well played.",
+        (CpuTone::Neutral, Some(_)) => " Better luck next time.",
+        (CpuTone::Neutral, None) => "",
+        (CpuTone::Playful, Some(color)) if color == human => " You win. I demand a rematch!",
+        (CpuTone::Playful, Some(_)) => " I win. Hahaha.",
+        (CpuTone::Playful, None) => " Let's call it even.",
+    };
+
+    fill("Game Over. {}{}", &[&result, tr(remark)])
+}
+
+pub fn perft(pos: &Chess, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = pos.legal_moves();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    moves
+        .iter()
+        .map(|m| {
+            let mut child = pos.clone();
+            child.play_unchecked(m);
+            perft(&child, depth - 1)
+        })
+        .sum()
+}
+
+pub fn parse_variant_fen(variant: Variant, fen: &str) -> Result<VariantPosition, String> {
+    let setup = Fen::from_ascii(fen.trim().as_bytes())
+        .map_err(|e| format!("Invalid FEN: {}", e))?
+        .into_setup();
+    let mode = CastlingMode::detect(&setup);
+    VariantPosition::from_setup(variant, setup, mode)
+        .map_err(|e| format!("Illegal position: {}", e))
+}
+
+pub fn parse_fen<P: FromSetup + Position>(fen: &str) -> Result<P, String> {
+    // Castling rights naming rook files (Shredder-FEN) mark a Chess960 game.
+    let setup = Fen::from_ascii(fen.trim().as_bytes())
+        .map_err(|e| format!("Invalid FEN: {}", e))?
+        .into_setup();
+    let mode = CastlingMode::detect(&setup);
+    P::from_setup(setup, mode).map_err(|e| format!("Illegal position: {}", e))
+}
+
+const FUZZ_MAX_HALFMOVES: u32 = 150;
+
+// Release builds drop the debug assertions, so the harness checks each move
+// against the legal move list itself.
+fn fuzz_check(view: &BoardView, mv: &shakmaty::Move) -> Result<(), String> {
+    if view.board.legal_moves().contains(mv) {
+        return Ok(());
+    }
+    Err(format!(
+        "illegal move {} in {}",
+        mv.to_uci(CastlingMode::Standard),
+        view.fen()
+    ))
+}
+
+// One game of random moves against the CPU, every move checked before it is
+// played. The game has to stop at a recognized ending or the move limit.
+fn fuzz_game(rng: &mut StdRng) -> Result<BoardView, String> {
+    let settings = Settings {
+        difficulty: Difficulty::Random,
+        ..Settings::default()
+    };
+    let mut view =
+        BoardView::with_rng(settings, Chess::default(), StdRng::seed_from_u64(rng.gen()));
+
+    while view.board.halfmoves() < FUZZ_MAX_HALFMOVES {
+        let mv = match view.board.legal_moves().choose(rng) {
+            Some(mv) => mv.clone(),
+            None => {
+                return Err(format!(
+                    "no legal moves in an unfinished game: {}",
+                    view.fen()
+                ))
+            }
+        };
+
+        fuzz_check(&view, &mv)?;
+        view.play(&mv);
+        if view.check_game_over().is_some() {
+            break;
+        }
+        let Some(reply) = view.cpu_choice() else {
+            return Err(format!(
+                "the CPU found no reply in an unfinished game: {}",
+                view.fen()
+            ));
+        };
+        fuzz_check(&view, &reply)?;
+        if view.play_cpu_move(reply).is_some() {
+            break;
+        }
+    }
+
+    if view.ending().is_none() && view.board.halfmoves() < FUZZ_MAX_HALFMOVES {
+        return Err(format!("ended in an unrecognized state: {}", view.fen()));
+    }
+    Ok(view)
+}
+
+pub fn fuzz(games: u32, seed: u64) -> Result<String, String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (mut decisive, mut drawn, mut plies) = (0, 0, 0);
+
+    for game in 1..=games {
+        let view = fuzz_game(&mut rng).map_err(|e| format!("game {}: {}", game, e))?;
+
+        plies += 2 * (view.board.fullmoves().get() - 1) + u32::from(view.board.turn().is_black());
+
+        match view.ending() {
+            Some((Some(_), _)) => decisive += 1,
+            _ => drawn += 1,
+        }
+    }
+
+    Ok(format!(
+        "{} games, {} plies: {} decisive, {} drawn (seed {})",
+        games, plies, decisive, drawn, seed
+    ))
+}
+
+pub fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::Pawn => "Pawn",
+        Role::Knight => "Knight",
+        Role::Bishop => "Bishop",
+        Role::Rook => "Rook",
+        Role::Queen => "Queen",
+        Role::King => "King",
+    }
+}
+
+pub fn piece_to_char(p: shakmaty::Piece, style: PieceStyle) -> char {
+    let color = match style {
+        PieceStyle::Ascii => return p.char(),
+        PieceStyle::Unicode => p.color,
+        // Filled figurines for both sides; piece_color tells them apart.
+        PieceStyle::Solid => CColor::Black,
+    };
+
+    match (color, p.role) {
+        (CColor::Black, Role::Pawn) => '\u{265F}',
+        (CColor::Black, Role::Knight) => '\u{265E}',
+        (CColor::Black, Role::Bishop) => '\u{265D}',
+        (CColor::Black, Role::Rook) => '\u{265C}',
+        (CColor::Black, Role::Queen) => '\u{265B}',
+        (CColor::Black, Role::King) => '\u{265A}',
+        (CColor::White, Role::Pawn) => '\u{2659}',
+        (CColor::White, Role::Knight) => '\u{2658}',
+        (CColor::White, Role::Bishop) => '\u{2657}',
+        (CColor::White, Role::Rook) => '\u{2656}',
+        (CColor::White, Role::Queen) => '\u{2655}',
+        (CColor::White, Role::King) => '\u{2654}',
+    }
+}
+
+pub fn piece_color(p: Option<shakmaty::Piece>, style: PieceStyle) -> Color {
+    match p {
+        Some(p) if style == PieceStyle::Solid && p.color == CColor::White => {
+            Color::Light(BaseColor::White)
+        }
+        _ => Color::Dark(BaseColor::Black),
+    }
+}
+
+const VARIANT_MENU: [&str; 14] = [
+    "Chess",
+    "Atomic",
+    "Chess960",
+    "King of the Hill",
+    "Racing Kings",
+    "Crazyhouse",
+    "Three-check",
+    "Horde",
+    "Antichess",
+    "Odds Game",
+    "FEN Position",
+    "Set Up Position",
+    "EPD Position",
+    "Game Code",
+];
+
+fn menu_variant(option: &str) -> Option<Variant> {
+    match option {
+        "Chess" => Some(Variant::Chess),
+        "Atomic" => Some(Variant::Atomic),
+        "King of the Hill" => Some(Variant::KingOfTheHill),
+        "Racing Kings" => Some(Variant::RacingKings),
+        "Crazyhouse" => Some(Variant::Crazyhouse),
+        "Three-check" => Some(Variant::ThreeCheck),
+        "Horde" => Some(Variant::Horde),
+        "Antichess" => Some(Variant::Antichess),
+        _ => None,
+    }
+}
+
+pub fn show_options(siv: &mut Cursive) {
+    // The menu opens on the variant played last.
+    let last = settings::current(siv).last_variant;
+    let selected = VARIANT_MENU
+        .iter()
+        .position(|&option| menu_variant(option) == Some(last))
+        .unwrap_or(0);
+    let difficulty = settings::radio_list(
+        &Difficulty::ALL.map(|d| (d, d.label())),
+        settings::current(siv).difficulty,
+        |settings, value| settings.difficulty = value,
+    );
+
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Select Variant"))
+            .content(
+                LinearLayout::vertical()
+                    .child(
+                        SelectView::new()
+                            .with_all_str(VARIANT_MENU)
+                            .selected(selected)
+                            .on_submit(|s, option: &str| {
+                                s.pop_layer();
+                                if let Some(variant) = menu_variant(option) {
+                                    settings::update(s, |settings| settings.last_variant = variant);
+                                    return new_game(s, VariantPosition::new(variant), "Play As");
+                                }
+                                match option {
+                                    "Chess960" => show_chess960_entry(s),
+                                    "Odds Game" => show_odds_entry(s),
+                                    "FEN Position" => show_fen_entry(s),
+                                    "Set Up Position" => editor::show_editor(s, Setup::default()),
+                                    "EPD Position" => show_epd_entry(s),
+                                    "Game Code" => show_code_entry(s),
+                                    _ => unreachable!(),
+                                };
+                            })
+                            .scrollable(),
+                    )
+                    .child(TextView::new("\nDifficulty:"))
+                    .child(difficulty),
+            )
+            .button(tr("Experimental"), show_experimental)
+            .button(tr("Settings"), settings::show_settings)
+            .dismiss_button(tr("Back")),
+    );
+}
+
+fn show_experimental(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Experimental"))
+            .content(
+                SelectView::new()
+                    .item_str("Duck Chess")
+                    .on_submit(|s, option: &str| {
+                        s.pop_layer();
+                        s.pop_layer();
+                        if option == "Duck Chess" {
+                            duck::new_game(s);
+                        }
+                    }),
+            )
+            .dismiss_button(tr("Back")),
+    );
+}
+
+fn show_fen_entry(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("FEN Position"))
+            .content(EditView::new().with_name("fen").fixed_width(70))
+            .button(tr("Start"), |s| {
+                let fen = s
+                    .call_on_name("fen", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                match BoardView::from_fen(settings::current(s), &fen) {
+                    Ok(view) => {
+                        s.pop_layer();
+                        open_game(s, view);
+                    }
+                    Err(e) => s.add_layer(Dialog::info(e)),
+                }
+            })
+            .button(tr("Edit"), |s| {
+                let fen = s
+                    .call_on_name("fen", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                let setup = if fen.trim().is_empty() {
+                    Ok(Setup::default())
+                } else {
+                    Fen::from_ascii(fen.trim().as_bytes())
+                        .map(Fen::into_setup)
+                        .map_err(|e| format!("Invalid FEN: {}", e))
+                };
+                match setup {
+                    Ok(setup) => {
+                        s.pop_layer();
+                        editor::show_editor(s, setup);
+                    }
+                    Err(e) => s.add_layer(Dialog::info(e)),
+                }
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
+
+fn show_epd_entry(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("EPD Position"))
+            .content(EditView::new().with_name("epd").fixed_width(70))
+            .button(tr("Start"), |s| {
+                let record = s
+                    .call_on_name("epd", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                match Epd::parse(&record) {
+                    Ok(epd) => {
+                        s.pop_layer();
+                        if !epd.best.is_empty() || !epd.avoid.is_empty() {
+                            open_puzzle(s, Puzzle::from_epd(epd));
+                        } else {
+                            let settings = settings::current(s);
+                            open_game(s, BoardView::from_position(settings, epd.position));
+                        }
+                    }
+                    Err(e) => s.add_layer(Dialog::info(e)),
+                }
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
+
+fn show_code_entry(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Game Code"))
+            .content(EditView::new().with_name("code").fixed_width(70))
+            .button(tr("Start"), |s| {
+                let code = s
+                    .call_on_name("code", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                match GameCode::decode(&code) {
+                    Ok(game) => {
+                        s.pop_layer();
+                        let mut settings = settings::current(s);
+                        settings.step_mode = game.step_mode;
+                        settings.difficulty = game.difficulty.unwrap_or(settings.difficulty);
+                        let view = match game.human {
+                            Some(human) => BoardView::playing_as(settings, game.position, human),
+                            None => BoardView::from_position(settings, game.position),
+                        };
+                        open_game(s, view);
+                    }
+                    Err(e) => s.add_layer(Dialog::info(e)),
+                }
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
+
+fn show_share(siv: &mut Cursive) {
+    let code = siv
+        .call_on_name("board", |board: &mut BoardView| {
+            GameCode::encode(
+                &board.fen(),
+                board.variant(),
+                board.difficulty,
+                board.human,
+                board.step_mode,
+            )
+        })
+        .unwrap();
+
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Game Code"))
+            .content(EditView::new().content(code).fixed_width(70))
+            .dismiss_button(tr("Close")),
+    );
+}
+
+// For beginners: the CPU starts without some of its pieces.
+fn show_odds_entry(siv: &mut Cursive) {
+    let mut color = RadioGroup::new();
+    let colors = LinearLayout::vertical()
+        .child(TextView::new("\nPlay as:"))
+        .child(color.button(CColor::White, "White").selected())
+        .child(color.button(CColor::Black, "Black"));
+
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Odds Game"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new("The CPU plays without:"))
+                    .child(
+                        SelectView::new()
+                            .with_all(odds::ODDS.iter().enumerate().map(|(i, o)| (o.label, i)))
+                            .on_submit(move |s, &i: &usize| {
+                                let player = *color.selection();
+                                let settings = settings::current(s);
+                                let time_control = settings.clock();
+                                let start = odds::position(&odds::ODDS[i], player);
+                                let mut view =
+                                    BoardView::playing_as(settings, start.into(), player);
+                                view.start_clock(time_control);
+                                s.pop_layer();
+                                open_game(s, view);
+                            }),
+                    )
+                    .child(colors),
+            )
+            .dismiss_button(tr("Back")),
+    );
+}
+
+fn show_chess960_entry(siv: &mut Cursive) {
+    let start = |s: &mut Cursive, number: u32, pos: Chess| {
+        s.pop_layer();
+        new_game(s, pos.into(), &format!("Play As (Chess960 #{})", number));
+    };
+
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Chess960"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new(format!(
+                        "Position number (0-{}), or leave empty:",
+                        chess960::POSITIONS - 1
+                    )))
+                    .child(EditView::new().with_name("chess960").fixed_width(10)),
+            )
+            .button(tr("Start"), move |s| {
+                let number = s
+                    .call_on_name("chess960", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                if number.trim().is_empty() {
+                    let (number, pos) = chess960::random();
+                    return start(s, number, pos);
+                }
+                let position = number
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| format!("\"{}\" is not a position number", number.trim()))
+                    .and_then(|number| Ok((number, chess960::position(number)?)));
+                match position {
+                    Ok((number, pos)) => start(s, number, pos),
+                    Err(e) => s.add_layer(Dialog::info(e)),
+                }
+            })
+            .button(tr("Random"), move |s| {
+                let (number, pos) = chess960::random();
+                start(s, number, pos);
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
+
+pub fn new_game(siv: &mut Cursive, start: VariantPosition, title: &str) {
+    let time_control = settings::radio_list(
+        &TimeControl::ALL.map(|t| (t, t.label())),
+        settings::current(siv).time_control,
+        |settings, value| settings.time_control = value,
+    );
+    let custom = settings::clock_text(settings::current(siv).custom_clock);
+    let clock_mode = settings::radio_list(
+        &clock::Mode::ALL.map(|m| (m, m.label())),
+        settings::current(siv).clock_mode,
+        |settings, value| settings.clock_mode = value,
+    );
+
+    siv.add_layer(
+        Dialog::new()
+            .title(title)
+            .content(
+                LinearLayout::vertical()
+                    .child(
+                        SelectView::new()
+                            .item_str("White")
+                            .item_str("Black")
+                            .item_str("Random")
+                            .item_str("Two Players")
+                            .on_submit(move |s, option: &str| {
+                                if let Err(e) = read_custom_clock(s) {
+                                    return s.add_layer(Dialog::info(e));
+                                }
+                                s.pop_layer();
+                                let settings = settings::current(s);
+                                let time_control = settings.clock();
+                                let mut view = match option {
+                                    "White" => BoardView::playing_as(
+                                        settings,
+                                        start.clone(),
+                                        CColor::White,
+                                    ),
+                                    "Black" => BoardView::playing_as(
+                                        settings,
+                                        start.clone(),
+                                        CColor::Black,
+                                    ),
+                                    "Random" => {
+                                        let color = CColor::from_white(rand::random());
+                                        BoardView::playing_as(settings, start.clone(), color)
+                                    }
+                                    _ => BoardView::two_player(settings, start.clone()),
+                                };
+                                view.start_clock(time_control);
+                                open_game(s, view);
+                            }),
+                    )
+                    .child(
+                        LinearLayout::horizontal()
+                            .child(
+                                LinearLayout::vertical()
+                                    .child(TextView::new("\nClock:"))
+                                    .child(time_control),
+                            )
+                            .child(
+                                LinearLayout::vertical()
+                                    .child(TextView::new("\n    Seconds per move:"))
+                                    .child(
+                                        LinearLayout::horizontal()
+                                            .child(TextView::new("    "))
+                                            .child(clock_mode),
+                                    ),
+                            ),
+                    )
+                    .child(
+                        LinearLayout::horizontal()
+                            .child(TextView::new("Custom: "))
+                            .child(
+                                EditView::new()
+                                    .content(custom.to_string())
+                                    .with_name("custom_clock")
+                                    .fixed_width(10),
+                            )
+                            .child(TextView::new(" min+sec")),
+                    )
+                    .child(TextView::new("White/Black minutes, as 10/5+3, give odds.")),
+            )
+            .dismiss_button(tr("Back")),
+    );
+}
+
+fn read_custom_clock(siv: &mut Cursive) -> Result<(), String> {
+    if settings::current(siv).time_control != TimeControl::Custom {
+        return Ok(());
+    }
+    let text = siv
+        .call_on_name("custom_clock", |v: &mut EditView| v.get_content())
+        .unwrap();
+    let clock = settings::parse_clock(&text).ok_or_else(|| {
+        format!(
+            "\"{}\" is not a time control: use minutes+seconds, as in 15+10, or \
+             white/black minutes+seconds, as in 10/5+3, with 1 to {} minutes",
+            text.trim(),
+            settings::MAX_CLOCK_MINUTES
+        )
+    })?;
+    settings::update(siv, |settings| settings.custom_clock = clock);
+    Ok(())
+}
+
+fn submit_move(siv: &mut Cursive, text: &str) {
+    let played = siv.call_on_name("board", |board: &mut BoardView| board.play_text(text));
+    match played {
+        None => {}
+        Some(Err(e)) => siv.add_layer(Dialog::info(e)),
+        Some(Ok(result)) => {
+            siv.pop_layer();
+            result.process(siv);
+            refresh_side_views(siv);
+        }
+    }
+}
+
+fn show_move_entry(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Enter Move"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new("SAN (Nf3, O-O, exd8=Q) or UCI (g1f3):"))
+                    .child(
+                        EditView::new()
+                            .on_submit(submit_move)
+                            .with_name("move_entry")
+                            .fixed_width(12),
+                    ),
+            )
+            .button(tr("Play"), |s| {
+                let text = s
+                    .call_on_name("move_entry", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                submit_move(s, &text);
+            })
+            .dismiss_button(tr("Cancel")),
+    );
+}
+
+fn show_annotate(siv: &mut Cursive) {
+    let Some((index, san, note)) = siv
+        .call_on_name("board", |board: &mut BoardView| board.annotated_move())
+        .flatten()
+    else {
+        return siv.add_layer(Dialog::info(tr("No move has been played yet.")));
+    };
+
+    let mut nags = SelectView::new().item("(none)", None);
+    for &(nag, symbol, meaning) in &NAGS {
+        nags.add_item(format!("{:<3}{}", symbol, meaning), Some(nag));
+    }
+    let selected = note
+        .nag
+        .and_then(|nag| NAGS.iter().position(|&(n, _, _)| n == nag))
+        .map_or(0, |i| i + 1);
+    nags.set_selection(selected);
+
+    siv.add_layer(
+        Dialog::new()
+            .title(fill("Annotate {}", &[&san]))
+            .content(
+                LinearLayout::vertical()
+                    .child(nags.with_name("note_nag"))
+                    .child(TextView::new("\nComment:"))
+                    .child(
+                        EditView::new()
+                            .content(note.comment)
+                            .with_name("note_comment")
+                            .fixed_width(36),
+                    ),
+            )
+            .button(tr("Ok"), move |s| {
+                let nag = s
+                    .call_on_name("note_nag", |v: &mut SelectView<Option<u8>>| v.selection())
+                    .flatten()
+                    .and_then(|nag| *nag);
+                let comment = s
+                    .call_on_name("note_comment", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                let note = Note {
+                    nag,
+                    comment: pgn::clean_comment(&comment),
+                };
+                s.call_on_name("board", |board: &mut BoardView| board.set_note(index, note));
+                s.pop_layer();
+                refresh_side_views(s);
+            })
+            .dismiss_button(tr("Cancel")),
+    );
+}
+
+pub fn open_game(siv: &mut Cursive, view: BoardView) {
+    let show_controls = view.settings.show_controls;
+    let rows = view.board_rows();
+    // A fitted board sets the height of the row; the others set it here.
+    let height = match view.settings.board_size {
+        BoardSize::Fit => SizeConstraint::Free,
+        _ => SizeConstraint::Fixed(rows + 2 + STATUS_ROWS + view.board_origin().y),
+    };
+    let eval_bar = view.settings.eval_bar;
+    let perspective = view.settings.eval_perspective;
+    if view.has_clock() {
+        siv.set_fps(4);
+    }
+    let moves = view.move_panel();
+    let turn = view.turn_text();
+    let status = view.status_bar(None);
+    let announce = view.settings.announce_moves;
+    let tabs = view.tab_bar();
+
+    let mut layout = LinearLayout::horizontal().child(
+        LinearLayout::vertical()
+            .child(TextView::new(turn).with_name("turn"))
+            .child(Panel::new(view.with_name("board")).with_name("board_panel")),
+    );
+    if eval_bar {
+        // Line the bar up with the board inside its panel.
+        layout.add_child(PaddedView::lrtb(
+            1,
+            0,
+            2,
+            0,
+            EvalBar::new(rows, perspective).with_name("eval"),
+        ));
+    }
+
+    layout.add_child(Panel::new(
+        TextView::new(moves)
+            .with_name("moves")
+            .scrollable()
+            .scroll_strategy(ScrollStrategy::StickToBottom)
+            .resized(SizeConstraint::Fixed(16), height),
+    ));
+    layout.add_child(PaddedView::lrtb(
+        1,
+        0,
+        1,
+        0,
+        TextView::new(tabs).with_name("tabs"),
+    ));
+    let layout = OnLayoutView::new(layout, |layout, size| {
+        layout.layout(size);
+        let rows = layout.call_on_name("board", |board: &mut BoardView| board.board_rows());
+        if let Some(rows) = rows {
+            layout.call_on_name("eval", |bar: &mut EvalBar| bar.set_height(rows));
+        }
+    });
+
+    let mut content = LinearLayout::vertical()
+        .child(layout)
+        .child(TextView::new(status).with_name("status_bar"));
+    if announce {
+        content.add_child(
+            Panel::new(
+                TextView::new("")
+                    .with_name("announcements")
+                    .scrollable()
+                    .scroll_strategy(ScrollStrategy::StickToBottom)
+                    .fixed_height(ANNOUNCED_ROWS),
+            )
+            .title(tr("Moves read out")),
+        );
+    }
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Chess"))
+            .content(content)
+            .button(tr("New Tab"), |s| {
+                let settings = settings::current(s);
+                s.call_on_name("board", |board: &mut BoardView| board.new_tab(settings));
+                refresh_side_views(s);
+            })
+            .button(tr("Close Tab"), end_game)
+            .button(tr("Undo"), |s| {
+                s.call_on_name("board", |board: &mut BoardView| board.take_back());
+                refresh_side_views(s);
+            })
+            .button(tr("Resign"), |s| {
+                if let Some(result) =
+                    s.call_on_name("board", |board: &mut BoardView| board.resign())
+                {
+                    result.process(s);
+                }
+            })
+            .button(tr("Offer Draw"), |s| {
+                if let Some(result) =
+                    s.call_on_name("board", |board: &mut BoardView| board.offer_draw())
+                {
+                    result.process(s);
+                }
+            })
+            .button(tr("Save Game"), library::show_save)
+            .button(tr("Export PGN"), pgn::show_export)
+            .button(tr("Share"), show_share)
+            .button(tr("Quit Game"), |s| {
+                library::save_resume(s);
+                s.call_on_name("board", |board: &mut BoardView| board.close_all_tabs());
+                s.pop_layer();
+            }),
+    );
+    refresh_side_views(siv);
+
+    if show_controls {
+        siv.add_layer(
+            Dialog::around(
+                LinearLayout::vertical()
+                    .child(TextView::new(
+                        "Controls:
+Click with the mouse on the piece you want to move,
+then click on the square you want to move it to, or drag it there.
+Right-click marks a square and right-drag draws an arrow.
+Or use Arrows and Space, or press M to type a move such as Nf3 or e2e4.
+Press A to see every square your pieces can reach, or H for a hint.
+Press / and a square such as e4 to put the cursor there.
+F flips the board. ? lists the keys, which can be rebound.
+Rest the cursor on any piece to inspect its moves.
+Press S to snapshot the position and C to compare against it.
+B hides the opponent's pieces, then all pieces, for blindfold play.
+Press < to look back through the game and > to step forward again.
+N annotates the move on show with a symbol such as ! or ?! and a comment.
+Y copies the FEN and Shift+Y the PGN; V analyzes a pasted FEN or PGN.
+U takes back your last move and the CPU reply.
+With auto-queen on, X picks another promotion piece.
+In Crazyhouse, D or a click on your pocket picks a piece to drop.
+Press = or Offer Draw to claim a draw by repetition or the fifty-move rule.
+Shift+R resigns, Shift+D offers a draw, Shift+C plans replies ahead.
+Alt+1 to Alt+9 or G switch between games opened with New Tab;
+a CPU keeps thinking in the games you leave.
+P toggles step mode: the CPU waits for Enter before replying.",
+                    ))
+                    .child(
+                        LinearLayout::horizontal()
+                            .child(Checkbox::new().on_change(|s, checked| {
+                                settings::update(s, |settings| settings.show_controls = !checked)
+                            }))
+                            .child(TextView::new(format!(" {}", tr("Don't show again")))),
+                    ),
+            )
+            .dismiss_button(tr("Ok")),
+        );
+    }
+}
+
+pub fn square_at(
+    mouse_pos: Vec2,
+    offset: Vec2,
+    orientation: CColor,
+    (w, h): (usize, usize),
+) -> Option<Square> {
+    mouse_pos
+        .checked_sub(offset)
+        .map(|pos| Vec2::new(pos.x / w, pos.y / h))
+        .and_then(|pos| {
+            if pos.fits_in(Vec2::new(8, 8)) {
+                let (file, rank) = match orientation {
+                    CColor::White => (pos.x, 7 - pos.y),
+                    CColor::Black => (7 - pos.x, pos.y),
+                };
+                Some(Square::new((file + 8 * rank).try_into().unwrap()))
+            } else {
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(fen: &str, settings: Settings) -> BoardView {
+        BoardView::from_fen(settings, fen).unwrap()
+    }
+
+    #[test]
+    fn material_readout_follows_piece_values() {
+        let fen = "4k3/8/8/8/8/8/8/1B2K3 w - - 0 1";
+        assert_eq!(
+            view(fen, Settings::default()).material_text(),
+            "Material (White): +3"
+        );
+
+        let mut settings = Settings::default();
+        settings.piece_values.set(Role::Bishop, 3.25);
+        assert_eq!(
+            view(fen, settings).material_text(),
+            "Material (White): +3.25"
+        );
+    }
+
+    #[test]
+    fn cursor_clamps_at_every_edge() {
+        let board = BoardView::new(Settings::default());
+        assert_eq!(board.step_cursor(Square::A4, -1, 0), Square::A4);
+        assert_eq!(board.step_cursor(Square::H4, 1, 0), Square::H4);
+        assert_eq!(board.step_cursor(Square::D1, 0, -1), Square::D1);
+        assert_eq!(board.step_cursor(Square::D8, 0, 1), Square::D8);
+    }
+
+    #[test]
+    fn cursor_wraps_at_every_edge() {
+        let board = BoardView::new(Settings {
+            cursor_edges: CursorEdges::Wrap,
+            ..Settings::default()
+        });
+        assert_eq!(board.step_cursor(Square::A4, -1, 0), Square::H4);
+        assert_eq!(board.step_cursor(Square::H4, 1, 0), Square::A4);
+        assert_eq!(board.step_cursor(Square::D1, 0, -1), Square::D8);
+        assert_eq!(board.step_cursor(Square::D8, 0, 1), Square::D1);
+    }
+
+    #[test]
+    fn arrow_keys_stay_on_the_board() {
+        let mut board = BoardView::new(Settings::default());
+        board.highlighted = Some(Square::A1);
+        for key in [Key::Left, Key::Down, Key::Left, Key::Down] {
+            assert!(matches!(
+                board.on_event(Event::Key(key)),
+                EventResult::Consumed(_)
+            ));
+            assert_eq!(board.highlighted, Some(Square::A1));
+        }
+        board.highlighted = Some(Square::H8);
+        for key in [Key::Right, Key::Up, Key::Right, Key::Up] {
+            board.on_event(Event::Key(key));
+            assert_eq!(board.highlighted, Some(Square::H8));
+        }
+        board.highlighted = Some(Square::A4);
+        board.on_event(Event::Key(Key::Left));
+        assert_eq!(board.highlighted, Some(Square::A4));
+    }
+
+    #[test]
+    fn arrow_keys_at_the_edge_keep_focus_off_dialog_buttons() {
+        let mut board = BoardView::new(Settings::default());
+        board.highlighted = Some(Square::A1);
+        let mut dialog = Dialog::around(board.with_name("board"))
+            .button("Ok", |_| {})
+            .button("Close", |_| {});
+        dialog.layout(Vec2::new(100, 40));
+
+        dialog.on_event(Event::Key(Key::Down));
+        dialog.on_event(Event::Key(Key::Right));
+        let highlighted = dialog.call_on_name("board", |board: &mut BoardView| board.highlighted);
+        assert_eq!(highlighted, Some(Some(Square::B1)));
+    }
+
+    #[test]
+    fn black_pawns_promote_from_the_second_rank() {
+        let board = view("4k3/7p/8/8/8/8/p7/4K3 b - - 0 1", Settings::default());
+        assert!(board.promotes(Square::A2));
+        assert!(!board.promotes(Square::H7));
+    }
+
+    #[test]
+    fn cpu_without_a_move_ends_the_game() {
+        let mut board = view("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", Settings::default());
+        assert_eq!(board.cpu_choice(), None);
+        let opponent = board.opponent.take().unwrap();
+        assert!(board.receive(opponent, None).is_some());
+        assert!(matches!(board.ending(), Some((None, Ending::Stalemate))));
+
+        let mut board = view("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", Settings::default());
+        assert_eq!(board.cpu_choice(), None);
+        let opponent = board.opponent.take().unwrap();
+        assert!(board.receive(opponent, None).is_some());
+        assert!(matches!(
+            board.ending(),
+            Some((Some(CColor::White), Ending::Checkmate))
+        ));
+    }
+
+    #[test]
+    fn clicks_follow_the_cell_size_after_a_resize() {
+        let mut board = BoardView::new(Settings {
+            board_size: BoardSize::Fit,
+            ..Settings::default()
+        });
+        for cell in [(7, 3), (3, 1)] {
+            board.layout(view_size(cell, false));
+            assert_eq!(board.cell, cell);
+
+            // The middle of e4: fifth file, fifth row from the top.
+            let click = Vec2::new(LABEL_COLS + 4 * cell.0 + cell.0 / 2, 4 * cell.1);
+            let origin = board.board_origin();
+            assert_eq!(
+                square_at(click, origin, CColor::White, board.cell),
+                Some(Square::E4)
+            );
+            board.highlighted = None;
+            board.on_event(Event::Mouse {
+                offset: Vec2::zero(),
+                position: click,
+                event: MouseEvent::Press(MouseButton::Left),
+            });
+            assert_eq!(board.highlighted, Some(Square::E4));
+        }
+    }
+
+    #[test]
+    fn playing_black_starts_with_one_cpu_move() {
+        let settings = Settings {
+            difficulty: Difficulty::Random,
+            ..Settings::default()
+        };
+        let board = BoardView::playing_as(settings, Chess::default().into(), CColor::Black);
+        assert_eq!(board.moves.len(), 1);
+        assert!(board.human_to_move());
+    }
+
+    #[test]
+    fn checked_king_is_the_side_to_move() {
+        let board = view(
+            "rnbqkbnr/ppppp2p/5p2/6pQ/4P3/8/PPPP1PPP/RNB1KBNR b KQkq - 1 3",
+            Settings::default(),
+        );
+        assert_eq!(board.checked_king(), Some(Square::E8));
+        assert_eq!(BoardView::new(Settings::default()).checked_king(), None);
+    }
+
+    #[test]
+    fn stalemate_ends_with_a_draw_message() {
+        let board = view("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", Settings::default());
+        let (winner, ending) = board.ending().unwrap();
+        assert_eq!(
+            game_over_message(CpuTone::Silent, winner, board.human, Some(ending)),
+            "Game Over. Stalemate \u{2014} draw."
+        );
+    }
+
+    #[test]
+    fn cursor_starts_on_the_players_king() {
+        let settings = Settings {
+            difficulty: Difficulty::Random,
+            ..Settings::default()
+        };
+        let mut white = BoardView::new(settings.clone());
+        white.on_event(Event::Key(Key::Up));
+        assert_eq!(white.highlighted, Some(Square::E1));
+
+        let mut black = BoardView::playing_as(settings, Chess::default().into(), CColor::Black);
+        black.on_event(Event::Key(Key::Up));
+        assert_eq!(black.highlighted, Some(Square::E8));
+    }
+
+    #[test]
+    fn take_back_undoes_the_move_and_the_reply() {
+        let mut board = BoardView::new(Settings {
+            difficulty: Difficulty::Random,
+            ..Settings::default()
+        });
+        let start = board.fen();
+        let mv = board.board.legal_moves()[0].clone();
+        board.play(&mv);
+        let reply = board.cpu_choice().unwrap();
+        board.play_cpu_move(reply);
+        assert_eq!(board.moves.len(), 2);
+
+        assert!(board.take_back());
+        assert_eq!(board.fen(), start);
+        assert!(board.moves.is_empty());
+    }
+
+    #[test]
+    fn clicking_another_piece_reselects() {
+        let mut board = BoardView::new(Settings {
+            friendly_click: FriendlyClick::Reselect,
+            ..Settings::default()
+        });
+        board.process_focus_change(Square::E2);
+        assert_eq!(board.focused, Some(Square::E2));
+        board.process_focus_change(Square::G1);
+        assert_eq!(board.focused, Some(Square::G1));
+    }
+
+    #[test]
+    fn clicking_another_piece_deselects() {
+        let mut board = BoardView::new(Settings {
+            friendly_click: FriendlyClick::Deselect,
+            ..Settings::default()
+        });
+        board.process_focus_change(Square::E2);
+        assert_eq!(board.focused, Some(Square::E2));
+        board.process_focus_change(Square::G1);
+        assert_eq!(board.focused, None);
+        assert!(board.moves.is_empty());
+    }
+
+    #[test]
+    fn clicks_while_the_cpu_thinks_do_not_move() {
+        let mut board = BoardView::new(Settings {
+            difficulty: Difficulty::Random,
+            ..Settings::default()
+        });
+        let mv = Uci::from_ascii(b"e2e4")
+            .unwrap()
+            .to_move(&board.board)
+            .unwrap();
+        assert!(board.move_and_reply(mv).is_some());
+        assert!(board.thinking.is_some());
+        let fen = board.fen();
+
+        board.process_focus_change(Square::E7);
+        assert_eq!(board.focused, None);
+        board.process_focus_change(Square::D2);
+        board.process_focus_change(Square::D4);
+        assert_eq!(board.fen(), fen);
+        assert_eq!(board.moves.len(), 1);
+        assert_eq!(board.focused, None);
+        assert_eq!(board.premove, Some((Square::D2, Square::D4)));
+        assert!(board.play_uci("e7e5").is_err());
+    }
+
+    #[test]
+    fn finished_games_ignore_resign_and_draw_keys() {
+        let mut board = view("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", Settings::default());
+        for key in ['R', 'D'] {
+            assert!(matches!(
+                board.on_event(Event::Char(key)),
+                EventResult::Ignored
+            ));
+        }
+    }
+
+    #[test]
+    fn fuzz_games_reach_an_ending() {
+        let report = fuzz(3, 1).unwrap();
+        assert!(report.starts_with("3 games"), "{}", report);
+    }
+
+    #[test]
+    fn fuzz_games_only_play_legal_moves() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..4 {
+            let view = fuzz_game(&mut rng).unwrap();
+            assert!(!view.moves.is_empty());
+        }
+
+        let view = BoardView::new(Settings::default());
+        let illegal = shakmaty::Move::Normal {
+            role: Role::Pawn,
+            from: Square::E2,
+            capture: None,
+            to: Square::E5,
+            promotion: None,
+        };
+        assert!(fuzz_check(&view, &illegal).is_err());
+    }
 }
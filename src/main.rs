@@ -1,23 +1,343 @@
-use cursive::views::{Button, Dialog, LinearLayout};
+use std::{io::IsTerminal, path::PathBuf, process, time::Instant};
 
+use cursive::{
+    event::Event,
+    view::Nameable,
+    views::{Button, Dialog, DummyView, LinearLayout, TextView},
+};
+
+mod analysis;
+mod book;
+mod broadcast;
+mod chess960;
+mod cli;
+mod clipboard;
+mod clock;
+mod code;
+mod conditional;
+mod duck;
+mod duel;
+mod eco;
+mod editor;
+mod engine;
+mod epd;
+mod evalbar;
+mod explorer;
+mod http;
+mod keymap;
+mod lang;
+mod library;
+mod lichess;
 mod logic;
+mod marks;
+mod net;
+mod odds;
+#[cfg(feature = "online")]
+mod online;
+mod pgn;
+mod picture;
+mod problem;
+mod puzzle;
+mod repertoire;
+mod replay;
+mod review;
+mod settings;
+mod sound;
+mod spectate;
+mod speech;
+mod stats;
+mod tutorial;
+mod uci;
+
+use lang::tr;
 
 fn main() {
+    let mut http_port = None;
+    let mut fuzz_games = None;
+    let mut seed = None;
+    let mut ascii = false;
+    let mut engine_log = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--http" => match args.next().and_then(|port| port.parse::<u16>().ok()) {
+                Some(port) => http_port = Some(port),
+                None => {
+                    eprintln!("--http expects a port number");
+                    process::exit(2);
+                }
+            },
+            "--ascii" => ascii = true,
+            "--engine-log" => match args.next() {
+                Some(path) => engine_log = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("--engine-log expects a file path");
+                    process::exit(2);
+                }
+            },
+            "perft" => {
+                let depth = args.next().and_then(|depth| depth.parse::<u32>().ok());
+                let fen = args.collect::<Vec<_>>().join(" ");
+                match depth {
+                    Some(depth) => run_perft(depth, &fen),
+                    None => {
+                        eprintln!("usage: perft <depth> [FEN]");
+                        process::exit(2);
+                    }
+                }
+            }
+            "analyze" | "selfplay" | "puzzle" | "render" | "match" => {
+                run_command(&arg, args.collect())
+            }
+            "--fuzz" => match args.next().and_then(|games| games.parse::<u32>().ok()) {
+                Some(games) => fuzz_games = Some(games),
+                None => {
+                    eprintln!("--fuzz expects a number of games");
+                    process::exit(2);
+                }
+            },
+            "--seed" => match args.next().and_then(|seed| seed.parse::<u64>().ok()) {
+                Some(s) => seed = Some(s),
+                None => {
+                    eprintln!("--seed expects a number");
+                    process::exit(2);
+                }
+            },
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                process::exit(2);
+            }
+        }
+    }
+
+    if let Some(games) = fuzz_games {
+        match logic::fuzz(games, seed.unwrap_or_else(rand::random)) {
+            Ok(report) => {
+                println!("{}", report);
+                process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        eprintln!("No interactive terminal detected; run terminal_chess_app in a real terminal.");
+        process::exit(1);
+    }
+
+    let log_error = engine_log.and_then(|path| {
+        engine::open_log(&path).err().map(|e| {
+            format!(
+                "Could not open the engine log {}: {}. Logging is off.",
+                path.display(),
+                e
+            )
+        })
+    });
+
     let mut siv = cursive::default();
+    let mut settings = settings::Settings::load();
+    if ascii {
+        settings.piece_style = settings::PieceStyle::Ascii;
+    }
+    lang::set(settings.language);
+    siv.set_user_data(settings);
+    siv.set_on_pre_event(Event::Refresh, logic::tick_clock);
+    siv.set_on_pre_event(Event::Refresh, spectate::tick);
+    siv.set_on_pre_event(Event::Refresh, broadcast::tick);
+
+    if let Some(port) = http_port {
+        if let Err(e) = http::serve(port, siv.cb_sink().clone()) {
+            eprintln!("Could not listen on port {}: {}", port, e);
+            process::exit(1);
+        }
+    }
 
     siv.add_layer(
         Dialog::new()
-            .title("Chess")
+            .title(tr("Chess"))
             .padding_lrtb(2, 2, 1, 1)
             .content(
                 LinearLayout::vertical()
-                    .child(Button::new_raw("New game", logic::show_options))
-                    .child(Button::new_raw("Rules", |s| {
-                        s.add_layer(Dialog::info("You probably know how to play!").title("Rules"))
-                    }))
-                    .child(Button::new_raw("Exit", |s| s.quit())),
+                    .child(TextView::new(stats::rating_text()).with_name("menu_rating"))
+                    .child(DummyView)
+                    .child(Button::new_raw(tr("New game"), logic::show_options))
+                    .child(Button::new_raw(tr("Resume game"), library::resume))
+                    .child(Button::new_raw(tr("Load game"), library::show_library))
+                    .child(Button::new_raw(
+                        tr("Correspondence"),
+                        library::show_correspondence,
+                    ))
+                    .child(Button::new_raw(tr("Open PGN"), replay::show_open))
+                    .child(Button::new_raw(
+                        tr("Puzzle of the day"),
+                        lichess::show_daily,
+                    ))
+                    .child(Button::new_raw(tr("Puzzle trainer"), puzzle::show_trainer))
+                    .child(Button::new_raw(tr("Mate problems"), problem::show_setup))
+                    .child(Button::new_raw(tr("Repertoire"), repertoire::show_trainer))
+                    .child(Button::new_raw(tr("Network game"), net::show_setup))
+                    .child(Button::new_raw(tr("Watch engines"), spectate::show_setup))
+                    .child(Button::new_raw(
+                        tr("Follow broadcast"),
+                        broadcast::show_setup,
+                    ))
+                    .child(Button::new_raw(tr("Stats"), stats::show_stats))
+                    .child(Button::new_raw(tr("Settings"), settings::show_settings))
+                    .child(Button::new_raw(tr("Rules"), tutorial::show_lessons))
+                    .child(Button::new_raw(tr("Exit"), |s| s.quit())),
             ),
     );
 
-    siv.run();
+    library::offer_recovery(&mut siv);
+    if let Some(e) = log_error {
+        siv.add_layer(Dialog::info(e));
+    }
+
+    if let Err(e) = siv.try_run() {
+        eprintln!("Could not start the terminal interface: {}", e);
+        process::exit(1);
+    }
+}
+
+// Options are "--name value"; everything else is positional.
+fn run_command(command: &str, args: Vec<String>) -> ! {
+    let (names, usage): (&[&str], &str) = match command {
+        "analyze" => (&["depth"], "usage: analyze [--depth N] [FEN]"),
+        "selfplay" => (
+            &["depth", "games", "seed"],
+            "usage: selfplay [--depth N] [--games N] [--seed N]",
+        ),
+        "render" => (
+            &["format", "pgn", "out"],
+            "usage: render [--format svg|ansi] [--pgn <game.pgn> | FEN] [--out <path>]",
+        ),
+        "match" => (
+            &["first", "second", "openings", "games", "elo0", "elo1", "pgn", "out"],
+            "usage: match --first SPEC --second SPEC --openings <suite.epd> [--games N] \
+             [--elo0 E] [--elo1 E] [--pgn <games.pgn>] [--out <report.txt>]\n\
+             SPEC is depth=N for the built-in AI, or engine=<path>[,skill=N][,name=X][,Option=Value...]",
+        ),
+        _ => (
+            &["depth", "file"],
+            "usage: puzzle --file <puzzles.epd> [--depth N]",
+        ),
+    };
+
+    let mut options = Vec::new();
+    let mut positional = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some(name) if names.contains(&name) => match args.next() {
+                Some(value) => options.push((name.to_owned(), value)),
+                None => {
+                    eprintln!("{}", usage);
+                    process::exit(2);
+                }
+            },
+            Some(_) => {
+                eprintln!("{}", usage);
+                process::exit(2);
+            }
+            None => positional.push(arg),
+        }
+    }
+    let option = |name: &str| {
+        options
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.clone())
+    };
+    let number = |name: &str, default: u64| match option(name) {
+        Some(value) => value.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!("--{} expects a number", name);
+            process::exit(2);
+        }),
+        None => default,
+    };
+
+    let elo = |name: &str, default: f64| match option(name) {
+        Some(value) => value.parse::<f64>().unwrap_or_else(|_| {
+            eprintln!("--{} expects a number", name);
+            process::exit(2);
+        }),
+        None => default,
+    };
+    let required = |name: &str| {
+        option(name).unwrap_or_else(|| {
+            eprintln!("{}", usage);
+            process::exit(2);
+        })
+    };
+
+    let depth = number("depth", 4) as u32;
+    let result = match command {
+        "analyze" => cli::analyze(&positional.join(" "), depth),
+        "selfplay" => cli::selfplay(
+            number("depth", 3) as u32,
+            number("games", 1) as u32,
+            number("seed", rand::random()),
+        ),
+        "render" => cli::render(
+            &option("format").unwrap_or_else(|| "svg".to_owned()),
+            &positional.join(" "),
+            option("pgn").as_deref(),
+            option("out").as_deref(),
+        ),
+        "match" => duel::run(
+            &required("first"),
+            &required("second"),
+            &required("openings"),
+            option("games").map(|_| number("games", 0) as u32),
+            (elo("elo0", 0.0), elo("elo1", 5.0)),
+            option("pgn").as_deref(),
+        )
+        .and_then(|report| match option("out") {
+            Some(path) => cli::write(&path, &report),
+            None => Ok(report),
+        }),
+        _ => match option("file").or_else(|| positional.first().cloned()) {
+            Some(file) => cli::puzzles(&file, depth),
+            None => {
+                eprintln!("{}", usage);
+                process::exit(2);
+            }
+        },
+    };
+    match result {
+        Ok(output) => {
+            println!("{}", output);
+            process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run_perft(depth: u32, fen: &str) -> ! {
+    let pos = if fen.trim().is_empty() {
+        shakmaty::Chess::default()
+    } else {
+        match logic::parse_fen(fen) {
+            Ok(pos) => pos,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    };
+
+    for d in 1..=depth {
+        let start = Instant::now();
+        let nodes = logic::perft(&pos, d);
+        println!("depth {}: {} nodes in {:.2?}", d, nodes, start.elapsed());
+    }
+
+    process::exit(0);
 }
@@ -0,0 +1,148 @@
+use cursive::{
+    theme::{BaseColor, Color as Ink},
+    Vec2,
+};
+use shakmaty::{Bitboard, Color, Square};
+
+pub const ARROW: Ink = Ink::Light(BaseColor::Red);
+
+// Squares and arrows marked with the right mouse button: a click toggles a
+// square, a drag toggles an arrow between two squares.
+#[derive(Default)]
+pub struct Marks {
+    pub squares: Bitboard,
+    arrows: Vec<(Square, Square)>,
+    start: Option<Square>,
+}
+
+impl Marks {
+    pub fn press(&mut self, sq: Option<Square>) {
+        self.start = sq;
+    }
+
+    pub fn release(&mut self, sq: Option<Square>) {
+        match (self.start.take(), sq) {
+            (Some(from), Some(to)) if from == to => self.squares.toggle(to),
+            (Some(from), Some(to)) => match self.arrows.iter().position(|&a| a == (from, to)) {
+                Some(i) => {
+                    self.arrows.remove(i);
+                }
+                None => self.arrows.push((from, to)),
+            },
+            _ => {}
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Marks::default();
+    }
+
+    // Arrow glyphs by character position on a board of `cell`-sized squares,
+    // leaving the pieces in `occupied` visible.
+    pub fn overlay(
+        &self,
+        orientation: Color,
+        (w, h): (usize, usize),
+        occupied: Bitboard,
+    ) -> Vec<(Vec2, char)> {
+        let screen = |sq: Square| -> (i32, i32) {
+            let (file, rank) = (i32::from(sq.file()), i32::from(sq.rank()));
+            match orientation {
+                Color::White => (file, 7 - rank),
+                Color::Black => (7 - file, rank),
+            }
+        };
+        let center = |(col, row): (i32, i32)| {
+            (
+                col * w as i32 + w as i32 / 2,
+                row * h as i32 + (h as i32 - 1) / 2,
+            )
+        };
+
+        let mut glyphs = Vec::new();
+        for &(from, to) in &self.arrows {
+            let steps = path(screen(from), screen(to));
+            let mut square = screen(from);
+            for (i, &(dx, dy)) in steps.iter().enumerate() {
+                let (x, y) = center(square);
+                // The run of line between this square's centre and the next.
+                if dy == 0 {
+                    glyphs.extend((1..w as i32).map(|k| ((x + dx * k, y), '─')));
+                } else if dx == 0 {
+                    glyphs.extend((1..h as i32).map(|k| ((x, y + dy * k), '│')));
+                }
+                square = (square.0 + dx, square.1 + dy);
+                let glyph = match steps.get(i + 1) {
+                    Some(&next) if next == (dx, dy) => line(dx, dy),
+                    Some(&next) => corner((dx, dy), next),
+                    None => head(dx, dy),
+                };
+                glyphs.push((center(square), glyph));
+            }
+
+            // A head on a piece moves to the side it is approached from.
+            let (x, y) = center(square);
+            if occupied.contains(to) && w > 1 {
+                let (dx, _) = steps.last().copied().unwrap_or((1, 0));
+                let side = if dx == 0 { -1 } else { -dx };
+                let head = glyphs.pop().unwrap().1;
+                glyphs.push(((x + side, y), head));
+            }
+        }
+
+        let pieces: Vec<(i32, i32)> = occupied.into_iter().map(|sq| center(screen(sq))).collect();
+        glyphs
+            .into_iter()
+            .filter(|&((x, y), _)| x >= 0 && y >= 0 && !pieces.contains(&(x, y)))
+            .map(|((x, y), glyph)| (Vec2::new(x as usize, y as usize), glyph))
+            .collect()
+    }
+}
+
+// One step per square: straight lines and diagonals go directly, anything
+// else (a knight's move) runs along its longer side first, then turns.
+fn path((x0, y0): (i32, i32), (x1, y1): (i32, i32)) -> Vec<(i32, i32)> {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let unit = (dx.signum(), dy.signum());
+    if dx == 0 || dy == 0 || dx.abs() == dy.abs() {
+        return vec![unit; dx.abs().max(dy.abs()) as usize];
+    }
+    let (long, short) = if dx.abs() > dy.abs() {
+        ((unit.0, 0), (0, unit.1))
+    } else {
+        ((0, unit.1), (unit.0, 0))
+    };
+    let (n, m) = (dx.abs().max(dy.abs()), dx.abs().min(dy.abs()));
+    [vec![long; n as usize], vec![short; m as usize]].concat()
+}
+
+fn line(dx: i32, dy: i32) -> char {
+    match (dx, dy) {
+        (_, 0) => '─',
+        (0, _) => '│',
+        _ if dx == dy => '╲',
+        _ => '╱',
+    }
+}
+
+fn corner(from: (i32, i32), to: (i32, i32)) -> char {
+    match (from, to) {
+        ((1, 0), (0, 1)) | ((0, -1), (-1, 0)) => '┐',
+        ((1, 0), (0, -1)) | ((0, 1), (-1, 0)) => '┘',
+        ((-1, 0), (0, 1)) | ((0, -1), (1, 0)) => '┌',
+        _ => '└',
+    }
+}
+
+fn head(dx: i32, dy: i32) -> char {
+    match (dx, dy) {
+        (1, 0) => '▶',
+        (-1, 0) => '◀',
+        (0, -1) => '▲',
+        (0, 1) => '▼',
+        (1, -1) => '◥',
+        (-1, -1) => '◤',
+        (1, 1) => '◢',
+        _ => '◣',
+    }
+}
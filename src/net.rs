@@ -0,0 +1,410 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{IpAddr, Shutdown, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use cursive::{
+    view::{Nameable, Resizable},
+    views::{Dialog, EditView, LinearLayout, RadioGroup, TextView},
+    CbSink, Cursive,
+};
+#[cfg(feature = "online")]
+use shakmaty::ByColor;
+use shakmaty::Color;
+
+use crate::lang::{fill, tr};
+use crate::logic::{self, BoardView};
+#[cfg(feature = "online")]
+use crate::online;
+use crate::settings;
+
+pub const DEFAULT_PORT: u16 = 7531;
+const GREETING: &str = "HELLO terminal_chess_app 1";
+const TIMEOUT: Duration = Duration::from_secs(10);
+const POLL: Duration = Duration::from_millis(100);
+
+// Each connection gets its own id, so a message that arrives after its game
+// was closed cannot land in the next one.
+static LINKS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn link_id() -> usize {
+    LINKS.fetch_add(1, Ordering::SeqCst)
+}
+
+// One line per message. The joiner greets first and the host answers with
+// the same greeting followed by the joiner's colour, as in
+// "HELLO terminal_chess_app 1 black". After that:
+//   MOVE <uci>  a move by the sender
+//   RESIGN
+//   DRAW        offers a draw, accepts the one on offer or claims one
+//   DECLINE     turns down a draw offer
+pub enum Message {
+    Move(String),
+    Resign,
+    Draw,
+    Decline,
+    // Not sent: the connection closed.
+    Gone,
+    // Not sent: how a game on Lichess ended there, and who won.
+    #[cfg(feature = "online")]
+    Over(Option<Color>, &'static str),
+    // Not sent: the clocks as Lichess keeps them.
+    #[cfg(feature = "online")]
+    Clock(ByColor<Duration>),
+}
+
+impl Message {
+    fn parse(line: &str) -> Option<Message> {
+        let mut words = line.split_whitespace();
+        let message = match (words.next()?, words.next()) {
+            ("MOVE", Some(uci)) => Message::Move(uci.to_owned()),
+            ("RESIGN", None) => Message::Resign,
+            ("DRAW", None) => Message::Draw,
+            ("DECLINE", None) => Message::Decline,
+            _ => return None,
+        };
+        Some(message)
+    }
+
+    fn line(&self) -> Option<String> {
+        match self {
+            Message::Move(uci) => Some(format!("MOVE {}", uci)),
+            Message::Resign => Some("RESIGN".to_owned()),
+            Message::Draw => Some("DRAW".to_owned()),
+            Message::Decline => Some("DECLINE".to_owned()),
+            _ => None,
+        }
+    }
+}
+
+enum Link {
+    Lan(TcpStream),
+    #[cfg(feature = "online")]
+    Lichess(online::Game),
+}
+
+pub struct Peer {
+    pub id: usize,
+    link: Link,
+    // Whether our draw offer is waiting for an answer.
+    pub offered: bool,
+    open: bool,
+}
+
+impl Peer {
+    #[cfg(feature = "online")]
+    pub fn lichess(id: usize, game: online::Game) -> Peer {
+        Peer {
+            id,
+            link: Link::Lichess(game),
+            offered: false,
+            open: true,
+        }
+    }
+
+    pub fn send(&mut self, message: &Message) -> Result<(), String> {
+        let Some(line) = message.line().filter(|_| self.open) else {
+            return Ok(());
+        };
+        if let Message::Draw = message {
+            self.offered = true;
+        }
+        match &mut self.link {
+            Link::Lan(stream) => writeln!(stream, "{}", line)
+                .and_then(|()| stream.flush())
+                .map_err(|e| format!("Lost the connection to the other player: {}", e)),
+            #[cfg(feature = "online")]
+            Link::Lichess(game) => {
+                game.send(message);
+                Ok(())
+            }
+        }
+    }
+
+    // Shutting down rather than just closing also ends our own reader, which
+    // holds a clone of the socket.
+    pub fn hang_up(&mut self) {
+        self.open = false;
+        match &mut self.link {
+            Link::Lan(stream) => {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            #[cfg(feature = "online")]
+            Link::Lichess(game) => game.hang_up(),
+        }
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        self.hang_up();
+    }
+}
+
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "the connection closed",
+        ));
+    }
+    Ok(line.trim_end().to_owned())
+}
+
+fn not_a_peer() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "the other side is not a terminal_chess_app game",
+    )
+}
+
+fn color_key(color: Color) -> &'static str {
+    match color {
+        Color::White => "white",
+        Color::Black => "black",
+    }
+}
+
+fn greet_joiner(stream: &mut TcpStream, joiner: Color) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    if read_line(&mut reader)? != GREETING {
+        return Err(not_a_peer());
+    }
+    writeln!(stream, "{} {}", GREETING, color_key(joiner))?;
+    stream.set_read_timeout(None)
+}
+
+fn greet_host(stream: &mut TcpStream) -> io::Result<Color> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    writeln!(stream, "{}", GREETING)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let line = read_line(&mut reader)?;
+    let color = match line.strip_prefix(GREETING).map(str::trim) {
+        Some("white") => Color::White,
+        Some("black") => Color::Black,
+        _ => return Err(not_a_peer()),
+    };
+    stream.set_read_timeout(None)?;
+    Ok(color)
+}
+
+fn listen(stream: TcpStream, id: usize, cb_sink: CbSink) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if let Some(message) = Message::parse(&line) {
+                let sent = cb_sink.send(Box::new(move |s| logic::peer_message(s, id, message)));
+                if sent.is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = cb_sink.send(Box::new(move |s| logic::peer_message(s, id, Message::Gone)));
+    });
+}
+
+fn start(siv: &mut Cursive, stream: TcpStream, human: Color) {
+    let id = link_id();
+    match stream.try_clone() {
+        Ok(reader) => listen(reader, id, siv.cb_sink().clone()),
+        Err(e) => {
+            siv.add_layer(Dialog::info(fill(
+                "Could not start the game: {}",
+                &[&e.to_string()],
+            )));
+            return;
+        }
+    }
+    let peer = Peer {
+        id,
+        link: Link::Lan(stream),
+        offered: false,
+        open: true,
+    };
+    let settings = settings::current(siv);
+    logic::open_game(siv, BoardView::against_peer(settings, peer, human));
+}
+
+// Connecting a UDP socket sends nothing, but picks the address other
+// machines would reach us on.
+fn lan_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).ok()?;
+    socket.connect(("192.0.2.1", 9)).ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+// Both sides wait with a dialog that can be cancelled. A result that comes
+// in after Cancel is dropped.
+fn waiting(siv: &mut Cursive, text: String) -> Arc<AtomicBool> {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let flag = cancelled.clone();
+    siv.add_layer(Dialog::text(text).button(tr("Cancel"), move |s| {
+        flag.store(true, Ordering::SeqCst);
+        s.pop_layer();
+    }));
+    cancelled
+}
+
+fn finish(cb_sink: &CbSink, cancelled: Arc<AtomicBool>, connected: io::Result<(TcpStream, Color)>) {
+    let _ = cb_sink.send(Box::new(move |s| {
+        if cancelled.load(Ordering::SeqCst) {
+            return;
+        }
+        s.pop_layer();
+        match connected {
+            Ok((stream, human)) => start(s, stream, human),
+            Err(e) => s.add_layer(Dialog::info(fill("No game: {}", &[&e.to_string()]))),
+        }
+    }));
+}
+
+fn host(siv: &mut Cursive, port: u16, human: Color) {
+    let listener = match TcpListener::bind(("0.0.0.0", port)).and_then(|listener| {
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    }) {
+        Ok(listener) => listener,
+        Err(e) => {
+            siv.add_layer(Dialog::info(fill(
+                "Could not listen on port {}: {}",
+                &[&port.to_string(), &e.to_string()],
+            )));
+            return;
+        }
+    };
+    let address = lan_address().map_or("this machine".to_owned(), |ip| ip.to_string());
+    let cancelled = waiting(
+        siv,
+        format!(
+            "Waiting for the other player to join {}:{}\nYou play {}.",
+            address,
+            port,
+            logic::color_name(human).to_lowercase()
+        ),
+    );
+    let cb_sink = siv.cb_sink().clone();
+    thread::spawn(move || {
+        let connected = loop {
+            if cancelled.load(Ordering::SeqCst) {
+                return;
+            }
+            match listener.accept() {
+                Ok((mut stream, _)) => {
+                    break stream
+                        .set_nonblocking(false)
+                        .and_then(|()| greet_joiner(&mut stream, !human))
+                        .map(|()| (stream, human));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL),
+                Err(e) => break Err(e),
+            }
+        };
+        finish(&cb_sink, cancelled, connected);
+    });
+}
+
+fn join(siv: &mut Cursive, address: String) {
+    let cancelled = waiting(siv, format!("Connecting to {}...", address));
+    let cb_sink = siv.cb_sink().clone();
+    thread::spawn(move || {
+        let connect = || -> io::Result<(TcpStream, Color)> {
+            let target = address
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such address"))?;
+            let mut stream = TcpStream::connect_timeout(&target, TIMEOUT)?;
+            let human = greet_host(&mut stream)?;
+            Ok((stream, human))
+        };
+        finish(&cb_sink, cancelled, connect());
+    });
+}
+
+// A bare host name or address joins on the default port.
+fn with_port(address: &str) -> String {
+    let address = address.trim();
+    if address
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok())
+    {
+        address.to_owned()
+    } else {
+        format!("{}:{}", address, DEFAULT_PORT)
+    }
+}
+
+pub fn show_setup(siv: &mut Cursive) {
+    let mut group = RadioGroup::new();
+    let mut colors = LinearLayout::vertical();
+    for (label, color) in [
+        ("White", Some(Color::White)),
+        ("Black", Some(Color::Black)),
+        ("Random", None),
+    ] {
+        colors.add_child(group.button(color, label));
+    }
+
+    let dialog = Dialog::new()
+        .title(tr("Network Game"))
+        .content(
+            LinearLayout::vertical()
+                .child(TextView::new("Host a game on this port:"))
+                .child(
+                    EditView::new()
+                        .content(DEFAULT_PORT.to_string())
+                        .with_name("net_port")
+                        .fixed_width(8),
+                )
+                .child(TextView::new("playing as:"))
+                .child(colors)
+                .child(TextView::new("\nor join one at host:port:"))
+                .child(EditView::new().with_name("net_address").fixed_width(30)),
+        )
+        .button(tr("Host"), move |s| {
+            let port = s
+                .call_on_name("net_port", |v: &mut EditView| v.get_content())
+                .unwrap();
+            let Ok(port) = port.trim().parse::<u16>() else {
+                s.add_layer(Dialog::info(fill("\"{}\" is not a port", &[port.trim()])));
+                return;
+            };
+            let human = (*group.selection()).unwrap_or_else(|| {
+                if rand::random() {
+                    Color::White
+                } else {
+                    Color::Black
+                }
+            });
+            s.pop_layer();
+            host(s, port, human);
+        })
+        .button(tr("Join"), |s| {
+            let address = s
+                .call_on_name("net_address", |v: &mut EditView| v.get_content())
+                .unwrap();
+            if address.trim().is_empty() {
+                s.add_layer(Dialog::info(tr(
+                    "Enter the host's address, as in 192.168.1.20:7531",
+                )));
+                return;
+            }
+            s.pop_layer();
+            join(s, with_port(&address));
+        });
+    #[cfg(feature = "online")]
+    let dialog = dialog.button(tr("Lichess"), |s| {
+        s.pop_layer();
+        online::show_setup(s);
+    });
+    siv.add_layer(dialog.dismiss_button(tr("Back")));
+}
@@ -0,0 +1,60 @@
+use shakmaty::{CastlingMode, Chess, Color, FromSetup, Setup, Square};
+
+pub struct Odds {
+    pub label: &'static str,
+    // Taken off the side giving odds, as seen from White.
+    removed: &'static [Square],
+    // The player moves first, even with Black.
+    tempo: bool,
+}
+
+pub const ODDS: [Odds; 6] = [
+    Odds {
+        label: "Pawn and move",
+        removed: &[Square::F2],
+        tempo: true,
+    },
+    Odds {
+        label: "Knight",
+        removed: &[Square::B1],
+        tempo: false,
+    },
+    Odds {
+        label: "Rook",
+        removed: &[Square::A1],
+        tempo: false,
+    },
+    Odds {
+        label: "Rook and knight",
+        removed: &[Square::A1, Square::B1],
+        tempo: false,
+    },
+    Odds {
+        label: "Queen",
+        removed: &[Square::D1],
+        tempo: false,
+    },
+    Odds {
+        label: "Queen and rook",
+        removed: &[Square::A1, Square::D1],
+        tempo: false,
+    },
+];
+
+// The starting position with the CPU's pieces taken off. A missing rook
+// takes its castling right with it.
+pub fn position(odds: &Odds, player: Color) -> Chess {
+    let mut setup = Setup::default();
+    for &sq in odds.removed {
+        let sq = match player {
+            Color::White => sq.flip_vertical(),
+            Color::Black => sq,
+        };
+        setup.board.discard_piece_at(sq);
+        setup.castling_rights.discard(sq);
+    }
+    if odds.tempo {
+        setup.turn = player;
+    }
+    Chess::from_setup(setup, CastlingMode::Standard).expect("odds positions are legal")
+}
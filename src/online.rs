@@ -0,0 +1,428 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::Child,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use cursive::{
+    view::Nameable,
+    views::{Dialog, LinearLayout, RadioGroup, SelectView, TextView},
+    CbSink, Cursive,
+};
+use shakmaty::{ByColor, Color};
+
+use crate::clock::Mode;
+use crate::lang::{fill, tr};
+use crate::lichess::{self, string_field};
+use crate::logic::{self, BoardView};
+use crate::net::{self, Message, Peer};
+use crate::settings;
+
+const API: &str = "https://lichess.org/api";
+// Seeks are rapid, the quickest the Board API lets a client play.
+const SEEK_MINUTES: u64 = 15;
+const SEEK_INCREMENT: u64 = 10;
+
+#[derive(Clone, Copy)]
+enum Ask {
+    Ai(u8, Color),
+    Seek,
+}
+
+type Children = Arc<Mutex<Vec<Child>>>;
+
+fn kill(children: &Children) {
+    for mut child in children.lock().unwrap().drain(..) {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+fn post(url: &str, form: &[(&str, String)]) -> Result<String, String> {
+    let fields: Vec<String> = form.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    let mut args = vec!["-sSf", "--max-time", "15", "-X", "POST"];
+    for field in &fields {
+        args.extend(["-d", field.as_str()]);
+    }
+    lichess::finish(lichess::curl(url, &args)?, url)
+}
+
+// A request that stays open, read a line at a time as Lichess sends them.
+fn stream(url: &str, form: &[(&str, String)]) -> Result<Child, String> {
+    let fields: Vec<String> = form.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    let mut args = vec!["-sSfN"];
+    for field in &fields {
+        args.extend(["-d", field.as_str()]);
+    }
+    lichess::curl(url, &args)
+}
+
+fn lines(child: &mut Child) -> impl Iterator<Item = String> {
+    let stdout = child.stdout.take().expect("stdout is piped");
+    BufReader::new(stdout)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty())
+}
+
+fn color_key(color: Color) -> &'static str {
+    match color {
+        Color::White => "white",
+        Color::Black => "black",
+    }
+}
+
+fn number_field(json: &str, key: &str) -> Option<u64> {
+    let start = json.find(&format!("\"{}\":", key))? + key.len() + 3;
+    let digits: String = json[start..]
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+// Every game the account is already in, so the event stream's news of
+// those is not taken for the one just asked for.
+fn playing() -> Result<Vec<String>, String> {
+    let json = lichess::fetch(&format!("{}/account/playing", API))?;
+    Ok(json
+        .split("\"gameId\":\"")
+        .skip(1)
+        .filter_map(|rest| rest.split('"').next())
+        .map(str::to_owned)
+        .collect())
+}
+
+// The game the challenge or the seek started, with our side in it.
+fn begin(ask: Ask, children: &Children, cancelled: &AtomicBool) -> Result<(String, Color), String> {
+    if let Ask::Ai(level, color) = ask {
+        let reply = post(
+            &format!("{}/challenge/ai", API),
+            &[
+                ("level", level.to_string()),
+                ("color", color_key(color).to_owned()),
+            ],
+        )?;
+        let id = string_field(&reply, "id").ok_or("unexpected reply from Lichess")?;
+        return Ok((id, color));
+    }
+
+    let known = playing()?;
+    let mut events = stream(&format!("{}/stream/event", API), &[])?;
+    let started = lines(&mut events);
+    children.lock().unwrap().push(events);
+    // The seek is open for as long as its request is, and ends with it.
+    let seek = stream(
+        &format!("{}/board/seek", API),
+        &[
+            ("rated", "false".to_owned()),
+            ("time", SEEK_MINUTES.to_string()),
+            ("increment", SEEK_INCREMENT.to_string()),
+        ],
+    )?;
+    children.lock().unwrap().push(seek);
+    for line in started {
+        if string_field(&line, "type").as_deref() != Some("gameStart") {
+            continue;
+        }
+        let Some(id) = string_field(&line, "gameId").filter(|id| !known.contains(id)) else {
+            continue;
+        };
+        let human = match string_field(&line, "color").as_deref() {
+            Some("black") => Color::Black,
+            _ => Color::White,
+        };
+        return Ok((id, human));
+    }
+    if cancelled.load(Ordering::SeqCst) {
+        return Err("cancelled".to_owned());
+    }
+    Err("Lichess closed the event stream".to_owned())
+}
+
+// A game on Lichess. Our moves and offers go out one request at a time, in
+// order, from a thread of their own; the game's stream comes in on another.
+pub struct Game {
+    outbox: Sender<String>,
+    stream: Children,
+}
+
+impl Game {
+    fn new(id: &str, cb_sink: CbSink) -> Game {
+        let (outbox, actions) = mpsc::channel::<String>();
+        let game = format!("{}/board/game/{}", API, id);
+        thread::spawn(move || {
+            for action in actions {
+                if let Err(e) = post(&format!("{}/{}", game, action), &[]) {
+                    let _ = cb_sink.send(Box::new(move |s| {
+                        s.add_layer(Dialog::info(fill("Lichess turned that down: {}", &[&e])))
+                    }));
+                }
+            }
+        });
+        Game {
+            outbox,
+            stream: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn send(&self, message: &Message) {
+        let action = match message {
+            Message::Move(uci) => format!("move/{}", uci),
+            Message::Resign => "resign".to_owned(),
+            Message::Draw => "draw/yes".to_owned(),
+            Message::Decline => "draw/no".to_owned(),
+            _ => return,
+        };
+        let _ = self.outbox.send(action);
+    }
+
+    // Stops following the game. It goes on on Lichess until it ends there.
+    pub fn hang_up(&self) {
+        kill(&self.stream);
+    }
+}
+
+// Reads the game's stream the way a network peer would be heard: new moves
+// by the other side, its draw offers and answers to ours, the clocks and,
+// when it comes, how the game ended on the server.
+struct Follow {
+    human: Color,
+    seen: usize,
+    theirs: bool,
+    ours: bool,
+    over: bool,
+}
+
+impl Follow {
+    fn read(&mut self, line: &str) -> Vec<Message> {
+        let state = match string_field(line, "type").as_deref() {
+            Some("gameFull") => match line.find("\"state\":") {
+                Some(i) => &line[i..],
+                None => return Vec::new(),
+            },
+            Some("gameState") => line,
+            _ => return Vec::new(),
+        };
+
+        let mut heard = Vec::new();
+        let moves = string_field(state, "moves").unwrap_or_default();
+        let moves: Vec<&str> = moves.split_whitespace().collect();
+        let mut moved = false;
+        for (i, uci) in moves.iter().enumerate().skip(self.seen) {
+            let mover = if i % 2 == 0 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            if mover != self.human {
+                heard.push(Message::Move((*uci).to_owned()));
+                moved = true;
+            }
+        }
+        self.seen = self.seen.max(moves.len());
+
+        if let (Some(white), Some(black)) =
+            (number_field(state, "wtime"), number_field(state, "btime"))
+        {
+            heard.push(Message::Clock(ByColor {
+                white: Duration::from_millis(white),
+                black: Duration::from_millis(black),
+            }));
+        }
+
+        let offers = |color| state.contains(&format!("\"{}draw\":true", &color_key(color)[..1]));
+        let (theirs, ours) = (offers(!self.human), offers(self.human));
+        let winner = match string_field(state, "winner").as_deref() {
+            Some("white") => Some(Color::White),
+            Some("black") => Some(Color::Black),
+            _ => None,
+        };
+        match string_field(state, "status")
+            .as_deref()
+            .unwrap_or("started")
+        {
+            "created" | "started" => {
+                if theirs && !self.theirs {
+                    heard.push(Message::Draw);
+                }
+                if self.ours && !ours && !moved {
+                    heard.push(Message::Decline);
+                }
+            }
+            // The board sees these for itself after the last move.
+            "mate" | "stalemate" | "variantEnd" => self.over = true,
+            "resign" if winner == Some(self.human) => {
+                heard.push(Message::Resign);
+                self.over = true;
+            }
+            status => {
+                heard.push(Message::Over(winner, reason(status)));
+                self.over = true;
+            }
+        }
+        self.theirs = theirs;
+        self.ours = ours;
+        heard
+    }
+}
+
+fn reason(status: &str) -> &'static str {
+    match status {
+        "draw" => "a draw was agreed",
+        "resign" => "a player resigned",
+        "outoftime" => "a flag fell",
+        "timeout" => "a player left",
+        "aborted" => "the game was aborted",
+        "noStart" => "the game never started",
+        _ => "the game was stopped",
+    }
+}
+
+fn follow(
+    incoming: impl Iterator<Item = String> + Send + 'static,
+    id: usize,
+    human: Color,
+    cb_sink: CbSink,
+) {
+    thread::spawn(move || {
+        let mut follow = Follow {
+            human,
+            seen: 0,
+            theirs: false,
+            ours: false,
+            over: false,
+        };
+        for line in incoming {
+            for message in follow.read(&line) {
+                let sent = cb_sink.send(Box::new(move |s| logic::peer_message(s, id, message)));
+                if sent.is_err() {
+                    return;
+                }
+            }
+            if follow.over {
+                return;
+            }
+        }
+        let _ = cb_sink.send(Box::new(move |s| logic::peer_message(s, id, Message::Gone)));
+    });
+}
+
+fn open(siv: &mut Cursive, id: &str, human: Color, timed: bool) -> Result<(), String> {
+    let link = net::link_id();
+    let game = Game::new(id, siv.cb_sink().clone());
+    let mut child = stream(&format!("{}/board/game/stream/{}", API, id), &[])?;
+    follow(lines(&mut child), link, human, siv.cb_sink().clone());
+    game.stream.lock().unwrap().push(child);
+
+    let mut settings = settings::current(siv);
+    settings.clock_mode = Mode::Fischer;
+    let mut view = BoardView::against_peer(settings, Peer::lichess(link, game), human);
+    if timed {
+        view.start_clock(Some((
+            ByColor {
+                white: SEEK_MINUTES,
+                black: SEEK_MINUTES,
+            },
+            SEEK_INCREMENT,
+        )));
+    }
+    logic::open_game(siv, view);
+    Ok(())
+}
+
+fn start(siv: &mut Cursive, ask: Ask) {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let children: Children = Arc::new(Mutex::new(Vec::new()));
+    let (flag, waiting) = (cancelled.clone(), children.clone());
+    let text = match ask {
+        Ask::Ai(..) => tr("Challenging the Lichess AI..."),
+        Ask::Seek => tr("Looking for an opponent on Lichess..."),
+    };
+    siv.add_layer(Dialog::text(text).button(tr("Cancel"), move |s| {
+        flag.store(true, Ordering::SeqCst);
+        kill(&waiting);
+        s.pop_layer();
+    }));
+    let cb_sink = siv.cb_sink().clone();
+    thread::spawn(move || {
+        let begun = begin(ask, &children, &cancelled);
+        kill(&children);
+        let _ = cb_sink.send(Box::new(move |s| {
+            if cancelled.load(Ordering::SeqCst) {
+                return;
+            }
+            s.pop_layer();
+            let opened =
+                begun.and_then(|(id, human)| open(s, &id, human, matches!(ask, Ask::Seek)));
+            if let Err(e) = opened {
+                s.add_layer(Dialog::info(fill("No game on Lichess: {}", &[&e])));
+            }
+        }));
+    });
+}
+
+// Needs a personal token with the board:play scope in LICHESS_TOKEN.
+pub fn show_setup(siv: &mut Cursive) {
+    if std::env::var_os("LICHESS_TOKEN").is_none() {
+        return siv.add_layer(Dialog::info(tr(
+            "Set LICHESS_TOKEN to a Lichess token with the board:play scope.",
+        )));
+    }
+    let mut group = RadioGroup::new();
+    let mut colors = LinearLayout::vertical();
+    for (label, color) in [
+        ("White", Some(Color::White)),
+        ("Black", Some(Color::Black)),
+        ("Random", None),
+    ] {
+        colors.add_child(group.button(color, tr(label)));
+    }
+    let mut levels = SelectView::new().popup();
+    for level in 1..=8u8 {
+        levels.add_item(level.to_string(), level);
+    }
+    levels.set_selection(2);
+
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Lichess"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new(tr("Play the Lichess AI at level:")))
+                    .child(levels.with_name("lichess_level"))
+                    .child(TextView::new(tr("as:")))
+                    .child(colors)
+                    .child(TextView::new(fill(
+                        "\nor seek a casual {}+{} game against a person.",
+                        &[&SEEK_MINUTES.to_string(), &SEEK_INCREMENT.to_string()],
+                    ))),
+            )
+            .button(tr("Play AI"), move |s| {
+                let level = s
+                    .call_on_name("lichess_level", |v: &mut SelectView<u8>| v.selection())
+                    .flatten()
+                    .map_or(3, |level| *level);
+                let human = (*group.selection()).unwrap_or_else(|| {
+                    if rand::random() {
+                        Color::White
+                    } else {
+                        Color::Black
+                    }
+                });
+                s.pop_layer();
+                start(s, Ask::Ai(level, human));
+            })
+            .button(tr("Seek"), |s| {
+                s.pop_layer();
+                start(s, Ask::Seek);
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
@@ -0,0 +1,546 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use cursive::{
+    view::{Nameable, Resizable, Scrollable},
+    views::{Dialog, EditView, LinearLayout, TextView},
+    Cursive,
+};
+use shakmaty::{
+    fen::Fen,
+    san::SanPlus,
+    variant::{Variant, VariantPosition},
+    Color, EnPassantMode, Move, Position,
+};
+
+use crate::clock::Stamp;
+use crate::lang::tr;
+use crate::logic::{color_name, parse_variant_fen, BoardView};
+use crate::picture;
+
+const LINE_WIDTH: usize = 79;
+
+// Days since the epoch to a proleptic Gregorian date, after Howard Hinnant's
+// civil_from_days.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+pub fn variant_name(board: &BoardView) -> Option<&'static str> {
+    match board.variant() {
+        Variant::Chess if board.position().castles().mode().is_chess960() => Some("Chess960"),
+        Variant::Chess => None,
+        Variant::Atomic => Some("Atomic"),
+        Variant::KingOfTheHill => Some("King of the Hill"),
+        Variant::RacingKings => Some("Racing Kings"),
+        Variant::Antichess => Some("Antichess"),
+        Variant::Crazyhouse => Some("Crazyhouse"),
+        Variant::Horde => Some("Horde"),
+        Variant::ThreeCheck => Some("Three-check"),
+    }
+}
+
+// The move annotations PGN numbers $1 to $6.
+pub const NAGS: [(u8, &str, &str); 6] = [
+    (1, "!", "good move"),
+    (2, "?", "mistake"),
+    (3, "!!", "brilliant move"),
+    (4, "??", "blunder"),
+    (5, "!?", "interesting move"),
+    (6, "?!", "dubious move"),
+];
+
+// What a player wrote about a move.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Note {
+    pub nag: Option<u8>,
+    pub comment: String,
+}
+
+impl Note {
+    pub fn symbol(&self) -> &'static str {
+        self.nag
+            .and_then(|nag| NAGS.iter().find(|&&(n, _, _)| n == nag))
+            .map_or("", |&(_, symbol, _)| symbol)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nag.is_none() && self.comment.is_empty()
+    }
+
+    // "! {Best by test}" after the move in the move list, or
+    // "$1 {Best by test}" in PGN.
+    pub fn text(&self, pgn: bool) -> String {
+        let mut text = match self.nag {
+            Some(nag) if pgn => format!(" ${}", nag),
+            _ => self.symbol().to_owned(),
+        };
+        if !self.comment.is_empty() {
+            text += &format!(" {{{}}}", self.comment);
+        }
+        text
+    }
+}
+
+// Braces would end the comment early in PGN.
+pub fn clean_comment(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace(['{', '}'], "")
+}
+
+#[derive(Clone)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub positions: Vec<VariantPosition>,
+    pub moves: Vec<Move>,
+    pub sans: Vec<String>,
+    // By the index of the move they follow.
+    pub notes: BTreeMap<usize, Note>,
+    pub times: BTreeMap<usize, Stamp>,
+}
+
+impl Game {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((name.to_owned(), value.replace("\\\"", "\"")))
+}
+
+fn parse_variant(name: &str) -> Result<Variant, String> {
+    match name.to_lowercase().as_str() {
+        "standard" | "chess" | "chess960" | "fischerandom" | "from position" => Ok(Variant::Chess),
+        "atomic" => Ok(Variant::Atomic),
+        "king of the hill" => Ok(Variant::KingOfTheHill),
+        "racing kings" => Ok(Variant::RacingKings),
+        "antichess" => Ok(Variant::Antichess),
+        "crazyhouse" => Ok(Variant::Crazyhouse),
+        "horde" => Ok(Variant::Horde),
+        "three-check" => Ok(Variant::ThreeCheck),
+        _ => Err(format!("unsupported variant {}", name)),
+    }
+}
+
+// Strips variations, move numbers and the result, leaving the main line's
+// SAN tokens, its NAGs and its comments, each whole and in braces.
+fn movetext_tokens(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let (mut comment, mut depth) = (false, 0);
+    let mut rest_of_line = false;
+
+    for c in text.chars().chain([' ']) {
+        match c {
+            '\n' if rest_of_line => rest_of_line = false,
+            _ if rest_of_line => {}
+            '}' if comment => {
+                comment = false;
+                let text = clean_comment(&std::mem::take(&mut token));
+                if depth == 0 && !text.is_empty() {
+                    tokens.push(format!("{{{}}}", text));
+                }
+            }
+            _ if comment => token.push(c),
+            '{' => {
+                tokens.push(std::mem::take(&mut token));
+                comment = true;
+            }
+            ';' => rest_of_line = true,
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ if depth > 0 => {}
+            c if c.is_whitespace() => {
+                tokens.push(std::mem::take(&mut token));
+            }
+            c => token.push(c),
+        }
+    }
+
+    tokens
+        .into_iter()
+        .map(|token| {
+            let start = match token.starts_with('{') {
+                true => 0,
+                false => token.rfind('.').map_or(0, |i| i + 1),
+            };
+            token[start..].to_owned()
+        })
+        .filter(|token| {
+            !token.is_empty() && !["1-0", "0-1", "1/2-1/2", "*"].contains(&token.as_str())
+        })
+        .collect()
+}
+
+// The note on the last move read, if there is one yet; a comment before the
+// first move has nowhere to go.
+fn note_on(game: &mut Game) -> Option<&mut Note> {
+    let last = game.moves.len().checked_sub(1)?;
+    Some(game.notes.entry(last).or_default())
+}
+
+pub fn parse(text: &str) -> Result<Game, String> {
+    let mut tags = Vec::new();
+    let mut movetext = String::new();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            // A second header block starts the next game; only the first is read.
+            if !movetext.trim().is_empty() {
+                break;
+            }
+            tags.extend(parse_tag(line));
+        } else if !line.starts_with('%') {
+            movetext += line;
+            movetext.push('\n');
+        }
+    }
+
+    let mut game = Game {
+        tags,
+        positions: Vec::new(),
+        moves: Vec::new(),
+        sans: Vec::new(),
+        notes: BTreeMap::new(),
+        times: BTreeMap::new(),
+    };
+    let mut clocks = BTreeMap::new();
+    let variant = game
+        .tag("Variant")
+        .map_or(Ok(Variant::Chess), parse_variant)?;
+    let start = match game.tag("FEN") {
+        Some(fen) => parse_variant_fen(variant, fen)?,
+        None => VariantPosition::new(variant),
+    };
+
+    game.positions.push(start);
+    for token in movetext_tokens(&movetext) {
+        if let Some(comment) = token.strip_prefix('{') {
+            let (comment, emt, clk) = take_times(comment.trim_end_matches('}'));
+            if let Some(last) = game.moves.len().checked_sub(1) {
+                let (spent, left) = clocks.entry(last).or_insert((None, None));
+                *spent = emt.or(*spent);
+                *left = clk.or(*left);
+            }
+            if comment.is_empty() {
+                continue;
+            }
+            if let Some(note) = note_on(&mut game) {
+                if !note.comment.is_empty() {
+                    note.comment.push(' ');
+                }
+                note.comment += &comment;
+            }
+            continue;
+        }
+        if let Some(nag) = token.strip_prefix('$') {
+            let nag = nag.parse().ok().filter(|n| (1..=6).contains(n));
+            if let (Some(nag), Some(note)) = (nag, note_on(&mut game)) {
+                note.nag = Some(nag);
+            }
+            continue;
+        }
+        let symbol = token.trim_start_matches(|c| c != '!' && c != '?');
+        let nag = NAGS
+            .iter()
+            .find(|&&(_, s, _)| s == symbol)
+            .map(|&(n, _, _)| n);
+        let token = &token[..token.len() - symbol.len()];
+
+        let pos = game.positions.last().unwrap();
+        let mv = SanPlus::from_ascii(token.as_bytes())
+            .ok()
+            .and_then(|san| san.san.to_move(pos).ok())
+            .ok_or_else(|| {
+                let ply = game.sans.len();
+                format!("illegal move {} at ply {}", token, ply + 1)
+            })?;
+        let mut next = pos.clone();
+        game.sans
+            .push(SanPlus::from_move(pos.clone(), &mv).to_string());
+        next.play_unchecked(&mv);
+        game.positions.push(next);
+        game.moves.push(mv);
+        if let (Some(nag), Some(note)) = (nag, note_on(&mut game)) {
+            note.nag = Some(nag);
+        }
+    }
+    game.notes.retain(|_, note| !note.is_empty());
+    game.times = stamps(&clocks, game.tag("TimeControl"));
+
+    Ok(game)
+}
+
+// "0:09:57" or "9:57.3".
+fn parse_time(text: &str) -> Option<Duration> {
+    let secs = text.split(':').try_fold(0.0, |secs, part| {
+        part.trim().parse::<f64>().ok().map(|n| secs * 60.0 + n)
+    })?;
+    (secs.is_finite() && secs >= 0.0).then(|| Duration::from_secs_f64(secs))
+}
+
+// Takes the [%emt] and [%clk] commands out of a comment, leaving the rest.
+fn take_times(comment: &str) -> (String, Option<Duration>, Option<Duration>) {
+    let (mut rest, mut emt, mut clk) = (String::new(), None, None);
+    let mut text = comment;
+    while let Some(start) = text.find("[%") {
+        let Some(len) = text[start..].find(']') else {
+            break;
+        };
+        let command = &text[start + 2..start + len];
+        rest += &text[..start];
+        match command.split_once(' ') {
+            Some(("emt", time)) => emt = parse_time(time),
+            Some(("clk", time)) => clk = parse_time(time),
+            _ => rest += &text[start..=start + len],
+        }
+        text = &text[start + len + 1..];
+    }
+    rest += text;
+    (clean_comment(&rest), emt, clk)
+}
+
+// Where a game gives only the clock readings, each move's time is the
+// drop from the mover's last reading, with the increment of a
+// "300+3" TimeControl tag put back.
+fn stamps(
+    clocks: &BTreeMap<usize, (Option<Duration>, Option<Duration>)>,
+    time_control: Option<&str>,
+) -> BTreeMap<usize, Stamp> {
+    let control = time_control.and_then(|tc| {
+        let (base, increment) = tc.split_once('+').unwrap_or((tc, "0"));
+        Some((parse_time(base)?, parse_time(increment)?))
+    });
+    clocks
+        .iter()
+        .filter_map(|(&i, &(spent, left))| {
+            let before = match i.checked_sub(2) {
+                Some(last) => clocks.get(&last).and_then(|&(_, left)| left),
+                None => control.map(|(base, _)| base),
+            };
+            let increment = control.map_or(Duration::ZERO, |(_, increment)| increment);
+            let spent = spent.or_else(|| Some((before? + increment).saturating_sub(left?)))?;
+            Some((i, Stamp { spent, left }))
+        })
+        .collect()
+}
+
+fn hms(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+// The comment that carries a move's time in PGN.
+pub fn time_comment(stamp: &Stamp) -> String {
+    let clk = stamp
+        .left
+        .map_or(String::new(), |left| format!("[%clk {}] ", hms(left)));
+    format!(" {{{}[%emt {}]}}", clk, hms(stamp.spent))
+}
+
+// The text of each game, from its tags to the end of its movetext.
+pub fn split_games(text: &str) -> Vec<String> {
+    let mut chunks = vec![String::new()];
+    let mut in_movetext = false;
+    for line in text.lines() {
+        let header = line.trim().starts_with('[');
+        if header && in_movetext {
+            chunks.push(String::new());
+        }
+        in_movetext = !header && (in_movetext || !line.trim().is_empty());
+        let chunk = chunks.last_mut().unwrap();
+        *chunk += line;
+        chunk.push('\n');
+    }
+    chunks.retain(|chunk| !chunk.trim().is_empty());
+    chunks
+}
+
+pub fn parse_all(text: &str) -> Result<Vec<Game>, String> {
+    split_games(text)
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| parse(chunk).map_err(|e| format!("game {}: {}", i + 1, e)))
+        .collect()
+}
+
+fn wrap(tokens: impl Iterator<Item = String>) -> String {
+    let mut text = String::new();
+    let mut line = 0;
+    for token in tokens {
+        if line > 0 && line + 1 + token.len() > LINE_WIDTH {
+            text.push('\n');
+            line = 0;
+        } else if line > 0 {
+            text.push(' ');
+            line += 1;
+        }
+        line += token.len();
+        text += &token;
+    }
+    text
+}
+
+pub fn render(board: &BoardView) -> String {
+    render_result(board, board.result())
+}
+
+// Resignations and agreed draws end a game the position cannot show.
+pub fn render_result(board: &BoardView, result: &str) -> String {
+    let player = |color: Color| match board.cpu() {
+        Some(cpu) if cpu == color => "CPU".to_owned(),
+        _ => format!("{} player", color_name(color)),
+    };
+
+    let mut tags = seven_tags(
+        "Casual game",
+        player(Color::White),
+        player(Color::Black),
+        result,
+    );
+    if let Some(variant) = variant_name(board) {
+        tags.push(("Variant", variant.to_owned()));
+    }
+    let start = board.start_fen();
+    let standard = VariantPosition::new(board.variant());
+    if start != Fen::from_position(standard, EnPassantMode::Legal).to_string() {
+        tags.push(("SetUp", "1".to_owned()));
+        tags.push(("FEN", start));
+    }
+
+    write(tags, &board.movetext(), result)
+}
+
+pub fn seven_tags(
+    event: &str,
+    white: String,
+    black: String,
+    result: &str,
+) -> Vec<(&'static str, String)> {
+    vec![
+        ("Event", event.to_owned()),
+        ("Site", "terminal_chess_app".to_owned()),
+        ("Date", today()),
+        ("Round", "-".to_owned()),
+        ("White", white),
+        ("Black", black),
+        ("Result", result.to_owned()),
+    ]
+}
+
+// Numbers the moves from where the game began, so a game that starts with
+// Black to move reads "12... Nf6 13. c4".
+pub fn numbered(start: &impl Position, sans: &[String]) -> String {
+    let first = start.fullmoves().get() as usize;
+    let black = start.turn() == Color::Black;
+    let mut tokens = Vec::new();
+    for (i, san) in sans.iter().enumerate() {
+        let ply = i + usize::from(black);
+        if ply % 2 == 0 {
+            tokens.push(format!("{}. {}", first + ply / 2, san));
+        } else if i == 0 {
+            tokens.push(format!("{}... {}", first, san));
+        } else {
+            tokens.push(san.clone());
+        }
+    }
+    tokens.join(" ")
+}
+
+pub fn write(tags: Vec<(&str, String)>, movetext: &str, result: &str) -> String {
+    let mut pgn: String = tags
+        .into_iter()
+        .map(|(tag, value)| format!("[{} \"{}\"]\n", tag, value.replace('"', "\\\"")))
+        .collect();
+    pgn.push('\n');
+
+    let tokens = movetext
+        .split_whitespace()
+        .map(str::to_owned)
+        .chain([result.to_owned()]);
+    pgn += &wrap(tokens);
+    pgn.push('\n');
+    pgn
+}
+
+// The PGN goes to the path as typed; pictures of the position go next to it
+// under their own extension.
+fn write_export(siv: &mut Cursive, extension: Option<&str>, contents: &str) {
+    let typed = siv
+        .call_on_name("pgn_path", |v: &mut EditView| v.get_content())
+        .unwrap();
+    let mut path = PathBuf::from(typed.trim());
+    if let Some(extension) = extension {
+        path.set_extension(extension);
+    }
+    let msg = match fs::write(&path, contents) {
+        Ok(()) => format!("Wrote {}.", path.display()),
+        Err(e) => format!("Could not write {}: {}", path.display(), e),
+    };
+    siv.add_layer(Dialog::info(msg));
+}
+
+pub fn show_export(siv: &mut Cursive) {
+    let exported = siv.call_on_name("board", |board: &mut BoardView| {
+        let (pieces, facing, last) = (
+            board.position().board(),
+            board.orientation(),
+            board.last_move(),
+        );
+        (
+            render(board),
+            picture::svg(pieces, facing, last),
+            picture::ansi(pieces, facing, last),
+        )
+    });
+    let Some((pgn, svg, ansi)) = exported else {
+        return;
+    };
+
+    let contents = pgn.clone();
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Export PGN"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new(pgn).scrollable().max_height(15))
+                    .child(TextView::new("\nWrite to file:"))
+                    .child(
+                        EditView::new()
+                            .content("game.pgn")
+                            .with_name("pgn_path")
+                            .fixed_width(40),
+                    ),
+            )
+            .button(tr("Write File"), move |s| write_export(s, None, &contents))
+            .button(tr("Write SVG"), move |s| write_export(s, Some("svg"), &svg))
+            .button(tr("Write ANSI"), move |s| {
+                write_export(s, Some("ans"), &ansi)
+            })
+            .dismiss_button(tr("Close")),
+    );
+}
@@ -0,0 +1,105 @@
+use shakmaty::{Board, Color, File, Move, Rank, Square};
+
+use crate::logic::piece_to_char;
+use crate::settings::PieceStyle;
+
+const SQUARE: usize = 45;
+const LIGHT: (u8, u8, u8) = (240, 217, 181);
+const DARK: (u8, u8, u8) = (181, 136, 99);
+const MOVED: (u8, u8, u8) = (205, 210, 106);
+
+// The squares from the top left, as seen from `orientation`'s side.
+fn squares(orientation: Color) -> impl Iterator<Item = (usize, usize, Square)> {
+    (0..8).flat_map(move |row| {
+        (0..8).map(move |col| {
+            let (file, rank) = match orientation {
+                Color::White => (col, 7 - row),
+                Color::Black => (7 - col, row),
+            };
+            let sq = Square::from_coords(File::new(file as u32), Rank::new(rank as u32));
+            (row, col, sq)
+        })
+    })
+}
+
+fn shade(sq: Square, last_move: Option<&Move>) -> (u8, u8, u8) {
+    if last_move.is_some_and(|mv| mv.from() == Some(sq) || mv.to() == sq) {
+        MOVED
+    } else if sq.is_light() {
+        LIGHT
+    } else {
+        DARK
+    }
+}
+
+// A standalone SVG. The pieces are the filled chess symbols, so the viewer
+// needs a font that has them.
+pub fn svg(board: &Board, orientation: Color, last_move: Option<&Move>) -> String {
+    let size = SQUARE * 8;
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {0} {0}\">\n",
+        size
+    );
+    for (row, col, sq) in squares(orientation) {
+        let (r, g, b) = shade(sq, last_move);
+        out += &format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{2}\" height=\"{2}\" fill=\"#{3:02x}{4:02x}{5:02x}\"/>\n",
+            col * SQUARE,
+            row * SQUARE,
+            SQUARE,
+            r,
+            g,
+            b
+        );
+        if let Some(piece) = board.piece_at(sq) {
+            let (fill, stroke) = match piece.color {
+                Color::White => ("#ffffff", "#000000"),
+                Color::Black => ("#000000", "#000000"),
+            };
+            out += &format!(
+                "<text x=\"{}\" y=\"{}\" font-size=\"{}\" text-anchor=\"middle\" fill=\"{}\" stroke=\"{}\">{}</text>\n",
+                col * SQUARE + SQUARE / 2,
+                row * SQUARE + SQUARE * 4 / 5,
+                SQUARE * 4 / 5,
+                fill,
+                stroke,
+                piece_to_char(piece, PieceStyle::Solid)
+            );
+        }
+    }
+    out + "</svg>\n"
+}
+
+// Coloured with 24-bit escapes, two columns a square, with the files and
+// ranks along the edges.
+pub fn ansi(board: &Board, orientation: Color, last_move: Option<&Move>) -> String {
+    let mut out = String::new();
+    for (row, col, sq) in squares(orientation) {
+        if col == 0 {
+            out += &format!("{} ", sq.rank().char());
+        }
+        let (r, g, b) = shade(sq, last_move);
+        out += &format!("\x1b[48;2;{};{};{}m", r, g, b);
+        match board.piece_at(sq) {
+            Some(piece) => {
+                let fg = match piece.color {
+                    Color::White => "\x1b[1;97m",
+                    Color::Black => "\x1b[1;30m",
+                };
+                out += &format!("{}{} ", fg, piece_to_char(piece, PieceStyle::Solid));
+            }
+            None => out += "  ",
+        }
+        if col == 7 {
+            out += "\x1b[0m\n";
+        }
+        if row == 7 && col == 7 {
+            out.push_str("  ");
+            for (_, _, sq) in squares(orientation).skip(56) {
+                out += &format!("{} ", sq.file().char());
+            }
+            out.push('\n');
+        }
+    }
+    out
+}
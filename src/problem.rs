@@ -0,0 +1,307 @@
+use std::{fs, thread};
+
+use cursive::{
+    view::{Nameable, Resizable},
+    views::{Dialog, EditView, LinearLayout, RadioGroup, TextView},
+    Cursive,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use shakmaty::{
+    san::SanPlus, Bitboard, Board, CastlingMode, Chess, Color, File, FromSetup, Move, Position,
+    Role, Setup, Square,
+};
+
+use crate::epd::Epd;
+use crate::lang::{fill, tr};
+use crate::logic::{self, open_problem};
+
+// Random positions tried before giving up on composing one.
+const TRIES: u32 = 4000;
+const ATTACKERS: [Role; 4] = [Role::Queen, Role::Rook, Role::Bishop, Role::Knight];
+const DEFENDERS: [Role; 3] = [Role::Pawn, Role::Knight, Role::Bishop];
+
+// The side to move mates in `moves` against any defence.
+#[derive(Clone)]
+pub struct Problem {
+    pub id: Option<String>,
+    pub start: Chess,
+    pub moves: u32,
+}
+
+impl Problem {
+    pub fn title(&self) -> String {
+        match &self.id {
+            Some(id) => format!("Problem {}: mate in {}", id, self.moves),
+            None => format!("Mate in {}", self.moves),
+        }
+    }
+}
+
+// The side to move mates within `n` of its moves, whatever the defence.
+pub fn mates(pos: &Chess, n: u32) -> bool {
+    n > 0
+        && pos
+            .legal_moves()
+            .iter()
+            .any(|mv| forced(&after(pos, mv), n - 1))
+}
+
+// The defender is to move and is mated within `n` more attacking moves.
+// Stalemate is the defender's escape.
+pub fn forced(pos: &Chess, n: u32) -> bool {
+    if pos.is_checkmate() {
+        return true;
+    }
+    let replies = pos.legal_moves();
+    n > 0 && !replies.is_empty() && replies.iter().all(|r| mates(&after(pos, r), n))
+}
+
+fn after(pos: &Chess, mv: &Move) -> Chess {
+    let mut pos = pos.clone();
+    pos.play_unchecked(mv);
+    pos
+}
+
+// Every first move that forces mate in `n`.
+pub fn keys(pos: &Chess, n: u32) -> Vec<Move> {
+    pos.legal_moves()
+        .into_iter()
+        .filter(|mv| forced(&after(pos, mv), n - 1))
+        .collect()
+}
+
+// The reply that puts off mate the longest, after a move that forces it
+// within `n`.
+pub fn defence(pos: &Chess, n: u32) -> Option<Move> {
+    let held = |r: &Move| {
+        let pos = after(pos, r);
+        (1..=n).find(|&k| mates(&pos, k)).unwrap_or(n + 1)
+    };
+    pos.legal_moves().into_iter().max_by_key(held)
+}
+
+// A reply after which there is no mate within `n`, when the try allows one.
+pub fn refutation(pos: &Chess, n: u32) -> Option<Move> {
+    pos.legal_moves()
+        .into_iter()
+        .find(|r| !mates(&after(pos, r), n))
+}
+
+// One EPD record per line, each with a "dm" (direct mate) operation.
+pub fn load(text: &str) -> Result<Vec<Problem>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            let epd = Epd::parse(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            let moves = epd
+                .mate
+                .filter(|&n| n > 0)
+                .ok_or_else(|| format!("line {}: no \"dm\" mate count", i + 1))?;
+            Ok(Problem {
+                id: epd.id,
+                start: epd.position,
+                moves,
+            })
+        })
+        .collect()
+}
+
+fn random_position(rng: &mut StdRng) -> Option<Chess> {
+    let mut board = Board::empty();
+    let mut free: Vec<Square> = Square::ALL.to_vec();
+    free.shuffle(rng);
+    // A king on the edge is easier to mate.
+    let edge = free.iter().position(|sq| {
+        Bitboard::BACKRANKS.contains(*sq) || matches!(sq.file(), File::A | File::H)
+    })?;
+    board.set_piece_at(free.swap_remove(edge), Role::King.of(Color::Black));
+    board.set_piece_at(free.pop()?, Role::King.of(Color::White));
+    for _ in 0..rng.gen_range(2..=3) {
+        let role = *ATTACKERS.choose(rng)?;
+        board.set_piece_at(free.pop()?, role.of(Color::White));
+    }
+    for _ in 0..rng.gen_range(0..=2) {
+        let role = *DEFENDERS.choose(rng)?;
+        let sq = free.pop()?;
+        if role == Role::Pawn && Bitboard::BACKRANKS.contains(sq) {
+            continue;
+        }
+        board.set_piece_at(sq, role.of(Color::Black));
+    }
+    let setup = Setup {
+        board,
+        ..Setup::empty()
+    };
+    let pos = Chess::from_setup(setup, CastlingMode::Standard).ok()?;
+    (!pos.is_check()).then_some(pos)
+}
+
+// A position with a single key that mates in `moves` and no quicker mate,
+// as compositions have.
+pub fn compose(moves: u32, seed: u64) -> Option<Problem> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..TRIES).find_map(|_| {
+        let pos = random_position(&mut rng)?;
+        (!mates(&pos, moves - 1) && keys(&pos, moves).len() == 1).then_some(Problem {
+            id: None,
+            start: pos,
+            moves,
+        })
+    })
+}
+
+// The problems from a file, or ones composed one after another.
+#[derive(Clone)]
+pub struct Problems {
+    list: Vec<Problem>,
+    current: usize,
+    composing: Option<u32>,
+}
+
+impl Problems {
+    pub fn current(&self) -> Option<&Problem> {
+        self.list.get(self.current)
+    }
+}
+
+pub fn solution_text(problem: &Problem) -> String {
+    let keys: Vec<String> = keys(&problem.start, problem.moves)
+        .iter()
+        .map(|mv| SanPlus::from_move(problem.start.clone(), mv).to_string())
+        .collect();
+    if keys.is_empty() {
+        format!("There is no mate in {} here.", problem.moves)
+    } else {
+        format!("Key: {}", keys.join(", "))
+    }
+}
+
+pub fn next(siv: &mut Cursive, mut problems: Problems) {
+    problems.current += 1;
+    match problems.composing {
+        _ if problems.current().is_some() => start(siv, problems),
+        Some(moves) => compose_next(siv, problems, moves),
+        None => siv.add_layer(Dialog::info(tr("That was the last problem."))),
+    }
+}
+
+pub fn start(siv: &mut Cursive, problems: Problems) {
+    if let Some(problem) = problems.current().cloned() {
+        open_problem(siv, problem, problems);
+    }
+}
+
+// Composing can take a few seconds, so it runs off the main thread.
+fn compose_next(siv: &mut Cursive, mut problems: Problems, moves: u32) {
+    siv.add_layer(
+        Dialog::text(fill("Composing a mate in {}...", &[&moves.to_string()]))
+            .title(tr("Problems")),
+    );
+    let cb_sink = siv.cb_sink().clone();
+    let seed = rand::random();
+    thread::spawn(move || {
+        let problem = compose(moves, seed);
+        let _ = cb_sink.send(Box::new(move |s| {
+            s.pop_layer();
+            match problem {
+                Some(problem) => {
+                    problems.current = problems.list.len();
+                    problems.list.push(problem);
+                    start(s, problems);
+                }
+                None => s.add_layer(Dialog::info(tr("No problem came up this time. Try again."))),
+            }
+        }));
+    });
+}
+
+pub fn over(siv: &mut Cursive, msg: &str, refutation: Option<String>, problems: Problems) {
+    logic::end_game(siv);
+    let Some(problem) = problems.current().cloned() else {
+        return;
+    };
+    let mut dialog = Dialog::text(msg).title(problem.title());
+    dialog.add_button(tr("Next"), {
+        let problems = problems.clone();
+        move |s| {
+            s.pop_layer();
+            next(s, problems.clone());
+        }
+    });
+    if let Some(refutation) = refutation {
+        dialog.add_button(tr("Show Refutation"), move |s| {
+            s.add_layer(Dialog::info(refutation.clone()).title(tr("Refutation")))
+        });
+        dialog.add_button(tr("Retry"), move |s| {
+            s.pop_layer();
+            start(s, problems.clone());
+        });
+    }
+    dialog.add_button(tr("Show Solution"), move |s| {
+        s.add_layer(Dialog::info(solution_text(&problem)).title(tr("Solution")))
+    });
+    siv.add_layer(dialog.dismiss_button(tr("Close")));
+}
+
+pub fn show_setup(siv: &mut Cursive) {
+    let mut moves = RadioGroup::new();
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Mate Problems"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new("Problem file (EPD with dm):"))
+                    .child(
+                        EditView::new()
+                            .content("problems.epd")
+                            .with_name("problem_path")
+                            .fixed_width(40),
+                    )
+                    .child(TextView::new(
+                        "\nOr compose one, white to play and mate in:",
+                    ))
+                    .child(moves.button(2, "2 moves").selected())
+                    .child(moves.button(3, "3 moves")),
+            )
+            .button(tr("Open"), |s| {
+                let path = s
+                    .call_on_name("problem_path", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                let list = fs::read_to_string(path.trim())
+                    .map_err(|e| format!("Could not read {}: {}", path.trim(), e))
+                    .and_then(|text| load(&text));
+                match list {
+                    Ok(list) if list.is_empty() => {
+                        s.add_layer(Dialog::info(fill("No problems in {}", &[path.trim()])))
+                    }
+                    Ok(list) => {
+                        s.pop_layer();
+                        start(
+                            s,
+                            Problems {
+                                list,
+                                current: 0,
+                                composing: None,
+                            },
+                        );
+                    }
+                    Err(e) => s.add_layer(Dialog::info(e)),
+                }
+            })
+            .button(tr("Compose"), move |s| {
+                let moves = *moves.selection();
+                s.pop_layer();
+                compose_next(
+                    s,
+                    Problems {
+                        list: Vec::new(),
+                        current: 0,
+                        composing: Some(moves),
+                    },
+                    moves,
+                );
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
@@ -0,0 +1,261 @@
+use std::fs;
+
+use cursive::{
+    view::{Nameable, Resizable},
+    views::{Dialog, EditView, LinearLayout, RadioGroup, TextView},
+    Cursive,
+};
+use shakmaty::{san::SanPlus, variant::VariantPosition, Chess, Move, Position};
+
+use crate::epd::Epd;
+use crate::lang::{fill, tr};
+use crate::logic::open_training;
+use crate::pgn::{self, Game};
+use crate::repertoire::Schedule;
+
+pub const THEMES: [(&str, Option<&str>); 4] = [
+    ("All", None),
+    ("Forks", Some("fork")),
+    ("Pins", Some("pin")),
+    ("Mates", Some("mate")),
+];
+
+// The solution alternates the solver's moves with the replies, starting with
+// the solver. A record that only names moves to avoid has no solution.
+#[derive(Clone)]
+pub struct Puzzle {
+    pub id: Option<String>,
+    pub start: Chess,
+    pub solution: Vec<Move>,
+    pub alternatives: Vec<Move>,
+    pub avoid: Vec<Move>,
+    pub themes: Vec<String>,
+    // The opponent's move that led to the start, if known.
+    pub lead: Option<Move>,
+}
+
+impl Puzzle {
+    pub fn from_epd(epd: Epd) -> Self {
+        let mut best = epd.best.into_iter();
+        Puzzle {
+            id: epd.id,
+            start: epd.position,
+            solution: best.next().into_iter().collect(),
+            alternatives: best.collect(),
+            avoid: epd.avoid,
+            themes: split_themes(&epd.comments.join(" ")),
+            lead: None,
+        }
+    }
+
+    // A puzzle PGN is a game from a FEN whose moves are the solution.
+    fn from_game(game: Game) -> Result<Self, String> {
+        let start = match &game.positions[0] {
+            VariantPosition::Chess(pos) => pos.clone(),
+            _ => return Err("puzzles must be standard chess".to_owned()),
+        };
+        Ok(Puzzle {
+            id: game
+                .tag("PuzzleId")
+                .or(game.tag("Event"))
+                .map(str::to_owned),
+            themes: split_themes(game.tag("Themes").unwrap_or_default()),
+            start,
+            solution: game.moves,
+            alternatives: Vec::new(),
+            avoid: Vec::new(),
+            lead: None,
+        })
+    }
+
+    // Mates are recognised from the solution itself, other themes only
+    // from the file's tags or comments.
+    pub fn has_theme(&self, theme: &str) -> bool {
+        if self.themes.iter().any(|t| t.to_lowercase().contains(theme)) {
+            return true;
+        }
+        let mut pos = self.start.clone();
+        for mv in &self.solution {
+            pos.play_unchecked(mv);
+        }
+        theme == "mate" && pos.is_checkmate()
+    }
+
+    pub fn moves_to_find(&self) -> usize {
+        self.solution.len().div_ceil(2).max(1)
+    }
+
+    // Any mate also counts on the last move, where puzzles often have several.
+    pub fn accepts<P: Position + Clone>(&self, ply: usize, pos: &P, mv: &Move) -> bool {
+        if ply == 0 && self.avoid.contains(mv) {
+            return false;
+        }
+        if ply == 0 && (self.solution.is_empty() || self.alternatives.contains(mv)) {
+            return true;
+        }
+        if self.solution.get(ply) == Some(mv) {
+            return true;
+        }
+        let mut after = pos.clone();
+        after.play_unchecked(mv);
+        ply + 1 == self.solution.len() && after.is_checkmate()
+    }
+
+    pub fn solution_text(&self) -> String {
+        let mut pos = self.start.clone();
+        let mut line = Vec::new();
+        for mv in &self.solution {
+            line.push(SanPlus::from_move(pos.clone(), mv).to_string());
+            pos.play_unchecked(mv);
+        }
+        let mut text = if line.is_empty() {
+            "Any move but the one to avoid.".to_owned()
+        } else {
+            line.join(" ")
+        };
+        if !self.themes.is_empty() {
+            text += &format!("\nThemes: {}", self.themes.join(", "));
+        }
+        text
+    }
+}
+
+fn split_themes(text: &str) -> Vec<String> {
+    text.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|theme| !theme.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+// Either a puzzle PGN or one EPD record per line.
+pub fn load(text: &str) -> Result<Vec<Puzzle>, String> {
+    if text.trim_start().starts_with('[') {
+        return pgn::parse_all(text)?
+            .into_iter()
+            .map(Puzzle::from_game)
+            .collect();
+    }
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            Epd::parse(line)
+                .map(Puzzle::from_epd)
+                .map_err(|e| format!("line {}: {}", i + 1, e))
+        })
+        .collect()
+}
+
+pub struct Trainer {
+    puzzles: Vec<Puzzle>,
+    current: usize,
+    scored: bool,
+    solved: u32,
+    failed: u32,
+    schedule: Option<Schedule>,
+}
+
+impl Trainer {
+    pub fn new(puzzles: Vec<Puzzle>) -> Self {
+        Trainer {
+            puzzles,
+            current: 0,
+            scored: false,
+            solved: 0,
+            failed: 0,
+            schedule: None,
+        }
+    }
+
+    // Repertoire lines, whose results move them along the review schedule.
+    pub fn with_schedule(puzzles: Vec<Puzzle>, schedule: Schedule) -> Self {
+        Trainer {
+            schedule: Some(schedule),
+            ..Trainer::new(puzzles)
+        }
+    }
+
+    pub fn current(&self) -> Option<&Puzzle> {
+        self.puzzles.get(self.current)
+    }
+
+    // Only the first attempt at each puzzle counts.
+    pub fn record(&mut self, solved: bool) {
+        if !self.scored {
+            self.scored = true;
+            if let Some(schedule) = &mut self.schedule {
+                schedule.record(self.current, solved);
+            }
+            if solved {
+                self.solved += 1;
+            } else {
+                self.failed += 1;
+            }
+        }
+    }
+
+    pub fn advance(&mut self) -> Option<Puzzle> {
+        self.current += 1;
+        self.scored = false;
+        self.current().cloned()
+    }
+
+    pub fn score(&self) -> String {
+        let left = self.puzzles.len().saturating_sub(self.current + 1);
+        fill(
+            "Solved {}, failed {}, {} left",
+            &[
+                &self.solved.to_string(),
+                &self.failed.to_string(),
+                &left.to_string(),
+            ],
+        )
+    }
+}
+
+pub fn show_trainer(siv: &mut Cursive) {
+    let mut theme = RadioGroup::new();
+    let mut themes = LinearLayout::vertical().child(TextView::new("\nTheme:"));
+    for (i, (label, key)) in THEMES.into_iter().enumerate() {
+        let button = theme.button(key, label);
+        themes.add_child(if i == 0 { button.selected() } else { button });
+    }
+
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Puzzle Trainer"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new(
+                        "Puzzle file (EPD with bm, or PGN from a FEN):",
+                    ))
+                    .child(
+                        EditView::new()
+                            .content("puzzles.epd")
+                            .with_name("puzzle_path")
+                            .fixed_width(40),
+                    )
+                    .child(themes),
+            )
+            .button(tr("Start"), move |s| {
+                let path = s
+                    .call_on_name("puzzle_path", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                let puzzles = fs::read_to_string(path.trim())
+                    .map_err(|e| format!("Could not read {}: {}", path.trim(), e))
+                    .and_then(|text| load(&text));
+                match puzzles {
+                    Ok(puzzles) => {
+                        let puzzles = match *theme.selection() {
+                            Some(key) => puzzles.into_iter().filter(|p| p.has_theme(key)).collect(),
+                            None => puzzles,
+                        };
+                        s.pop_layer();
+                        open_training(s, Trainer::new(puzzles));
+                    }
+                    Err(e) => s.add_layer(Dialog::info(e)),
+                }
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
@@ -0,0 +1,351 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use cursive::{
+    view::{Nameable, Resizable},
+    views::{Dialog, EditView, LinearLayout, RadioGroup, TextView},
+    Cursive,
+};
+use shakmaty::{san::SanPlus, variant::VariantPosition, Color, Position};
+
+use crate::lang::{fill, tr};
+use crate::library::data_dir;
+use crate::logic::{self, open_training};
+use crate::pgn;
+use crate::puzzle::{Puzzle, Trainer};
+
+const SCHEDULE_FILE: &str = "repertoire.txt";
+
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() / 86_400)
+}
+
+// Moves, move numbers, "(" and ")". Comments, NAGs and results are dropped,
+// as are the ! and ? that annotate a move.
+fn tokens(movetext: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let (mut comment, mut rest_of_line) = (false, false);
+    for c in movetext.chars().chain([' ']) {
+        match c {
+            '\n' if rest_of_line => rest_of_line = false,
+            _ if rest_of_line => {}
+            '}' if comment => comment = false,
+            _ if comment => {}
+            '{' => comment = true,
+            ';' => rest_of_line = true,
+            '(' | ')' => {
+                tokens.push(std::mem::take(&mut token));
+                tokens.push(c.to_string());
+            }
+            c if c.is_whitespace() => tokens.push(std::mem::take(&mut token)),
+            c => token.push(c),
+        }
+    }
+    tokens
+        .into_iter()
+        .map(|token| {
+            let start = token.rfind('.').map_or(0, |i| i + 1);
+            token[start..].trim_end_matches(['!', '?']).to_owned()
+        })
+        .filter(|token| {
+            !token.is_empty()
+                && !token.starts_with('$')
+                && !["1-0", "0-1", "1/2-1/2", "*"].contains(&token.as_str())
+        })
+        .collect()
+}
+
+// Every line from the start to the end of the main line or of a variation.
+// A variation replaces the move before it.
+fn variations(movetext: &str) -> Vec<Vec<String>> {
+    let mut lines = Vec::new();
+    let mut outer = Vec::new();
+    let mut line: Vec<String> = Vec::new();
+    for token in tokens(movetext) {
+        match token.as_str() {
+            "(" => {
+                let mut branch = line.clone();
+                branch.pop();
+                outer.push(std::mem::replace(&mut line, branch));
+            }
+            ")" => {
+                if let Some(resumed) = outer.pop() {
+                    lines.push(std::mem::replace(&mut line, resumed));
+                }
+            }
+            _ => line.push(token),
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+struct Line {
+    key: String,
+    puzzle: Puzzle,
+}
+
+// A line becomes a puzzle from the learner's first move, ending on their
+// last one. Black's lines start after White's first move.
+fn line_puzzle(
+    start: &VariantPosition,
+    sans: &[String],
+    side: Color,
+) -> Result<Option<Line>, String> {
+    let VariantPosition::Chess(start) = start else {
+        return Err("repertoires must be standard chess".to_owned());
+    };
+    let mut pos = start.clone();
+    let mut moves = Vec::new();
+    for san in sans {
+        let mv = SanPlus::from_ascii(san.as_bytes())
+            .ok()
+            .and_then(|san| san.san.to_move(&pos).ok())
+            .ok_or_else(|| format!("illegal move {} in {}", san, sans.join(" ")))?;
+        pos.play_unchecked(&mv);
+        moves.push(mv);
+    }
+
+    let mut start = start.clone();
+    let mut lead = None;
+    if start.turn() != side && !moves.is_empty() {
+        let first = moves.remove(0);
+        start.play_unchecked(&first);
+        lead = Some(first);
+    }
+    if moves.len() % 2 == 0 {
+        moves.pop();
+    }
+    if moves.is_empty() {
+        return Ok(None);
+    }
+    let played = usize::from(lead.is_some()) + moves.len();
+    let key = format!(
+        "{} {}",
+        logic::color_name(side).to_lowercase(),
+        sans[..played].join(" ")
+    );
+    Ok(Some(Line {
+        key,
+        puzzle: Puzzle {
+            id: None,
+            start,
+            solution: moves,
+            alternatives: Vec::new(),
+            avoid: Vec::new(),
+            themes: Vec::new(),
+            lead,
+        },
+    }))
+}
+
+fn load(text: &str, side: Color) -> Result<Vec<Line>, String> {
+    let mut lines: Vec<Line> = Vec::new();
+    for (i, chunk) in pgn::split_games(text).iter().enumerate() {
+        let movetext: String = chunk
+            .lines()
+            .filter(|line| !line.trim_start().starts_with(['[', '%']))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let header: String = chunk
+            .lines()
+            .filter(|line| line.trim_start().starts_with('['))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let start = pgn::parse(&format!("{}\n*", header))
+            .map_err(|e| format!("game {}: {}", i + 1, e))?
+            .positions
+            .swap_remove(0);
+        for sans in variations(&movetext) {
+            let line =
+                line_puzzle(&start, &sans, side).map_err(|e| format!("game {}: {}", i + 1, e))?;
+            lines.extend(line);
+        }
+    }
+
+    // A line that another one continues is drilled as part of it.
+    let keys: Vec<String> = lines.iter().map(|line| line.key.clone()).collect();
+    lines.retain(|line| {
+        !keys
+            .iter()
+            .any(|key| key != &line.key && key.starts_with(&format!("{} ", line.key)))
+    });
+    let mut seen = Vec::new();
+    lines.retain(|line| {
+        let first = !seen.contains(&line.key);
+        seen.push(line.key.clone());
+        first
+    });
+    Ok(lines)
+}
+
+// Each line is due again after an interval in days that doubles with every
+// correct run. A mistake keeps the line due and starts the interval over.
+struct Card {
+    key: String,
+    due: u64,
+    interval: u64,
+}
+
+fn schedule_path() -> Option<PathBuf> {
+    Some(data_dir()?.join(SCHEDULE_FILE))
+}
+
+fn read_cards() -> Vec<Card> {
+    let text = schedule_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .unwrap_or_default();
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ' ');
+            let due = fields.next()?.parse().ok()?;
+            let interval = fields.next()?.parse().ok()?;
+            Some(Card {
+                key: fields.next()?.to_owned(),
+                due,
+                interval,
+            })
+        })
+        .collect()
+}
+
+pub struct Schedule {
+    cards: Vec<Card>,
+    // The card of each puzzle in the session, by index.
+    drilled: Vec<String>,
+}
+
+impl Schedule {
+    pub fn record(&mut self, index: usize, correct: bool) {
+        let Some(key) = self.drilled.get(index) else {
+            return;
+        };
+        let card = match self.cards.iter().position(|card| &card.key == key) {
+            Some(i) => &mut self.cards[i],
+            None => {
+                self.cards.push(Card {
+                    key: key.clone(),
+                    due: 0,
+                    interval: 0,
+                });
+                self.cards.last_mut().unwrap()
+            }
+        };
+        card.interval = if correct {
+            (card.interval * 2).max(1)
+        } else {
+            1
+        };
+        card.due = today() + if correct { card.interval } else { 0 };
+        let _ = self.save();
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let path = schedule_path().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no home directory")
+        })?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text: String = self
+            .cards
+            .iter()
+            .map(|card| format!("{} {} {}\n", card.due, card.interval, card.key))
+            .collect();
+        fs::write(path, text)
+    }
+}
+
+// The lines due today, the longest overdue first, then the new ones.
+fn start(siv: &mut Cursive, path: &str, side: Color) {
+    let lines = match fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path, e))
+        .and_then(|text| load(&text, side))
+    {
+        Ok(lines) => lines,
+        Err(e) => return siv.add_layer(Dialog::info(e)),
+    };
+    if lines.is_empty() {
+        return siv.add_layer(Dialog::info(fill(
+            "{} has no lines with a move for {} to play.",
+            &[path, tr(logic::color_name(side))],
+        )));
+    }
+
+    let cards = read_cards();
+    let due_on = |key: &str| {
+        cards
+            .iter()
+            .find(|card| card.key == key)
+            .map(|card| card.due)
+    };
+    let today = today();
+    let mut due: Vec<(u64, Line)> = Vec::new();
+    let mut next = None;
+    for line in lines {
+        match due_on(&line.key) {
+            Some(day) if day > today => next = Some(next.map_or(day, |n: u64| n.min(day))),
+            day => due.push((day.unwrap_or(u64::MAX), line)),
+        }
+    }
+    if due.is_empty() {
+        let days = next.unwrap_or(today) - today;
+        let template = if days == 1 {
+            "Every line is learned for now. The next one is due in {} day."
+        } else {
+            "Every line is learned for now. The next one is due in {} days."
+        };
+        return siv.add_layer(Dialog::info(fill(template, &[&days.to_string()])));
+    }
+    due.sort_by_key(|(day, _)| *day);
+
+    let total = due.len();
+    let (drilled, puzzles) = due
+        .into_iter()
+        .enumerate()
+        .map(|(i, (_, line))| {
+            let mut puzzle = line.puzzle;
+            puzzle.id = Some(format!("{}/{}", i + 1, total));
+            (line.key, puzzle)
+        })
+        .unzip();
+    siv.pop_layer();
+    open_training(
+        siv,
+        Trainer::with_schedule(puzzles, Schedule { cards, drilled }),
+    );
+}
+
+pub fn show_trainer(siv: &mut Cursive) {
+    let mut side = RadioGroup::new();
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Repertoire Trainer"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new("Repertoire PGN, variations included:"))
+                    .child(
+                        EditView::new()
+                            .content("repertoire.pgn")
+                            .with_name("repertoire_path")
+                            .fixed_width(40),
+                    )
+                    .child(TextView::new("\nI play:"))
+                    .child(side.button(Color::White, "White").selected())
+                    .child(side.button(Color::Black, "Black")),
+            )
+            .button(tr("Start"), move |s| {
+                let path = s
+                    .call_on_name("repertoire_path", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                start(s, path.trim(), *side.selection());
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
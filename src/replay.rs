@@ -0,0 +1,782 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+use cursive::{
+    direction::Direction,
+    event::{Event, EventResult, Key, MouseButton, MouseEvent},
+    theme::{BaseColor, Color, ColorStyle, Effect},
+    utils::markup::StyledString,
+    view::{CannotFocus, Nameable, Resizable, Scrollable},
+    views::{
+        Dialog, EditView, HideableView, LinearLayout, NamedView, PaddedView, Panel, ResizedView,
+        SelectView, TextView,
+    },
+    CbSink, Cursive, Printer, Vec2,
+};
+use shakmaty::{
+    fen::Fen, san::SanPlus, uci::Uci, variant::VariantPosition, Bitboard, Board, Color as CColor,
+    EnPassantMode, File, Position, Rank, Square,
+};
+
+use crate::analysis::Analysis;
+use crate::clipboard;
+use crate::clock;
+use crate::engine;
+use crate::evalbar::{score_label, viewed, EvalBar};
+use crate::explorer::{self, Continuation};
+use crate::lang::tr;
+use crate::logic::{self, piece_color, piece_to_char, square_at, BoardView, PieceValues};
+use crate::marks::{Marks, ARROW};
+use crate::pgn::{self, Game};
+use crate::review::{self, Class, Review};
+use crate::settings::{self, BoardTheme, PieceStyle, Settings};
+
+pub struct ReplayView {
+    game: Game,
+    ply: usize,
+    style: PieceStyle,
+    theme: BoardTheme,
+    analysis: Option<Analysis>,
+    review: Vec<Review>,
+    marks: Marks,
+    // The explorer lookup whose answer is still wanted.
+    lookup: usize,
+    // The engine's move and eval, by FEN, for each position it was asked
+    // about; none while the search runs.
+    revealed: HashMap<String, Option<String>>,
+}
+
+// Kept low, so the answer comes straight back.
+const REVEAL_DEPTH: u32 = 4;
+
+static LOOKUPS: AtomicUsize = AtomicUsize::new(0);
+
+impl ReplayView {
+    fn new(game: Game, style: PieceStyle, theme: BoardTheme) -> Self {
+        ReplayView {
+            game,
+            ply: 0,
+            style,
+            theme,
+            analysis: None,
+            review: Vec::new(),
+            marks: Marks::default(),
+            lookup: 0,
+            revealed: HashMap::new(),
+        }
+    }
+
+    fn move_list(&self) -> StyledString {
+        let start = &self.game.positions[0];
+        let mut number = start.fullmoves().get();
+        let mut text = StyledString::new();
+
+        for (i, san) in self.game.sans.iter().enumerate() {
+            let white = start.turn() == CColor::White;
+            if i % 2 == usize::from(!white) {
+                text.append_plain(format!("{}. ", number));
+            } else if i == 0 {
+                text.append_plain(format!("{}... ", number));
+            }
+            // The annotator's symbol wins over the review's.
+            let note = self.game.notes.get(&i);
+            let suffix = match note.map(|note| note.symbol()) {
+                Some(symbol) if !symbol.is_empty() => symbol,
+                _ => self.review.get(i).map_or("", |r| r.class.suffix()),
+            };
+            let san = format!("{}{}", san, suffix);
+            if i + 1 == self.ply {
+                text.append_styled(san, Effect::Reverse);
+            } else {
+                text.append_plain(san);
+            }
+            if let Some(note) = note.filter(|note| !note.comment.is_empty()) {
+                text.append_styled(format!(" {{{}}}", note.comment), Effect::Italic);
+            }
+            if i % 2 == usize::from(white) || i + 1 == self.game.sans.len() {
+                text.append_plain("\n");
+                number += 1;
+            } else {
+                text.append_plain(" ");
+            }
+        }
+
+        text
+    }
+
+    fn status(&self) -> String {
+        let result = self.game.tag("Result").unwrap_or("*");
+        format!("Ply {}/{}  {}", self.ply, self.game.sans.len(), result)
+    }
+
+    fn verdict(&self) -> Option<String> {
+        let i = self.ply.checked_sub(1)?;
+        let review = self.review.get(i)?;
+        let best = match &review.best {
+            Some(best) if review.class != Class::Best => best,
+            _ => return Some(review.class.label().to_owned()),
+        };
+        let san = SanPlus::from_move(self.game.positions[i].clone(), best);
+        Some(format!(
+            "{} -{:.1}, best {}",
+            review.class.label(),
+            review.loss as f32 / 100.0,
+            san
+        ))
+    }
+
+    fn start_review(&mut self, values: &PieceValues) {
+        self.review = review::review(&self.game.positions, &self.game.moves, values);
+    }
+
+    fn review_summary(&self) -> String {
+        let count = |color: CColor, class: Class| {
+            let first = usize::from(self.game.positions[0].turn() != color);
+            self.review
+                .iter()
+                .skip(first)
+                .step_by(2)
+                .filter(|r| r.class == class)
+                .count()
+        };
+        CColor::ALL
+            .map(|color| {
+                format!(
+                    "{}: {}?! {}? {}??",
+                    logic::color_name(color),
+                    count(color, Class::Inaccuracy),
+                    count(color, Class::Mistake),
+                    count(color, Class::Blunder)
+                )
+            })
+            .join("\n")
+    }
+
+    fn step(&mut self, ply: usize) -> EventResult {
+        self.ply = ply.min(self.game.sans.len());
+        self.marks.clear();
+        if let Some(analysis) = &self.analysis {
+            analysis.start(self.game.positions[self.ply].clone());
+        }
+        let text = self.move_list();
+        let analyzing = self.analysis.is_some();
+        EventResult::with_cb(move |s| {
+            s.call_on_name("replay_moves", |v: &mut TextView| {
+                v.set_content(text.clone())
+            });
+            if analyzing {
+                explore(s);
+            }
+        })
+    }
+
+    fn toggle_analysis(&mut self, cb_sink: CbSink, settings: &Settings) -> bool {
+        match self.analysis.take() {
+            Some(analysis) => analysis.stop(),
+            None => {
+                let analysis = Analysis::new(cb_sink, settings);
+                analysis.start(self.game.positions[self.ply].clone());
+                self.analysis = Some(analysis);
+            }
+        }
+        self.analysis.is_some()
+    }
+
+    fn start_lookup(&mut self) -> (usize, VariantPosition) {
+        self.lookup = LOOKUPS.fetch_add(1, Ordering::SeqCst) + 1;
+        (self.lookup, self.game.positions[self.ply].clone())
+    }
+
+    // Trying a move mid-game branches off: the rest of the line is dropped.
+    fn fen(&self) -> String {
+        let pos = self.game.positions[self.ply].clone();
+        Fen::from_position(pos, EnPassantMode::Legal).to_string()
+    }
+
+    fn try_move(&mut self, text: &str) -> Result<EventResult, String> {
+        let text = text.trim();
+        let pos = &self.game.positions[self.ply];
+        let mv = Uci::from_ascii(text.as_bytes())
+            .ok()
+            .and_then(|uci| uci.to_move(pos).ok())
+            .or_else(|| {
+                SanPlus::from_ascii(text.as_bytes())
+                    .ok()
+                    .and_then(|san| san.san.to_move(pos).ok())
+            })
+            .ok_or_else(|| format!("illegal move: {}", text))?;
+
+        let mut next = pos.clone();
+        let san = SanPlus::from_move(pos.clone(), &mv).to_string();
+        next.play_unchecked(&mv);
+        self.game.positions.truncate(self.ply + 1);
+        self.game.moves.truncate(self.ply);
+        self.game.sans.truncate(self.ply);
+        self.game.notes.retain(|&i, _| i < self.ply);
+        self.game.positions.push(next);
+        self.game.moves.push(mv);
+        self.game.sans.push(san);
+        self.game.tags.retain(|(tag, _)| tag != "Result");
+        self.review.truncate(self.ply);
+        Ok(self.step(self.ply + 1))
+    }
+}
+
+// A compact board with coordinates, ten rows high and 26 columns wide.
+pub fn draw_board(
+    printer: &Printer,
+    board: &Board,
+    style: PieceStyle,
+    theme: BoardTheme,
+    arrows: &[(Vec2, char)],
+    highlight: impl Fn(Square) -> Option<Color>,
+) {
+    let background = |sq: Square| highlight(sq).unwrap_or_else(|| theme.square(sq.is_dark()));
+    printer.with_color(ColorStyle::secondary(), |printer| {
+        for i in 0..8 {
+            printer.print((0, i), &Rank::new(7 - i as u32).char().to_string());
+            printer.print((3 + 3 * i, 8), &File::new(i as u32).char().to_string());
+        }
+    });
+
+    for sq in Bitboard::FULL {
+        let x = 2 + 3 * usize::from(sq.file());
+        let y = 7 - usize::from(sq.rank());
+
+        let piece = board.piece_at(sq);
+        let text = match piece {
+            Some(p) => format!(" {} ", piece_to_char(p, style)),
+            None => "   ".to_owned(),
+        };
+        printer.with_color(
+            ColorStyle::new(piece_color(piece, style), background(sq)),
+            |printer| printer.print((x, y), &text),
+        );
+    }
+
+    for &(pos, glyph) in arrows.iter().filter(|(pos, _)| pos.x < 24 && pos.y < 8) {
+        let sq = Square::from_coords(File::new(pos.x as u32 / 3), Rank::new(7 - pos.y as u32));
+        printer.with_color(ColorStyle::new(ARROW, background(sq)), |printer| {
+            printer.print((2 + pos.x, pos.y), &glyph.to_string())
+        });
+    }
+}
+
+impl cursive::view::View for ReplayView {
+    fn draw(&self, printer: &Printer) {
+        let pos = &self.game.positions[self.ply];
+        let last_move = match self.ply.checked_sub(1) {
+            Some(i) => {
+                let mv = &self.game.moves[i];
+                mv.from().into_iter().chain([mv.to()]).collect()
+            }
+            None => Bitboard::EMPTY,
+        };
+        // The engine's choice, where the move played was not it.
+        let better = match self.ply.checked_sub(1).and_then(|i| self.review.get(i)) {
+            Some(Review {
+                best: Some(mv),
+                class,
+                ..
+            }) if *class != Class::Best => mv.from().into_iter().chain([mv.to()]).collect(),
+            _ => Bitboard::EMPTY,
+        };
+
+        let arrows = self
+            .marks
+            .overlay(CColor::White, (3, 1), pos.board().occupied());
+        draw_board(
+            printer,
+            pos.board(),
+            self.style,
+            self.theme,
+            &arrows,
+            |sq| {
+                if self.marks.squares.contains(sq) {
+                    Some(Color::Dark(BaseColor::Magenta))
+                } else if last_move.contains(sq) {
+                    Some(Color::Dark(BaseColor::Green))
+                } else if better.contains(sq) {
+                    Some(Color::Dark(BaseColor::Blue))
+                } else {
+                    None
+                }
+            },
+        );
+
+        printer.print((0, 10), &self.status());
+        if let Some(verdict) = self.verdict() {
+            printer.print((0, 11), &verdict);
+        }
+        match self.revealed.get(&self.fen()) {
+            Some(Some(text)) => printer.print((0, 12), text),
+            Some(None) => printer.print((0, 12), "Engine: thinking..."),
+            None => {}
+        }
+    }
+
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::Consumed(None))
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        match event {
+            Event::Key(Key::Left) => self.step(self.ply.saturating_sub(1)),
+            Event::Key(Key::Right) => self.step(self.ply + 1),
+            Event::Key(Key::Home) => self.step(0),
+            Event::Key(Key::End) => self.step(self.game.sans.len()),
+            Event::Char('a') => EventResult::with_cb(toggle_analysis),
+            Event::Char('b') => EventResult::with_cb(reveal),
+            Event::Char(':' | 'm') => EventResult::with_cb(show_move_entry),
+            Event::Char('y') => {
+                let fen = self.fen();
+                EventResult::with_cb(move |s| clipboard::copy_text(s, "FEN", &fen))
+            }
+            Event::Char('v') => EventResult::with_cb(clipboard::paste_position),
+            Event::Mouse {
+                offset,
+                position,
+                event: MouseEvent::Press(MouseButton::Right),
+            } => {
+                let sq = square_at(position, offset + (2, 0), CColor::White, (3, 1));
+                self.marks.press(sq);
+                EventResult::Consumed(None)
+            }
+            Event::Mouse {
+                offset,
+                position,
+                event: MouseEvent::Release(MouseButton::Right),
+            } => {
+                let sq = square_at(position, offset + (2, 0), CColor::White, (3, 1));
+                self.marks.release(sq);
+                EventResult::Consumed(None)
+            }
+            _ => EventResult::Ignored,
+        }
+    }
+
+    fn required_size(&mut self, _: Vec2) -> Vec2 {
+        Vec2::new(26, 13)
+    }
+}
+
+// Searched once per position and kept, so stepping back shows it at once.
+fn reveal(siv: &mut Cursive) {
+    let settings = settings::current(siv);
+    let wanted = siv
+        .call_on_name("replay", |view: &mut ReplayView| {
+            let fen = view.fen();
+            (!view.revealed.contains_key(&fen)).then(|| {
+                view.revealed.insert(fen.clone(), None);
+                (fen, view.game.positions[view.ply].clone())
+            })
+        })
+        .flatten();
+    let Some((fen, pos)) = wanted else {
+        return;
+    };
+    let cb_sink = siv.cb_sink().clone();
+    thread::spawn(move || {
+        let search = engine::search(&pos, REVEAL_DEPTH, &settings.piece_values);
+        let text = match &search.best {
+            Some(best) => {
+                let score = pos.turn().fold_wb(search.score, -search.score);
+                format!(
+                    "Engine: {} {}",
+                    SanPlus::from_move(pos.clone(), best),
+                    score_label(viewed(score, settings.eval_perspective, pos.turn()))
+                )
+            }
+            None => "Engine: no moves".to_owned(),
+        };
+        let _ = cb_sink.send(Box::new(move |s| {
+            s.call_on_name("replay", |view: &mut ReplayView| {
+                view.revealed.insert(fen, Some(text))
+            });
+        }));
+    });
+}
+
+fn toggle_analysis(siv: &mut Cursive) {
+    let cb_sink = siv.cb_sink().clone();
+    let settings = settings::current(siv);
+    let on = siv
+        .call_on_name("replay", |view: &mut ReplayView| {
+            view.toggle_analysis(cb_sink, &settings)
+        })
+        .unwrap();
+    siv.call_on_name(
+        "analysis_bar",
+        |bar: &mut HideableView<PaddedView<NamedView<EvalBar>>>| bar.set_visible(on),
+    );
+    siv.call_on_name(
+        "explorer_panel",
+        |panel: &mut HideableView<ResizedView<Panel<LinearLayout>>>| panel.set_visible(on),
+    );
+    if on {
+        explore(siv);
+    } else {
+        siv.call_on_name("analysis_pv", |view: &mut TextView| {
+            view.set_content(ANALYSIS_OFF)
+        });
+    }
+}
+
+// The network is asked off the main thread; an answer for a position that
+// is no longer on show is dropped.
+fn explore(siv: &mut Cursive) {
+    let Some((id, pos)) = siv.call_on_name("replay", ReplayView::start_lookup) else {
+        return;
+    };
+    siv.call_on_name("explorer_source", |v: &mut TextView| {
+        v.set_content("Looking up...")
+    });
+    siv.call_on_name("explorer", |v: &mut SelectView<String>| v.clear());
+    let cb_sink = siv.cb_sink().clone();
+    thread::spawn(move || {
+        let found = explorer::lookup(&pos);
+        let _ = cb_sink.send(Box::new(move |s| show_explorer(s, id, found)));
+    });
+}
+
+fn show_explorer(siv: &mut Cursive, id: usize, found: Result<(&str, Vec<Continuation>), String>) {
+    if siv.call_on_name("replay", |view: &mut ReplayView| view.lookup) != Some(id) {
+        return;
+    }
+    let (source, moves) = match found {
+        Ok((_, moves)) if moves.is_empty() => ("No games reach this position.".to_owned(), moves),
+        Ok((source, moves)) => (format!("{}:\n{}", source, explorer::HEADER), moves),
+        Err(e) => (e, Vec::new()),
+    };
+    siv.call_on_name("explorer_source", |v: &mut TextView| v.set_content(source));
+    siv.call_on_name("explorer", |v: &mut SelectView<String>| {
+        v.clear();
+        v.add_all(moves.into_iter().map(|c| (c.row(), c.uci)));
+    });
+}
+
+fn play_continuation(siv: &mut Cursive, uci: &str) {
+    let tried = siv.call_on_name("replay", |view: &mut ReplayView| view.try_move(uci));
+    match tried {
+        Some(Ok(result)) => result.process(siv),
+        Some(Err(e)) => siv.add_layer(Dialog::info(e)),
+        None => {}
+    }
+}
+
+fn run_review(siv: &mut Cursive) {
+    let values = settings::current(siv).piece_values;
+    let texts = siv.call_on_name("replay", |view: &mut ReplayView| {
+        view.start_review(&values);
+        (view.move_list(), view.review_summary())
+    });
+    if let Some((moves, summary)) = texts {
+        siv.call_on_name("replay_moves", |v: &mut TextView| v.set_content(moves));
+        siv.call_on_name("analysis_pv", |v: &mut TextView| {
+            v.set_content(format!("{}\nBlue: the engine's move", summary))
+        });
+    }
+}
+
+fn submit_move(siv: &mut Cursive, text: &str) {
+    let tried = siv.call_on_name("replay", |view: &mut ReplayView| view.try_move(text));
+    match tried {
+        None => {}
+        Some(Err(e)) => siv.add_layer(Dialog::info(e)),
+        Some(Ok(result)) => {
+            siv.pop_layer();
+            result.process(siv);
+        }
+    }
+}
+
+fn show_move_entry(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Try Move"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new("SAN (Nf3) or UCI (g1f3):"))
+                    .child(
+                        EditView::new()
+                            .on_submit(submit_move)
+                            .with_name("replay_entry")
+                            .fixed_width(12),
+                    ),
+            )
+            .button(tr("Play"), |s| {
+                let text = s
+                    .call_on_name("replay_entry", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                submit_move(s, &text);
+            })
+            .dismiss_button(tr("Cancel")),
+    );
+}
+
+const BAR_WIDTH: usize = 10;
+
+// A row a move number, each side's bar as long as its move took against
+// the longest move of the game.
+fn time_chart(game: &Game) -> String {
+    let longest = game.times.values().map(|stamp| stamp.spent).max();
+    let longest = longest.unwrap_or_default().max(Duration::from_millis(1));
+    let column = |i: Option<usize>| {
+        let Some(spent) = i.and_then(|i| game.times.get(&i)).map(|stamp| stamp.spent) else {
+            return format!("{:<w$}", "", w = BAR_WIDTH + 6);
+        };
+        let length = (spent.as_secs_f64() / longest.as_secs_f64() * BAR_WIDTH as f64).round();
+        let bar = "\u{2588}".repeat((length as usize).max(1));
+        format!("{:<w$} {:>5}", bar, clock::format(spent), w = BAR_WIDTH)
+    };
+
+    let start = &game.positions[0];
+    let first = usize::from(start.turn() == CColor::Black);
+    let mut text = format!("    {:<w$}{}\n", "White", "Black", w = BAR_WIDTH + 8);
+    let rows = (game.sans.len() + first).div_ceil(2);
+    for row in 0..rows {
+        let white = (row * 2).checked_sub(first);
+        let black = Some(row * 2 + 1 - first).filter(|&i| i < game.sans.len());
+        text += &format!(
+            "{:>3} {}  {}\n",
+            start.fullmoves().get() + row as u32,
+            column(white),
+            column(black)
+        );
+    }
+    let total = |color: CColor| {
+        let offset = usize::from(start.turn() != color);
+        let spent = game
+            .times
+            .iter()
+            .filter(|(&i, _)| i % 2 == offset)
+            .map(|(_, stamp)| stamp.spent)
+            .sum();
+        clock::format(spent)
+    };
+    text += &format!(
+        "\nTotal{:>w$}  {:>v$}",
+        total(CColor::White),
+        total(CColor::Black),
+        w = BAR_WIDTH + 5,
+        v = BAR_WIDTH + 6
+    );
+    text
+}
+
+fn show_time_chart(siv: &mut Cursive) {
+    let chart = siv.call_on_name("replay", |view: &mut ReplayView| time_chart(&view.game));
+    if let Some(chart) = chart {
+        siv.add_layer(
+            Dialog::around(TextView::new(chart).scrollable().max_height(18))
+                .title(tr("Time per Move"))
+                .dismiss_button(tr("Ok")),
+        );
+    }
+}
+
+const ANALYSIS_OFF: &str = "Press A to analyze.\nPress M to try a move.\nY copies the FEN.";
+
+pub fn open_replay(siv: &mut Cursive, game: Game, analyze: bool) {
+    let title = match (game.tag("White"), game.tag("Black")) {
+        (Some(white), Some(black)) => format!("{} vs {}", white, black),
+        _ => "Replay".to_owned(),
+    };
+    let settings = settings::current(siv);
+    let timed = !game.times.is_empty();
+    let view = ReplayView::new(game, settings.piece_style, settings.board_theme);
+    let moves = view.move_list();
+
+    let mut dialog = Dialog::new()
+        .title(title)
+        .content(
+            LinearLayout::horizontal()
+                .child(
+                    LinearLayout::vertical()
+                        .child(Panel::new(view.with_name("replay")))
+                        .child(
+                            Panel::new(TextView::new(ANALYSIS_OFF).with_name("analysis_pv"))
+                                .title(tr("Analysis"))
+                                .fixed_size((28, 5.max(3 + settings.multipv()))),
+                        ),
+                )
+                .child(
+                    HideableView::new(PaddedView::lrtb(
+                        1,
+                        0,
+                        1,
+                        0,
+                        EvalBar::new(8, settings.eval_perspective).with_name("analysis_eval"),
+                    ))
+                    .hidden()
+                    .with_name("analysis_bar"),
+                )
+                .child(
+                    Panel::new(TextView::new(moves).with_name("replay_moves").scrollable())
+                        .title(tr("Moves"))
+                        .fixed_size((16, 18)),
+                )
+                .child(
+                    HideableView::new(
+                        Panel::new(
+                            LinearLayout::vertical()
+                                .child(TextView::new("").with_name("explorer_source"))
+                                .child(
+                                    SelectView::<String>::new()
+                                        .on_submit(|s, uci: &String| play_continuation(s, uci))
+                                        .with_name("explorer")
+                                        .scrollable(),
+                                ),
+                        )
+                        .title(tr("Explorer"))
+                        .fixed_size((24, 18)),
+                    )
+                    .hidden()
+                    .with_name("explorer_panel"),
+                ),
+        )
+        .button(tr("Analyze"), toggle_analysis)
+        .button(tr("Review"), run_review)
+        .button(tr("Best Move"), reveal)
+        .button(tr("Try Move"), show_move_entry);
+    if timed {
+        dialog.add_button(tr("Times"), show_time_chart);
+    }
+    siv.add_layer(dialog.button(tr("Close"), |s| {
+        s.pop_layer();
+    }));
+    if analyze {
+        toggle_analysis(siv);
+    }
+}
+
+pub fn finished_game(siv: &mut Cursive) -> Option<Game> {
+    let game = siv
+        .call_on_name("board", |board: &mut BoardView| pgn::render(board))
+        .map(|text| pgn::parse(&text));
+    match game {
+        Some(Ok(mut game)) => {
+            game.tags
+                .retain(|(tag, _)| tag != "White" && tag != "Black");
+            Some(game)
+        }
+        Some(Err(e)) => {
+            siv.add_layer(Dialog::info(e));
+            None
+        }
+        None => None,
+    }
+}
+
+pub fn analyze_game(siv: &mut Cursive) {
+    if let Some(game) = finished_game(siv) {
+        open_replay(siv, game, true);
+    }
+}
+
+// The player's worst moves in a lost game, at most three, by how much each
+// gave away against the built-in search's choice. Found off the UI thread.
+pub fn show_mistakes(siv: &mut Cursive, game: Game, human: CColor) {
+    let values = settings::current(siv).piece_values;
+    siv.add_layer(
+        Dialog::around(TextView::new("Looking for your mistakes...").with_name("mistakes"))
+            .title(tr("Learn From This Loss"))
+            .dismiss_button(tr("Close")),
+    );
+    let cb_sink = siv.cb_sink().clone();
+    thread::spawn(move || {
+        let text = mistakes(&game, human, &values);
+        let _ = cb_sink.send(Box::new(move |s| {
+            s.call_on_name("mistakes", |view: &mut TextView| view.set_content(text));
+        }));
+    });
+}
+
+fn mistakes(game: &Game, human: CColor, values: &PieceValues) -> String {
+    let reviews = review::review(&game.positions, &game.moves, values);
+    let mut worst: Vec<usize> = (0..reviews.len())
+        .filter(|&i| game.positions[i].turn() == human)
+        .filter(|&i| !matches!(reviews[i].class, Class::Best | Class::Good))
+        .collect();
+    worst.sort_by_key(|&i| -reviews[i].loss);
+    worst.truncate(3);
+    worst.sort_unstable();
+    if worst.is_empty() {
+        return "No clear mistakes: the game slipped away a little at a time.".to_owned();
+    }
+
+    let mut text = String::new();
+    for i in worst {
+        let pos = &game.positions[i];
+        let dots = if pos.turn() == CColor::White {
+            "."
+        } else {
+            "..."
+        };
+        let review = &reviews[i];
+        let best = review.best.as_ref().map_or("-".to_owned(), |mv| {
+            SanPlus::from_move(pos.clone(), mv).to_string()
+        });
+        text += &format!(
+            "{}{} {}{}  -{:.1}, better was {}\n",
+            pos.fullmoves(),
+            dots,
+            game.sans[i],
+            review.class.suffix(),
+            review.loss as f32 / 100.0,
+            best
+        );
+    }
+    text.trim_end().to_owned()
+}
+
+pub fn open_review(siv: &mut Cursive, game: Game) {
+    open_replay(siv, game, false);
+    run_review(siv);
+}
+
+pub fn review_game(siv: &mut Cursive) {
+    if let Some(game) = finished_game(siv) {
+        open_review(siv, game);
+    }
+}
+
+pub fn show_open(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Open PGN"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new("Path (Left/Right to step through moves):"))
+                    .child(
+                        EditView::new()
+                            .content("game.pgn")
+                            .with_name("open_pgn_path")
+                            .fixed_width(40),
+                    ),
+            )
+            .button(tr("Open"), |s| {
+                let path = s
+                    .call_on_name("open_pgn_path", |v: &mut EditView| v.get_content())
+                    .unwrap();
+                let game = fs::read_to_string(path.trim())
+                    .map_err(|e| format!("Could not read {}: {}", path.trim(), e))
+                    .and_then(|text| pgn::parse(&text));
+                match game {
+                    Ok(game) => {
+                        s.pop_layer();
+                        open_replay(s, game, false);
+                    }
+                    Err(e) => {
+                        s.add_layer(Dialog::info(e));
+                    }
+                }
+            })
+            .dismiss_button(tr("Cancel")),
+    );
+}
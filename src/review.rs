@@ -0,0 +1,89 @@
+use shakmaty::{variant::VariantPosition, Move, Outcome, Position};
+
+use crate::engine::{self, MATE};
+use crate::logic::PieceValues;
+
+const DEPTH: u32 = 3;
+// Mate scores are cut down so that missing a mate costs a lot, not millions.
+const CLAMP: i32 = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Class {
+    fn of(loss: i32) -> Class {
+        match loss {
+            i32::MIN..=49 => Class::Good,
+            50..=99 => Class::Inaccuracy,
+            100..=299 => Class::Mistake,
+            _ => Class::Blunder,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Class::Best => "Best move",
+            Class::Good => "Good move",
+            Class::Inaccuracy => "Inaccuracy",
+            Class::Mistake => "Mistake",
+            Class::Blunder => "Blunder",
+        }
+    }
+
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Class::Best | Class::Good => "",
+            Class::Inaccuracy => "?!",
+            Class::Mistake => "?",
+            Class::Blunder => "??",
+        }
+    }
+}
+
+pub struct Review {
+    pub class: Class,
+    pub loss: i32,
+    pub best: Option<Move>,
+}
+
+// From the mover's point of view, after the move.
+fn score_after(pos: &VariantPosition, mover: shakmaty::Color, values: &PieceValues) -> i32 {
+    match pos.outcome() {
+        Some(Outcome::Decisive { winner }) if winner == mover => MATE,
+        Some(Outcome::Decisive { .. }) => -MATE,
+        Some(Outcome::Draw) => 0,
+        None => -engine::search(pos, DEPTH - 1, values).score,
+    }
+}
+
+// Each move is scored against the engine's choice at the same depth.
+pub fn review(positions: &[VariantPosition], moves: &[Move], values: &PieceValues) -> Vec<Review> {
+    moves
+        .iter()
+        .zip(positions.windows(2))
+        .map(|(mv, pair)| {
+            let search = engine::search(&pair[0], DEPTH, values);
+            if search.best.as_ref() == Some(mv) {
+                return Review {
+                    class: Class::Best,
+                    loss: 0,
+                    best: search.best,
+                };
+            }
+            let best = search.score.clamp(-CLAMP, CLAMP);
+            let played = score_after(&pair[1], pair[0].turn(), values).clamp(-CLAMP, CLAMP);
+            let loss = (best - played).max(0);
+            Review {
+                class: Class::of(loss),
+                loss,
+                best: search.best,
+            }
+        })
+        .collect()
+}
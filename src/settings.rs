@@ -0,0 +1,1230 @@
+use std::{env, fs, io, path::PathBuf};
+
+use cursive::{
+    theme::{BaseColor, Color},
+    traits::{Nameable, Resizable, Scrollable},
+    views::{
+        Checkbox, Dialog, EditView, LinearLayout, ListView, RadioGroup, SelectView, SliderView,
+    },
+    Cursive, View,
+};
+use shakmaty::{variant::Variant, ByColor, Role};
+
+use crate::clock;
+use crate::keymap::{self, Keymap};
+use crate::lang::{self, fill, tr, Language};
+use crate::logic::{role_name, PieceValues};
+use crate::uci::{self, EngineOption, UciEngine};
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvalPerspective {
+    #[default]
+    White,
+    SideToMove,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum CpuTone {
+    Silent,
+    #[default]
+    Neutral,
+    Playful,
+}
+
+impl CpuTone {
+    const ALL: [CpuTone; 3] = [CpuTone::Silent, CpuTone::Neutral, CpuTone::Playful];
+
+    fn key(self) -> &'static str {
+        match self {
+            CpuTone::Silent => "silent",
+            CpuTone::Neutral => "neutral",
+            CpuTone::Playful => "playful",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            CpuTone::Silent => "Silent",
+            CpuTone::Neutral => "Neutral",
+            CpuTone::Playful => "Playful",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum CursorEdges {
+    #[default]
+    Clamp,
+    Wrap,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum FriendlyClick {
+    #[default]
+    Reselect,
+    Deselect,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum HighlightIntensity {
+    Subtle,
+    #[default]
+    Normal,
+    Bold,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoardFacing {
+    White,
+    Black,
+    #[default]
+    MyColor,
+    SideToMove,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Difficulty {
+    Random,
+    #[default]
+    Easy,
+    Medium,
+    Hard,
+    // The built-in search at adaptive_level, moved by the results.
+    Adaptive,
+    Engine,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 6] = [
+        Difficulty::Random,
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Adaptive,
+        Difficulty::Engine,
+    ];
+
+    // Level 0 plays at random; the others search that many plies.
+    pub fn depth(self, adaptive_level: u8) -> Option<u32> {
+        match self {
+            Difficulty::Random | Difficulty::Engine => None,
+            Difficulty::Easy => Some(2),
+            Difficulty::Medium => Some(4),
+            Difficulty::Hard => Some(6),
+            Difficulty::Adaptive => (adaptive_level > 0).then_some(u32::from(adaptive_level)),
+        }
+    }
+
+    // Nominal strengths to rate the player against; Stockfish's skill levels
+    // span roughly 1300 to 3000.
+    pub fn rating(self, engine_skill: u8, adaptive_level: u8) -> i32 {
+        match self {
+            Difficulty::Adaptive if adaptive_level == 0 => 400,
+            Difficulty::Adaptive => 600 + 200 * i32::from(adaptive_level),
+            Difficulty::Random => 400,
+            Difficulty::Easy => 1000,
+            Difficulty::Medium => 1400,
+            Difficulty::Hard => 1800,
+            Difficulty::Engine => 1300 + 85 * i32::from(engine_skill),
+        }
+    }
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Difficulty::Random => "random",
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+            Difficulty::Adaptive => "adaptive",
+            Difficulty::Engine => "engine",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Difficulty::Random => "Random",
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+            Difficulty::Adaptive => "Adaptive",
+            Difficulty::Engine => "External engine",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeControl {
+    #[default]
+    Untimed,
+    OnePlusZero,
+    ThreePlusTwo,
+    FivePlusZero,
+    TenPlusFive,
+    FifteenPlusTen,
+    Custom,
+}
+
+impl TimeControl {
+    pub const ALL: [TimeControl; 7] = [
+        TimeControl::Untimed,
+        TimeControl::OnePlusZero,
+        TimeControl::ThreePlusTwo,
+        TimeControl::FivePlusZero,
+        TimeControl::TenPlusFive,
+        TimeControl::FifteenPlusTen,
+        TimeControl::Custom,
+    ];
+
+    // Minutes on the clock and seconds added per move.
+    pub fn clock(self) -> Option<(u64, u64)> {
+        match self {
+            TimeControl::Untimed => None,
+            TimeControl::OnePlusZero => Some((1, 0)),
+            TimeControl::ThreePlusTwo => Some((3, 2)),
+            TimeControl::FivePlusZero => Some((5, 0)),
+            TimeControl::TenPlusFive => Some((10, 5)),
+            TimeControl::FifteenPlusTen => Some((15, 10)),
+            // The minutes and increment live in Settings::custom_clock.
+            TimeControl::Custom => None,
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            TimeControl::Untimed => "untimed",
+            TimeControl::OnePlusZero => "1+0",
+            TimeControl::ThreePlusTwo => "3+2",
+            TimeControl::FivePlusZero => "5+0",
+            TimeControl::TenPlusFive => "10+5",
+            TimeControl::FifteenPlusTen => "15+10",
+            TimeControl::Custom => "custom",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TimeControl::Untimed => "Untimed",
+            TimeControl::Custom => "Custom",
+            _ => self.key(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum PieceStyle {
+    #[default]
+    Unicode,
+    Ascii,
+    Solid,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sound {
+    #[default]
+    Off,
+    Bell,
+    Files,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoardSize {
+    #[default]
+    Fit,
+    Small,
+    Medium,
+    Large,
+}
+
+impl BoardSize {
+    // Columns and rows taken up by one square. A fitted board takes the
+    // biggest squares `fits` allows, down to a single character each.
+    pub fn cell(self, fits: impl Fn((usize, usize)) -> bool) -> (usize, usize) {
+        match self {
+            BoardSize::Fit => [(7, 3), (5, 2), (3, 2), (3, 1)]
+                .into_iter()
+                .find(|&cell| fits(cell))
+                .unwrap_or((1, 1)),
+            BoardSize::Small => (3, 1),
+            BoardSize::Medium => (5, 2),
+            BoardSize::Large => (7, 3),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoardTheme {
+    #[default]
+    Grey,
+    Brown,
+    Blue,
+    Green,
+    HighContrast,
+}
+
+impl BoardTheme {
+    const ALL: [BoardTheme; 5] = [
+        BoardTheme::Grey,
+        BoardTheme::Brown,
+        BoardTheme::Blue,
+        BoardTheme::Green,
+        BoardTheme::HighContrast,
+    ];
+
+    // True-colour shades are matched to the nearest palette entry on
+    // 256-colour terminals; high contrast sticks to the basic colours.
+    pub fn square(self, dark: bool) -> Color {
+        match (self, dark) {
+            (BoardTheme::Grey, false) => Color::RgbLowRes(4, 4, 4),
+            (BoardTheme::Grey, true) => Color::RgbLowRes(1, 1, 1),
+            (BoardTheme::Brown, false) => Color::Rgb(240, 217, 181),
+            (BoardTheme::Brown, true) => Color::Rgb(181, 136, 99),
+            (BoardTheme::Blue, false) => Color::Rgb(222, 227, 230),
+            (BoardTheme::Blue, true) => Color::Rgb(120, 150, 175),
+            (BoardTheme::Green, false) => Color::Rgb(238, 238, 210),
+            (BoardTheme::Green, true) => Color::Rgb(118, 150, 86),
+            (BoardTheme::HighContrast, false) => Color::Light(BaseColor::White),
+            (BoardTheme::HighContrast, true) => Color::Dark(BaseColor::Cyan),
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            BoardTheme::Grey => "grey",
+            BoardTheme::Brown => "brown",
+            BoardTheme::Blue => "blue",
+            BoardTheme::Green => "green",
+            BoardTheme::HighContrast => "high_contrast",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            BoardTheme::Grey => "Grey",
+            BoardTheme::Brown => "Brown",
+            BoardTheme::Blue => "Blue",
+            BoardTheme::Green => "Green",
+            BoardTheme::HighContrast => "High contrast",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Settings {
+    pub piece_values: PieceValues,
+    pub eval_perspective: EvalPerspective,
+    pub show_controls: bool,
+    pub cpu_tone: CpuTone,
+    pub cursor_edges: CursorEdges,
+    pub step_mode: bool,
+    pub friendly_click: FriendlyClick,
+    pub highlight_intensity: HighlightIntensity,
+    pub board_facing: BoardFacing,
+    pub move_preview: bool,
+    pub auto_queen: bool,
+    pub eval_bar: bool,
+    pub difficulty: Difficulty,
+    pub time_control: TimeControl,
+    pub last_variant: Variant,
+    // Minutes for each side, which differ in a game at time odds, and the
+    // seconds per move.
+    pub custom_clock: (ByColor<u64>, u64),
+    pub clock_mode: clock::Mode,
+    pub engine_path: String,
+    pub engine_skill: u8,
+    pub adaptive_level: u8,
+    // Set from the Engine Options dialog, by name. Options left at the
+    // engine's default are not kept.
+    pub engine_options: Vec<(String, String)>,
+    pub book_path: String,
+    pub book_moves: u32,
+    pub tablebase_path: String,
+    pub piece_style: PieceStyle,
+    pub board_size: BoardSize,
+    pub board_theme: BoardTheme,
+    // Neither clock runs until the game's first move is played.
+    pub clock_on_first_move: bool,
+    // Files above and ranks to the right of the board as well.
+    pub labels_all_sides: bool,
+    // The last move's number in a corner of its squares, on big enough squares.
+    pub last_move_numbers: bool,
+    // Promotions in the move list show the new piece as it looks on the board.
+    pub promotion_glyphs: bool,
+    // The CPU guesses your move and works out its answer while you think.
+    pub ponder: bool,
+    pub sound: Sound,
+    pub vim_keys: bool,
+    // Reads out each move and keeps colour from being the only cue.
+    pub announce_moves: bool,
+    pub language: Language,
+    // Read from its own file, and never written back.
+    pub keys: Keymap,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            piece_values: PieceValues::default(),
+            eval_perspective: EvalPerspective::default(),
+            show_controls: true,
+            cpu_tone: CpuTone::default(),
+            cursor_edges: CursorEdges::default(),
+            step_mode: false,
+            friendly_click: FriendlyClick::default(),
+            highlight_intensity: HighlightIntensity::default(),
+            board_facing: BoardFacing::default(),
+            move_preview: false,
+            auto_queen: false,
+            eval_bar: false,
+            difficulty: Difficulty::default(),
+            time_control: TimeControl::default(),
+            custom_clock: (
+                ByColor {
+                    white: 20,
+                    black: 20,
+                },
+                10,
+            ),
+            clock_mode: clock::Mode::default(),
+            last_variant: Variant::Chess,
+            engine_path: String::new(),
+            engine_skill: uci::MAX_SKILL,
+            adaptive_level: 2,
+            engine_options: Vec::new(),
+            book_path: String::new(),
+            book_moves: 10,
+            tablebase_path: String::new(),
+            piece_style: PieceStyle::default(),
+            board_size: BoardSize::default(),
+            board_theme: BoardTheme::default(),
+            clock_on_first_move: false,
+            labels_all_sides: false,
+            last_move_numbers: false,
+            promotion_glyphs: false,
+            ponder: false,
+            sound: Sound::default(),
+            vim_keys: false,
+            announce_moves: false,
+            language: Language::default(),
+            keys: Keymap::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn clock(&self) -> Option<(ByColor<u64>, u64)> {
+        match self.time_control {
+            TimeControl::Custom => Some(self.custom_clock),
+            time => time
+                .clock()
+                .map(|(minutes, increment)| (ByColor::new_with(|_| minutes), increment)),
+        }
+    }
+
+    // How many lines analysis shows, from the engine's MultiPV option.
+    pub fn multipv(&self) -> usize {
+        self.engine_options
+            .iter()
+            .find(|(name, _)| name == "MultiPV")
+            .and_then(|(_, value)| value.parse().ok())
+            .unwrap_or(1)
+            .clamp(1, MAX_MULTIPV)
+    }
+
+    pub fn load() -> Self {
+        let mut settings = Settings::default();
+        let contents = settings_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .unwrap_or_default();
+
+        for (key, value) in contents.lines().filter_map(|line| line.split_once('=')) {
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "eval_perspective" => match value {
+                    "white" => settings.eval_perspective = EvalPerspective::White,
+                    "side_to_move" => settings.eval_perspective = EvalPerspective::SideToMove,
+                    _ => {}
+                },
+                "cpu_tone" => {
+                    if let Some(&tone) = CpuTone::ALL.iter().find(|t| t.key() == value) {
+                        settings.cpu_tone = tone;
+                    }
+                }
+                "time_control" => {
+                    if let Some(&time) = TimeControl::ALL.iter().find(|t| t.key() == value) {
+                        settings.time_control = time;
+                    }
+                }
+                "last_variant" => {
+                    if let Ok(variant) = Variant::from_uci(value) {
+                        settings.last_variant = variant;
+                    }
+                }
+                "custom_clock" => {
+                    if let Some(clock) = parse_clock(value) {
+                        settings.custom_clock = clock;
+                    }
+                }
+                "clock_mode" => {
+                    if let Some(mode) = clock::Mode::from_key(value) {
+                        settings.clock_mode = mode;
+                    }
+                }
+                "difficulty" => {
+                    if let Some(&difficulty) = Difficulty::ALL.iter().find(|d| d.key() == value) {
+                        settings.difficulty = difficulty;
+                    }
+                }
+                "engine_path" => settings.engine_path = value.to_owned(),
+                "engine_skill" => {
+                    if let Ok(skill) = value.parse::<u8>() {
+                        settings.engine_skill = skill.min(uci::MAX_SKILL);
+                    }
+                }
+                "adaptive_level" => {
+                    if let Ok(level) = value.parse::<u8>() {
+                        settings.adaptive_level = level.min(MAX_ADAPTIVE_LEVEL);
+                    }
+                }
+                "engine_option" => {
+                    if let Some((name, value)) = value.split_once('=') {
+                        let option = (name.trim().to_owned(), value.trim().to_owned());
+                        settings.engine_options.push(option);
+                    }
+                }
+                "book_path" => settings.book_path = value.to_owned(),
+                "book_moves" => {
+                    if let Ok(moves) = value.parse::<u32>() {
+                        settings.book_moves = moves.min(MAX_BOOK_MOVES);
+                    }
+                }
+                "tablebase_path" => settings.tablebase_path = value.to_owned(),
+                "piece_style" => match value {
+                    "unicode" => settings.piece_style = PieceStyle::Unicode,
+                    "ascii" => settings.piece_style = PieceStyle::Ascii,
+                    "solid" => settings.piece_style = PieceStyle::Solid,
+                    _ => {}
+                },
+                "board_theme" => {
+                    if let Some(&theme) = BoardTheme::ALL.iter().find(|t| t.key() == value) {
+                        settings.board_theme = theme;
+                    }
+                }
+                "board_size" => match value {
+                    "fit" => settings.board_size = BoardSize::Fit,
+                    "small" => settings.board_size = BoardSize::Small,
+                    "medium" => settings.board_size = BoardSize::Medium,
+                    "large" => settings.board_size = BoardSize::Large,
+                    _ => {}
+                },
+                "sound" => match value {
+                    "off" => settings.sound = Sound::Off,
+                    "bell" => settings.sound = Sound::Bell,
+                    "files" => settings.sound = Sound::Files,
+                    _ => {}
+                },
+                "cursor_edges" => match value {
+                    "clamp" => settings.cursor_edges = CursorEdges::Clamp,
+                    "wrap" => settings.cursor_edges = CursorEdges::Wrap,
+                    _ => {}
+                },
+                "friendly_click" => match value {
+                    "reselect" => settings.friendly_click = FriendlyClick::Reselect,
+                    "deselect" => settings.friendly_click = FriendlyClick::Deselect,
+                    _ => {}
+                },
+                "highlight_intensity" => match value {
+                    "subtle" => settings.highlight_intensity = HighlightIntensity::Subtle,
+                    "normal" => settings.highlight_intensity = HighlightIntensity::Normal,
+                    "bold" => settings.highlight_intensity = HighlightIntensity::Bold,
+                    _ => {}
+                },
+                "board_facing" => match value {
+                    "white" => settings.board_facing = BoardFacing::White,
+                    "black" => settings.board_facing = BoardFacing::Black,
+                    "my_color" => settings.board_facing = BoardFacing::MyColor,
+                    "side_to_move" => settings.board_facing = BoardFacing::SideToMove,
+                    _ => {}
+                },
+                "show_controls" => {
+                    if let Ok(show) = value.parse() {
+                        settings.show_controls = show;
+                    }
+                }
+                "move_preview" => {
+                    if let Ok(preview) = value.parse() {
+                        settings.move_preview = preview;
+                    }
+                }
+                "eval_bar" => {
+                    if let Ok(bar) = value.parse() {
+                        settings.eval_bar = bar;
+                    }
+                }
+                "auto_queen" => {
+                    if let Ok(auto) = value.parse() {
+                        settings.auto_queen = auto;
+                    }
+                }
+                "step_mode" => {
+                    if let Ok(step) = value.parse() {
+                        settings.step_mode = step;
+                    }
+                }
+                "vim_keys" => {
+                    if let Ok(vim) = value.parse() {
+                        settings.vim_keys = vim;
+                    }
+                }
+                "language" => {
+                    if let Some(language) = Language::from_code(value) {
+                        settings.language = language;
+                    }
+                }
+                "clock_on_first_move" => {
+                    if let Ok(on) = value.parse() {
+                        settings.clock_on_first_move = on;
+                    }
+                }
+                "labels_all_sides" => {
+                    if let Ok(on) = value.parse() {
+                        settings.labels_all_sides = on;
+                    }
+                }
+                "last_move_numbers" => {
+                    if let Ok(on) = value.parse() {
+                        settings.last_move_numbers = on;
+                    }
+                }
+                "promotion_glyphs" => {
+                    if let Ok(on) = value.parse() {
+                        settings.promotion_glyphs = on;
+                    }
+                }
+                "ponder" => {
+                    if let Ok(on) = value.parse() {
+                        settings.ponder = on;
+                    }
+                }
+                "announce_moves" => {
+                    if let Ok(announce) = value.parse() {
+                        settings.announce_moves = announce;
+                    }
+                }
+                _ => {
+                    if let Some(&role) = VALUE_ROLES.iter().find(|&&r| role_name(r) == key) {
+                        if let Ok(v) = value.parse() {
+                            settings.piece_values.set(role, v);
+                        }
+                    }
+                }
+            }
+        }
+
+        settings.keys = Keymap::load(settings.vim_keys);
+        settings
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = settings_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut contents = String::new();
+        let perspective = match self.eval_perspective {
+            EvalPerspective::White => "white",
+            EvalPerspective::SideToMove => "side_to_move",
+        };
+        contents += &format!("eval_perspective = {}\n", perspective);
+        contents += &format!("show_controls = {}\n", self.show_controls);
+        contents += &format!("cpu_tone = {}\n", self.cpu_tone.key());
+        contents += &format!("difficulty = {}\n", self.difficulty.key());
+        contents += &format!("time_control = {}\n", self.time_control.key());
+        contents += &format!("last_variant = {}\n", self.last_variant.uci());
+        contents += &format!("custom_clock = {}\n", clock_text(self.custom_clock));
+        contents += &format!("clock_mode = {}\n", self.clock_mode.key());
+        contents += &format!("engine_path = {}\n", self.engine_path);
+        contents += &format!("engine_skill = {}\n", self.engine_skill);
+        contents += &format!("adaptive_level = {}\n", self.adaptive_level);
+        for (name, value) in &self.engine_options {
+            contents += &format!("engine_option = {}={}\n", name, value);
+        }
+        contents += &format!("book_path = {}\n", self.book_path);
+        contents += &format!("book_moves = {}\n", self.book_moves);
+        contents += &format!("tablebase_path = {}\n", self.tablebase_path);
+        let style = match self.piece_style {
+            PieceStyle::Unicode => "unicode",
+            PieceStyle::Ascii => "ascii",
+            PieceStyle::Solid => "solid",
+        };
+        contents += &format!("piece_style = {}\n", style);
+        let size = match self.board_size {
+            BoardSize::Fit => "fit",
+            BoardSize::Small => "small",
+            BoardSize::Medium => "medium",
+            BoardSize::Large => "large",
+        };
+        contents += &format!("board_size = {}\n", size);
+        contents += &format!("board_theme = {}\n", self.board_theme.key());
+        let sound = match self.sound {
+            Sound::Off => "off",
+            Sound::Bell => "bell",
+            Sound::Files => "files",
+        };
+        contents += &format!("sound = {}\n", sound);
+        let edges = match self.cursor_edges {
+            CursorEdges::Clamp => "clamp",
+            CursorEdges::Wrap => "wrap",
+        };
+        contents += &format!("cursor_edges = {}\n", edges);
+        contents += &format!("step_mode = {}\n", self.step_mode);
+        contents += &format!("vim_keys = {}\n", self.vim_keys);
+        contents += &format!("announce_moves = {}\n", self.announce_moves);
+        contents += &format!("language = {}\n", self.language.code());
+        contents += &format!("move_preview = {}\n", self.move_preview);
+        contents += &format!("auto_queen = {}\n", self.auto_queen);
+        contents += &format!("eval_bar = {}\n", self.eval_bar);
+        contents += &format!("ponder = {}\n", self.ponder);
+        contents += &format!("promotion_glyphs = {}\n", self.promotion_glyphs);
+        contents += &format!("last_move_numbers = {}\n", self.last_move_numbers);
+        contents += &format!("labels_all_sides = {}\n", self.labels_all_sides);
+        contents += &format!("clock_on_first_move = {}\n", self.clock_on_first_move);
+        let friendly_click = match self.friendly_click {
+            FriendlyClick::Reselect => "reselect",
+            FriendlyClick::Deselect => "deselect",
+        };
+        contents += &format!("friendly_click = {}\n", friendly_click);
+        let intensity = match self.highlight_intensity {
+            HighlightIntensity::Subtle => "subtle",
+            HighlightIntensity::Normal => "normal",
+            HighlightIntensity::Bold => "bold",
+        };
+        contents += &format!("highlight_intensity = {}\n", intensity);
+        let facing = match self.board_facing {
+            BoardFacing::White => "white",
+            BoardFacing::Black => "black",
+            BoardFacing::MyColor => "my_color",
+            BoardFacing::SideToMove => "side_to_move",
+        };
+        contents += &format!("board_facing = {}\n", facing);
+        for role in VALUE_ROLES {
+            contents += &format!("{} = {}\n", role_name(role), self.piece_values.get(role));
+        }
+
+        fs::write(path, contents)
+    }
+}
+
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join("terminal_chess_app").join(name))
+}
+
+fn settings_path() -> Option<PathBuf> {
+    config_file("settings")
+}
+
+pub fn current(siv: &mut Cursive) -> Settings {
+    siv.user_data::<Settings>().cloned().unwrap_or_default()
+}
+
+pub fn update(siv: &mut Cursive, f: impl FnOnce(&mut Settings)) {
+    let mut settings = current(siv);
+    f(&mut settings);
+    let _ = settings.save();
+    siv.set_user_data(settings);
+}
+
+pub fn show_settings(siv: &mut Cursive) {
+    let settings = current(siv);
+
+    let perspective = radio_list(
+        &[
+            (EvalPerspective::White, "White's view (+ is White)"),
+            (
+                EvalPerspective::SideToMove,
+                "Mover's view (+ is side to move)",
+            ),
+        ],
+        settings.eval_perspective,
+        |settings, value| settings.eval_perspective = value,
+    );
+
+    let tones = radio_list(
+        &CpuTone::ALL.map(|tone| (tone, tone.label())),
+        settings.cpu_tone,
+        |settings, value| settings.cpu_tone = value,
+    );
+
+    let edges = radio_list(
+        &[
+            (CursorEdges::Clamp, "Stop at the edge"),
+            (CursorEdges::Wrap, "Wrap to the other side"),
+        ],
+        settings.cursor_edges,
+        |settings, value| settings.cursor_edges = value,
+    );
+
+    let friendly_click = radio_list(
+        &[
+            (FriendlyClick::Reselect, "Select the new piece"),
+            (FriendlyClick::Deselect, "Drop the selection"),
+        ],
+        settings.friendly_click,
+        |settings, value| settings.friendly_click = value,
+    );
+
+    let intensity = radio_list(
+        &[
+            (HighlightIntensity::Subtle, "Subtle"),
+            (HighlightIntensity::Normal, "Normal"),
+            (HighlightIntensity::Bold, "Bold"),
+        ],
+        settings.highlight_intensity,
+        |settings, value| settings.highlight_intensity = value,
+    );
+
+    let facing = radio_list(
+        &[
+            (BoardFacing::White, "Always White"),
+            (BoardFacing::Black, "Always Black"),
+            (BoardFacing::MyColor, "My color"),
+            (BoardFacing::SideToMove, "Side to move"),
+        ],
+        settings.board_facing,
+        |settings, value| settings.board_facing = value,
+    );
+
+    let style = radio_list(
+        &[
+            (PieceStyle::Unicode, "Chess symbols"),
+            (PieceStyle::Ascii, "Letters (KQRBNP)"),
+            (PieceStyle::Solid, "Solid symbols in side colours"),
+        ],
+        settings.piece_style,
+        |settings, value| settings.piece_style = value,
+    );
+
+    let size = radio_list(
+        &[
+            (BoardSize::Fit, "Fit to the window"),
+            (BoardSize::Small, "Small"),
+            (BoardSize::Medium, "Medium"),
+            (BoardSize::Large, "Large"),
+        ],
+        settings.board_size,
+        |settings, value| settings.board_size = value,
+    );
+
+    let theme = radio_list(
+        &BoardTheme::ALL.map(|t| (t, t.label())),
+        settings.board_theme,
+        |settings, value| settings.board_theme = value,
+    );
+
+    let sound = radio_list(
+        &[
+            (Sound::Off, "Off"),
+            (Sound::Bell, "Terminal bell"),
+            (Sound::Files, "Sound files, else the bell"),
+        ],
+        settings.sound,
+        |settings, value| settings.sound = value,
+    );
+
+    // The main menu keeps the language it was opened in until a restart.
+    let language = radio_list(
+        &Language::ALL.map(|language| (language, language.label())),
+        settings.language,
+        |settings, value| {
+            settings.language = value;
+            lang::set(value);
+        },
+    );
+
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Settings"))
+            .content(
+                ListView::new()
+                    .child(tr("Language"), language)
+                    .child(tr("Evaluation"), perspective)
+                    .child(tr("CPU messages"), tones)
+                    .child(tr("Cursor edges"), edges)
+                    .child(tr("Clicking another own piece"), friendly_click)
+                    .child(tr("Pieces"), style)
+                    .child(tr("Board size"), size)
+                    .child(tr("Board colours"), theme)
+                    .child(tr("Highlights"), intensity)
+                    .child(tr("Board faces"), facing)
+                    .child(tr("Sounds"), sound)
+                    .child(
+                        "Engine path",
+                        EditView::new()
+                            .content(settings.engine_path.clone())
+                            .on_edit(|s, text, _| {
+                                update(s, |settings| settings.engine_path = text.to_owned())
+                            })
+                            .fixed_width(30),
+                    )
+                    .child(
+                        "Engine skill",
+                        SliderView::horizontal(usize::from(uci::MAX_SKILL) + 1)
+                            .value(usize::from(settings.engine_skill))
+                            .on_change(|s, skill| {
+                                update(s, |settings| settings.engine_skill = skill as u8)
+                            }),
+                    )
+                    .child(
+                        "Opening book",
+                        EditView::new()
+                            .content(settings.book_path.clone())
+                            .on_edit(|s, text, _| {
+                                update(s, |settings| settings.book_path = text.to_owned())
+                            })
+                            .fixed_width(30),
+                    )
+                    .child(
+                        "Book moves",
+                        SliderView::horizontal(MAX_BOOK_MOVES as usize + 1)
+                            .value(settings.book_moves as usize)
+                            .on_change(|s, moves| {
+                                update(s, |settings| settings.book_moves = moves as u32)
+                            }),
+                    )
+                    .child(
+                        "Syzygy tablebases",
+                        EditView::new()
+                            .content(settings.tablebase_path.clone())
+                            .on_edit(|s, text, _| {
+                                update(s, |settings| settings.tablebase_path = text.to_owned())
+                            })
+                            .fixed_width(30),
+                    )
+                    .child(
+                        tr("Step mode"),
+                        Checkbox::new()
+                            .with_checked(settings.step_mode)
+                            .on_change(|s, checked| {
+                                update(s, |settings| settings.step_mode = checked)
+                            }),
+                    )
+                    .child(
+                        "Move previews",
+                        Checkbox::new()
+                            .with_checked(settings.move_preview)
+                            .on_change(|s, checked| {
+                                update(s, |settings| settings.move_preview = checked)
+                            }),
+                    )
+                    .child(
+                        tr("Evaluation bar"),
+                        Checkbox::new()
+                            .with_checked(settings.eval_bar)
+                            .on_change(|s, checked| {
+                                update(s, |settings| settings.eval_bar = checked)
+                            }),
+                    )
+                    .child(
+                        "Auto-queen",
+                        Checkbox::new().with_checked(settings.auto_queen).on_change(
+                            |s, checked| update(s, |settings| settings.auto_queen = checked),
+                        ),
+                    )
+                    .child(
+                        "Vim keys",
+                        Checkbox::new()
+                            .with_checked(settings.vim_keys)
+                            .on_change(|s, checked| {
+                                update(s, |settings| {
+                                    settings.vim_keys = checked;
+                                    settings.keys = Keymap::load(checked);
+                                })
+                            }),
+                    )
+                    .child(
+                        tr("Announce moves"),
+                        Checkbox::new()
+                            .with_checked(settings.announce_moves)
+                            .on_change(|s, checked| {
+                                update(s, |settings| settings.announce_moves = checked)
+                            }),
+                    )
+                    .child(
+                        "Clocks start on move 1",
+                        Checkbox::new()
+                            .with_checked(settings.clock_on_first_move)
+                            .on_change(|s, checked| {
+                                update(s, |settings| settings.clock_on_first_move = checked)
+                            }),
+                    )
+                    .child(
+                        "Coordinates on all sides",
+                        Checkbox::new()
+                            .with_checked(settings.labels_all_sides)
+                            .on_change(|s, checked| {
+                                update(s, |settings| settings.labels_all_sides = checked)
+                            }),
+                    )
+                    .child(
+                        "Move number on last move",
+                        Checkbox::new()
+                            .with_checked(settings.last_move_numbers)
+                            .on_change(|s, checked| {
+                                update(s, |settings| settings.last_move_numbers = checked)
+                            }),
+                    )
+                    .child(
+                        "Promotion glyph in move list",
+                        Checkbox::new()
+                            .with_checked(settings.promotion_glyphs)
+                            .on_change(|s, checked| {
+                                update(s, |settings| settings.promotion_glyphs = checked)
+                            }),
+                    )
+                    .child(
+                        "CPU thinks on your time",
+                        Checkbox::new()
+                            .with_checked(settings.ponder)
+                            .on_change(|s, checked| {
+                                update(s, |settings| settings.ponder = checked)
+                            }),
+                    )
+                    .child(
+                        "Show controls",
+                        Checkbox::new()
+                            .with_checked(settings.show_controls)
+                            .on_change(|s, checked| {
+                                update(s, |settings| settings.show_controls = checked)
+                            }),
+                    )
+                    .scrollable(),
+            )
+            .button(tr("Piece Values"), show_piece_values)
+            .button(tr("Engine Options"), show_engine_options)
+            .button(tr("Key Bindings"), keymap::show_bindings)
+            .dismiss_button(tr("Back")),
+    );
+}
+
+pub const MAX_CLOCK_MINUTES: u64 = 180;
+pub const MAX_BOOK_MOVES: u32 = 20;
+pub const MAX_MULTIPV: usize = 5;
+pub const MAX_ADAPTIVE_LEVEL: u8 = 6;
+
+// "minutes+increment", as in 15+10, or "white/black+increment" to give
+// the sides different times, as in 10/5+3.
+pub fn parse_clock(text: &str) -> Option<(ByColor<u64>, u64)> {
+    let (minutes, increment) = text.trim().split_once('+')?;
+    let (white, black) = minutes.split_once('/').unwrap_or((minutes, minutes));
+    let minutes = ByColor {
+        white: white.trim().parse().ok()?,
+        black: black.trim().parse().ok()?,
+    };
+    let increment = increment.trim().parse().ok()?;
+    [minutes.white, minutes.black]
+        .iter()
+        .all(|m| (1..=MAX_CLOCK_MINUTES).contains(m))
+        .then_some((minutes, increment))
+}
+
+pub fn clock_text((minutes, increment): (ByColor<u64>, u64)) -> String {
+    if minutes.white == minutes.black {
+        format!("{}+{}", minutes.white, increment)
+    } else {
+        format!("{}/{}+{}", minutes.white, minutes.black, increment)
+    }
+}
+
+pub fn radio_list<T: Copy + PartialEq + 'static>(
+    options: &[(T, &str)],
+    selected: T,
+    set: fn(&mut Settings, T),
+) -> LinearLayout {
+    let mut group = RadioGroup::new();
+    group.set_on_change(move |s, &value: &T| update(s, |settings| set(settings, value)));
+
+    let mut list = LinearLayout::vertical();
+    for &(value, label) in options {
+        let mut button = group.button(value, label);
+        if value == selected {
+            button.select();
+        }
+        list.add_child(button);
+    }
+    list
+}
+
+const VALUE_ROLES: [Role; 5] = [
+    Role::Pawn,
+    Role::Knight,
+    Role::Bishop,
+    Role::Rook,
+    Role::Queen,
+];
+
+fn show_piece_values(siv: &mut Cursive) {
+    let values = current(siv).piece_values;
+
+    let mut list = ListView::new();
+    for role in VALUE_ROLES {
+        list.add_child(
+            role_name(role),
+            EditView::new()
+                .content(values.get(role).to_string())
+                .with_name(role_name(role)),
+        );
+    }
+
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Piece Values"))
+            .content(list)
+            .button(tr("Save"), |s| {
+                let mut values = PieceValues::default();
+                for role in VALUE_ROLES {
+                    let content = s
+                        .call_on_name(role_name(role), |v: &mut EditView| v.get_content())
+                        .unwrap();
+                    match content.trim().parse::<f32>() {
+                        Ok(value) if value >= 0.0 => values.set(role, value),
+                        _ => {
+                            s.add_layer(Dialog::info(fill(
+                                "Invalid value for {}",
+                                &[tr(role_name(role))],
+                            )));
+                            return;
+                        }
+                    }
+                }
+                update(s, |settings| settings.piece_values = values);
+                s.pop_layer();
+            })
+            .button(tr("Defaults"), |s| {
+                for role in VALUE_ROLES {
+                    let value = PieceValues::default().get(role);
+                    s.call_on_name(role_name(role), |v: &mut EditView| {
+                        v.set_content(value.to_string())
+                    });
+                }
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
+
+fn option_view(option: &EngineOption, value: &str) -> Box<dyn View> {
+    let name = format!("option {}", option.name);
+    match option.kind.as_str() {
+        "check" => Box::new(
+            Checkbox::new()
+                .with_checked(value == "true")
+                .with_name(name),
+        ),
+        "combo" => {
+            let mut choices = SelectView::new().popup();
+            choices.add_all_str(&option.choices);
+            if let Some(i) = option.choices.iter().position(|c| c == value) {
+                choices.set_selection(i);
+            }
+            Box::new(choices.with_name(name))
+        }
+        _ => Box::new(
+            EditView::new()
+                .content(value)
+                .with_name(name)
+                .fixed_width(20),
+        ),
+    }
+}
+
+fn option_value(siv: &mut Cursive, option: &EngineOption) -> String {
+    let name = format!("option {}", option.name);
+    match option.kind.as_str() {
+        "check" => siv
+            .call_on_name(&name, |v: &mut Checkbox| v.is_checked().to_string())
+            .unwrap_or_default(),
+        "combo" => siv
+            .call_on_name(&name, |v: &mut SelectView| v.selection())
+            .flatten()
+            .map_or(String::new(), |choice| (*choice).clone()),
+        _ => siv
+            .call_on_name(&name, |v: &mut EditView| v.get_content().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+// The engine is started to ask for its options, and quits once they are in.
+fn show_engine_options(siv: &mut Cursive) {
+    let settings = current(siv);
+    let Some(path) = uci::engine_path(&settings.engine_path) else {
+        return siv.add_layer(Dialog::info(tr(
+            "No engine path set (Settings or CHESS_ENGINE).",
+        )));
+    };
+    let options: Vec<EngineOption> =
+        match UciEngine::spawn(&path, settings.engine_skill, &settings.tablebase_path, &[]) {
+            Ok(engine) => engine
+                .options
+                .iter()
+                .filter(|option| option.kind != "button")
+                .cloned()
+                .collect(),
+            Err(e) => return siv.add_layer(Dialog::info(fill("Engine options: {}", &[&e]))),
+        };
+    if options.is_empty() {
+        return siv.add_layer(Dialog::info(tr("The engine has no options to set.")));
+    }
+
+    let mut list = ListView::new();
+    for option in &options {
+        let value = settings
+            .engine_options
+            .iter()
+            .find(|(name, _)| *name == option.name)
+            .map_or(option.default.as_str(), |(_, value)| value.as_str());
+        let label = match (option.min, option.max) {
+            (Some(min), Some(max)) => format!("{} ({}-{})", option.name, min, max),
+            _ => option.name.clone(),
+        };
+        list.add_child(&label, option_view(option, value));
+    }
+
+    let defaults = options.clone();
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Engine Options"))
+            .content(list.scrollable())
+            .button(tr("Save"), move |s| {
+                let mut chosen = Vec::new();
+                for option in &options {
+                    match option.check(&option_value(s, option)) {
+                        Ok(value) if value == option.default => {}
+                        Ok(value) => chosen.push((option.name.clone(), value)),
+                        Err(e) => return s.add_layer(Dialog::info(e)),
+                    }
+                }
+                update(s, |settings| settings.engine_options = chosen);
+                s.pop_layer();
+            })
+            .button(tr("Defaults"), move |s| {
+                for option in &defaults {
+                    let name = format!("option {}", option.name);
+                    s.call_on_name(&name, |v: &mut Checkbox| {
+                        v.set_checked(option.default == "true")
+                    });
+                    s.call_on_name(&name, |v: &mut SelectView| {
+                        if let Some(i) = option.choices.iter().position(|c| *c == option.default) {
+                            v.set_selection(i);
+                        }
+                    });
+                    s.call_on_name(&name, |v: &mut EditView| {
+                        v.set_content(option.default.clone())
+                    });
+                }
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
@@ -0,0 +1,75 @@
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+    thread,
+};
+
+use crate::library::data_dir;
+use crate::settings::Sound;
+
+#[derive(Clone, Copy)]
+pub enum Cue {
+    Move,
+    Capture,
+    Check,
+    LowTime,
+    GameEnd,
+}
+
+impl Cue {
+    fn name(self) -> &'static str {
+        match self {
+            Cue::Move => "move",
+            Cue::Capture => "capture",
+            Cue::Check => "check",
+            Cue::LowTime => "low_time",
+            Cue::GameEnd => "game_end",
+        }
+    }
+}
+
+// Tried in turn until one of them starts.
+const PLAYERS: [(&str, &[&str]); 3] = [("paplay", &[]), ("aplay", &["-q"]), ("afplay", &[])];
+
+fn bell() {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(b"\x07").and_then(|()| stdout.flush());
+}
+
+// Sound files are looked for as sounds/<cue>.wav in the data directory.
+fn play_file(cue: Cue) -> bool {
+    let Some(path) = data_dir()
+        .map(|dir| dir.join("sounds").join(format!("{}.wav", cue.name())))
+        .filter(|path| path.is_file())
+    else {
+        return false;
+    };
+    PLAYERS.iter().any(|(program, args)| {
+        let child = Command::new(program)
+            .args(*args)
+            .arg(&path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match child {
+            Ok(mut child) => {
+                thread::spawn(move || child.wait());
+                true
+            }
+            Err(_) => false,
+        }
+    })
+}
+
+// A missing sound file or player falls back to the bell.
+pub fn play(sound: Sound, cue: Cue) {
+    match sound {
+        Sound::Off => {}
+        Sound::Bell => bell(),
+        Sound::Files => {
+            if !play_file(cue) {
+                bell();
+            }
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use cursive::{
+    direction::Direction,
+    event::{Event, EventResult, Key},
+    theme::{BaseColor, Color},
+    view::{CannotFocus, Nameable, Resizable, Scrollable},
+    views::{Dialog, EditView, LinearLayout, Panel, SelectView, TextView},
+    CbSink, Cursive, Printer, Vec2,
+};
+use shakmaty::{
+    fen::Fen,
+    san::SanPlus,
+    uci::Uci,
+    zobrist::{Zobrist64, ZobristHash},
+    Bitboard, ByColor, Chess, Color as CColor, EnPassantMode, Move, Outcome, Position,
+};
+
+use crate::engine;
+use crate::lang::tr;
+use crate::logic::{color_name, PieceValues, UCI_DEPTH};
+use crate::pgn;
+use crate::replay::draw_board;
+use crate::settings::{self, BoardTheme, PieceStyle};
+use crate::stats::{self, Entry, Side};
+use crate::uci::{self, UciEngine};
+
+const DEPTHS: [u32; 4] = [1, 2, 3, 4];
+const DELAY_STEP: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(5);
+
+// Each match gets its own id, so a move that arrives after the window was
+// closed cannot land in the next match.
+static MATCHES: AtomicUsize = AtomicUsize::new(0);
+
+pub enum Player {
+    BuiltIn(u32, PieceValues),
+    Engine(String, UciEngine),
+}
+
+impl Player {
+    pub fn name(&self) -> String {
+        match self {
+            Player::BuiltIn(depth, _) => format!("Built-in AI, depth {}", depth),
+            Player::Engine(name, _) => name.clone(),
+        }
+    }
+
+    pub fn choose(&mut self, pos: &Chess) -> Result<Move, String> {
+        match self {
+            Player::BuiltIn(depth, values) => engine::search(pos, *depth, values)
+                .best
+                .ok_or_else(|| "no move found".to_owned()),
+            Player::Engine(_, engine) => {
+                let fen = Fen::from_position(pos.clone(), EnPassantMode::Legal).to_string();
+                let reply = engine.best_move(&fen, UCI_DEPTH)?;
+                Uci::from_ascii(reply.as_bytes())
+                    .ok()
+                    .and_then(|uci| uci.to_move(pos).ok())
+                    .ok_or_else(|| format!("engine played an illegal move: {}", reply))
+            }
+        }
+    }
+}
+
+pub struct MatchView {
+    id: usize,
+    pos: Chess,
+    last_move: Option<Move>,
+    sans: Vec<String>,
+    seen: Vec<u64>,
+    // A player is away while it thinks, and gone for good if it failed.
+    players: ByColor<Option<Player>>,
+    names: ByColor<String>,
+    thinking: bool,
+    paused: bool,
+    step: bool,
+    delay: Duration,
+    last: Instant,
+    result: Option<(&'static str, &'static str)>,
+    note: String,
+    style: PieceStyle,
+    theme: BoardTheme,
+}
+
+impl MatchView {
+    fn new(players: ByColor<Player>, style: PieceStyle, theme: BoardTheme) -> Self {
+        let pos = Chess::default();
+        MatchView {
+            id: MATCHES.fetch_add(1, Ordering::SeqCst),
+            seen: vec![pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0],
+            pos,
+            last_move: None,
+            sans: Vec::new(),
+            names: ByColor {
+                white: players.white.name(),
+                black: players.black.name(),
+            },
+            players: ByColor {
+                white: Some(players.white),
+                black: Some(players.black),
+            },
+            thinking: false,
+            paused: false,
+            step: false,
+            delay: Duration::from_secs(1),
+            last: Instant::now(),
+            result: None,
+            note: String::new(),
+            style,
+            theme,
+        }
+    }
+
+    fn title(&self) -> String {
+        format!("{} vs {}", self.names.white, self.names.black)
+    }
+
+    fn movetext(&self) -> String {
+        pgn::numbered(&Chess::default(), &self.sans)
+    }
+
+    fn status(&self) -> String {
+        let turn = color_name(self.pos.turn());
+        match self.result {
+            Some((result, reason)) => format!("{}, {}", result, reason),
+            None if self.players.get(self.pos.turn()).is_none() && !self.thinking => {
+                "Match stopped".to_owned()
+            }
+            None if self.thinking => format!("{} is thinking", turn),
+            None if self.paused => "Paused".to_owned(),
+            None => format!("{} to move", turn),
+        }
+    }
+
+    fn ready(&self) -> bool {
+        !self.thinking
+            && self.result.is_none()
+            && self.players.get(self.pos.turn()).is_some()
+            && (self.step || (!self.paused && self.last.elapsed() >= self.delay))
+    }
+
+    fn tick(&mut self, cb_sink: &CbSink) {
+        if !self.ready() {
+            return;
+        }
+        let mut player = self.players.get_mut(self.pos.turn()).take().unwrap();
+        let (id, pos, cb_sink) = (self.id, self.pos.clone(), cb_sink.clone());
+        self.thinking = true;
+        self.step = false;
+        thread::spawn(move || {
+            let choice = player.choose(&pos);
+            let _ = cb_sink.send(Box::new(move |s| receive(s, id, player, choice)));
+        });
+    }
+
+    fn receive(&mut self, player: Player, choice: Result<Move, String>) {
+        self.thinking = false;
+        self.last = Instant::now();
+        let mover = self.pos.turn();
+        match choice {
+            Ok(mv) if self.pos.is_legal(&mv) => {
+                *self.players.get_mut(mover) = Some(player);
+                self.sans
+                    .push(SanPlus::from_move(self.pos.clone(), &mv).to_string());
+                self.pos.play_unchecked(&mv);
+                self.seen
+                    .push(self.pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0);
+                self.last_move = Some(mv);
+                self.check_over();
+            }
+            Ok(mv) => self.note = format!("{} played an illegal move: {}", player.name(), mv),
+            Err(e) => self.note = format!("{} stopped: {}", player.name(), e),
+        }
+    }
+
+    fn check_over(&mut self) {
+        let hash = self.seen.last().copied();
+        let repeated = self.seen.iter().filter(|&&h| Some(h) == hash).count();
+        self.result = match self.pos.outcome() {
+            Some(Outcome::Decisive { winner }) => Some((winner.fold_wb("1-0", "0-1"), "checkmate")),
+            Some(Outcome::Draw) if self.pos.is_stalemate() => Some(("1/2-1/2", "stalemate")),
+            Some(Outcome::Draw) => Some(("1/2-1/2", "insufficient material")),
+            None if self.pos.halfmoves() >= 100 => Some(("1/2-1/2", "fifty-move rule")),
+            None if repeated >= 3 => Some(("1/2-1/2", "threefold repetition")),
+            None => None,
+        };
+        if let Some((result, _)) = self.result {
+            let tags = pgn::seven_tags(
+                "Engine match",
+                self.names.white.clone(),
+                self.names.black.clone(),
+                result,
+            );
+            let entry = Entry {
+                result: result.to_owned(),
+                side: Side::Engines,
+                variant: "Standard".to_owned(),
+            };
+            self.note =
+                match stats::append_game(&pgn::write(tags, &self.movetext(), result), &entry) {
+                    Ok(()) => "Saved to the game database".to_owned(),
+                    Err(e) => e,
+                };
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.last = Instant::now();
+    }
+
+    fn step(&mut self) {
+        self.paused = true;
+        self.step = true;
+    }
+
+    fn slower(&mut self) {
+        self.delay = (self.delay + DELAY_STEP).min(MAX_DELAY);
+    }
+
+    fn faster(&mut self) {
+        self.delay = self.delay.saturating_sub(DELAY_STEP);
+    }
+}
+
+impl cursive::view::View for MatchView {
+    fn draw(&self, printer: &Printer) {
+        let last_move: Bitboard = match &self.last_move {
+            Some(mv) => mv.from().into_iter().chain([mv.to()]).collect(),
+            None => Bitboard::EMPTY,
+        };
+        draw_board(
+            printer,
+            self.pos.board(),
+            self.style,
+            self.theme,
+            &[],
+            |sq| {
+                last_move
+                    .contains(sq)
+                    .then_some(Color::Dark(BaseColor::Green))
+            },
+        );
+
+        printer.print((0, 10), &self.status());
+        printer.print((0, 11), &format!("Delay {:.1}s", self.delay.as_secs_f32()));
+        printer.print((0, 12), &self.note);
+    }
+
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::Consumed(None))
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        match event {
+            Event::Char(' ' | 'p') => self.toggle_pause(),
+            Event::Char('n') | Event::Key(Key::Right) => self.step(),
+            Event::Char('+') => self.slower(),
+            Event::Char('-') => self.faster(),
+            _ => return EventResult::Ignored,
+        }
+        EventResult::Consumed(None)
+    }
+
+    fn required_size(&mut self, _: Vec2) -> Vec2 {
+        Vec2::new(34, 13)
+    }
+}
+
+fn receive(siv: &mut Cursive, id: usize, player: Player, choice: Result<Move, String>) {
+    let moves = siv
+        .call_on_name("spectate", |view: &mut MatchView| {
+            (view.id == id).then(|| {
+                view.receive(player, choice);
+                view.movetext()
+            })
+        })
+        .flatten();
+    if let Some(moves) = moves {
+        siv.call_on_name("spectate_moves", |v: &mut TextView| v.set_content(moves));
+    }
+}
+
+pub fn tick(siv: &mut Cursive) {
+    let cb_sink = siv.cb_sink().clone();
+    siv.call_on_name("spectate", |view: &mut MatchView| view.tick(&cb_sink));
+}
+
+fn control(siv: &mut Cursive, f: fn(&mut MatchView)) {
+    siv.call_on_name("spectate", f);
+}
+
+fn open_match(siv: &mut Cursive, players: ByColor<Player>) {
+    let settings = settings::current(siv);
+    let view = MatchView::new(players, settings.piece_style, settings.board_theme);
+    let title = view.title();
+    siv.set_fps(4);
+
+    siv.add_layer(
+        Dialog::new()
+            .title(title)
+            .content(
+                LinearLayout::horizontal()
+                    .child(Panel::new(view.with_name("spectate")))
+                    .child(
+                        Panel::new(TextView::new("").with_name("spectate_moves").scrollable())
+                            .title(tr("Moves"))
+                            .fixed_size((24, 15)),
+                    ),
+            )
+            .button(tr("Pause"), |s| control(s, MatchView::toggle_pause))
+            .button(tr("Step"), |s| control(s, MatchView::step))
+            .button(tr("Slower"), |s| control(s, MatchView::slower))
+            .button(tr("Faster"), |s| control(s, MatchView::faster))
+            .button(tr("Close"), |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn side_picker(color: CColor, engine_path: &str) -> LinearLayout {
+    let key = color.fold_wb("white", "black");
+    let mut player = SelectView::<Option<u32>>::new().popup();
+    for depth in DEPTHS {
+        player.add_item(format!("Built-in AI, depth {}", depth), Some(depth));
+    }
+    player.add_item("External engine", None);
+
+    LinearLayout::vertical()
+        .child(TextView::new(format!("\n{}:", color_name(color))))
+        .child(player.with_name(format!("{}_player", key)))
+        .child(TextView::new("Engine path:"))
+        .child(
+            EditView::new()
+                .content(engine_path)
+                .with_name(format!("{}_engine", key))
+                .fixed_width(40),
+        )
+}
+
+fn chosen_player(siv: &mut Cursive, color: CColor) -> Result<Player, String> {
+    let key = color.fold_wb("white", "black");
+    let depth = siv
+        .call_on_name(
+            &format!("{}_player", key),
+            |v: &mut SelectView<Option<u32>>| v.selection().and_then(|depth| *depth),
+        )
+        .unwrap();
+    if let Some(depth) = depth {
+        let values = settings::current(siv).piece_values;
+        return Ok(Player::BuiltIn(depth, values));
+    }
+
+    let path = siv
+        .call_on_name(&format!("{}_engine", key), |v: &mut EditView| {
+            v.get_content()
+        })
+        .unwrap();
+    let path = path.trim();
+    if path.is_empty() {
+        return Err(format!("No engine path set for {}.", color_name(color)));
+    }
+    let settings = settings::current(siv);
+    let engine = UciEngine::spawn(
+        path,
+        settings.engine_skill,
+        &settings.tablebase_path,
+        &settings.engine_options,
+    )
+    .map_err(|e| format!("{} engine: {}", color_name(color), e))?;
+    let name = Path::new(path)
+        .file_name()
+        .map_or(path.to_owned(), |name| name.to_string_lossy().into_owned());
+    Ok(Player::Engine(name, engine))
+}
+
+pub fn show_setup(siv: &mut Cursive) {
+    let settings = settings::current(siv);
+    let engine_path = uci::engine_path(&settings.engine_path).unwrap_or_default();
+
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Watch Engines"))
+            .content(
+                LinearLayout::vertical()
+                    .child(TextView::new(
+                        "Space pauses, N steps one move, + and - change the delay.",
+                    ))
+                    .child(side_picker(CColor::White, &engine_path))
+                    .child(side_picker(CColor::Black, &engine_path)),
+            )
+            .button(tr("Start"), |s| {
+                let players = chosen_player(s, CColor::White).and_then(|white| {
+                    Ok(ByColor {
+                        white,
+                        black: chosen_player(s, CColor::Black)?,
+                    })
+                });
+                match players {
+                    Ok(players) => {
+                        s.pop_layer();
+                        open_match(s, players);
+                    }
+                    Err(e) => s.add_layer(Dialog::info(e)),
+                }
+            })
+            .dismiss_button(tr("Back")),
+    );
+}
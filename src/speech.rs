@@ -0,0 +1,71 @@
+use shakmaty::{san::SanPlus, variant::VariantPosition, Move, Position, Role};
+
+use crate::logic::color_name;
+
+fn role_word(role: Role) -> &'static str {
+    match role {
+        Role::Pawn => "pawn",
+        Role::Knight => "knight",
+        Role::Bishop => "bishop",
+        Role::Rook => "rook",
+        Role::Queen => "queen",
+        Role::King => "king",
+    }
+}
+
+// "White knight from g1 to f3, check", as a screen reader would say it.
+pub fn sentence(before: &VariantPosition, mv: &Move) -> String {
+    let mover = color_name(before.turn());
+    let foe = color_name(!before.turn()).to_lowercase();
+    let mut text = match mv {
+        Move::Castle { king, rook } => {
+            let side = if rook.file() > king.file() {
+                "kingside"
+            } else {
+                "queenside"
+            };
+            format!("{} castles {}", mover, side)
+        }
+        Move::Put { role, to } => format!("{} drops a {} on {}", mover, role_word(*role), to),
+        Move::Normal {
+            role,
+            from,
+            capture,
+            to,
+            promotion,
+        } => {
+            let mut text = format!("{} {} from {} to {}", mover, role_word(*role), from, to);
+            if let Some(taken) = capture {
+                text += &format!(", takes {} {}", foe, role_word(*taken));
+            }
+            if let Some(role) = promotion {
+                text += &format!(", promotes to {}", role_word(*role));
+            }
+            text
+        }
+        Move::EnPassant { from, to } => format!(
+            "{} pawn from {} to {}, takes {} pawn en passant",
+            mover, from, to, foe
+        ),
+    };
+    let san = SanPlus::from_move(before.clone(), mv).to_string();
+    if san.ends_with('#') {
+        text += ", checkmate";
+    } else if san.ends_with('+') {
+        text += ", check";
+    }
+    text
+}
+
+// What stands on a square, as in "g1: white knight".
+pub fn piece_on(pos: &VariantPosition, sq: shakmaty::Square) -> String {
+    match pos.board().piece_at(sq) {
+        Some(piece) => format!(
+            "{}: {} {}",
+            sq,
+            color_name(piece.color).to_lowercase(),
+            role_word(piece.role)
+        ),
+        None => format!("{}: empty", sq),
+    }
+}
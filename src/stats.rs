@@ -0,0 +1,355 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use cursive::{
+    view::Scrollable,
+    views::{Dialog, TextView},
+    Cursive,
+};
+use shakmaty::Color;
+
+use crate::lang::tr;
+use crate::library::data_dir;
+use crate::logic::BoardView;
+use crate::pgn;
+use crate::settings::{self, MAX_ADAPTIVE_LEVEL};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Human(Color),
+    TwoPlayer,
+    Engines,
+}
+
+impl Side {
+    fn key(self) -> &'static str {
+        match self {
+            Side::Human(Color::White) => "white",
+            Side::Human(Color::Black) => "black",
+            Side::TwoPlayer => "two",
+            Side::Engines => "engines",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "white" => Some(Side::Human(Color::White)),
+            "black" => Some(Side::Human(Color::Black)),
+            "two" => Some(Side::TwoPlayer),
+            "engines" => Some(Side::Engines),
+            _ => None,
+        }
+    }
+}
+
+pub struct Entry {
+    pub result: String,
+    pub side: Side,
+    pub variant: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Score {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Entry {
+    fn score(&self) -> Option<Score> {
+        let Side::Human(human) = self.side else {
+            return None;
+        };
+        match (self.result.as_str(), human) {
+            ("1-0", Color::White) | ("0-1", Color::Black) => Some(Score::Win),
+            ("1-0", Color::Black) | ("0-1", Color::White) => Some(Score::Loss),
+            ("1/2-1/2", _) => Some(Score::Draw),
+            _ => None,
+        }
+    }
+}
+
+fn database_dir() -> io::Result<PathBuf> {
+    let dir =
+        data_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn append(path: &Path, text: &str) -> io::Result<()> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+}
+
+// Games are only ever appended to games.pgn. Each one also gets a line in
+// games.idx with its byte offset there, so stats never parse the PGN.
+pub fn append_game(pgn: &str, entry: &Entry) -> Result<(), String> {
+    let write = || -> io::Result<()> {
+        let dir = database_dir()?;
+        let path = dir.join("games.pgn");
+        let offset = fs::metadata(&path).map_or(0, |meta| meta.len());
+        append(&path, &format!("{}\n", pgn))?;
+        append(
+            &dir.join("games.idx"),
+            &format!(
+                "{} {} {} {}\n",
+                offset,
+                entry.result,
+                entry.side.key(),
+                entry.variant
+            ),
+        )
+    };
+    write().map_err(|e| format!("Could not save the game: {}", e))
+}
+
+fn read_index() -> Vec<Entry> {
+    let text = data_dir()
+        .and_then(|dir| fs::read_to_string(dir.join("games.idx")).ok())
+        .unwrap_or_default();
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, ' ').skip(1);
+            Some(Entry {
+                result: fields.next()?.to_owned(),
+                side: Side::from_key(fields.next()?)?,
+                variant: fields.next()?.to_owned(),
+            })
+        })
+        .collect()
+}
+
+pub const START_RATING: i32 = 1200;
+const K_FACTOR: f64 = 32.0;
+
+struct Rated {
+    rating: i32,
+}
+
+// One line per rated game: the new rating, the CPU's and the result.
+fn read_ratings() -> Vec<Rated> {
+    let text = data_dir()
+        .and_then(|dir| fs::read_to_string(dir.join("ratings")).ok())
+        .unwrap_or_default();
+    text.lines()
+        .filter_map(|line| {
+            Some(Rated {
+                rating: line.split(' ').next()?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+pub fn rating() -> i32 {
+    read_ratings().last().map_or(START_RATING, |r| r.rating)
+}
+
+pub fn rating_text() -> String {
+    format!("Rating {}", rating())
+}
+
+fn rate(rating: i32, opponent: i32, score: Score) -> i32 {
+    let expected = 1.0 / (1.0 + 10f64.powf(f64::from(opponent - rating) / 400.0));
+    let actual = match score {
+        Score::Win => 1.0,
+        Score::Loss => 0.0,
+        Score::Draw => 0.5,
+    };
+    rating + (K_FACTOR * (actual - expected)).round() as i32
+}
+
+fn update_rating(opponent: i32, entry: &Entry) -> Result<i32, String> {
+    let score = entry.score().ok_or("not a rated result")?;
+    let rated = rate(rating(), opponent, score);
+    database_dir()
+        .and_then(|dir| {
+            append(
+                &dir.join("ratings"),
+                &format!("{} {} {}\n", rated, opponent, entry.result),
+            )
+        })
+        .map_err(|e| format!("Could not save the rating: {}", e))?;
+    Ok(rated)
+}
+
+// Puzzles are practice and are not kept.
+pub fn record(siv: &mut Cursive, winner: Option<Color>) {
+    let result = match winner {
+        Some(Color::White) => "1-0",
+        Some(Color::Black) => "0-1",
+        None => "1/2-1/2",
+    };
+    let game = siv
+        .call_on_name("board", |board: &mut BoardView| {
+            if board.is_puzzle() {
+                return None;
+            }
+            let entry = Entry {
+                result: result.to_owned(),
+                side: board.cpu().map_or(Side::TwoPlayer, |cpu| Side::Human(!cpu)),
+                variant: pgn::variant_name(board).unwrap_or("Standard").to_owned(),
+            };
+            Some((
+                pgn::render_result(board, result),
+                entry,
+                board.cpu_rating(),
+                board.adapts(),
+            ))
+        })
+        .flatten();
+    let Some((pgn, entry, opponent, adapts)) = game else {
+        return;
+    };
+    if let Some(score) = entry.score().filter(|_| adapts) {
+        adapt(siv, score);
+    }
+    let saved = append_game(&pgn, &entry)
+        .and_then(|()| opponent.map(|cpu| update_rating(cpu, &entry)).transpose());
+    match saved {
+        Ok(Some(_)) => {
+            siv.call_on_name("menu_rating", |v: &mut TextView| {
+                v.set_content(rating_text())
+            });
+        }
+        Ok(None) => {}
+        Err(e) => siv.add_layer(Dialog::info(e)),
+    }
+}
+
+// A win moves the adaptive CPU up a level and a loss down one, so it
+// settles where the games are close.
+fn adapt(siv: &mut Cursive, score: Score) {
+    settings::update(siv, |settings| {
+        let level = settings.adaptive_level;
+        settings.adaptive_level = match score {
+            Score::Win => (level + 1).min(MAX_ADAPTIVE_LEVEL),
+            Score::Loss => level.saturating_sub(1),
+            Score::Draw => level,
+        };
+    });
+}
+
+#[derive(Default)]
+struct Tally {
+    wins: u32,
+    losses: u32,
+    draws: u32,
+}
+
+impl Tally {
+    fn add(&mut self, score: Score) {
+        match score {
+            Score::Win => self.wins += 1,
+            Score::Loss => self.losses += 1,
+            Score::Draw => self.draws += 1,
+        }
+    }
+
+    fn line(&self, label: &str) -> String {
+        format!(
+            "{:<18} {:>4} {:>4} {:>4}\n",
+            label, self.wins, self.losses, self.draws
+        )
+    }
+}
+
+fn streak(scores: &[Score]) -> String {
+    let Some(&last) = scores.last() else {
+        return "none".to_owned();
+    };
+    let run = scores.iter().rev().take_while(|&&s| s == last).count();
+    let (one, many) = match last {
+        Score::Win => ("win", "wins"),
+        Score::Loss => ("loss", "losses"),
+        Score::Draw => ("draw", "draws"),
+    };
+    format!("{} {}", run, if run == 1 { one } else { many })
+}
+
+fn best_streak(scores: &[Score]) -> usize {
+    scores
+        .split(|&s| s != Score::Win)
+        .map(<[Score]>::len)
+        .max()
+        .unwrap_or(0)
+}
+
+fn summary(entries: &[Entry], adaptive_level: u8) -> String {
+    let count = |side: Side| entries.iter().filter(|e| e.side == side).count();
+    let others = format!(
+        "\nTwo-player games: {}\nEngine matches: {}\nAdaptive CPU level: {} of {}",
+        count(Side::TwoPlayer),
+        count(Side::Engines),
+        adaptive_level,
+        MAX_ADAPTIVE_LEVEL
+    );
+    let ratings = read_ratings();
+    let best = ratings
+        .iter()
+        .map(|r| r.rating)
+        .fold(START_RATING, i32::max);
+    let rating = format!(
+        "Rating {} after {} rated games, best {}\n\n",
+        rating(),
+        ratings.len(),
+        best
+    );
+    let scores: Vec<Score> = entries.iter().filter_map(Entry::score).collect();
+    if scores.is_empty() {
+        return format!(
+            "{}No finished games against the CPU yet.\n{}",
+            rating, others
+        );
+    }
+
+    let mut total = Tally::default();
+    let mut colors = [Tally::default(), Tally::default()];
+    let mut variants: Vec<(&str, Tally)> = Vec::new();
+    for entry in entries {
+        let (Some(score), Side::Human(color)) = (entry.score(), entry.side) else {
+            continue;
+        };
+        total.add(score);
+        colors[usize::from(color == Color::Black)].add(score);
+        match variants.iter_mut().find(|(name, _)| *name == entry.variant) {
+            Some((_, tally)) => tally.add(score),
+            None => {
+                let mut tally = Tally::default();
+                tally.add(score);
+                variants.push((&entry.variant, tally));
+            }
+        }
+    }
+
+    let mut text = rating + &format!("{:<18} {:>4} {:>4} {:>4}\n", "", "Won", "Lost", "Drew");
+    text += &total.line("Total");
+    text += "\n";
+    text += &colors[0].line("As White");
+    text += &colors[1].line("As Black");
+    text += "\n";
+    for (name, tally) in &variants {
+        text += &tally.line(name);
+    }
+    text += &format!(
+        "\nCurrent streak: {}\nLongest winning streak: {}\n",
+        streak(&scores),
+        best_streak(&scores)
+    );
+    text + &others
+}
+
+pub fn show_stats(siv: &mut Cursive) {
+    let level = settings::current(siv).adaptive_level;
+    siv.add_layer(
+        Dialog::new()
+            .title(tr("Statistics"))
+            .content(TextView::new(summary(&read_index(), level)).scrollable())
+            .dismiss_button(tr("Close")),
+    );
+}
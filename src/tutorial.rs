@@ -0,0 +1,285 @@
+use cursive::{
+    views::{Dialog, SelectView},
+    Cursive,
+};
+use shakmaty::{CastlingSide, Move, Role, Square};
+
+use crate::lang::tr;
+use crate::logic::{self, role_name};
+
+#[derive(Clone, Copy)]
+enum Goal {
+    Reach(Role, Square),
+    Castle(CastlingSide),
+    EnPassant,
+    Promote,
+}
+
+pub struct Step {
+    pub fen: &'static str,
+    pub prompt: &'static str,
+    // The move that the step starts after, shown as the last move.
+    pub lead: Option<Move>,
+    goal: Goal,
+}
+
+impl Step {
+    // Only the move the step teaches is let through; anything else legal is
+    // turned down with the reason.
+    pub fn check(&self, mv: &Move) -> Result<(), String> {
+        match self.goal {
+            Goal::Reach(role, _) if mv.role() != role => Err(format!(
+                "this lesson is about the {}",
+                role_name(role).to_lowercase()
+            )),
+            Goal::Reach(_, to) if mv.to() != to => Err(format!("the target is {}", to)),
+            Goal::Castle(side) if mv.castling_side() != Some(side) => Err(if mv.is_castle() {
+                "that castles the other way".to_owned()
+            } else {
+                "move the king two squares toward the rook".to_owned()
+            }),
+            Goal::EnPassant if !mv.is_en_passant() => {
+                Err("take the pawn on the square it passed".to_owned())
+            }
+            Goal::Promote if !mv.is_promotion() => Err("push the pawn to the last rank".to_owned()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn task(&self) -> String {
+        match self.goal {
+            Goal::Reach(role, to) => format!("{} to {}", role_name(role), to),
+            Goal::Castle(CastlingSide::KingSide) => "Castle kingside".to_owned(),
+            Goal::Castle(CastlingSide::QueenSide) => "Castle queenside".to_owned(),
+            Goal::EnPassant => "Take en passant".to_owned(),
+            Goal::Promote => "Promote the pawn".to_owned(),
+        }
+    }
+}
+
+fn step(fen: &'static str, prompt: &'static str, goal: Goal) -> Step {
+    Step {
+        fen,
+        prompt,
+        lead: None,
+        goal,
+    }
+}
+
+fn lessons() -> Vec<(&'static str, Vec<Step>)> {
+    vec![
+        (
+            "The rook",
+            vec![
+                step(
+                    "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+                    "The rook moves any number of squares along a file or a rank, \
+                     but cannot jump over pieces. Move it up the board, from a1 to a7.",
+                    Goal::Reach(Role::Rook, Square::A7),
+                ),
+                step(
+                    "4k3/8/8/8/3R4/8/8/4K3 w - - 0 1",
+                    "Now sideways: move the rook along the rank from d4 to h4.",
+                    Goal::Reach(Role::Rook, Square::H4),
+                ),
+            ],
+        ),
+        (
+            "The bishop",
+            vec![step(
+                "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+                "The bishop moves any number of squares diagonally, so it never \
+                 leaves the colour it starts on. Move it from c1 to h6.",
+                Goal::Reach(Role::Bishop, Square::H6),
+            )],
+        ),
+        (
+            "The queen",
+            vec![step(
+                "4k3/8/8/8/8/8/8/3QK3 w - - 0 1",
+                "The queen moves like a rook and a bishop together: along files, \
+                 ranks and diagonals. Move it diagonally from d1 to a4.",
+                Goal::Reach(Role::Queen, Square::A4),
+            )],
+        ),
+        (
+            "The knight",
+            vec![
+                step(
+                    "4k3/8/8/8/8/8/8/1N2K3 w - - 0 1",
+                    "The knight jumps in an L: two squares one way and one to the \
+                     side. It is the only piece that jumps over others. Jump from b1 \
+                     to c3.",
+                    Goal::Reach(Role::Knight, Square::C3),
+                ),
+                step(
+                    "4k3/8/8/8/8/2N5/8/4K3 w - - 0 1",
+                    "Jump again, from c3 to d5.",
+                    Goal::Reach(Role::Knight, Square::D5),
+                ),
+            ],
+        ),
+        (
+            "The king",
+            vec![step(
+                "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+                "The king steps one square in any direction, never onto a square \
+                 that is attacked. Step from e1 to f2.",
+                Goal::Reach(Role::King, Square::F2),
+            )],
+        ),
+        (
+            "The pawn",
+            vec![
+                step(
+                    "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+                    "Pawns move straight ahead one square, or two from their \
+                     starting square. Push the pawn two squares, from e2 to e4.",
+                    Goal::Reach(Role::Pawn, Square::E4),
+                ),
+                step(
+                    "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1",
+                    "Pawns capture one square diagonally forward, not straight \
+                     ahead. Take the pawn on d5.",
+                    Goal::Reach(Role::Pawn, Square::D5),
+                ),
+            ],
+        ),
+        (
+            "Castling",
+            vec![
+                step(
+                    "4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1",
+                    "Castling moves the king two squares toward a rook, which then \
+                     jumps to the other side of it. Neither may have moved before, \
+                     the squares between must be empty, and the king may not be in, \
+                     pass through or land in check. Castle kingside: king from e1 to g1.",
+                    Goal::Castle(CastlingSide::KingSide),
+                ),
+                step(
+                    "4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1",
+                    "Now castle queenside: king from e1 to c1.",
+                    Goal::Castle(CastlingSide::QueenSide),
+                ),
+            ],
+        ),
+        (
+            "En passant",
+            vec![Step {
+                fen: "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+                prompt: "Black's pawn has just moved two squares, passing your pawn. \
+                         A pawn that does that can be taken as if it had moved one, \
+                         but only straight away. Take it en passant: e5 to d6.",
+                lead: Some(Move::Normal {
+                    role: Role::Pawn,
+                    from: Square::D7,
+                    capture: None,
+                    to: Square::D5,
+                    promotion: None,
+                }),
+                goal: Goal::EnPassant,
+            }],
+        ),
+        (
+            "Promotion",
+            vec![step(
+                "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1",
+                "A pawn that reaches the last rank turns into a queen, rook, bishop \
+                 or knight of your choice. Push the pawn to b8 and promote it.",
+                Goal::Promote,
+            )],
+        ),
+    ]
+}
+
+#[derive(Clone, Copy)]
+pub struct Lesson {
+    pub index: usize,
+    pub step: usize,
+}
+
+impl Lesson {
+    pub fn title(self) -> &'static str {
+        lessons()[self.index].0
+    }
+
+    pub fn current(self) -> Step {
+        lessons().swap_remove(self.index).1.swap_remove(self.step)
+    }
+
+    fn steps(self) -> usize {
+        lessons()[self.index].1.len()
+    }
+
+    fn next(self) -> Option<Lesson> {
+        if self.step + 1 < self.steps() {
+            Some(Lesson {
+                step: self.step + 1,
+                ..self
+            })
+        } else if self.index + 1 < lessons().len() {
+            Some(Lesson {
+                index: self.index + 1,
+                step: 0,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+pub fn start(siv: &mut Cursive, lesson: Lesson) {
+    logic::open_lesson(siv, lesson);
+    siv.add_layer(
+        Dialog::text(lesson.current().prompt)
+            .title(format!(
+                "{} ({}/{})",
+                lesson.title(),
+                lesson.step + 1,
+                lesson.steps()
+            ))
+            .dismiss_button(tr("Ok")),
+    );
+}
+
+pub fn step_done(siv: &mut Cursive, lesson: Lesson) {
+    logic::end_game(siv);
+    let next = lesson.next();
+    let msg = match next {
+        Some(_) => "Well done!",
+        None => "Well done! That was the last lesson.",
+    };
+    let mut dialog = Dialog::text(msg).title(lesson.title());
+    if let Some(next) = next {
+        let label = if next.index == lesson.index {
+            "Next step"
+        } else {
+            "Next lesson"
+        };
+        dialog.add_button(tr(label), move |s| {
+            s.pop_layer();
+            start(s, next);
+        });
+    }
+    dialog.add_button(tr("Lessons"), |s| {
+        s.pop_layer();
+        show_lessons(s);
+    });
+    siv.add_layer(dialog.dismiss_button(tr("Close")));
+}
+
+pub fn show_lessons(siv: &mut Cursive) {
+    let mut list = SelectView::new();
+    for (index, (title, _)) in lessons().into_iter().enumerate() {
+        list.add_item(title, index);
+    }
+    list.set_on_submit(|s, &index| {
+        s.pop_layer();
+        start(s, Lesson { index, step: 0 });
+    });
+    siv.add_layer(
+        Dialog::around(list)
+            .title(tr("Rules"))
+            .dismiss_button(tr("Back")),
+    );
+}
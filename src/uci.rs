@@ -0,0 +1,254 @@
+use std::{
+    env,
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+const MOVE_TIMEOUT: Duration = Duration::from_secs(20);
+// Analysis runs until it reaches its depth or is stopped; this only catches
+// an engine that has hung.
+const ANALYSIS_TIMEOUT: Duration = Duration::from_secs(120);
+pub const MAX_SKILL: u8 = 20;
+
+// One "option" line of the engine's reply to "uci", as in
+// "option name Hash type spin default 16 min 1 max 33554432".
+#[derive(Clone)]
+pub struct EngineOption {
+    pub name: String,
+    // check, spin, combo, button or string.
+    pub kind: String,
+    pub default: String,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub choices: Vec<String>,
+}
+
+impl EngineOption {
+    // Names and values may hold spaces, so each field runs up to the next
+    // keyword.
+    fn parse(line: &str) -> Option<EngineOption> {
+        let mut fields: Vec<(&str, String)> = Vec::new();
+        for word in line.split_whitespace().skip(1) {
+            match word {
+                "name" | "type" | "default" | "min" | "max" | "var" => {
+                    fields.push((word, String::new()))
+                }
+                _ => {
+                    let (_, value) = fields.last_mut()?;
+                    if !value.is_empty() {
+                        value.push(' ');
+                    }
+                    value.push_str(word);
+                }
+            }
+        }
+        let field = |key: &str| {
+            fields
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, value)| value.clone())
+        };
+        Some(EngineOption {
+            name: field("name").filter(|name| !name.is_empty())?,
+            kind: field("type")?,
+            default: field("default").unwrap_or_default(),
+            min: field("min").and_then(|n| n.parse().ok()),
+            max: field("max").and_then(|n| n.parse().ok()),
+            choices: fields
+                .iter()
+                .filter(|(k, _)| *k == "var")
+                .map(|(_, value)| value.clone())
+                .collect(),
+        })
+    }
+
+    // A value the engine would accept, or why not.
+    pub fn check(&self, value: &str) -> Result<String, String> {
+        let value = value.trim();
+        match self.kind.as_str() {
+            "spin" => {
+                let n: i64 = value
+                    .parse()
+                    .map_err(|_| format!("{} must be a number", self.name))?;
+                let (min, max) = (self.min.unwrap_or(i64::MIN), self.max.unwrap_or(i64::MAX));
+                if n < min || n > max {
+                    return Err(format!("{} must be from {} to {}", self.name, min, max));
+                }
+                Ok(n.to_string())
+            }
+            "combo" if !self.choices.iter().any(|c| c == value) => Err(format!(
+                "{} must be one of {}",
+                self.name,
+                self.choices.join(", ")
+            )),
+            _ => Ok(value.to_owned()),
+        }
+    }
+}
+
+pub struct UciEngine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    pub options: Vec<EngineOption>,
+}
+
+impl UciEngine {
+    // The options chosen in the Engine Options dialog come last, so that
+    // they win over the skill and tablebase settings.
+    pub fn spawn(
+        path: &str,
+        skill: u8,
+        tablebases: &str,
+        options: &[(String, String)],
+    ) -> Result<Self, String> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("could not start {}: {}", path, e))?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = UciEngine {
+            child,
+            stdin,
+            lines,
+            options: Vec::new(),
+        };
+        engine.send("uci")?;
+        let (_, reply) = engine.read_until("uciok", STARTUP_TIMEOUT)?;
+        engine.options = reply
+            .iter()
+            .filter(|line| line.starts_with("option "))
+            .filter_map(|line| EngineOption::parse(line))
+            .collect();
+        // Stockfish's name for it; engines without the option ignore it.
+        if skill < MAX_SKILL {
+            engine.send(&format!("setoption name Skill Level value {}", skill))?;
+        }
+        if !tablebases.trim().is_empty() {
+            engine.send(&format!(
+                "setoption name SyzygyPath value {}",
+                tablebases.trim()
+            ))?;
+        }
+        for (name, value) in options {
+            if engine.options.iter().any(|option| &option.name == name) {
+                engine.send(&format!("setoption name {} value {}", name, value))?;
+            }
+        }
+        engine.send("isready")?;
+        engine.wait_for("readyok", STARTUP_TIMEOUT)?;
+        Ok(engine)
+    }
+
+    pub fn best_move(&mut self, fen: &str, depth: u32) -> Result<String, String> {
+        self.best_move_within(fen, depth, MOVE_TIMEOUT)
+    }
+
+    pub fn best_move_within(
+        &mut self,
+        fen: &str,
+        depth: u32,
+        timeout: Duration,
+    ) -> Result<String, String> {
+        self.send(&format!("position fen {}", fen))?;
+        self.send(&format!("go depth {}", depth))?;
+        let line = self.wait_for("bestmove", timeout)?;
+        line.split_whitespace()
+            .nth(1)
+            .map(str::to_owned)
+            .ok_or_else(|| format!("malformed reply: {}", line))
+    }
+
+    // Each "info" line goes to `each` until the search reaches the depth or
+    // `each` returns false, which stops it.
+    pub fn analyse(
+        &mut self,
+        fen: &str,
+        depth: u32,
+        mut each: impl FnMut(&str) -> bool,
+    ) -> Result<(), String> {
+        self.send(&format!("position fen {}", fen))?;
+        self.send(&format!("go depth {}", depth))?;
+        let mut stopped = false;
+        loop {
+            let Some(line) = self.read_line(ANALYSIS_TIMEOUT)? else {
+                return Err("timed out waiting for the analysis".to_owned());
+            };
+            match line.split_whitespace().next() {
+                Some("bestmove") => return Ok(()),
+                Some("info") if !stopped && !each(&line) => {
+                    self.send("stop")?;
+                    stopped = true;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", command).map_err(|e| format!("engine pipe closed: {}", e))
+    }
+
+    fn wait_for(&self, token: &str, timeout: Duration) -> Result<String, String> {
+        self.read_until(token, timeout).map(|(line, _)| line)
+    }
+
+    // The line starting with `token`, and every line before it.
+    fn read_until(&self, token: &str, timeout: Duration) -> Result<(String, Vec<String>), String> {
+        let deadline = Instant::now() + timeout;
+        let mut before = Vec::new();
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.read_line(left)? {
+                Some(line) if line.split_whitespace().next() == Some(token) => {
+                    return Ok((line, before))
+                }
+                Some(line) => before.push(line),
+                None => return Err(format!("timed out waiting for {}", token)),
+            }
+        }
+    }
+
+    // None when nothing came in time.
+    fn read_line(&self, timeout: Duration) -> Result<Option<String>, String> {
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(Some(line)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err("the engine exited".to_owned()),
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+pub fn engine_path(configured: &str) -> Option<String> {
+    if !configured.trim().is_empty() {
+        return Some(configured.trim().to_owned());
+    }
+    env::var("CHESS_ENGINE")
+        .ok()
+        .filter(|path| !path.is_empty())
+}